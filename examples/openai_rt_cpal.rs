@@ -13,7 +13,7 @@ use base64::prelude::BASE64_STANDARD;
 use futures::StreamExt;
 use rig_experimental::providers::openai_realtime::realtime::{
     AudioFormat, GPT_4O_REALTIME_PREVIEW_20250603, InputEvent, Modality, ReceivedEventKind,
-    ReceivedItemEventKind, Session, SessionEvent, TurnDetection,
+    ReceivedItemEventKind, Session, SessionEvent, TurnDetection, Voice,
 };
 use rig_experimental::providers::openai_realtime::{
     Client,
@@ -28,7 +28,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let openai_client = Client::new(&api_key).realtime_client(GPT_4O_REALTIME_PREVIEW_20250603);
 
     let session = Session::new()
-        .voice("sage")
+        .voice(Voice::Sage)
         .input_audio_format(AudioFormat::Pcm16)
         .output_audio_format(AudioFormat::Pcm16)
         .modalities(vec![Modality::Text, Modality::Audio])
@@ -80,6 +80,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 data: ReceivedItemEventKind::AudioDone,
                 ..
             } => {}
+            _ => {}
         }
     }
 
@@ -3,20 +3,18 @@
 //!
 //! In production, you would typically use something like CPAL and stream the audio bytes into the sender
 //! (or potentially something else, depending on what you're trying to do).
-//! You would then open the stream and convert the received audio deltas into bytes then use something like rodio to play the soundbytes back.
-use byteorder::{LittleEndian, ReadBytesExt};
-use std::io::Cursor;
-
+//! You would then open the stream and convert the received audio deltas into samples then use something like rodio to play the soundbytes back.
 use base64::Engine;
 use base64::prelude::BASE64_STANDARD;
 use futures::StreamExt;
 use hound::{SampleFormat, WavSpec, WavWriter};
 use rig_experimental::providers::openai_realtime::realtime::{
     AudioFormat, GPT_4O_REALTIME_PREVIEW_20250603, InputEvent, Modality, ReceivedEventKind,
-    ReceivedItemEventKind, Session, SessionEvent,
+    ReceivedItemEventKind, Session, SessionEvent, Voice,
 };
 use rig_experimental::providers::openai_realtime::{
     Client,
+    audio::AudioOutput,
     realtime::{RealtimeClient, RealtimeVoice, RealtimeVoiceRequest},
 };
 
@@ -31,11 +29,14 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let openai_client = Client::new(&api_key).realtime_client(GPT_4O_REALTIME_PREVIEW_20250603);
 
     let session = Session::new()
-        .voice("sage")
+        .voice(Voice::Sage)
         .input_audio_format(AudioFormat::Pcm16)
         .output_audio_format(AudioFormat::Pcm16)
         .modalities(vec![Modality::Text, Modality::Audio]);
 
+    // Decodes audio deltas according to the output format in the session config
+    let audio = AudioOutput::from_session(&session);
+
     let req = RealtimeVoiceRequest::with_session(session);
 
     let (sender, mut stream) = openai_client.realtime_voice(req).await?;
@@ -45,7 +46,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .await
         .unwrap();
 
-    let mut output_bytes: Vec<u8> = Vec::new();
+    let mut samples: Vec<i16> = Vec::new();
 
     while let Some(evt) = stream.next().await {
         match evt.data {
@@ -53,8 +54,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 data: ReceivedItemEventKind::AudioDelta { delta },
                 ..
             } => {
-                let bytes = BASE64_STANDARD.decode(delta).unwrap();
-                output_bytes.extend(bytes);
+                samples.extend(audio.decode(&delta).unwrap());
             }
             ReceivedEventKind::Session(SessionEvent::SessionUpdated { session }) => {
                 println!("Updated session: {session:?}");
@@ -66,25 +66,18 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 data: ReceivedItemEventKind::AudioDone,
                 ..
             } => break,
+            _ => {}
         }
     }
 
-    println!("{len} bytes received from OpenAI", len = output_bytes.len());
-
-    let mut rdr = Cursor::new(&output_bytes);
-    let mut samples = Vec::new();
-
-    // We need to convert Vec<u8> to Vec<i16> as PCM bytes are i16 by default
-    while let Ok(sample) = rdr.read_i16::<LittleEndian>() {
-        samples.push(sample);
-    }
+    println!("{len} samples received from OpenAI", len = samples.len());
 
     // It should be noted that OpenAI returns pcm16 data at a sample rate of 24kHz.
     // We should therefore reflect this in our sample rate/etc to avoid distorted audio... although if you wanted to change the pitch a bit
     // you can do so by playing around with the sampling rate
     let spec = WavSpec {
         channels: 1,
-        sample_rate: 24_000,
+        sample_rate: audio.sample_rate(),
         bits_per_sample: 16,
         sample_format: SampleFormat::Int, // this gives you signed 16-bit PCM
     };
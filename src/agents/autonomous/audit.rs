@@ -0,0 +1,220 @@
+//! Audit trails of autonomous runs, so that what an agent did can be reviewed after the fact.
+use std::{
+    fmt::Debug,
+    fs::File,
+    io::Write,
+    path::Path,
+    sync::{Arc, Mutex, RwLock},
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::{RunOutcome, ToolCallRecord};
+
+/// Something that happened during an autonomous run. Turns are numbered from 1.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum AuditEvent {
+    /// The run started, or was resumed after `turn` rounds.
+    RunStarted { goal: String, turn: u32 },
+    /// The agent was sent a prompt, after the before-turn hooks ran.
+    Prompt { turn: u32, prompt: String },
+    /// The agent responded.
+    Response { turn: u32, response: String },
+    /// The agent called a tool.
+    ToolCall(ToolCallRecord),
+    /// The approval hook replaced the response.
+    ResponseEdited { turn: u32, response: String },
+    /// The round failed after its retries, and the fallback agent was used instead.
+    FallbackUsed { turn: u32, error: String },
+    /// The round failed after its retries, and was skipped.
+    TurnSkipped { turn: u32, error: String },
+    /// Tasks were added to the task queue.
+    TasksAdded { turn: u32, tasks: Vec<String> },
+    /// The agent's response was carried out in its environment.
+    Observation { turn: u32, observation: String },
+    /// The critic reviewed the response.
+    Reflection { turn: u32, feedback: String },
+    /// The run stopped.
+    RunEnded { turn: u32, outcome: RunOutcome },
+}
+
+/// An entry of an audit trail.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditRecord {
+    pub timestamp: DateTime<Utc>,
+    /// The id of the run, as set with [`AutonomousAgentBuilder::audit_log`](super::AutonomousAgentBuilder::audit_log).
+    pub run_id: String,
+    #[serde(flatten)]
+    pub event: AuditEvent,
+}
+
+/// Where audit records are written to. Records are written as the run happens, and a failed write ends the run with
+/// the error, so that no round goes unaudited.
+pub trait AuditWriter: Debug + Send + Sync {
+    fn write(&self, record: &AuditRecord) -> Result<(), anyhow::Error>;
+}
+
+/// Writes audit records as JSON lines, one record per line. Each record is flushed as it's written.
+pub struct JsonlAuditWriter<W> {
+    inner: Mutex<W>,
+}
+
+impl<W> JsonlAuditWriter<W>
+where
+    W: Write + Send,
+{
+    pub fn new(writer: W) -> Self {
+        Self {
+            inner: Mutex::new(writer),
+        }
+    }
+
+    /// Returns the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.inner
+            .into_inner()
+            .unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+impl JsonlAuditWriter<File> {
+    /// Opens a JSONL file to append audit records to, so that several runs can share one audit file.
+    pub fn create<P>(path: P) -> std::io::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let file = File::options().create(true).append(true).open(path)?;
+
+        Ok(Self::new(file))
+    }
+}
+
+impl<W> Debug for JsonlAuditWriter<W> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("JsonlAuditWriter").finish_non_exhaustive()
+    }
+}
+
+impl<W> AuditWriter for JsonlAuditWriter<W>
+where
+    W: Write + Send,
+{
+    fn write(&self, record: &AuditRecord) -> Result<(), anyhow::Error> {
+        let mut line = serde_json::to_vec(record)?;
+        line.push(b'\n');
+
+        let mut writer = self
+            .inner
+            .lock()
+            .map_err(|_| anyhow::anyhow!("Audit writer lock poisoned"))?;
+        writer.write_all(&line)?;
+        writer.flush()?;
+
+        Ok(())
+    }
+}
+
+/// An in-memory audit log, mostly useful for tests. Pass a clone to
+/// [`AutonomousAgentBuilder::audit_log`](super::AutonomousAgentBuilder::audit_log) and read the records from the original.
+#[derive(Clone, Debug, Default)]
+pub struct InMemoryAuditLog {
+    inner: Arc<RwLock<Vec<AuditRecord>>>,
+}
+
+impl InMemoryAuditLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The records written so far, in order.
+    pub fn records(&self) -> Vec<AuditRecord> {
+        self.inner
+            .read()
+            .map(|x| x.clone())
+            .unwrap_or_else(|err| err.into_inner().clone())
+    }
+}
+
+impl AuditWriter for InMemoryAuditLog {
+    fn write(&self, record: &AuditRecord) -> Result<(), anyhow::Error> {
+        self.inner
+            .write()
+            .map_err(|_| anyhow::anyhow!("Audit log lock poisoned"))?
+            .push(record.clone());
+
+        Ok(())
+    }
+}
+
+/// Where an autonomous agent writes its audit trail to, and the run id it's written under.
+#[derive(Clone, Debug)]
+pub(super) struct Auditing {
+    pub(super) writer: Arc<dyn AuditWriter>,
+    pub(super) id: String,
+}
+
+impl Auditing {
+    pub(super) fn record(&self, event: AuditEvent) -> Result<(), anyhow::Error> {
+        self.writer.write(&AuditRecord {
+            timestamp: Utc::now(),
+            run_id: self.id.clone(),
+            event,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use serde_json::json;
+
+    use super::{AuditEvent, AuditRecord, AuditWriter, JsonlAuditWriter};
+    use crate::agents::autonomous::{RunOutcome, ToolCallRecord};
+
+    #[test]
+    fn writes_records_as_json_lines() {
+        let record = |event| AuditRecord {
+            timestamp: Utc.with_ymd_and_hms(2025, 1, 1, 12, 0, 0).unwrap(),
+            run_id: "job-1".into(),
+            event,
+        };
+        let records = [
+            record(AuditEvent::ToolCall(ToolCallRecord {
+                turn: 1,
+                name: "search".into(),
+                arguments: json!({ "query": "rust" }),
+            })),
+            record(AuditEvent::RunEnded {
+                turn: 1,
+                outcome: RunOutcome::ExitConditionMet,
+            }),
+        ];
+
+        let writer = JsonlAuditWriter::new(Vec::new());
+        for record in &records {
+            writer.write(record).unwrap();
+        }
+        let output = String::from_utf8(writer.into_inner()).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+
+        assert_eq!(lines.len(), 2);
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(lines[0]).unwrap(),
+            json!({
+                "timestamp": "2025-01-01T12:00:00Z",
+                "run_id": "job-1",
+                "event": "tool_call",
+                "turn": 1,
+                "name": "search",
+                "arguments": { "query": "rust" },
+            })
+        );
+        let parsed: Vec<AuditRecord> = lines
+            .iter()
+            .map(|x| serde_json::from_str(x).unwrap())
+            .collect();
+        assert_eq!(parsed, records);
+    }
+}
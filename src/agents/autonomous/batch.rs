@@ -0,0 +1,64 @@
+//! Running many autonomous runs concurrently, ie for batch research or data processing.
+use futures::{StreamExt, stream};
+use rig::completion::CompletionModel;
+
+use super::{AutonomousAgent, ExitCheck, RunReport};
+
+/// The number of runs a batch runner runs at once, if not set.
+const DEFAULT_CONCURRENCY: usize = 4;
+
+/// Runs an autonomous agent on many prompts concurrently, with a limit on how many run at once.
+///
+/// Each run gets its own agent from `make_agent`, which receives the index of the run. This keeps the runs' chat
+/// histories separate, and lets runs be varied (ie with a different seed or temperature).
+///
+/// ```rust,ignore
+/// let reports = BatchRunner::new(|_| AutonomousAgent::new(research_agent(), ExitCondition::contains("DONE")))
+///     .concurrency(8)
+///     .run(companies.iter().map(|x| format!("Research {x}")))
+///     .await;
+/// ```
+#[derive(Debug, Clone)]
+pub struct BatchRunner<F> {
+    make_agent: F,
+    concurrency: usize,
+}
+
+impl<F, M, E> BatchRunner<F>
+where
+    F: Fn(usize) -> AutonomousAgent<M, E>,
+    M: CompletionModel,
+    E: ExitCheck,
+{
+    pub fn new(make_agent: F) -> Self {
+        Self {
+            make_agent,
+            concurrency: DEFAULT_CONCURRENCY,
+        }
+    }
+
+    /// Set how many runs may run at once. At least one run always runs.
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+
+        self
+    }
+
+    /// Run the agent on each prompt. Returns the result of each run, in the order of the prompts.
+    pub async fn run<I, S>(&self, prompts: I) -> Vec<Result<RunReport, anyhow::Error>>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        stream::iter(prompts.into_iter().enumerate())
+            .map(|(index, prompt)| async move {
+                let prompt: String = prompt.into();
+                let mut agent = (self.make_agent)(index);
+
+                agent.run(&prompt).await
+            })
+            .buffered(self.concurrency)
+            .collect()
+            .await
+    }
+}
@@ -0,0 +1,199 @@
+//! Token and cost budgets, which stop an autonomous run before it spends too much.
+//!
+//! `rig`'s chat API doesn't report token usage, so usage is estimated from the text sent and received each round using a
+//! [`TokenCounter`]. The default counter assumes roughly 4 characters per token; set a tokenizer-based counter for
+//! accurate numbers.
+use std::{ops::AddAssign, sync::Arc};
+
+use rig::message::Message;
+use serde::{Deserialize, Serialize};
+
+/// Counts the tokens in a piece of text.
+pub type TokenCounter = Arc<dyn Fn(&str) -> u64 + Send + Sync>;
+
+/// Estimate the number of tokens in a piece of text, at roughly 4 characters per token.
+pub fn estimate_tokens(text: &str) -> u64 {
+    (text.chars().count() as u64).div_ceil(4)
+}
+
+/// Token usage, accumulated over the rounds of a run.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Usage {
+    /// Tokens sent to the model: the prompt and the chat history.
+    pub input_tokens: u64,
+    /// Tokens in the model's responses.
+    pub output_tokens: u64,
+}
+
+impl Usage {
+    pub fn total_tokens(&self) -> u64 {
+        self.input_tokens + self.output_tokens
+    }
+}
+
+impl AddAssign for Usage {
+    fn add_assign(&mut self, rhs: Self) {
+        self.input_tokens += rhs.input_tokens;
+        self.output_tokens += rhs.output_tokens;
+    }
+}
+
+/// The price of a model's tokens, in US dollars per million tokens.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct TokenPricing {
+    pub input_per_million: f64,
+    pub output_per_million: f64,
+}
+
+impl TokenPricing {
+    pub fn new(input_per_million: f64, output_per_million: f64) -> Self {
+        Self {
+            input_per_million,
+            output_per_million,
+        }
+    }
+
+    /// The estimated cost (in US dollars) of the given usage.
+    pub fn cost(&self, usage: &Usage) -> f64 {
+        (usage.input_tokens as f64 * self.input_per_million
+            + usage.output_tokens as f64 * self.output_per_million)
+            / 1_000_000.0
+    }
+}
+
+/// The budget limit that stopped a run.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BudgetLimit {
+    Tokens { limit: u64, used: u64 },
+    Cost { limit: f64, spent: f64 },
+}
+
+impl std::fmt::Display for BudgetLimit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Tokens { limit, used } => write!(f, "used {used} tokens (limit {limit})"),
+            Self::Cost { limit, spent } => write!(f, "spent ${spent:.4} (limit ${limit:.4})"),
+        }
+    }
+}
+
+/// The token and cost limits of an autonomous agent.
+#[derive(Clone)]
+pub(super) struct Budget {
+    max_total_tokens: Option<u64>,
+    max_cost: Option<(f64, TokenPricing)>,
+    counter: TokenCounter,
+}
+
+impl Default for Budget {
+    fn default() -> Self {
+        Self {
+            max_total_tokens: None,
+            max_cost: None,
+            counter: Arc::new(estimate_tokens),
+        }
+    }
+}
+
+impl Budget {
+    pub(super) fn set_max_total_tokens(&mut self, max: u64) {
+        self.max_total_tokens = Some(max);
+    }
+
+    pub(super) fn set_max_cost(&mut self, max: f64, pricing: TokenPricing) {
+        self.max_cost = Some((max, pricing));
+    }
+
+    pub(super) fn set_counter(&mut self, counter: TokenCounter) {
+        self.counter = counter;
+    }
+
+    /// The tokens in a message, counted over its JSON representation.
+    pub(super) fn message_tokens(&self, message: &Message) -> u64 {
+        serde_json::to_string(message)
+            .map(|text| (self.counter)(&text))
+            .unwrap_or_default()
+    }
+
+    /// The usage of a single round.
+    pub(super) fn round_usage(&self, prompt: &str, history: &[Message], response: &str) -> Usage {
+        let history: u64 = history.iter().map(|x| self.message_tokens(x)).sum();
+
+        Usage {
+            input_tokens: (self.counter)(prompt) + history,
+            output_tokens: (self.counter)(response),
+        }
+    }
+
+    /// The first limit that the usage goes over, if any.
+    pub(super) fn exceeded(&self, usage: &Usage) -> Option<BudgetLimit> {
+        if let Some(limit) = self.max_total_tokens
+            && usage.total_tokens() > limit
+        {
+            return Some(BudgetLimit::Tokens {
+                limit,
+                used: usage.total_tokens(),
+            });
+        }
+
+        if let Some((limit, pricing)) = self.max_cost {
+            let spent = pricing.cost(usage);
+            if spent > limit {
+                return Some(BudgetLimit::Cost { limit, spent });
+            }
+        }
+
+        None
+    }
+}
+
+impl std::fmt::Debug for Budget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Budget")
+            .field("max_total_tokens", &self.max_total_tokens)
+            .field("max_cost", &self.max_cost)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Budget, BudgetLimit, TokenPricing, Usage};
+
+    #[test]
+    fn stops_when_over_budget() {
+        let mut budget = Budget::default();
+        let usage = budget.round_usage("12345678", &[], "1234");
+        assert_eq!(
+            usage,
+            Usage {
+                input_tokens: 2,
+                output_tokens: 1
+            }
+        );
+        assert_eq!(budget.exceeded(&usage), None);
+
+        budget.set_max_total_tokens(2);
+        assert_eq!(
+            budget.exceeded(&usage),
+            Some(BudgetLimit::Tokens { limit: 2, used: 3 })
+        );
+
+        let mut budget = Budget::default();
+        budget.set_max_cost(1.0, TokenPricing::new(2.0, 10.0));
+        let usage = Usage {
+            input_tokens: 250_000,
+            output_tokens: 50_000,
+        };
+        assert_eq!(budget.exceeded(&usage), None);
+        let usage = Usage {
+            input_tokens: 250_000,
+            output_tokens: 60_000,
+        };
+        assert!(matches!(
+            budget.exceeded(&usage),
+            Some(BudgetLimit::Cost { spent, .. }) if spent > 1.0
+        ));
+    }
+}
@@ -0,0 +1,85 @@
+//! Reflection: a critic agent reviews each response against the goal, and its feedback is added to the next prompt.
+use std::sync::Arc;
+
+use crate::{PromptTemplate, agents::DynAgent};
+
+/// The number of responses a critic reviews before it stops, if not set.
+const DEFAULT_MAX_REFLECTIONS: usize = 3;
+
+/// The critic's prompt, if not set. See [`Critic::template`].
+pub const DEFAULT_CRITIC_TEMPLATE: &str = "You are reviewing the work of an autonomous agent.\n\n\
+    Goal: {{ goal }}\n\nThe agent's latest response:\n{{ response }}\n\n\
+    Point out mistakes, gaps and anything that doesn't serve the goal, and suggest what to do next. Be brief.";
+
+/// Reviews an autonomous agent's responses against the run's goal (its first prompt). The feedback is added to the
+/// agent's next prompt.
+#[derive(Clone)]
+pub struct Critic {
+    agent: Arc<dyn DynAgent>,
+    template: String,
+    max_reflections: usize,
+}
+
+impl Critic {
+    /// Create a critic. This can be the same agent as the autonomous agent, or a different one.
+    pub fn new<A>(agent: A) -> Self
+    where
+        A: DynAgent + 'static,
+    {
+        Self {
+            agent: Arc::new(agent),
+            template: DEFAULT_CRITIC_TEMPLATE.to_string(),
+            max_reflections: DEFAULT_MAX_REFLECTIONS,
+        }
+    }
+
+    /// Set the critic's prompt, as a [`PromptTemplate`]. The template can use the `goal`, `response` and `turn` variables.
+    pub fn template(mut self, template: &str) -> Self {
+        self.template = template.to_string();
+
+        self
+    }
+
+    /// Set how many responses the critic reviews in a run. Later responses aren't reviewed.
+    pub fn max_reflections(mut self, max_reflections: usize) -> Self {
+        self.max_reflections = max_reflections;
+
+        self
+    }
+
+    pub(super) fn max(&self) -> usize {
+        self.max_reflections
+    }
+
+    /// Review a response. Returns `None` if the template can't be rendered or the critic fails, so that a failed review
+    /// doesn't end the run.
+    pub(super) async fn review(&self, goal: &str, response: &str, turn: u32) -> Option<String> {
+        let prompt = PromptTemplate::new(&self.template)
+            .with_variable("goal", goal)
+            .with_variable("response", response)
+            .with_variable("turn", turn)
+            .try_render_to_string()
+            .inspect_err(|err| tracing::warn!("Failed to render the critic template: {err}"))
+            .ok()?;
+
+        self.agent
+            .dyn_prompt(prompt, 0)
+            .await
+            .inspect_err(|err| tracing::warn!("Critic failed: {err}"))
+            .ok()
+    }
+}
+
+/// The prompt for the round after a review: the previous response, followed by the critic's feedback.
+pub(super) fn with_feedback(response: &str, feedback: &str) -> String {
+    format!("{response}\n\nFeedback on your previous response:\n{feedback}")
+}
+
+impl std::fmt::Debug for Critic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Critic")
+            .field("template", &self.template)
+            .field("max_reflections", &self.max_reflections)
+            .finish_non_exhaustive()
+    }
+}
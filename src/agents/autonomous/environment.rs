@@ -0,0 +1,66 @@
+//! Environments that an autonomous agent acts in, ie simulations, browsers or games.
+use futures::future::BoxFuture;
+
+/// Something an autonomous agent observes and acts in, round by round.
+///
+/// Before the first round, the environment is observed, and its state is sent to the agent along with the run's first
+/// prompt. After each round, the agent's response is carried out as an action, and the resulting observation is the next
+/// round's prompt. The run ends once an observation is [done](Observation::done).
+///
+/// ```rust,ignore
+/// struct Counter(i64);
+///
+/// impl Environment for Counter {
+///     fn observe(&mut self) -> BoxFuture<'_, Result<String, anyhow::Error>> {
+///         Box::pin(async move { Ok(format!("The counter is at {}. Respond with +1 or -1.", self.0)) })
+///     }
+///
+///     fn act<'a>(&'a mut self, action: &'a str) -> BoxFuture<'a, Result<Observation, anyhow::Error>> {
+///         Box::pin(async move {
+///             self.0 += action.trim().parse::<i64>()?;
+///             match self.0 {
+///                 10 => Ok(Observation::done("The counter reached 10.")),
+///                 x => Ok(Observation::new(format!("The counter is at {x}."))),
+///             }
+///         })
+///     }
+/// }
+/// ```
+pub trait Environment: Send + Sync {
+    /// Describe the current state of the environment.
+    fn observe(&mut self) -> BoxFuture<'_, Result<String, anyhow::Error>>;
+
+    /// Carry out an action (the agent's response), and return what the agent observes as a result.
+    fn act<'a>(&'a mut self, action: &'a str) -> BoxFuture<'a, Result<Observation, anyhow::Error>>;
+}
+
+/// What an agent observes after acting in an [`Environment`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Observation {
+    /// Sent to the agent as the next round's prompt.
+    pub text: String,
+    /// Whether the environment has reached a final state (ie the game is over), which ends the run.
+    pub done: bool,
+}
+
+impl Observation {
+    pub fn new(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            done: false,
+        }
+    }
+
+    /// An observation of a final state, which ends the run.
+    pub fn done(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            done: true,
+        }
+    }
+}
+
+/// The first prompt of a run in an environment: the goal, followed by the environment's state.
+pub(super) fn first_prompt(goal: &str, state: &str) -> String {
+    format!("{goal}\n\nThe current state of the environment:\n{state}")
+}
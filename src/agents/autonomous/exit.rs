@@ -0,0 +1,142 @@
+//! Exit conditions, which decide when an autonomous run has reached its goal.
+use std::sync::Arc;
+
+use futures::future::BoxFuture;
+use regex::Regex;
+
+use crate::agents::DynAgent;
+
+/// Decides, after each round, whether an autonomous agent should stop.
+///
+/// This is implemented for async functions that take the response and return a bool, and for the built-in [`ExitCondition`]s.
+pub trait ExitCheck: Send + Sync {
+    fn should_exit<'a>(&'a self, response: &'a str) -> BoxFuture<'a, bool>;
+}
+
+impl<F, Fut> ExitCheck for F
+where
+    F: Fn(&str) -> Fut + Send + Sync,
+    Fut: Future<Output = bool> + Send + 'static,
+{
+    fn should_exit<'a>(&'a self, response: &'a str) -> BoxFuture<'a, bool> {
+        Box::pin(self(response))
+    }
+}
+
+/// Built-in exit conditions.
+#[derive(Clone)]
+pub enum ExitCondition {
+    /// Never stop early, so the agent runs for `max_turns` rounds.
+    MaxTurnsOnly,
+    /// Stop once a response contains the text, ie "TASK COMPLETE".
+    Contains(String),
+    /// Stop once a response matches the regex.
+    Regex(Regex),
+    /// Stop once a judge agent says the response meets the criteria.
+    LlmJudge {
+        judge: Arc<dyn DynAgent>,
+        criteria: String,
+    },
+}
+
+impl ExitCondition {
+    pub fn max_turns_only() -> Self {
+        Self::MaxTurnsOnly
+    }
+
+    pub fn contains(text: &str) -> Self {
+        Self::Contains(text.to_string())
+    }
+
+    pub fn regex(pattern: &str) -> Result<Self, regex::Error> {
+        Ok(Self::Regex(Regex::new(pattern)?))
+    }
+
+    /// Ask a judge agent (ie a small, cheap model) whether each response meets the criteria. The judge is prompted with the
+    /// criteria and the response, and should answer "yes" or "no". If prompting the judge fails, the run continues.
+    pub fn llm_judge<A>(judge: A, criteria: &str) -> Self
+    where
+        A: DynAgent + 'static,
+    {
+        Self::LlmJudge {
+            judge: Arc::new(judge),
+            criteria: criteria.to_string(),
+        }
+    }
+}
+
+/// The prompt the judge of [`ExitCondition::LlmJudge`] receives.
+fn judge_prompt(criteria: &str, response: &str) -> String {
+    format!(
+        "You are judging whether an autonomous agent has finished its task.\n\nCriteria: {criteria}\n\nThe agent's latest response:\n{response}\n\nAnswer with only \"yes\" if the response meets the criteria, or \"no\" if it doesn't."
+    )
+}
+
+impl ExitCheck for ExitCondition {
+    fn should_exit<'a>(&'a self, response: &'a str) -> BoxFuture<'a, bool> {
+        Box::pin(async move {
+            match self {
+                Self::MaxTurnsOnly => false,
+                Self::Contains(text) => response.contains(text.as_str()),
+                Self::Regex(regex) => regex.is_match(response),
+                Self::LlmJudge { judge, criteria } => {
+                    match judge.dyn_prompt(judge_prompt(criteria, response), 0).await {
+                        Ok(answer) => answer
+                            .trim()
+                            .trim_matches(|c: char| c == '"' || c == '\'' || c == '`' || c == '.')
+                            .eq_ignore_ascii_case("yes"),
+                        Err(err) => {
+                            tracing::warn!("Exit condition judge failed: {err}");
+                            false
+                        }
+                    }
+                }
+            }
+        })
+    }
+}
+
+impl std::fmt::Debug for ExitCondition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MaxTurnsOnly => f.write_str("MaxTurnsOnly"),
+            Self::Contains(text) => f.debug_tuple("Contains").field(text).finish(),
+            Self::Regex(regex) => f.debug_tuple("Regex").field(regex).finish(),
+            Self::LlmJudge { criteria, .. } => f
+                .debug_struct("LlmJudge")
+                .field("criteria", criteria)
+                .finish_non_exhaustive(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ExitCheck, ExitCondition};
+    use crate::test_utils::Fixed;
+
+    #[tokio::test]
+    async fn checks_exit_conditions() {
+        assert!(!ExitCondition::max_turns_only().should_exit("DONE").await);
+        assert!(
+            ExitCondition::contains("DONE")
+                .should_exit("All DONE")
+                .await
+        );
+        assert!(!ExitCondition::contains("DONE").should_exit("done").await);
+
+        let regex = ExitCondition::regex(r"(?i)\bfinal answer:").unwrap();
+        assert!(regex.should_exit("Final answer: 42").await);
+
+        let judge = ExitCondition::llm_judge(Fixed(" Yes.\n"), "The answer is a number");
+        assert!(judge.should_exit("42").await);
+        let judge = ExitCondition::llm_judge(Fixed("no"), "The answer is a number");
+        assert!(!judge.should_exit("forty-two").await);
+
+        let closure = |response: &str| {
+            let done = response.ends_with('!');
+            async move { done }
+        };
+        assert!(closure.should_exit("Finished!").await);
+    }
+}
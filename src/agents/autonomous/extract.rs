@@ -0,0 +1,64 @@
+//! Extraction of typed results from autonomous runs.
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+
+/// The number of times the agent is asked for the final answer as JSON, if it responds with invalid JSON.
+pub(super) const EXTRACTION_ATTEMPTS: usize = 2;
+
+/// The JSON object in a response, allowing for text or a code fence around it.
+pub(super) fn json_object(response: &str) -> &str {
+    match (response.find('{'), response.rfind('}')) {
+        (Some(start), Some(end)) if start < end => &response[start..=end],
+        _ => response,
+    }
+}
+
+/// The prompt that asks the agent for its final answer as JSON, following the schema of `T`.
+pub(super) fn extraction_prompt<T>() -> Result<String, serde_json::Error>
+where
+    T: JsonSchema,
+{
+    let schema = serde_json::to_string_pretty(&schemars::schema_for!(T))?;
+
+    Ok(format!(
+        "Give your final answer as a JSON object that follows this JSON schema. Respond with only the JSON object.\n\n{schema}"
+    ))
+}
+
+/// Parse the agent's final answer.
+pub(super) fn parse<T>(response: &str) -> Result<T, serde_json::Error>
+where
+    T: DeserializeOwned,
+{
+    serde_json::from_str(json_object(response))
+}
+
+#[cfg(test)]
+mod tests {
+    use schemars::JsonSchema;
+    use serde::Deserialize;
+
+    #[derive(Debug, PartialEq, Deserialize, JsonSchema)]
+    struct Answer {
+        city: String,
+        population: u64,
+    }
+
+    #[test]
+    fn extracts_typed_answers() {
+        let prompt = super::extraction_prompt::<Answer>().unwrap();
+        assert!(prompt.contains("\"population\""));
+
+        let answer: Answer = super::parse(
+            "Here you go:\n```json\n{\"city\": \"Paris\", \"population\": 2100000}\n```",
+        )
+        .unwrap();
+        assert_eq!(
+            answer,
+            Answer {
+                city: "Paris".into(),
+                population: 2_100_000
+            }
+        );
+    }
+}
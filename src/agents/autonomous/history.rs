@@ -0,0 +1,158 @@
+//! Chat history management, which keeps the history of a long autonomous run within the model's context window.
+use std::sync::Arc;
+
+use rig::message::Message;
+
+use crate::agents::DynAgent;
+
+/// How many messages the summary of dropped messages takes up: the summary, and an acknowledgement.
+const SUMMARY_MESSAGES: usize = 2;
+
+/// Limits on an autonomous agent's chat history. When the history goes over a limit, the oldest messages are dropped,
+/// and summarized first if a summarizer is set.
+#[derive(Clone, Default)]
+pub(super) struct HistoryPolicy {
+    max_messages: Option<usize>,
+    max_tokens: Option<u64>,
+    summarizer: Option<Arc<dyn DynAgent>>,
+}
+
+impl HistoryPolicy {
+    pub(super) fn set_max_messages(&mut self, max: usize) {
+        self.max_messages = Some(max);
+    }
+
+    pub(super) fn set_max_tokens(&mut self, max: u64) {
+        self.max_tokens = Some(max);
+    }
+
+    pub(super) fn set_summarizer(&mut self, summarizer: Arc<dyn DynAgent>) {
+        self.summarizer = Some(summarizer);
+    }
+
+    /// The number of messages to drop from the start of the history to bring it within the limits. The history never
+    /// starts with an assistant message after dropping.
+    fn overflow(&self, history: &[Message], tokens: &dyn Fn(&Message) -> u64) -> usize {
+        let max_messages = match (self.max_messages, &self.summarizer) {
+            (Some(max), Some(_)) => Some(max.saturating_sub(SUMMARY_MESSAGES)),
+            (max, _) => max,
+        };
+        let counts: Vec<u64> = history.iter().map(tokens).collect();
+        let mut total: u64 = counts.iter().sum();
+        let mut drop = 0;
+
+        while drop < history.len()
+            && (max_messages.is_some_and(|max| history.len() - drop > max)
+                || self.max_tokens.is_some_and(|max| total > max))
+        {
+            total -= counts[drop];
+            drop += 1;
+        }
+        while drop < history.len() && matches!(history[drop], Message::Assistant { .. }) {
+            drop += 1;
+        }
+
+        drop
+    }
+
+    /// Bring the history within the limits, summarizing the dropped messages if a summarizer is set. If summarizing
+    /// fails, the messages are dropped without a summary.
+    pub(super) async fn apply(&self, history: &mut Vec<Message>, tokens: &dyn Fn(&Message) -> u64) {
+        let drop = self.overflow(history, tokens);
+        if drop == 0 {
+            return;
+        }
+
+        let dropped: Vec<Message> = history.drain(..drop).collect();
+        tracing::debug!("Dropped {drop} messages from the chat history");
+
+        let Some(summarizer) = &self.summarizer else {
+            return;
+        };
+        match summarizer.dyn_prompt(summary_prompt(&dropped), 0).await {
+            Ok(summary) => {
+                history.splice(
+                    0..0,
+                    [
+                        Message::user(format!("Summary of our conversation so far:\n{summary}")),
+                        Message::assistant("Understood."),
+                    ],
+                );
+            }
+            Err(err) => tracing::warn!("Failed to summarize chat history: {err}"),
+        }
+    }
+}
+
+/// The prompt the summarizer receives.
+fn summary_prompt(messages: &[Message]) -> String {
+    let transcript = messages
+        .iter()
+        .filter_map(|x| serde_json::to_string(x).ok())
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    format!(
+        "Summarize the following conversation between a user and an assistant, keeping any facts, decisions and progress \
+        that later messages may depend on. Messages are in JSON, one per line.\n\n{transcript}"
+    )
+}
+
+impl std::fmt::Debug for HistoryPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HistoryPolicy")
+            .field("max_messages", &self.max_messages)
+            .field("max_tokens", &self.max_tokens)
+            .field("summarizes", &self.summarizer.is_some())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use rig::message::Message;
+
+    use super::HistoryPolicy;
+    use crate::test_utils::Fixed;
+
+    fn conversation(rounds: usize) -> Vec<Message> {
+        (0..rounds)
+            .flat_map(|i| {
+                [
+                    Message::user(format!("prompt {i}")),
+                    Message::assistant(format!("response {i}")),
+                ]
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn truncates_and_summarizes_history() {
+        let tokens = |_: &Message| 10;
+
+        let mut policy = HistoryPolicy::default();
+        policy.set_max_messages(3);
+        let mut history = conversation(3);
+        policy.apply(&mut history, &tokens).await;
+        // Dropping 3 messages would start the history with a response
+        assert_eq!(history, conversation(3)[4..]);
+
+        let mut policy = HistoryPolicy::default();
+        policy.set_max_tokens(40);
+        let mut history = conversation(3);
+        policy.apply(&mut history, &tokens).await;
+        assert_eq!(history, conversation(3)[2..]);
+
+        policy.set_summarizer(Arc::new(Fixed("Two rounds happened")));
+        let mut history = conversation(3);
+        policy.apply(&mut history, &tokens).await;
+        assert_eq!(history.len(), 6);
+        assert_eq!(
+            history[0],
+            Message::user("Summary of our conversation so far:\nTwo rounds happened")
+        );
+        assert_eq!(history[2..], conversation(3)[2..]);
+    }
+}
@@ -0,0 +1,158 @@
+//! Hooks that run before and after each round of an autonomous agent.
+use std::sync::Arc;
+
+use futures::future::BoxFuture;
+
+/// A round of an autonomous run, as seen by turn hooks.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TurnContext {
+    /// The round number, starting from 1.
+    pub turn: u32,
+    /// The prompt the agent is (or was) sent this round.
+    pub prompt: String,
+    /// The agent's response. `None` before the round has run.
+    pub response: Option<String>,
+}
+
+/// Runs before each round, and returns the prompt to send, ie to log it or to add instructions to it.
+pub type BeforeTurnHook = Arc<dyn Fn(TurnContext) -> BoxFuture<'static, String> + Send + Sync>;
+
+/// Runs before each round, and returns context to add to the prompt (ie fresh data, the time or retrieved documents), or
+/// `None` to add nothing.
+pub type ContextProvider =
+    Arc<dyn Fn(TurnContext) -> BoxFuture<'static, Option<String>> + Send + Sync>;
+
+/// Runs after each round, ie to log or persist the response.
+pub type AfterTurnHook = Arc<dyn Fn(TurnContext) -> BoxFuture<'static, ()> + Send + Sync>;
+
+/// Runs after each round with the proposed response, and decides whether the run may continue.
+pub type ApprovalHook = Arc<dyn Fn(TurnContext) -> BoxFuture<'static, Approval> + Send + Sync>;
+
+/// The decision of an [`ApprovalHook`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Approval {
+    /// Continue with the response as it is.
+    Approve,
+    /// Continue with this response instead.
+    Edit(String),
+    /// Stop the run.
+    Abort,
+}
+
+/// The turn hooks of an autonomous agent, run in the order they were added.
+#[derive(Clone, Default)]
+pub(super) struct TurnHooks {
+    context: Vec<ContextProvider>,
+    before: Vec<BeforeTurnHook>,
+    after: Vec<AfterTurnHook>,
+    approval: Option<ApprovalHook>,
+}
+
+impl TurnHooks {
+    pub(super) fn add_context(&mut self, provider: ContextProvider) {
+        self.context.push(provider);
+    }
+
+    pub(super) fn add_before(&mut self, hook: BeforeTurnHook) {
+        self.before.push(hook);
+    }
+
+    pub(super) fn add_after(&mut self, hook: AfterTurnHook) {
+        self.after.push(hook);
+    }
+
+    pub(super) fn set_approval(&mut self, hook: ApprovalHook) {
+        self.approval = Some(hook);
+    }
+
+    /// Run the context providers, appending their context to the prompt.
+    pub(super) async fn with_context(&self, turn: u32, mut prompt: String) -> String {
+        for provider in &self.context {
+            let context = provider(TurnContext {
+                turn,
+                prompt: prompt.clone(),
+                response: None,
+            })
+            .await;
+
+            if let Some(context) = context {
+                prompt = format!("{prompt}\n\n{context}");
+            }
+        }
+
+        prompt
+    }
+
+    /// Run the before-turn hooks, each receiving the prompt returned by the previous one.
+    pub(super) async fn before(&self, turn: u32, mut prompt: String) -> String {
+        for hook in &self.before {
+            prompt = hook(TurnContext {
+                turn,
+                prompt,
+                response: None,
+            })
+            .await;
+        }
+
+        prompt
+    }
+
+    pub(super) async fn after(&self, turn: u32, prompt: &str, response: &str) {
+        for hook in &self.after {
+            hook(TurnContext {
+                turn,
+                prompt: prompt.to_string(),
+                response: Some(response.to_string()),
+            })
+            .await;
+        }
+    }
+
+    /// Ask the approval hook about a response. Responses are approved if there's no approval hook.
+    pub(super) async fn approve(&self, turn: u32, prompt: &str, response: &str) -> Approval {
+        let Some(hook) = &self.approval else {
+            return Approval::Approve;
+        };
+
+        hook(TurnContext {
+            turn,
+            prompt: prompt.to_string(),
+            response: Some(response.to_string()),
+        })
+        .await
+    }
+}
+
+impl std::fmt::Debug for TurnHooks {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TurnHooks")
+            .field("context", &self.context.len())
+            .field("before", &self.before.len())
+            .field("after", &self.after.len())
+            .field("approval", &self.approval.is_some())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use futures::FutureExt;
+
+    use super::TurnHooks;
+
+    #[tokio::test]
+    async fn appends_context_to_prompts() {
+        let mut hooks = TurnHooks::default();
+        hooks.add_context(Arc::new(|ctx| {
+            async move { Some(format!("This is round {}.", ctx.turn)) }.boxed()
+        }));
+        hooks.add_context(Arc::new(|_| async { None }.boxed()));
+
+        assert_eq!(
+            hooks.with_context(2, "Check the feed.".into()).await,
+            "Check the feed.\n\nThis is round 2."
+        );
+    }
+}
@@ -0,0 +1,1286 @@
+use std::{sync::Arc, time::Duration};
+
+use futures::{FutureExt, Stream, StreamExt, channel::mpsc, stream};
+use rig::{
+    agent::Agent,
+    completion::{Chat, CompletionModel, Prompt, PromptError},
+    message::Message,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use serde_json::{Map, Value};
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+
+use crate::agents::DynAgent;
+
+mod audit;
+mod batch;
+mod budget;
+mod critic;
+mod environment;
+mod exit;
+mod extract;
+mod history;
+mod hooks;
+mod planner;
+mod report;
+mod retry;
+mod schedule;
+mod state;
+mod tasks;
+mod tools;
+
+pub use audit::{AuditEvent, AuditRecord, AuditWriter, InMemoryAuditLog, JsonlAuditWriter};
+pub use batch::BatchRunner;
+pub use budget::{BudgetLimit, TokenCounter, TokenPricing, Usage, estimate_tokens};
+pub use critic::{Critic, DEFAULT_CRITIC_TEMPLATE};
+pub use environment::{Environment, Observation};
+pub use exit::{ExitCheck, ExitCondition};
+pub use hooks::{
+    AfterTurnHook, Approval, ApprovalHook, BeforeTurnHook, ContextProvider, TurnContext,
+};
+pub use planner::{Plan, PlanStep, PlannerExecutor, StepStatus};
+pub use report::{RunReport, TurnRecord};
+pub use retry::{OnFailure, RetryPolicy};
+pub use schedule::{CronError, CronSchedule, Schedule, Timing};
+pub use state::{Checkpoint, FileStateStore, InMemoryStateStore, StateStore};
+pub use tasks::{CompletedTask, TaskQueue};
+pub use tools::ToolCallRecord;
+
+use audit::Auditing;
+use budget::Budget;
+use history::HistoryPolicy;
+use hooks::TurnHooks;
+use state::Checkpointing;
+
+/// The number of rounds an autonomous agent goes through before it stops, if not set.
+const DEFAULT_MAX_TURNS: u32 = 10;
+
+/// What an autonomous agent is doing, as reported by [`AutonomousAgent::run_stream`]. Turns are numbered from 1.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AutonomousEvent {
+    TurnStarted {
+        turn: u32,
+    },
+    TurnCompleted {
+        turn: u32,
+        response: String,
+        /// The tools the agent called during the round.
+        tool_calls: Vec<ToolCallRecord>,
+    },
+    /// The critic reviewed the round's response. The feedback is added to the next round's prompt.
+    Reflection {
+        turn: u32,
+        feedback: String,
+    },
+    /// The agent's response was carried out in its environment. The observation is the next round's prompt.
+    Observation {
+        turn: u32,
+        observation: String,
+    },
+    /// Tasks were added to the task queue, either when the goal was broken down or while doing a task.
+    TasksAdded {
+        turn: u32,
+        tasks: Vec<String>,
+    },
+    /// The round failed after its retries, and was skipped. See [`OnFailure::SkipTurn`].
+    TurnSkipped {
+        turn: u32,
+        error: String,
+    },
+    /// The run ended because the exit condition was met. This is the last event.
+    ExitConditionMet {
+        turn: u32,
+        response: String,
+    },
+    /// The run ended because every task in the task queue is done. This is the last event.
+    TasksCompleted {
+        turn: u32,
+        response: String,
+    },
+    /// The run ended because the environment reached a final state. This is the last event.
+    EnvironmentDone {
+        turn: u32,
+        response: String,
+    },
+    /// The run ended because it went through `max_turns` rounds. This is the last event.
+    MaxTurnsReached {
+        turns: u32,
+        response: String,
+    },
+    /// The run ended because it went over its token or cost budget. This is the last event.
+    BudgetExceeded {
+        turn: u32,
+        limit: BudgetLimit,
+        response: String,
+    },
+    /// The run ended because the approval hook aborted it. `response` is the rejected response. This is the last event.
+    Aborted {
+        turn: u32,
+        response: String,
+    },
+    /// The run ended because it went over `max_duration`. `response` is the last completed response. This is the last event.
+    TimedOut {
+        turn: u32,
+        response: String,
+    },
+    /// The run ended because it was cancelled. `response` is the last completed response. This is the last event.
+    Cancelled {
+        turn: u32,
+        response: String,
+    },
+}
+
+/// Why an autonomous run stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RunOutcome {
+    ExitConditionMet,
+    TasksCompleted,
+    EnvironmentDone,
+    MaxTurnsReached,
+    BudgetExceeded { limit: BudgetLimit },
+    Aborted,
+    Cancelled,
+    TimedOut,
+}
+
+pub struct AutonomousAgent<M, E>
+where
+    M: CompletionModel,
+{
+    /// Your agent.
+    agent: Agent<M>,
+    /// Decides when the agent has reached its goal. See [`ExitCondition`] for built-in conditions.
+    exit_condition: E,
+    /// The number of rounds that your autonomous agent may go through before it stops.
+    /// Use this as a failsafe in case it's possible for your agent to never reach the exit condition
+    max_turns: u32,
+    /// Internal chat history. Each round's prompt and response are added to it.
+    chat_history: Vec<Message>,
+    history_policy: HistoryPolicy,
+    /// The amount of delay between rounds.
+    delay_between_rounds: Duration,
+    /// How long a run may take.
+    max_duration: Option<Duration>,
+    /// The number of tool-calling turns the agent may take each round. With 0, tools aren't called.
+    turns_per_round: usize,
+    retry: RetryPolicy,
+    critic: Option<Critic>,
+    hooks: TurnHooks,
+    budget: Budget,
+    /// The first prompt of the last run.
+    goal: String,
+    /// The (estimated) token usage of the current run.
+    usage: Usage,
+    checkpoints: Option<Checkpointing>,
+    /// Saved with each checkpoint.
+    metadata: Map<String, Value>,
+    audit: Option<Auditing>,
+    environment: Option<Box<dyn Environment>>,
+    /// The task queue of the current run, in task queue mode.
+    tasks: Option<TaskQueue>,
+}
+
+impl<M, E> AutonomousAgent<M, E>
+where
+    M: CompletionModel,
+    E: ExitCheck,
+{
+    /// Create an autonomous agent with the default settings: up to 10 rounds, with no delay between them.
+    pub fn new(agent: Agent<M>, exit_condition: E) -> Self {
+        Self::builder(agent, exit_condition).build()
+    }
+
+    /// Create an instance of [`AutonomousAgentBuilder`].
+    pub fn builder(agent: Agent<M>, exit_condition: E) -> AutonomousAgentBuilder<M, E> {
+        AutonomousAgentBuilder::new(agent, exit_condition)
+    }
+
+    /// Run the agent until the exit condition is met, or until it's gone through `max_turns` rounds.
+    /// Each round, the agent is prompted with its previous response (starting with `prompt`).
+    /// Returns a report of the run, including its last response.
+    pub async fn run(&mut self, prompt: &str) -> Result<RunReport, anyhow::Error> {
+        self.run_with_events(prompt, &CancellationToken::new(), &mut |_| {})
+            .await
+    }
+
+    /// Like [`Self::run`], but stops early once `cancel` is cancelled. A round that's in progress is abandoned, which drops
+    /// the request to the provider, and the report covers the rounds that completed.
+    pub async fn run_cancellable(
+        &mut self,
+        prompt: &str,
+        cancel: CancellationToken,
+    ) -> Result<RunReport, anyhow::Error> {
+        self.run_with_events(prompt, &cancel, &mut |_| {}).await
+    }
+
+    /// Like [`Self::run`], but once the run ends, the agent is asked for its final answer as a JSON object following the
+    /// schema of `T`, which is parsed and returned. If the answer isn't valid, the agent is asked again with the error.
+    /// Runs that end for any reason other than meeting the exit condition or reaching `max_turns` return an error.
+    pub async fn run_extract<T>(&mut self, prompt: &str) -> Result<T, anyhow::Error>
+    where
+        T: JsonSchema + DeserializeOwned,
+    {
+        let report = self.run(prompt).await?;
+        match report.outcome {
+            RunOutcome::ExitConditionMet
+            | RunOutcome::TasksCompleted
+            | RunOutcome::EnvironmentDone
+            | RunOutcome::MaxTurnsReached => {}
+            outcome => {
+                anyhow::bail!("The run ended before a result could be extracted: {outcome:?}")
+            }
+        }
+
+        let mut request = extract::extraction_prompt::<T>()?;
+        let mut last_err = None;
+        for _ in 0..extract::EXTRACTION_ATTEMPTS {
+            self.audit(AuditEvent::Prompt {
+                turn: report.turns,
+                prompt: request.clone(),
+            })?;
+            let res = self
+                .agent
+                .chat(request.as_str(), self.chat_history.clone())
+                .await?;
+            self.audit(AuditEvent::Response {
+                turn: report.turns,
+                response: res.clone(),
+            })?;
+            self.chat_history.extend([
+                Message::user(request.as_str()),
+                Message::assistant(res.clone()),
+            ]);
+
+            match extract::parse(&res) {
+                Ok(value) => return Ok(value),
+                Err(err) => {
+                    request = format!(
+                        "That answer isn't valid: {err}. Respond with only the JSON object."
+                    );
+                    last_err = Some(err);
+                }
+            }
+        }
+
+        Err(anyhow::anyhow!(
+            "The agent's final answer isn't valid: {}",
+            last_err.expect("extraction is attempted at least once")
+        ))
+    }
+
+    /// Resume a run from its latest checkpoint in `store`, ie after the process restarted. The chat history, turn counter,
+    /// usage, metadata and task queue are restored, and the run continues to be checkpointed to `store`. Rounds count towards
+    /// `max_turns` across both runs, but the report only includes the rounds after the resume. If the run had already ended
+    /// after a round (it met its exit condition, completed its tasks, its environment was done, it went over its budget or
+    /// it was aborted), a report without any rounds is returned.
+    pub async fn resume<S>(&mut self, store: S, id: &str) -> Result<RunReport, anyhow::Error>
+    where
+        S: StateStore + 'static,
+    {
+        let checkpoint = store
+            .load(id)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("No checkpoint found for run {id}"))?;
+
+        self.chat_history = checkpoint.chat_history;
+        self.goal = checkpoint.goal;
+        self.usage = checkpoint.usage;
+        self.metadata = checkpoint.metadata;
+        if let Some(tasks) = checkpoint.tasks {
+            self.tasks = Some(tasks);
+        }
+        self.checkpoints = Some(Checkpointing {
+            store: Arc::new(store),
+            id: id.to_string(),
+        });
+
+        if let Some(
+            outcome @ (RunOutcome::ExitConditionMet
+            | RunOutcome::TasksCompleted
+            | RunOutcome::EnvironmentDone
+            | RunOutcome::BudgetExceeded { .. }
+            | RunOutcome::Aborted),
+        ) = checkpoint.outcome
+        {
+            return Ok(RunReport {
+                response: checkpoint.response,
+                outcome,
+                turns: checkpoint.turn,
+                elapsed: Duration::ZERO,
+                rounds: Vec::new(),
+                usage: self.usage,
+            });
+        }
+
+        self.run_rounds(
+            checkpoint.response,
+            checkpoint.turn,
+            &CancellationToken::new(),
+            &mut |_| {},
+        )
+        .await
+    }
+
+    /// The chat history, including the messages of the last run.
+    pub fn chat_history(&self) -> &[Message] {
+        &self.chat_history
+    }
+
+    /// The task queue of the last run, in task queue mode.
+    pub fn task_queue(&self) -> Option<&TaskQueue> {
+        self.tasks.as_ref()
+    }
+
+    /// Like [`Self::run`], but yields an [`AutonomousEvent`] as each round starts and completes, ie to show progress in a UI.
+    /// The stream ends after an exit event, or after an error.
+    pub fn run_stream<'a>(
+        &'a mut self,
+        prompt: &'a str,
+    ) -> impl Stream<Item = Result<AutonomousEvent, anyhow::Error>> + 'a {
+        let (tx, rx) = mpsc::unbounded();
+
+        let run = async move {
+            let mut emit = |event| {
+                let _ = tx.unbounded_send(Ok(event));
+            };
+
+            if let Err(err) = self
+                .run_with_events(prompt, &CancellationToken::new(), &mut emit)
+                .await
+            {
+                let _ = tx.unbounded_send(Err(err));
+            }
+        };
+
+        // The run only produces events through the channel, which closes once the run is done
+        stream::select(rx, run.into_stream().filter_map(|()| async { None }))
+    }
+
+    async fn run_with_events(
+        &mut self,
+        prompt: &str,
+        cancel: &CancellationToken,
+        emit: &mut (dyn FnMut(AutonomousEvent) + Send),
+    ) -> Result<RunReport, anyhow::Error> {
+        self.goal = prompt.to_owned();
+        self.usage = Usage::default();
+        if let Some(tasks) = &mut self.tasks {
+            *tasks = TaskQueue::new();
+        }
+
+        self.run_rounds(prompt.to_owned(), 0, cancel, emit).await
+    }
+
+    /// Run rounds, starting after `turn` rounds with `res` as the prompt.
+    async fn run_rounds(
+        &mut self,
+        mut res: String,
+        mut turn: u32,
+        cancel: &CancellationToken,
+        emit: &mut (dyn FnMut(AutonomousEvent) + Send),
+    ) -> Result<RunReport, anyhow::Error> {
+        let started = Instant::now();
+        let mut rounds = Vec::new();
+        let mut feedback: Option<String> = None;
+        let mut reflections = 0;
+        let deadline = self.max_duration.map(|x| started + x);
+        self.audit(AuditEvent::RunStarted {
+            goal: self.goal.clone(),
+            turn,
+        })?;
+        // In an environment, each round's prompt is an observation instead of the previous response
+        let mut observed = match &mut self.environment {
+            Some(environment) => {
+                let state = environment.observe().await?;
+                Some(match turn {
+                    0 => environment::first_prompt(&res, &state),
+                    _ => state,
+                })
+            }
+            None => None,
+        };
+
+        let outcome = loop {
+            if cancel.is_cancelled() {
+                tracing::info!("Run cancelled after {turn} turns");
+                emit(AutonomousEvent::Cancelled {
+                    turn,
+                    response: res.clone(),
+                });
+                break RunOutcome::Cancelled;
+            }
+            if deadline.is_some_and(|x| Instant::now() >= x) {
+                tracing::info!("Run timed out after {turn} turns");
+                emit(AutonomousEvent::TimedOut {
+                    turn,
+                    response: res.clone(),
+                });
+                break RunOutcome::TimedOut;
+            }
+            if turn >= self.max_turns {
+                tracing::info!("Max turns reached: {}", self.max_turns);
+                emit(AutonomousEvent::MaxTurnsReached {
+                    turns: turn,
+                    response: res.clone(),
+                });
+                break RunOutcome::MaxTurnsReached;
+            }
+            if turn > 0 && !self.delay_between_rounds.is_zero() {
+                tokio::select! {
+                    () = tokio::time::sleep(self.delay_between_rounds) => {}
+                    () = interrupted(cancel, deadline) => continue,
+                }
+            }
+
+            turn += 1;
+            emit(AutonomousEvent::TurnStarted { turn });
+            let prompt = match self.tasks.as_ref().and_then(|x| x.prompt(&self.goal)) {
+                Some(prompt) => prompt,
+                None => observed.take().unwrap_or_else(|| res.clone()),
+            };
+            let prompt = match &feedback {
+                Some(feedback) => critic::with_feedback(&prompt, feedback),
+                None => prompt,
+            };
+            let prompt = self.hooks.with_context(turn, prompt).await;
+            let prompt = self.hooks.before(turn, prompt).await;
+            self.audit(AuditEvent::Prompt {
+                turn,
+                prompt: prompt.clone(),
+            })?;
+            let round = tokio::select! {
+                round = self.complete_with_retries(turn, &prompt) => round?,
+                () = interrupted(cancel, deadline) => {
+                    // The round didn't complete
+                    turn -= 1;
+                    continue;
+                }
+            };
+            let (response, mut history) = match round {
+                Ok(round) => round,
+                Err(err) => {
+                    tracing::warn!("Skipping round {turn}: {err}");
+                    self.audit(AuditEvent::TurnSkipped {
+                        turn,
+                        error: err.to_string(),
+                    })?;
+                    emit(AutonomousEvent::TurnSkipped {
+                        turn,
+                        error: err.to_string(),
+                    });
+                    continue;
+                }
+            };
+            res = response;
+            let tool_calls = tools::tool_calls(turn, &history[self.chat_history.len()..]);
+            for call in &tool_calls {
+                self.audit(AuditEvent::ToolCall(call.clone()))?;
+            }
+            self.audit(AuditEvent::Response {
+                turn,
+                response: res.clone(),
+            })?;
+            let approval = self.hooks.approve(turn, &prompt, &res).await;
+            if let Approval::Edit(edited) = &approval {
+                res = edited.clone();
+                self.audit(AuditEvent::ResponseEdited {
+                    turn,
+                    response: res.clone(),
+                })?;
+                if let Some(last @ Message::Assistant { .. }) = history.last_mut() {
+                    *last = Message::assistant(res.clone());
+                }
+            }
+            self.usage += self.budget.round_usage(&prompt, &self.chat_history, &res);
+            self.chat_history = history;
+            self.history_policy
+                .apply(&mut self.chat_history, &|x| self.budget.message_tokens(x))
+                .await;
+            self.hooks.after(turn, &prompt, &res).await;
+            emit(AutonomousEvent::TurnCompleted {
+                turn,
+                response: res.clone(),
+                tool_calls: tool_calls.clone(),
+            });
+            rounds.push(TurnRecord {
+                turn,
+                prompt,
+                response: res.clone(),
+                tool_calls,
+            });
+
+            if approval != Approval::Abort
+                && let Some(tasks) = &mut self.tasks
+            {
+                let added = tasks.update(&res);
+                if !added.is_empty() {
+                    self.audit(AuditEvent::TasksAdded {
+                        turn,
+                        tasks: added.clone(),
+                    })?;
+                    emit(AutonomousEvent::TasksAdded { turn, tasks: added });
+                }
+            }
+
+            let observation = match &mut self.environment {
+                Some(environment) if approval != Approval::Abort => {
+                    let observation = environment.act(&res).await?;
+                    self.audit(AuditEvent::Observation {
+                        turn,
+                        observation: observation.text.clone(),
+                    })?;
+                    emit(AutonomousEvent::Observation {
+                        turn,
+                        observation: observation.text.clone(),
+                    });
+                    Some(observation)
+                }
+                _ => None,
+            };
+
+            let outcome = if approval == Approval::Abort {
+                tracing::info!("Run aborted after {turn} turns");
+                emit(AutonomousEvent::Aborted {
+                    turn,
+                    response: res.clone(),
+                });
+                Some(RunOutcome::Aborted)
+            } else if self.tasks.as_ref().is_some_and(TaskQueue::is_done) {
+                tracing::info!("Tasks completed after {turn} turns");
+                emit(AutonomousEvent::TasksCompleted {
+                    turn,
+                    response: res.clone(),
+                });
+                Some(RunOutcome::TasksCompleted)
+            } else if self.exit_condition.should_exit(&res).await {
+                tracing::info!("Exit condition met after {turn} turns");
+                emit(AutonomousEvent::ExitConditionMet {
+                    turn,
+                    response: res.clone(),
+                });
+                Some(RunOutcome::ExitConditionMet)
+            } else if observation.as_ref().is_some_and(|x| x.done) {
+                tracing::info!("Environment done after {turn} turns");
+                emit(AutonomousEvent::EnvironmentDone {
+                    turn,
+                    response: res.clone(),
+                });
+                Some(RunOutcome::EnvironmentDone)
+            } else if let Some(limit) = self.budget.exceeded(&self.usage) {
+                tracing::warn!("Budget exceeded after {turn} turns: {limit}");
+                emit(AutonomousEvent::BudgetExceeded {
+                    turn,
+                    limit,
+                    response: res.clone(),
+                });
+                Some(RunOutcome::BudgetExceeded { limit })
+            } else {
+                None
+            };
+
+            self.checkpoint(turn, &res, outcome).await?;
+            if let Some(outcome) = outcome {
+                break outcome;
+            }
+
+            observed = observation.map(|x| x.text);
+            feedback = None;
+            if let Some(critic) = &self.critic
+                && reflections < critic.max()
+                && turn < self.max_turns
+            {
+                feedback = critic.review(&self.goal, &res, turn).await;
+                if let Some(feedback) = &feedback {
+                    reflections += 1;
+                    self.audit(AuditEvent::Reflection {
+                        turn,
+                        feedback: feedback.clone(),
+                    })?;
+                    emit(AutonomousEvent::Reflection {
+                        turn,
+                        feedback: feedback.clone(),
+                    });
+                }
+            }
+        };
+        self.audit(AuditEvent::RunEnded { turn, outcome })?;
+
+        Ok(RunReport {
+            response: res,
+            outcome,
+            turns: turn,
+            elapsed: started.elapsed(),
+            rounds,
+            usage: self.usage,
+        })
+    }
+
+    /// Send a round's prompt to the agent, returning its response and the chat history with the round's messages added.
+    /// If the agent may call tools, the history includes the tool calls and their results.
+    async fn complete(&self, prompt: &str) -> Result<(String, Vec<Message>), PromptError> {
+        let mut history = self.chat_history.clone();
+
+        if self.turns_per_round > 0 {
+            let res = self
+                .agent
+                .prompt(prompt)
+                .multi_turn(self.turns_per_round)
+                .with_history(&mut history)
+                .await?;
+
+            return Ok((res, history));
+        }
+
+        let res = self.agent.chat(prompt, history.clone()).await?;
+        history.extend([Message::user(prompt), Message::assistant(res.clone())]);
+
+        Ok((res, history))
+    }
+
+    /// Complete a round, retrying it and handling failure as set by the retry policy.
+    /// Returns the error of the round if it should be skipped.
+    async fn complete_with_retries(
+        &self,
+        turn: u32,
+        prompt: &str,
+    ) -> Result<Result<(String, Vec<Message>), PromptError>, anyhow::Error> {
+        let err = match self.retry.retry(turn, || self.complete(prompt)).await {
+            Ok(round) => return Ok(Ok(round)),
+            Err(err) => err,
+        };
+
+        match self.retry.failure_policy() {
+            OnFailure::Abort => Err(err.into()),
+            OnFailure::SkipTurn => Ok(Err(err)),
+            OnFailure::Fallback(agent) => {
+                tracing::warn!("Round {turn} failed, using the fallback agent: {err}");
+                self.audit(AuditEvent::FallbackUsed {
+                    turn,
+                    error: err.to_string(),
+                })?;
+                let res = agent
+                    .dyn_chat(prompt.to_string(), self.chat_history.clone())
+                    .await?;
+                let mut history = self.chat_history.clone();
+                history.extend([Message::user(prompt), Message::assistant(res.clone())]);
+
+                Ok(Ok((res, history)))
+            }
+        }
+    }
+
+    /// Write an event to the audit trail, if an audit writer is set.
+    fn audit(&self, event: AuditEvent) -> Result<(), anyhow::Error> {
+        match &self.audit {
+            Some(audit) => audit.record(event),
+            None => Ok(()),
+        }
+    }
+
+    /// Save a checkpoint of the run, if a state store is set.
+    async fn checkpoint(
+        &self,
+        turn: u32,
+        response: &str,
+        outcome: Option<RunOutcome>,
+    ) -> Result<(), anyhow::Error> {
+        let Some(Checkpointing { store, id }) = &self.checkpoints else {
+            return Ok(());
+        };
+
+        store
+            .save(&Checkpoint {
+                id: id.clone(),
+                goal: self.goal.clone(),
+                turn,
+                response: response.to_string(),
+                chat_history: self.chat_history.clone(),
+                usage: self.usage,
+                metadata: self.metadata.clone(),
+                outcome,
+                tasks: self.tasks.clone(),
+            })
+            .await
+    }
+}
+
+/// Wait until the run is cancelled or reaches its deadline, if it has one.
+async fn interrupted(cancel: &CancellationToken, deadline: Option<Instant>) {
+    let timeout = async {
+        match deadline {
+            Some(deadline) => tokio::time::sleep_until(deadline).await,
+            None => std::future::pending().await,
+        }
+    };
+
+    tokio::select! {
+        () = cancel.cancelled() => {}
+        () = timeout => {}
+    }
+}
+
+/// A builder for [`AutonomousAgent`].
+pub struct AutonomousAgentBuilder<M, E>
+where
+    M: CompletionModel,
+{
+    agent: Agent<M>,
+    exit_condition: E,
+    max_turns: u32,
+    chat_history: Vec<Message>,
+    history_policy: HistoryPolicy,
+    delay_between_rounds: Duration,
+    max_duration: Option<Duration>,
+    turns_per_round: usize,
+    retry: RetryPolicy,
+    critic: Option<Critic>,
+    hooks: TurnHooks,
+    budget: Budget,
+    checkpoints: Option<Checkpointing>,
+    metadata: Map<String, Value>,
+    audit: Option<Auditing>,
+    environment: Option<Box<dyn Environment>>,
+    task_queue: bool,
+}
+
+impl<M, E> AutonomousAgentBuilder<M, E>
+where
+    M: CompletionModel,
+    E: ExitCheck,
+{
+    pub fn new(agent: Agent<M>, exit_condition: E) -> Self {
+        Self {
+            agent,
+            exit_condition,
+            max_turns: DEFAULT_MAX_TURNS,
+            chat_history: Vec::new(),
+            history_policy: HistoryPolicy::default(),
+            delay_between_rounds: Duration::ZERO,
+            max_duration: None,
+            turns_per_round: 0,
+            retry: RetryPolicy::default(),
+            critic: None,
+            hooks: TurnHooks::default(),
+            budget: Budget::default(),
+            checkpoints: None,
+            metadata: Map::new(),
+            audit: None,
+            environment: None,
+            task_queue: false,
+        }
+    }
+
+    /// Set the maximum number of rounds. At least one round is always run.
+    pub fn max_turns(mut self, max_turns: u32) -> Self {
+        self.max_turns = max_turns.max(1);
+
+        self
+    }
+
+    /// Wait between rounds, ie to stay within a provider's rate limits.
+    pub fn delay_between_rounds(mut self, delay: Duration) -> Self {
+        self.delay_between_rounds = delay;
+
+        self
+    }
+
+    /// Stop the run once it has taken this long, regardless of how many rounds it has gone through. A round that's in
+    /// progress is abandoned. When resuming a run, the time is counted from the resume.
+    pub fn max_duration(mut self, max_duration: Duration) -> Self {
+        self.max_duration = Some(max_duration);
+
+        self
+    }
+
+    /// Let the agent call its tools for up to this many turns each round, before it responds. The tools it calls are
+    /// reported in [`AutonomousEvent::TurnCompleted`] and in the [`RunReport`].
+    pub fn turns_per_round(mut self, turns: usize) -> Self {
+        self.turns_per_round = turns;
+
+        self
+    }
+
+    /// Retry rounds that fail with a transient error, and set what happens if they still fail (see [`RetryPolicy`]).
+    /// By default, the run ends with the error of the first failed round.
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry = policy;
+
+        self
+    }
+
+    /// Have a critic review each response against the run's goal, and add its feedback to the next round's prompt.
+    pub fn critic(mut self, critic: Critic) -> Self {
+        self.critic = Some(critic);
+
+        self
+    }
+
+    /// Start the agent with an existing chat history.
+    pub fn chat_history(mut self, history: Vec<Message>) -> Self {
+        self.chat_history = history;
+
+        self
+    }
+
+    /// Keep at most this many messages in the chat history, dropping the oldest ones.
+    pub fn max_history_messages(mut self, max: usize) -> Self {
+        self.history_policy.set_max_messages(max);
+
+        self
+    }
+
+    /// Keep the chat history under this many tokens, dropping the oldest messages. Tokens are counted with the
+    /// [token counter](Self::token_counter).
+    pub fn max_history_tokens(mut self, max: u64) -> Self {
+        self.history_policy.set_max_tokens(max);
+
+        self
+    }
+
+    /// Summarize messages with `agent` before they're dropped from the chat history, and keep the summary at the start
+    /// of the history instead. Has no effect unless the history is limited.
+    pub fn summarize_history<A>(mut self, agent: A) -> Self
+    where
+        A: DynAgent + 'static,
+    {
+        self.history_policy.set_summarizer(Arc::new(agent));
+
+        self
+    }
+
+    /// Run an async function before each round, and add what it returns to the end of the round's prompt, so the agent
+    /// sees changing external state (ie fresh data, the time or retrieved documents). Providers run in the order they're
+    /// added, before the before-turn hooks.
+    pub fn context_provider<F, ProviderFut>(mut self, provider: F) -> Self
+    where
+        F: Fn(TurnContext) -> ProviderFut + Send + Sync + 'static,
+        ProviderFut: Future<Output = Option<String>> + Send + 'static,
+    {
+        self.hooks
+            .add_context(Arc::new(move |ctx| provider(ctx).boxed()));
+
+        self
+    }
+
+    /// Run an async function before each round. It receives the round's prompt and returns the prompt to send instead,
+    /// so it can log the prompt or add to it. Hooks run in the order they're added.
+    pub fn on_before_turn<F, HookFut>(mut self, hook: F) -> Self
+    where
+        F: Fn(TurnContext) -> HookFut + Send + Sync + 'static,
+        HookFut: Future<Output = String> + Send + 'static,
+    {
+        self.hooks
+            .add_before(Arc::new(move |ctx| hook(ctx).boxed()));
+
+        self
+    }
+
+    /// Run an async function after each round, with the round's prompt and response, ie to log or persist them.
+    pub fn on_after_turn<F, HookFut>(mut self, hook: F) -> Self
+    where
+        F: Fn(TurnContext) -> HookFut + Send + Sync + 'static,
+        HookFut: Future<Output = ()> + Send + 'static,
+    {
+        self.hooks.add_after(Arc::new(move |ctx| hook(ctx).boxed()));
+
+        self
+    }
+
+    /// Ask an async function to approve each round's response before the run continues, ie to get a human's sign-off.
+    /// It can approve the response, replace it, or abort the run. Replacing a response also replaces it in the chat history.
+    pub fn approval<F, HookFut>(mut self, hook: F) -> Self
+    where
+        F: Fn(TurnContext) -> HookFut + Send + Sync + 'static,
+        HookFut: Future<Output = Approval> + Send + 'static,
+    {
+        self.hooks
+            .set_approval(Arc::new(move |ctx| hook(ctx).boxed()));
+
+        self
+    }
+
+    /// Stop the run once it has used more than this many tokens in total. Checked after each round, so the last round
+    /// can go over the limit.
+    pub fn max_total_tokens(mut self, max: u64) -> Self {
+        self.budget.set_max_total_tokens(max);
+
+        self
+    }
+
+    /// Stop the run once it has cost more than this (in US dollars) at the given pricing. Checked after each round,
+    /// so the last round can go over the limit.
+    pub fn max_cost(mut self, max: f64, pricing: TokenPricing) -> Self {
+        self.budget.set_max_cost(max, pricing);
+
+        self
+    }
+
+    /// Set how tokens are counted for budgets, ie with the model's tokenizer. Defaults to [`estimate_tokens`].
+    pub fn token_counter<F>(mut self, counter: F) -> Self
+    where
+        F: Fn(&str) -> u64 + Send + Sync + 'static,
+    {
+        self.budget.set_counter(Arc::new(counter));
+
+        self
+    }
+
+    /// Save a checkpoint of the run to `store` after each round, under the run id `id`.
+    /// The run can then be continued with [`AutonomousAgent::resume`].
+    pub fn state_store<S>(mut self, store: S, id: &str) -> Self
+    where
+        S: StateStore + 'static,
+    {
+        self.checkpoints = Some(Checkpointing {
+            store: Arc::new(store),
+            id: id.to_string(),
+        });
+
+        self
+    }
+
+    /// Add metadata to save with each checkpoint, ie the job that started the run.
+    pub fn checkpoint_metadata(mut self, key: &str, value: impl Into<Value>) -> Self {
+        self.metadata.insert(key.to_string(), value.into());
+
+        self
+    }
+
+    /// Write an audit trail of each run to `writer`, under the run id `id`: the prompts and responses of each round, the
+    /// tools the agent called, and the decisions made along the way (edits, fallbacks, skipped rounds, reflections and
+    /// why the run stopped), each with a timestamp. If a record can't be written, the run ends with the error.
+    pub fn audit_log<W>(mut self, writer: W, id: &str) -> Self
+    where
+        W: AuditWriter + 'static,
+    {
+        self.audit = Some(Auditing {
+            writer: Arc::new(writer),
+            id: id.to_string(),
+        });
+
+        self
+    }
+
+    /// Have the agent act in an environment: it's sent the environment's state with the first prompt, each response is
+    /// carried out as an action, and the resulting observation is the next prompt. The run ends once the environment
+    /// is done. Errors from the environment end the run.
+    pub fn environment<Env>(mut self, environment: Env) -> Self
+    where
+        Env: Environment + 'static,
+    {
+        self.environment = Some(Box::new(environment));
+
+        self
+    }
+
+    /// Run in task queue mode: the first round breaks the prompt down into a list of tasks (if the list isn't valid, the
+    /// next round asks again with the error), then each round does the next task, and may add new ones. The run ends once every task is done. Make sure `max_turns` leaves enough
+    /// rounds for the tasks. The queue is saved with each checkpoint, and can be read with [`AutonomousAgent::task_queue`].
+    pub fn task_queue(mut self) -> Self {
+        self.task_queue = true;
+
+        self
+    }
+
+    pub fn build(self) -> AutonomousAgent<M, E> {
+        AutonomousAgent {
+            agent: self.agent,
+            exit_condition: self.exit_condition,
+            max_turns: self.max_turns,
+            chat_history: self.chat_history,
+            history_policy: self.history_policy,
+            delay_between_rounds: self.delay_between_rounds,
+            max_duration: self.max_duration,
+            turns_per_round: self.turns_per_round,
+            retry: self.retry,
+            critic: self.critic,
+            hooks: self.hooks,
+            budget: self.budget,
+            goal: String::new(),
+            usage: Usage::default(),
+            checkpoints: self.checkpoints,
+            metadata: self.metadata,
+            audit: self.audit,
+            environment: self.environment,
+            tasks: self.task_queue.then(TaskQueue::new),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use futures::StreamExt;
+    use rig::{agent::AgentBuilder, message::Message};
+    use tokio_util::sync::CancellationToken;
+
+    use super::{
+        Approval, AutonomousAgent, AutonomousAgentBuilder, AutonomousEvent, ExitCondition,
+        InMemoryStateStore, OnFailure, RetryPolicy, RunOutcome,
+    };
+    use crate::test_utils::ScriptedModel;
+
+    fn builder(model: &ScriptedModel) -> AutonomousAgentBuilder<ScriptedModel, ExitCondition> {
+        AutonomousAgent::builder(
+            AgentBuilder::new(model.clone()).build(),
+            ExitCondition::contains("DONE"),
+        )
+    }
+
+    #[tokio::test]
+    async fn runs_rounds_until_the_exit_condition() {
+        let model = ScriptedModel::new([Ok("first"), Ok("second"), Ok("DONE")]);
+        let mut agent = builder(&model).max_turns(5).build();
+
+        let report = agent.run("Start").await.unwrap();
+        assert_eq!(report.outcome, RunOutcome::ExitConditionMet);
+        assert_eq!(report.turns, 3);
+        assert_eq!(report.response, "DONE");
+        assert!(report.usage.input_tokens > 0 && report.usage.output_tokens > 0);
+        let rounds: Vec<(u32, &str, &str)> = report
+            .rounds
+            .iter()
+            .map(|x| (x.turn, x.prompt.as_str(), x.response.as_str()))
+            .collect();
+        assert_eq!(
+            rounds,
+            [
+                (1, "Start", "first"),
+                (2, "first", "second"),
+                (3, "second", "DONE")
+            ]
+        );
+
+        // Each round is sent the history of the rounds before it
+        let requests = model.requests();
+        assert_eq!(requests[2].len(), 5);
+        assert_eq!(requests[2][3], Message::assistant("second"));
+        assert_eq!(agent.chat_history().len(), 6);
+
+        let model = ScriptedModel::new([Ok("a"), Ok("b"), Ok("c")]);
+        let report = builder(&model)
+            .max_turns(2)
+            .build()
+            .run("Start")
+            .await
+            .unwrap();
+        assert_eq!(report.outcome, RunOutcome::MaxTurnsReached);
+        assert_eq!((report.turns, report.response.as_str()), (2, "b"));
+    }
+
+    #[tokio::test]
+    async fn streams_events_in_order() {
+        let model = ScriptedModel::new([Ok("a"), Ok("DONE")]);
+        let mut agent = builder(&model).build();
+
+        let events: Vec<AutonomousEvent> = agent
+            .run_stream("Start")
+            .map(Result::unwrap)
+            .collect()
+            .await;
+        let completed = |turn, response: &str| AutonomousEvent::TurnCompleted {
+            turn,
+            response: response.into(),
+            tool_calls: Vec::new(),
+        };
+        assert_eq!(
+            events,
+            [
+                AutonomousEvent::TurnStarted { turn: 1 },
+                completed(1, "a"),
+                AutonomousEvent::TurnStarted { turn: 2 },
+                completed(2, "DONE"),
+                AutonomousEvent::ExitConditionMet {
+                    turn: 2,
+                    response: "DONE".into()
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn reports_rounds_completed_before_cancelling() {
+        let model = ScriptedModel::new([Ok("a"), Ok("b")]);
+        let cancel = CancellationToken::new();
+        let token = cancel.clone();
+        let mut agent = builder(&model)
+            .on_after_turn(move |_| {
+                token.cancel();
+                async {}
+            })
+            .build();
+
+        let report = agent.run_cancellable("Start", cancel).await.unwrap();
+        assert_eq!(report.outcome, RunOutcome::Cancelled);
+        assert_eq!(report.turns, 1);
+        assert_eq!(report.rounds.len(), 1);
+        assert_eq!(report.response, "a");
+    }
+
+    #[tokio::test]
+    async fn applies_approvals() {
+        let model = ScriptedModel::new([Ok("a"), Ok("b"), Ok("c")]);
+        let mut agent = builder(&model)
+            .approval(|ctx| async move {
+                match ctx.turn {
+                    1 => Approval::Edit("edited".into()),
+                    _ => Approval::Abort,
+                }
+            })
+            .build();
+
+        let report = agent.run("Start").await.unwrap();
+        assert_eq!(report.outcome, RunOutcome::Aborted);
+        assert_eq!(report.turns, 2);
+        assert_eq!(report.rounds[0].response, "edited");
+        assert_eq!(report.rounds[1].prompt, "edited");
+        assert_eq!(report.response, "b");
+
+        // The edit replaced the response in the history sent with the next round
+        assert_eq!(model.requests()[1][1], Message::assistant("edited"));
+    }
+
+    #[tokio::test]
+    async fn skipped_rounds_count_towards_max_turns() {
+        let model = ScriptedModel::new([Err("rate limited"), Ok("a"), Ok("b")]);
+        let mut agent = builder(&model)
+            .max_turns(2)
+            .retry_policy(RetryPolicy::new().on_failure(OnFailure::SkipTurn))
+            .build();
+
+        let skipped: Vec<u32> = agent
+            .run_stream("Start")
+            .filter_map(|event| async move {
+                match event.unwrap() {
+                    AutonomousEvent::TurnSkipped { turn, .. } => Some(turn),
+                    _ => None,
+                }
+            })
+            .collect()
+            .await;
+        assert_eq!(skipped, [1]);
+
+        let model = ScriptedModel::new([Err("rate limited"), Ok("a"), Ok("b")]);
+        let report = builder(&model)
+            .max_turns(2)
+            .retry_policy(RetryPolicy::new().on_failure(OnFailure::SkipTurn))
+            .build()
+            .run("Start")
+            .await
+            .unwrap();
+        assert_eq!(report.outcome, RunOutcome::MaxTurnsReached);
+        assert_eq!(report.turns, 2);
+        assert_eq!(report.rounds.len(), 1);
+        // The skipped round's prompt is sent again
+        assert_eq!(
+            (report.rounds[0].turn, report.rounds[0].prompt.as_str()),
+            (2, "Start")
+        );
+    }
+
+    #[tokio::test]
+    async fn asks_again_for_invalid_task_lists() {
+        let model = ScriptedModel::new([
+            Ok("First, find the population"),
+            Ok("{\"tasks\": [\"Find the population\", \"Find the area\"]}"),
+            Ok("68 million"),
+            Ok("551,695 km²"),
+        ]);
+        let mut agent = builder(&model).task_queue().max_turns(5).build();
+
+        let report = agent.run("Describe France").await.unwrap();
+        assert_eq!(report.outcome, RunOutcome::TasksCompleted);
+        assert_eq!(report.turns, 4);
+        assert!(
+            report.rounds[1]
+                .prompt
+                .contains("Your last answer isn't valid")
+        );
+        assert!(
+            report.rounds[2]
+                .prompt
+                .contains("Do this task, and respond with its result: Find the population")
+        );
+        let tasks = agent.task_queue().unwrap();
+        assert_eq!(tasks.rejected, None);
+        assert_eq!(tasks.completed.len(), 2);
+        assert_eq!(tasks.completed[1].result, "551,695 km²");
+    }
+
+    #[tokio::test]
+    async fn resumes_unfinished_runs() {
+        let store = InMemoryStateStore::new();
+
+        let model = ScriptedModel::new([Ok("a"), Err("down")]);
+        let res = builder(&model)
+            .state_store(store.clone(), "failed")
+            .build()
+            .run("Start")
+            .await;
+        assert!(res.is_err());
+
+        let model = ScriptedModel::new([Ok("DONE")]);
+        let report = builder(&model)
+            .build()
+            .resume(store.clone(), "failed")
+            .await
+            .unwrap();
+        assert_eq!(report.outcome, RunOutcome::ExitConditionMet);
+        assert_eq!(report.turns, 2);
+        assert_eq!(report.rounds[0].prompt, "a");
+
+        let model = ScriptedModel::new([Ok("a")]);
+        builder(&model)
+            .state_store(store.clone(), "aborted")
+            .approval(|_| async { Approval::Abort })
+            .build()
+            .run("Start")
+            .await
+            .unwrap();
+        let model = ScriptedModel::new([Ok("a")]);
+        builder(&model)
+            .state_store(store.clone(), "over_budget")
+            .max_total_tokens(1)
+            .build()
+            .run("Start")
+            .await
+            .unwrap();
+
+        // Runs that ended after a round aren't continued
+        let mut outcomes = Vec::new();
+        for id in ["aborted", "over_budget"] {
+            let model = ScriptedModel::default();
+            let report = builder(&model)
+                .build()
+                .resume(store.clone(), id)
+                .await
+                .unwrap();
+            assert_eq!(report.turns, 1);
+            assert!(report.rounds.is_empty());
+            assert!(model.requests().is_empty());
+            outcomes.push(report.outcome);
+        }
+        assert!(matches!(
+            outcomes[..],
+            [RunOutcome::Aborted, RunOutcome::BudgetExceeded { .. }]
+        ));
+    }
+
+    #[tokio::test]
+    async fn times_out_between_rounds() {
+        let model = ScriptedModel::new([Ok("a"), Ok("b")]);
+        let mut agent = builder(&model)
+            .max_duration(Duration::from_millis(50))
+            .delay_between_rounds(Duration::from_secs(10))
+            .build();
+
+        let report = agent.run("Start").await.unwrap();
+        assert_eq!(report.outcome, RunOutcome::TimedOut);
+        assert_eq!((report.turns, report.response.as_str()), (1, "a"));
+        assert!(report.elapsed < Duration::from_secs(10));
+    }
+}
@@ -0,0 +1,248 @@
+//! A planner/executor loop: a planner agent keeps a plan of steps up to date, and an executor agent performs them one by one.
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use super::extract::json_object;
+use crate::agents::DynAgent;
+
+/// The number of steps a planner/executor runs before it stops, if not set.
+const DEFAULT_MAX_STEPS: usize = 10;
+
+/// Whether a step of a [`Plan`] has been performed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StepStatus {
+    #[default]
+    Pending,
+    Done,
+}
+
+/// A step of a [`Plan`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlanStep {
+    pub description: String,
+    pub status: StepStatus,
+    /// The executor's response, once the step has been performed.
+    pub result: Option<String>,
+}
+
+/// The state of a planner/executor run: the goal, the steps performed so far and the steps still to do.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Plan {
+    pub goal: String,
+    /// Performed steps, followed by pending steps.
+    pub steps: Vec<PlanStep>,
+    /// Whether the planner considers the goal achieved.
+    pub done: bool,
+    /// The planner's final answer, once the goal is achieved.
+    pub answer: Option<String>,
+}
+
+/// What the planner responds with each round.
+#[derive(Debug, Deserialize)]
+struct PlanUpdate {
+    #[serde(default)]
+    steps: Vec<String>,
+    #[serde(default)]
+    done: bool,
+    #[serde(default)]
+    answer: Option<String>,
+}
+
+impl Plan {
+    pub fn new(goal: &str) -> Self {
+        Self {
+            goal: goal.to_string(),
+            steps: Vec::new(),
+            done: false,
+            answer: None,
+        }
+    }
+
+    /// The next step to perform, if there is one.
+    pub fn next_step(&self) -> Option<&PlanStep> {
+        self.steps.iter().find(|x| x.status == StepStatus::Pending)
+    }
+
+    /// Replace the pending steps with the planner's remaining steps.
+    fn update(&mut self, update: PlanUpdate) {
+        self.steps.retain(|x| x.status == StepStatus::Done);
+        self.steps
+            .extend(update.steps.into_iter().map(|description| PlanStep {
+                description,
+                status: StepStatus::Pending,
+                result: None,
+            }));
+        self.done = update.done;
+        self.answer = update.answer;
+    }
+
+    fn planner_prompt(&self) -> Result<String, serde_json::Error> {
+        let plan = serde_json::to_string_pretty(&self.steps)?;
+
+        Ok(format!(
+            "You are planning how to achieve a goal. Another agent performs the steps you plan, one at a time.\n\n\
+            Goal: {}\n\nThe plan so far, including the results of performed steps:\n{plan}\n\n\
+            Respond with only a JSON object with these fields:\n\
+            - \"steps\": the steps still to do, in order, as strings. Leave out steps that have been performed.\n\
+            - \"done\": true if the goal has been achieved, otherwise false.\n\
+            - \"answer\": if the goal has been achieved, the final answer.",
+            self.goal
+        ))
+    }
+
+    fn executor_prompt(&self, step: &PlanStep) -> String {
+        let performed = self
+            .steps
+            .iter()
+            .filter(|x| x.status == StepStatus::Done)
+            .map(|x| {
+                format!(
+                    "- {}: {}",
+                    x.description,
+                    x.result.as_deref().unwrap_or_default()
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        format!(
+            "You are performing one step of a plan.\n\nGoal: {}\n\nSteps performed so far, with their results:\n{performed}\n\n\
+            Perform this step, and respond with its result: {}",
+            self.goal, step.description
+        )
+    }
+}
+
+/// Parse the planner's response, allowing for text or a code fence around the JSON object.
+fn parse_update(response: &str) -> Result<PlanUpdate, anyhow::Error> {
+    serde_json::from_str(json_object(response))
+        .map_err(|err| anyhow::anyhow!("The planner responded with an invalid plan: {err}"))
+}
+
+/// Alternates between a planner agent, which keeps a [`Plan`] up to date, and an executor agent, which performs the
+/// plan's next step. The run ends once the planner says the goal is achieved, or after `max_steps` steps.
+///
+/// The agents can use different models, ie a strong model for planning and a cheap one for executing steps.
+#[derive(Clone)]
+pub struct PlannerExecutor {
+    planner: Arc<dyn DynAgent>,
+    executor: Arc<dyn DynAgent>,
+    max_steps: usize,
+    executor_turns: usize,
+}
+
+impl PlannerExecutor {
+    pub fn new<P, X>(planner: P, executor: X) -> Self
+    where
+        P: DynAgent + 'static,
+        X: DynAgent + 'static,
+    {
+        Self {
+            planner: Arc::new(planner),
+            executor: Arc::new(executor),
+            max_steps: DEFAULT_MAX_STEPS,
+            executor_turns: 0,
+        }
+    }
+
+    /// Set the maximum number of steps the executor performs.
+    pub fn max_steps(mut self, max_steps: usize) -> Self {
+        self.max_steps = max_steps;
+
+        self
+    }
+
+    /// Let the executor call its tools for up to this many turns for each step.
+    pub fn executor_turns(mut self, turns: usize) -> Self {
+        self.executor_turns = turns;
+
+        self
+    }
+
+    /// Plan and perform steps towards a goal. Returns the final plan, which is `done` if the goal was achieved.
+    pub async fn run(&self, goal: &str) -> Result<Plan, anyhow::Error> {
+        self.run_plan(Plan::new(goal)).await
+    }
+
+    /// Continue a plan, ie one saved from an earlier run.
+    pub async fn run_plan(&self, mut plan: Plan) -> Result<Plan, anyhow::Error> {
+        let mut steps = 0;
+
+        loop {
+            let response = self.planner.dyn_prompt(plan.planner_prompt()?, 0).await?;
+            plan.update(parse_update(&response)?);
+
+            if plan.done {
+                tracing::info!("Goal achieved after {steps} steps");
+                break;
+            }
+            let Some(step) = plan.next_step().cloned() else {
+                tracing::warn!("The planner has no steps left, but the goal isn't achieved");
+                break;
+            };
+            if steps >= self.max_steps {
+                tracing::info!("Max steps reached: {}", self.max_steps);
+                break;
+            }
+
+            tracing::debug!("Performing step: {}", step.description);
+            let result = self
+                .executor
+                .dyn_prompt(plan.executor_prompt(&step), self.executor_turns)
+                .await?;
+            if let Some(performed) = plan
+                .steps
+                .iter_mut()
+                .find(|x| x.status == StepStatus::Pending)
+            {
+                performed.status = StepStatus::Done;
+                performed.result = Some(result);
+            }
+            steps += 1;
+        }
+
+        Ok(plan)
+    }
+}
+
+impl std::fmt::Debug for PlannerExecutor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PlannerExecutor")
+            .field("max_steps", &self.max_steps)
+            .field("executor_turns", &self.executor_turns)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::{PlannerExecutor, StepStatus};
+    use crate::test_utils::Scripted;
+
+    #[tokio::test]
+    async fn plans_and_executes_steps() {
+        let planner = Scripted(Mutex::new(vec![
+            r#"```json
+{"steps": ["Find the population of France", "Find the population of Spain"], "done": false}
+```"#,
+            r#"{"steps": ["Find the population of Spain"], "done": false}"#,
+            r#"{"steps": [], "done": true, "answer": "France"}"#,
+        ]));
+        let executor = Scripted(Mutex::new(vec!["68 million", "48 million"]));
+
+        let plan = PlannerExecutor::new(planner, executor)
+            .run("Which is bigger, France or Spain?")
+            .await
+            .unwrap();
+
+        assert!(plan.done);
+        assert_eq!(plan.answer.as_deref(), Some("France"));
+        assert_eq!(plan.steps.len(), 2);
+        assert!(plan.steps.iter().all(|x| x.status == StepStatus::Done));
+        assert_eq!(plan.steps[1].result.as_deref(), Some("48 million"));
+    }
+}
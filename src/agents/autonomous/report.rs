@@ -0,0 +1,41 @@
+//! Reports of autonomous runs, for logging and auditing.
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use super::{RunOutcome, ToolCallRecord, Usage};
+
+/// A completed round of an autonomous run.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TurnRecord {
+    /// The round number, starting from 1.
+    pub turn: u32,
+    /// The prompt the agent was sent, after the before-turn hooks ran.
+    pub prompt: String,
+    /// The agent's response, after approval.
+    pub response: String,
+    pub tool_calls: Vec<ToolCallRecord>,
+}
+
+/// What happened during an autonomous run. See [`AutonomousAgent::run`](super::AutonomousAgent::run).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RunReport {
+    /// The last response, or the prompt if no round completed.
+    pub response: String,
+    /// Why the run stopped.
+    pub outcome: RunOutcome,
+    /// The number of rounds the run went through, including skipped rounds.
+    pub turns: u32,
+    pub elapsed: Duration,
+    /// The completed rounds, in order.
+    pub rounds: Vec<TurnRecord>,
+    /// The estimated token usage of the run.
+    pub usage: Usage,
+}
+
+impl RunReport {
+    /// The tools the agent called during the run, in order.
+    pub fn tool_calls(&self) -> impl Iterator<Item = &ToolCallRecord> {
+        self.rounds.iter().flat_map(|x| &x.tool_calls)
+    }
+}
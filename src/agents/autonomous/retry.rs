@@ -0,0 +1,197 @@
+//! Retries of failed rounds, so that a transient provider error (ie a rate limit) doesn't end a whole autonomous run.
+use std::{sync::Arc, time::Duration};
+
+use rig::completion::{CompletionError, PromptError};
+
+use crate::agents::DynAgent;
+
+/// What an autonomous agent does when a round still fails after its retries.
+#[derive(Clone, Default)]
+pub enum OnFailure {
+    /// End the run with the error.
+    #[default]
+    Abort,
+    /// Skip the round: it counts towards `max_turns`, and the next round is sent the same prompt.
+    SkipTurn,
+    /// Send the round's prompt and chat history to another agent, ie one using a different provider. If that fails too,
+    /// the run ends with its error.
+    Fallback(Arc<dyn DynAgent>),
+}
+
+impl std::fmt::Debug for OnFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Abort => f.write_str("Abort"),
+            Self::SkipTurn => f.write_str("SkipTurn"),
+            Self::Fallback(_) => f.write_str("Fallback"),
+        }
+    }
+}
+
+/// How an autonomous agent retries rounds that fail with a provider error.
+///
+/// Failed rounds are retried with exponential backoff, starting at `initial_backoff` and doubling with each retry up to
+/// `max_backoff`. Only transient errors are retried: HTTP errors (ie timeouts) and errors returned by the provider (ie rate
+/// limits). Other errors, such as a failed tool call or running out of tool-calling turns, would fail again, so they go
+/// straight to the [failure policy](OnFailure).
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    retries: usize,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    on_failure: OnFailure,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            retries: 0,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(30),
+            on_failure: OnFailure::Abort,
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Retry a failed round up to `retries` times.
+    pub fn retries(mut self, retries: usize) -> Self {
+        self.retries = retries;
+
+        self
+    }
+
+    /// Wait `initial` before the first retry, doubling the wait with each retry up to `max`.
+    pub fn backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.initial_backoff = initial;
+        self.max_backoff = max;
+
+        self
+    }
+
+    /// Set what happens when a round still fails after its retries. Defaults to [`OnFailure::Abort`].
+    pub fn on_failure(mut self, on_failure: OnFailure) -> Self {
+        self.on_failure = on_failure;
+
+        self
+    }
+
+    /// Send failed rounds to another agent. See [`OnFailure::Fallback`].
+    pub fn fallback_agent<A>(self, agent: A) -> Self
+    where
+        A: DynAgent + 'static,
+    {
+        self.on_failure(OnFailure::Fallback(Arc::new(agent)))
+    }
+
+    pub(super) fn failure_policy(&self) -> &OnFailure {
+        &self.on_failure
+    }
+
+    /// The wait before a retry, where the first retry is retry 0.
+    fn delay(&self, retry: usize) -> Duration {
+        let factor = 2u32.saturating_pow(retry.try_into().unwrap_or(u32::MAX));
+
+        self.initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+    }
+
+    /// Run `attempt`, retrying it with backoff if it fails with a transient error. Returns the error of the last attempt if
+    /// they all fail.
+    pub(super) async fn retry<T, F, Fut>(&self, turn: u32, mut attempt: F) -> Result<T, PromptError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, PromptError>>,
+    {
+        let mut retry = 0;
+
+        loop {
+            match attempt().await {
+                Ok(res) => return Ok(res),
+                Err(err) if retry < self.retries && is_transient(&err) => {
+                    let delay = self.delay(retry);
+                    tracing::warn!("Round {turn} failed, retrying in {delay:?}: {err}");
+                    tokio::time::sleep(delay).await;
+                    retry += 1;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+/// Whether an error may not happen again if the round is retried.
+fn is_transient(err: &PromptError) -> bool {
+    matches!(
+        err,
+        PromptError::CompletionError(
+            CompletionError::HttpError(_) | CompletionError::ProviderError(_)
+        )
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Duration,
+    };
+
+    use rig::completion::{CompletionError, PromptError};
+
+    use super::RetryPolicy;
+
+    #[tokio::test]
+    async fn retries_with_backoff() {
+        let policy = RetryPolicy::new()
+            .retries(2)
+            .backoff(Duration::from_millis(1), Duration::from_millis(3));
+        assert_eq!(policy.delay(0), Duration::from_millis(1));
+        assert_eq!(policy.delay(1), Duration::from_millis(2));
+        assert_eq!(policy.delay(5), Duration::from_millis(3));
+
+        let calls = AtomicUsize::new(0);
+        let res = policy
+            .retry(1, || async {
+                if calls.fetch_add(1, Ordering::SeqCst) < 2 {
+                    Err(PromptError::CompletionError(
+                        CompletionError::ProviderError("rate limited".into()),
+                    ))
+                } else {
+                    Ok("done")
+                }
+            })
+            .await;
+        assert_eq!(res.unwrap(), "done");
+
+        calls.store(0, Ordering::SeqCst);
+        let res: Result<(), _> = policy
+            .retry(1, || async {
+                calls.fetch_add(1, Ordering::SeqCst);
+                Err(PromptError::CompletionError(
+                    CompletionError::ProviderError("down".into()),
+                ))
+            })
+            .await;
+        assert!(res.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+        // Invalid responses would fail again
+        calls.store(0, Ordering::SeqCst);
+        let res: Result<(), _> = policy
+            .retry(1, || async {
+                calls.fetch_add(1, Ordering::SeqCst);
+                Err(PromptError::CompletionError(
+                    CompletionError::ResponseError("no content".into()),
+                ))
+            })
+            .await;
+        assert!(res.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+}
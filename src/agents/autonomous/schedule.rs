@@ -0,0 +1,311 @@
+//! Scheduled, recurring autonomous runs, ie for monitoring agents ("check the feed every hour and summarize changes").
+use std::time::Duration;
+
+use chrono::{DateTime, Datelike, NaiveDate, NaiveDateTime, TimeDelta, Timelike, Utc};
+use rig::completion::CompletionModel;
+use tokio::time::MissedTickBehavior;
+use tokio_util::sync::CancellationToken;
+
+use super::{AutonomousAgent, ExitCheck, RunReport};
+
+/// How far ahead a cron expression is searched for its next time, so that expressions that never match (ie the 31st
+/// of February) don't search forever.
+const MAX_CRON_SEARCH_DAYS: i64 = 366 * 5;
+
+/// An error from parsing a cron expression.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum CronError {
+    #[error("Expected 5 fields (minute, hour, day of month, month, day of week), got {0}")]
+    FieldCount(usize),
+    #[error("Invalid {field} field: {value}")]
+    InvalidField { field: &'static str, value: String },
+}
+
+/// A standard 5-field cron expression (minute, hour, day of month, month, day of week), evaluated in UTC.
+///
+/// Fields support `*`, values, ranges (`1-5`), lists (`1,15`) and steps (`*/15`, `0-30/10`). Days of the week are
+/// numbered from 0 (Sunday) to 6, and 7 is also Sunday. As with cron, if both the day of month and the day of week are
+/// restricted, a time matches if either matches.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronSchedule {
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+    /// Whether the day of month and day of week fields are `*`.
+    any_day_of_month: bool,
+    any_day_of_week: bool,
+}
+
+impl CronSchedule {
+    pub fn parse(expression: &str) -> Result<Self, CronError> {
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        let [minutes, hours, days_of_month, months, days_of_week] = fields[..] else {
+            return Err(CronError::FieldCount(fields.len()));
+        };
+
+        let mut days_of_week_bits = parse_field("day of week", days_of_week, 0, 7)?;
+        // 7 is also Sunday
+        if days_of_week_bits & (1 << 7) != 0 {
+            days_of_week_bits = (days_of_week_bits | 1) & !(1 << 7);
+        }
+
+        Ok(Self {
+            minutes: parse_field("minute", minutes, 0, 59)?,
+            hours: parse_field("hour", hours, 0, 23)?,
+            days_of_month: parse_field("day of month", days_of_month, 1, 31)?,
+            months: parse_field("month", months, 1, 12)?,
+            days_of_week: days_of_week_bits,
+            any_day_of_month: days_of_month.starts_with('*'),
+            any_day_of_week: days_of_week.starts_with('*'),
+        })
+    }
+
+    /// The first time after `after` that matches the expression, if there is one within the next few years.
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let start = after.naive_utc().with_second(0)?.with_nanosecond(0)? + TimeDelta::minutes(1);
+        let limit = start + TimeDelta::days(MAX_CRON_SEARCH_DAYS);
+        let mut time = start;
+
+        while time < limit {
+            if !matches(self.months, time.month()) {
+                let (year, month) = match time.month() {
+                    12 => (time.year() + 1, 1),
+                    month => (time.year(), month + 1),
+                };
+                time = NaiveDate::from_ymd_opt(year, month, 1)?.and_hms_opt(0, 0, 0)?;
+            } else if !self.matches_day(&time) {
+                time = (time.date() + TimeDelta::days(1)).and_hms_opt(0, 0, 0)?;
+            } else if !matches(self.hours, time.hour()) {
+                time = time.with_minute(0)? + TimeDelta::hours(1);
+            } else if !matches(self.minutes, time.minute()) {
+                time += TimeDelta::minutes(1);
+            } else {
+                return Some(time.and_utc());
+            }
+        }
+
+        None
+    }
+
+    fn matches_day(&self, time: &NaiveDateTime) -> bool {
+        let day_of_month = matches(self.days_of_month, time.day());
+        let day_of_week = matches(self.days_of_week, time.weekday().num_days_from_sunday());
+
+        if self.any_day_of_month || self.any_day_of_week {
+            day_of_month && day_of_week
+        } else {
+            day_of_month || day_of_week
+        }
+    }
+}
+
+impl std::str::FromStr for CronSchedule {
+    type Err = CronError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+fn matches(bits: u64, value: u32) -> bool {
+    bits & (1 << value) != 0
+}
+
+/// Parse a cron field into a bitset of the values it matches.
+fn parse_field(field: &'static str, text: &str, min: u32, max: u32) -> Result<u64, CronError> {
+    let invalid = || CronError::InvalidField {
+        field,
+        value: text.to_string(),
+    };
+    let parse = |value: &str| value.parse::<u32>().map_err(|_| invalid());
+    let mut bits = 0;
+
+    for part in text.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, Some(parse(step)?)),
+            None => (part, None),
+        };
+        let (start, end) = match range.split_once('-') {
+            _ if range == "*" => (min, max),
+            Some((start, end)) => (parse(start)?, parse(end)?),
+            // A single value with a step, ie `5/15`, runs from the value to the end of the range
+            None if step.is_some() => (parse(range)?, max),
+            None => (parse(range)?, parse(range)?),
+        };
+        let step = step.unwrap_or(1);
+
+        if start < min || end > max || start > end || step == 0 {
+            return Err(invalid());
+        }
+        for value in (start..=end).step_by(step as usize) {
+            bits |= 1 << value;
+        }
+    }
+
+    Ok(bits)
+}
+
+/// When a [`Schedule`] runs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Timing {
+    /// Run straight away, then every interval. If a run takes longer than the interval, the next run starts once it's done.
+    Interval(Duration),
+    /// Run at the times that match a cron expression. Times that pass while a run is going are skipped.
+    Cron(CronSchedule),
+}
+
+/// Re-runs an autonomous agent on a schedule, delivering each run's report to a callback.
+///
+/// Each run gets a fresh agent from `make_agent`, which receives the index of the run, so that runs don't share chat
+/// history. To receive reports through a channel instead, send them from the callback.
+#[derive(Debug, Clone)]
+pub struct Schedule<F> {
+    make_agent: F,
+    prompt: String,
+    timing: Timing,
+    max_runs: Option<usize>,
+    cancel: CancellationToken,
+}
+
+impl<F, M, E> Schedule<F>
+where
+    F: Fn(usize) -> AutonomousAgent<M, E>,
+    M: CompletionModel,
+    E: ExitCheck,
+{
+    /// Run the agent with `prompt` straight away, then every `interval`.
+    pub fn every(interval: Duration, prompt: &str, make_agent: F) -> Self {
+        Self::new(Timing::Interval(interval), prompt, make_agent)
+    }
+
+    /// Run the agent with `prompt` at the times that match a cron expression (in UTC), ie `0 * * * *` for every hour.
+    pub fn cron(expression: &str, prompt: &str, make_agent: F) -> Result<Self, CronError> {
+        Ok(Self::new(
+            Timing::Cron(CronSchedule::parse(expression)?),
+            prompt,
+            make_agent,
+        ))
+    }
+
+    pub fn new(timing: Timing, prompt: &str, make_agent: F) -> Self {
+        Self {
+            make_agent,
+            prompt: prompt.to_string(),
+            timing,
+            max_runs: None,
+            cancel: CancellationToken::new(),
+        }
+    }
+
+    /// Stop after this many runs. By default, the schedule runs until it's cancelled.
+    pub fn max_runs(mut self, max_runs: usize) -> Self {
+        self.max_runs = Some(max_runs);
+
+        self
+    }
+
+    /// Stop the schedule once `cancel` is cancelled. A run that's in progress is cancelled too, and its report delivered.
+    pub fn cancel_token(mut self, cancel: CancellationToken) -> Self {
+        self.cancel = cancel;
+
+        self
+    }
+
+    /// Run the schedule, calling `on_report` with the result of each run. Failed runs don't stop the schedule.
+    /// Returns once the schedule is cancelled or has done `max_runs` runs.
+    pub async fn run<C>(&self, mut on_report: C)
+    where
+        C: FnMut(Result<RunReport, anyhow::Error>),
+    {
+        let mut interval = match &self.timing {
+            Timing::Interval(period) => {
+                let mut interval = tokio::time::interval(*period);
+                interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+                Some(interval)
+            }
+            Timing::Cron(_) => None,
+        };
+
+        for index in 0.. {
+            if self.max_runs.is_some_and(|max| index >= max) {
+                break;
+            }
+
+            let wait = async {
+                match (&mut interval, &self.timing) {
+                    (Some(interval), _) => {
+                        interval.tick().await;
+                    }
+                    (None, Timing::Cron(cron)) => {
+                        let Some(next) = cron.next_after(Utc::now()) else {
+                            tracing::warn!("Cron schedule has no upcoming times");
+                            return false;
+                        };
+                        let delay = (next - Utc::now()).to_std().unwrap_or_default();
+                        tokio::time::sleep(delay).await;
+                    }
+                    (None, Timing::Interval(_)) => {
+                        unreachable!("interval timings have an interval")
+                    }
+                }
+
+                true
+            };
+
+            tokio::select! {
+                scheduled = wait => if !scheduled { break },
+                () = self.cancel.cancelled() => break,
+            }
+
+            tracing::debug!("Starting scheduled run {index}");
+            let mut agent = (self.make_agent)(index);
+            on_report(
+                agent
+                    .run_cancellable(&self.prompt, self.cancel.child_token())
+                    .await,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use super::{CronError, CronSchedule};
+
+    #[test]
+    fn finds_next_cron_time() {
+        let at = |y, mo, d, h, mi| Utc.with_ymd_and_hms(y, mo, d, h, mi, 0).unwrap();
+
+        let hourly = CronSchedule::parse("0 * * * *").unwrap();
+        assert_eq!(
+            hourly.next_after(at(2025, 1, 1, 10, 30)),
+            Some(at(2025, 1, 1, 11, 0))
+        );
+
+        // Every 15 minutes during working hours on weekdays; 2025-01-04 is a Saturday
+        let weekdays = CronSchedule::parse("*/15 9-17 * * 1-5").unwrap();
+        assert_eq!(
+            weekdays.next_after(at(2025, 1, 3, 17, 50)),
+            Some(at(2025, 1, 6, 9, 0))
+        );
+
+        let yearly = CronSchedule::parse("0 0 1 1 *").unwrap();
+        assert_eq!(
+            yearly.next_after(at(2025, 1, 1, 0, 0)),
+            Some(at(2026, 1, 1, 0, 0))
+        );
+
+        assert_eq!(
+            CronSchedule::parse("0 0 31 2 *")
+                .unwrap()
+                .next_after(at(2025, 1, 1, 0, 0)),
+            None
+        );
+        assert_eq!(CronSchedule::parse("* * *"), Err(CronError::FieldCount(3)));
+        assert!(CronSchedule::parse("60 * * * *").is_err());
+    }
+}
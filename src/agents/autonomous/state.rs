@@ -0,0 +1,185 @@
+//! Checkpointing of autonomous runs, so that long-running jobs can be resumed after the process restarts.
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    path::PathBuf,
+    sync::{Arc, RwLock},
+};
+
+use futures::future::BoxFuture;
+use rig::message::Message;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use super::{RunOutcome, TaskQueue, Usage};
+
+/// The state of an autonomous run after a round, as saved to a [`StateStore`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// The id of the run.
+    pub id: String,
+    /// The run's first prompt.
+    #[serde(default)]
+    pub goal: String,
+    /// The number of rounds completed.
+    pub turn: u32,
+    /// The last response, which is the next round's prompt.
+    pub response: String,
+    pub chat_history: Vec<Message>,
+    pub usage: Usage,
+    #[serde(default)]
+    pub metadata: Map<String, Value>,
+    /// Why the run stopped, if it stopped after this round.
+    #[serde(default)]
+    pub outcome: Option<RunOutcome>,
+    /// The task queue, in task queue mode.
+    #[serde(default)]
+    pub tasks: Option<TaskQueue>,
+}
+
+/// Storage for checkpoints of autonomous runs, keyed by run id. A checkpoint is saved after every round, and a failed
+/// save ends the run with the error, so that a run never gets ahead of what it can be resumed from.
+pub trait StateStore: Debug + Send + Sync {
+    /// Save a checkpoint, replacing any previous checkpoint of the same run.
+    fn save<'a>(&'a self, checkpoint: &'a Checkpoint) -> BoxFuture<'a, Result<(), anyhow::Error>>;
+
+    /// Load the latest checkpoint of a run, if there is one.
+    fn load<'a>(&'a self, id: &'a str) -> BoxFuture<'a, Result<Option<Checkpoint>, anyhow::Error>>;
+}
+
+/// An in-memory state store, for tests or for runs that only need to be resumed within the same process. Keep a clone
+/// of the store around to resume a run after its agent is dropped.
+#[derive(Clone, Debug, Default)]
+pub struct InMemoryStateStore {
+    inner: Arc<RwLock<HashMap<String, Checkpoint>>>,
+}
+
+impl InMemoryStateStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl StateStore for InMemoryStateStore {
+    fn save<'a>(&'a self, checkpoint: &'a Checkpoint) -> BoxFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            self.inner
+                .write()
+                .map_err(|_| anyhow::anyhow!("State store lock poisoned"))?
+                .insert(checkpoint.id.clone(), checkpoint.clone());
+
+            Ok(())
+        })
+    }
+
+    fn load<'a>(&'a self, id: &'a str) -> BoxFuture<'a, Result<Option<Checkpoint>, anyhow::Error>> {
+        Box::pin(async move {
+            Ok(self
+                .inner
+                .read()
+                .map_err(|_| anyhow::anyhow!("State store lock poisoned"))?
+                .get(id)
+                .cloned())
+        })
+    }
+}
+
+/// An on-disk state store. Each run's checkpoint is stored as a JSON file named after the run id, so ids should be valid
+/// file names. Files are read and written on tokio's blocking thread pool.
+#[derive(Clone, Debug)]
+pub struct FileStateStore {
+    dir: PathBuf,
+}
+
+impl FileStateStore {
+    /// Creates a new on-disk store in the given directory, creating the directory and its parents as needed.
+    pub fn new<P>(dir: P) -> std::io::Result<Self>
+    where
+        P: Into<PathBuf>,
+    {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)?;
+
+        Ok(Self { dir })
+    }
+
+    fn path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{id}.json"))
+    }
+}
+
+impl StateStore for FileStateStore {
+    fn save<'a>(&'a self, checkpoint: &'a Checkpoint) -> BoxFuture<'a, Result<(), anyhow::Error>> {
+        let path = self.path(&checkpoint.id);
+
+        Box::pin(async move {
+            let bytes = serde_json::to_vec(checkpoint)?;
+            tokio::task::spawn_blocking(move || {
+                // Write to a temporary file first, so that a crash mid-write doesn't corrupt the last checkpoint
+                let tmp = path.with_extension("json.tmp");
+                std::fs::write(&tmp, bytes)?;
+                std::fs::rename(tmp, path)
+            })
+            .await??;
+
+            Ok(())
+        })
+    }
+
+    fn load<'a>(&'a self, id: &'a str) -> BoxFuture<'a, Result<Option<Checkpoint>, anyhow::Error>> {
+        let path = self.path(id);
+
+        Box::pin(async move {
+            match tokio::task::spawn_blocking(move || std::fs::read(path)).await? {
+                Ok(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+                Err(err) => Err(err.into()),
+            }
+        })
+    }
+}
+
+/// Where and under which id an autonomous agent saves its checkpoints.
+#[derive(Clone, Debug)]
+pub(super) struct Checkpointing {
+    pub(super) store: Arc<dyn StateStore>,
+    pub(super) id: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use rig::message::Message;
+
+    use super::{Checkpoint, FileStateStore, StateStore};
+    use crate::agents::autonomous::{RunOutcome, Usage};
+
+    #[tokio::test]
+    async fn saves_and_loads_checkpoints() {
+        let dir = std::env::temp_dir().join(format!("autonomous-state-{}", std::process::id()));
+        let store = FileStateStore::new(&dir).unwrap();
+        assert_eq!(store.load("run").await.unwrap(), None);
+
+        let mut checkpoint = Checkpoint {
+            id: "run".to_string(),
+            goal: "Start".to_string(),
+            turn: 1,
+            response: "Step one done".to_string(),
+            chat_history: vec![Message::user("Start"), Message::assistant("Step one done")],
+            usage: Usage {
+                input_tokens: 10,
+                output_tokens: 4,
+            },
+            metadata: Default::default(),
+            outcome: None,
+            tasks: None,
+        };
+        store.save(&checkpoint).await.unwrap();
+
+        checkpoint.turn = 2;
+        checkpoint.outcome = Some(RunOutcome::ExitConditionMet);
+        store.save(&checkpoint).await.unwrap();
+        assert_eq!(store.load("run").await.unwrap(), Some(checkpoint));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
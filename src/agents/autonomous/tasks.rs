@@ -0,0 +1,169 @@
+//! Task queue mode: the agent breaks its goal down into tasks, then works through them one per round.
+use std::collections::VecDeque;
+
+use serde::{Deserialize, Serialize};
+
+use super::extract::json_object;
+
+/// A task the agent has completed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CompletedTask {
+    pub task: String,
+    /// The agent's response to the task.
+    pub result: String,
+}
+
+/// The tasks of an autonomous run in task queue mode. See
+/// [`AutonomousAgentBuilder::task_queue`](super::AutonomousAgentBuilder::task_queue).
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TaskQueue {
+    /// Whether the goal has been broken down into tasks yet.
+    pub decomposed: bool,
+    /// The tasks still to do, in order. The first one is done next.
+    pub pending: VecDeque<String>,
+    pub completed: Vec<CompletedTask>,
+    /// Why the agent's last attempt at breaking the goal down was rejected, if it was. The agent is told when it's asked
+    /// again.
+    #[serde(default)]
+    pub rejected: Option<String>,
+}
+
+/// What the agent responds with when it breaks the goal down.
+#[derive(Debug, Deserialize)]
+struct Decomposition {
+    tasks: Vec<String>,
+}
+
+/// What the agent may end its response to a task with.
+#[derive(Debug, Deserialize)]
+struct NewTasks {
+    new_tasks: Vec<String>,
+}
+
+impl TaskQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether the goal has been broken down, and every task is done.
+    pub fn is_done(&self) -> bool {
+        self.decomposed && self.pending.is_empty()
+    }
+
+    /// The prompt of the next round: to break the goal down, or to do the next task.
+    pub(super) fn prompt(&self, goal: &str) -> Option<String> {
+        if !self.decomposed {
+            let prompt = format!(
+                "Break this goal down into tasks that can be done one at a time, in order.\n\nGoal: {goal}\n\n\
+                Respond with only a JSON object with a \"tasks\" field: the tasks, in order, as strings."
+            );
+
+            return Some(match &self.rejected {
+                Some(err) => format!("{prompt}\n\nYour last answer isn't valid: {err}."),
+                None => prompt,
+            });
+        }
+
+        let task = self.pending.front()?;
+        let completed = self
+            .completed
+            .iter()
+            .map(|x| format!("- {}: {}", x.task, x.result))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let upcoming = self
+            .pending
+            .iter()
+            .skip(1)
+            .map(|x| format!("- {x}"))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        Some(format!(
+            "You are working through a list of tasks towards a goal.\n\nGoal: {goal}\n\n\
+            Tasks completed so far, with their results:\n{completed}\n\nTasks to do after this one:\n{upcoming}\n\n\
+            Do this task, and respond with its result: {task}\n\n\
+            If you find that more tasks are needed, end your response with a JSON object like \
+            {{\"new_tasks\": [\"...\"]}}."
+        ))
+    }
+
+    /// Update the queue with the agent's response to the last prompt. Returns the tasks that were added. If the
+    /// response to the prompt to break the goal down isn't a valid task list, the agent is asked again in the next round.
+    pub(super) fn update(&mut self, response: &str) -> Vec<String> {
+        if !self.decomposed {
+            return match serde_json::from_str::<Decomposition>(json_object(response)) {
+                Ok(Decomposition { tasks }) => {
+                    self.decomposed = true;
+                    self.rejected = None;
+                    self.pending.extend(tasks.iter().cloned());
+
+                    tasks
+                }
+                Err(err) => {
+                    tracing::warn!("The agent responded with an invalid task list: {err}");
+                    self.rejected = Some(err.to_string());
+
+                    Vec::new()
+                }
+            };
+        }
+
+        if let Some(task) = self.pending.pop_front() {
+            self.completed.push(CompletedTask {
+                task,
+                result: response.to_string(),
+            });
+        }
+        let tasks = new_tasks(response);
+        self.pending.extend(tasks.iter().cloned());
+
+        tasks
+    }
+}
+
+/// The new tasks at the end of a response to a task, if there are any.
+fn new_tasks(response: &str) -> Vec<String> {
+    // The response itself may contain JSON, so only look at the object with the new tasks
+    let start = response
+        .rfind("\"new_tasks\"")
+        .and_then(|end| response[..end].rfind('{'));
+
+    start
+        .and_then(|start| serde_json::from_str::<NewTasks>(json_object(&response[start..])).ok())
+        .map(|x| x.new_tasks)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TaskQueue;
+
+    #[test]
+    fn works_through_tasks() {
+        let mut queue = TaskQueue::new();
+        assert!(queue.prompt("Compare France and Spain").is_some());
+        assert!(!queue.is_done());
+
+        let added = queue
+            .update("```json\n{\"tasks\": [\"Find the population of France\", \"Find the population of Spain\"]}\n```");
+        assert_eq!(added.len(), 2);
+        assert!(
+            queue
+                .prompt("Compare France and Spain")
+                .unwrap()
+                .contains("respond with its result: Find the population of France")
+        );
+
+        let added = queue
+            .update("68 million, see {\"source\": \"INSEE\"}\n\n{\"new_tasks\": [\"Find the area of France\"]}");
+        assert_eq!(added, vec!["Find the area of France".to_string()]);
+        assert_eq!(queue.completed[0].task, "Find the population of France");
+
+        queue.update("48 million");
+        queue.update("551,695 km²");
+        assert!(queue.is_done());
+        assert_eq!(queue.completed.len(), 3);
+        assert_eq!(queue.prompt("Compare France and Spain"), None);
+    }
+}
@@ -0,0 +1,33 @@
+//! Tracking of the tools an autonomous agent calls.
+use rig::message::{AssistantContent, Message};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// A tool call the agent made during a round.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolCallRecord {
+    /// The round the tool was called in, starting from 1.
+    pub turn: u32,
+    pub name: String,
+    pub arguments: Value,
+}
+
+/// The tool calls in the given messages.
+pub(super) fn tool_calls(turn: u32, messages: &[Message]) -> Vec<ToolCallRecord> {
+    messages
+        .iter()
+        .filter_map(|message| match message {
+            Message::Assistant { content, .. } => Some(content.iter()),
+            _ => None,
+        })
+        .flatten()
+        .filter_map(|content| match content {
+            AssistantContent::ToolCall(call) => Some(ToolCallRecord {
+                turn,
+                name: call.function.name.clone(),
+                arguments: call.function.arguments.clone(),
+            }),
+            _ => None,
+        })
+        .collect()
+}
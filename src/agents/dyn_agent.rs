@@ -0,0 +1,91 @@
+//! Type-erased agents, so that agents of different completion models can be used interchangeably.
+use futures::{
+    StreamExt,
+    future::BoxFuture,
+    stream::{self, BoxStream},
+};
+use rig::{
+    agent::Agent,
+    completion::{Chat, CompletionModel, Prompt, PromptError},
+    message::{AssistantContent, Message},
+    streaming::StreamingPrompt,
+};
+
+/// A stream of text chunks from a [`DynAgent`].
+pub type AgentStream = BoxStream<'static, Result<String, PromptError>>;
+
+/// A dyn-compatible handle to an agent, ie one that a route sends queries to or that judges an autonomous agent's responses.
+///
+/// This is implemented for every [`Agent`], so that agents using different completion models (ie a local Candle model for one route and GPT-4o for another) can be used together.
+/// Implement it yourself to use anything else that can answer a prompt.
+pub trait DynAgent: Send + Sync {
+    /// Prompt the agent with a query. If `turns` is above 0, the agent may call tools for up to that many turns before answering.
+    fn dyn_prompt(&self, query: String, turns: usize)
+    -> BoxFuture<'_, Result<String, PromptError>>;
+
+    /// Prompt the agent with a query, as the next message in a conversation.
+    /// By default, the history is ignored and the agent is prompted with just the query.
+    fn dyn_chat(
+        &self,
+        query: String,
+        history: Vec<Message>,
+    ) -> BoxFuture<'_, Result<String, PromptError>> {
+        let _ = history;
+        self.dyn_prompt(query, 0)
+    }
+
+    /// Prompt the agent with a query, streaming the text of its response.
+    /// By default, this awaits the full response and yields it as a single chunk.
+    fn dyn_stream_prompt(&self, query: String) -> BoxFuture<'_, Result<AgentStream, PromptError>> {
+        Box::pin(async move {
+            let res = self.dyn_prompt(query, 0).await?;
+
+            Ok(stream::once(async move { Ok(res) }).boxed())
+        })
+    }
+}
+
+impl<M> DynAgent for Agent<M>
+where
+    M: CompletionModel,
+    M::StreamingResponse: 'static,
+{
+    fn dyn_prompt(
+        &self,
+        query: String,
+        turns: usize,
+    ) -> BoxFuture<'_, Result<String, PromptError>> {
+        Box::pin(async move {
+            if turns > 0 {
+                self.prompt(query).multi_turn(turns).await
+            } else {
+                self.prompt(query).await
+            }
+        })
+    }
+
+    fn dyn_chat(
+        &self,
+        query: String,
+        history: Vec<Message>,
+    ) -> BoxFuture<'_, Result<String, PromptError>> {
+        Box::pin(async move { self.chat(query, history).await })
+    }
+
+    fn dyn_stream_prompt(&self, query: String) -> BoxFuture<'_, Result<AgentStream, PromptError>> {
+        Box::pin(async move {
+            let response = self.stream_prompt(query.as_str()).await?;
+
+            // Tool calls aren't surfaced, only the text of the response
+            let stream = response.filter_map(|chunk| async move {
+                match chunk {
+                    Ok(AssistantContent::Text(text)) => Some(Ok(text.text)),
+                    Ok(_) => None,
+                    Err(err) => Some(Err(PromptError::from(err))),
+                }
+            });
+
+            Ok(stream.boxed())
+        })
+    }
+}
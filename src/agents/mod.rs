@@ -1 +1,4 @@
 pub mod autonomous;
+mod dyn_agent;
+
+pub use dyn_agent::{AgentStream, DynAgent};
@@ -2,6 +2,8 @@ pub mod agents;
 pub mod prompt_templating;
 pub mod providers;
 pub mod routing;
+#[cfg(test)]
+mod test_utils;
 
 pub use agents::autonomous::AutonomousAgent;
 pub use prompt_templating::PromptTemplate;
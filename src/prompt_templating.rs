@@ -75,7 +75,12 @@ impl PromptTemplate {
 
     /// Renders the template as a string.
     pub fn render_to_string(&self) -> String {
-        tera::Tera::one_off(&self.template, &self.variables, false).unwrap()
+        self.try_render_to_string().unwrap()
+    }
+
+    /// Renders the template as a string, returning an error if the template is invalid or uses undefined variables.
+    pub fn try_render_to_string(&self) -> Result<String, tera::Error> {
+        tera::Tera::one_off(&self.template, &self.variables, false)
     }
 }
 
@@ -4,6 +4,7 @@
 use futures::{SinkExt, StreamExt, stream::BoxStream};
 use reqwest_websocket::{Message, RequestBuilderExt};
 use std::fmt::{self, Debug};
+use std::sync::Arc;
 use tokio::sync::mpsc;
 
 use rig::{
@@ -16,6 +17,9 @@ use bytes::Bytes;
 use serde::{Deserialize, Serialize};
 use tokio::sync::mpsc::Sender;
 
+use super::cache::{AudioCache, CacheKey};
+use super::language::{LanguageDetector, model_supports_language_code};
+
 #[derive(Clone)]
 pub struct Client {
     base_url: String,
@@ -87,6 +91,17 @@ impl Client {
             .send()
             .await
     }
+
+    pub(crate) async fn get(&self, path: &str) -> Result<reqwest::Response, reqwest::Error> {
+        let mut url = self.base_url.clone();
+        url.push_str(path);
+
+        self.http_client
+            .get(&url)
+            .header("xi-api-key", &self.api_key)
+            .send()
+            .await
+    }
 }
 
 impl ProviderClient for Client {
@@ -125,6 +140,8 @@ impl_conversion_traits!(AsCompletion, AsEmbeddings, AsTranscription, AsImageGene
 pub struct AudioGenerationModel {
     client: Client,
     model: String,
+    cache: Option<Arc<dyn AudioCache>>,
+    language_detector: Option<Arc<dyn LanguageDetector>>,
 }
 
 impl AudioGenerationModel {
@@ -132,9 +149,44 @@ impl AudioGenerationModel {
         Self {
             client,
             model: model.to_owned(),
+            cache: None,
+            language_detector: None,
+        }
+    }
+
+    /// Automatically detect the language of the input text and set `language_code` for models that support it (turbo and flash models).
+    /// An explicitly set `language_code` will always take priority.
+    pub fn with_language_detection<D>(mut self, detector: D) -> Self
+    where
+        D: LanguageDetector + 'static,
+    {
+        self.language_detector = Some(Arc::new(detector));
+        self
+    }
+
+    /// Attach a response cache to this model. Requests with an identical model, voice, text and parameters will be served from the cache instead of being re-generated.
+    pub fn with_cache<C>(mut self, cache: C) -> Self
+    where
+        C: AudioCache + 'static,
+    {
+        self.cache = Some(Arc::new(cache));
+        self
+    }
+
+    /// The language to set as `language_code` for the given text, if language detection is enabled and the model supports it.
+    async fn detect_language(&self, text: &str) -> Option<String> {
+        let detector = self.language_detector.as_ref()?;
+
+        if !model_supports_language_code(&self.model) {
+            return None;
         }
+
+        detector.detect(text).await
     }
 
+    /// Open a streaming text-to-speech connection. Text sent through the returned sender is spoken with the given voice.
+    ///
+    /// When language detection applies to this model, the language is detected from the first text sent, so the connection is only opened once it arrives.
     pub async fn streaming(
         &self,
         voice_id: &str,
@@ -145,51 +197,126 @@ impl AudioGenerationModel {
         ),
         Box<dyn std::error::Error>,
     > {
-        let path = format!("/text-to-speech/{voice_id}/stream-input?output_format=pcm_44100");
-        let websocket = self
-            .client
-            .initiate_websocket(&path)
-            .await
-            .inspect_err(|x| println!("Error: {x}"))
-            .unwrap();
+        let (tx, mut rx) = mpsc::channel::<StreamingAudioGenRequest>(9999);
 
-        let (mut ws_tx, ws_rx) = websocket.split();
+        if self.language_detector.is_none() || !model_supports_language_code(&self.model) {
+            let path = stream_input_path(voice_id, &self.model, None);
+            let websocket = self
+                .client
+                .initiate_websocket(&path)
+                .await
+                .inspect_err(|x| println!("Error: {x}"))
+                .unwrap();
 
-        let (tx, mut rx) = mpsc::channel::<StreamingAudioGenRequest>(9999);
+            let (ws_tx, ws_rx) = websocket.split();
+
+            tokio::spawn(forward_requests(None, rx, ws_tx));
+
+            return Ok((tx, parse_responses(ws_rx).boxed()));
+        }
+
+        let (res_tx, mut res_rx) = mpsc::channel::<StreamingAudioGenResponse>(9999);
+        let model = self.clone();
+        let voice_id = voice_id.to_string();
 
         tokio::spawn(async move {
-            while let Some(message) = rx.recv().await {
-                let json = serde_json::to_string(&message).unwrap();
-                ws_tx.send(Message::Text(json)).await.unwrap();
-            }
-        });
+            let Some(first) = rx.recv().await else {
+                return;
+            };
+
+            let language_code = model.detect_language(&first.text).await;
+            let path = stream_input_path(&voice_id, &model.model, language_code.as_deref());
+
+            let websocket = match model.client.initiate_websocket(&path).await {
+                Ok(websocket) => websocket,
+                Err(err) => {
+                    tracing::warn!("Failed to open the streaming connection: {err}");
+                    return;
+                }
+            };
 
-        // Convert `ws_rx` (Stream of WebSocket messages) into a stream of `ReceivedEvent`
-        let mapped_stream = ws_rx
-            .filter_map(|msg_result| async {
-                match msg_result {
-                    Ok(reqwest_websocket::Message::Text(txt)) => {
-                        tracing::debug!("Received text: {txt}");
-                        serde_json::from_str::<StreamingAudioGenResponse>(&txt).ok()
-                    }
-                    Err(err) => {
-                        tracing::debug!("Received error: {err}");
-                        None
-                    }
-                    Ok(thing) => {
-                        tracing::debug!(
-                            "Got thing that was neither a text message nor an error: {thing:?}"
-                        );
-                        None
+            let (ws_tx, ws_rx) = websocket.split();
+
+            tokio::spawn(async move {
+                let mut responses = std::pin::pin!(parse_responses(ws_rx));
+                while let Some(response) = responses.next().await {
+                    if res_tx.send(response).await.is_err() {
+                        break;
                     }
                 }
-            })
-            .boxed();
+            });
 
-        Ok((tx, mapped_stream))
+            forward_requests(Some(first), rx, ws_tx).await;
+        });
+
+        let stream = futures::stream::poll_fn(move |cx| res_rx.poll_recv(cx)).boxed();
+
+        Ok((tx, stream))
+    }
+}
+
+/// The path of the streaming text-to-speech endpoint.
+fn stream_input_path(voice_id: &str, model: &str, language_code: Option<&str>) -> String {
+    let mut path =
+        format!("/text-to-speech/{voice_id}/stream-input?model_id={model}&output_format=pcm_44100");
+
+    if let Some(language_code) = language_code {
+        path.push_str(&format!("&language_code={language_code}"));
+    }
+
+    path
+}
+
+/// Send streaming requests over the websocket, starting with `first` (if any).
+async fn forward_requests<S>(
+    first: Option<StreamingAudioGenRequest>,
+    mut rx: mpsc::Receiver<StreamingAudioGenRequest>,
+    mut ws_tx: S,
+) where
+    S: futures::Sink<Message> + Unpin,
+    S::Error: Debug,
+{
+    let mut next = first;
+
+    loop {
+        let message = match next.take() {
+            Some(message) => message,
+            None => match rx.recv().await {
+                Some(message) => message,
+                None => break,
+            },
+        };
+
+        let json = serde_json::to_string(&message).unwrap();
+        ws_tx.send(Message::Text(json)).await.unwrap();
     }
 }
 
+/// Convert the websocket messages into a stream of responses.
+fn parse_responses<S>(ws_rx: S) -> impl futures::Stream<Item = StreamingAudioGenResponse>
+where
+    S: futures::Stream<Item = Result<Message, reqwest_websocket::Error>>,
+{
+    ws_rx.filter_map(|msg_result| async {
+        match msg_result {
+            Ok(reqwest_websocket::Message::Text(txt)) => {
+                tracing::debug!("Received text: {txt}");
+                serde_json::from_str::<StreamingAudioGenResponse>(&txt).ok()
+            }
+            Err(err) => {
+                tracing::debug!("Received error: {err}");
+                None
+            }
+            Ok(thing) => {
+                tracing::debug!(
+                    "Got thing that was neither a text message nor an error: {thing:?}"
+                );
+                None
+            }
+        }
+    })
+}
+
 #[derive(Clone, Deserialize, Serialize)]
 pub struct StreamingAudioGenRequest {
     text: String,
@@ -411,8 +538,26 @@ impl audio_generation::AudioGenerationModel for AudioGenerationModel {
         audio_generation::AudioGenerationResponse<Self::Response>,
         audio_generation::AudioGenerationError,
     > {
-        let req: AudioGenerationRequest =
+        let mut req: AudioGenerationRequest =
             AudioGenerationRequest::try_from((self.model.as_ref(), request))?;
+
+        let cache_key = self.cache.as_ref().map(|_| CacheKey::from_request(&req));
+        if let (Some(cache), Some(key)) = (&self.cache, &cache_key)
+            && let Some(response) = cache.get(key)
+        {
+            tracing::debug!("Serving audio from cache: {}", key.digest());
+            return Ok(audio_generation::AudioGenerationResponse {
+                audio: response.to_vec(),
+                response,
+            });
+        }
+
+        // Detect the language only on a cache miss. The cache key is built from the explicit language (or the text), so
+        // repeated requests are served from the cache without detecting the language again.
+        if req.params.language_code.is_none() {
+            req.params.language_code = self.detect_language(&req.text).await;
+        }
+
         let url = format!(
             "/text-to-speech/{voice_id}?output_format={output}",
             voice_id = req.voice_id,
@@ -420,7 +565,18 @@ impl audio_generation::AudioGenerationModel for AudioGenerationModel {
                 serde_json::to_string(&req.params.output_format).expect("This should never fail")
         );
 
-        let response = self.client.post(&url, &req).await.unwrap().bytes().await?;
+        // Only successful responses are cached, so that errors aren't served as audio
+        let response = self
+            .client
+            .post(&url, &req)
+            .await?
+            .error_for_status()?
+            .bytes()
+            .await?;
+
+        if let (Some(cache), Some(key)) = (&self.cache, cache_key) {
+            cache.insert(key, response.clone());
+        }
 
         Ok(audio_generation::AudioGenerationResponse {
             audio: response.to_vec(),
@@ -428,3 +584,39 @@ impl audio_generation::AudioGenerationModel for AudioGenerationModel {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use futures::{FutureExt, future::BoxFuture};
+
+    use super::{AudioGenerationModel, Client, stream_input_path};
+    use crate::providers::elevenlabs::language::LanguageDetector;
+
+    #[derive(Debug)]
+    struct Japanese;
+
+    impl LanguageDetector for Japanese {
+        fn detect<'a>(&'a self, _text: &'a str) -> BoxFuture<'a, Option<String>> {
+            futures::future::ready(Some("ja".to_string())).boxed()
+        }
+    }
+
+    #[tokio::test]
+    async fn detects_streaming_language() {
+        let client = Client::new("key");
+
+        let model = AudioGenerationModel::new(client.clone(), "eleven_flash_v2_5")
+            .with_language_detection(Japanese);
+        let language_code = model.detect_language("こんにちは").await;
+        assert_eq!(language_code.as_deref(), Some("ja"));
+        assert_eq!(
+            stream_input_path("voice", &model.model, language_code.as_deref()),
+            "/text-to-speech/voice/stream-input?model_id=eleven_flash_v2_5&output_format=pcm_44100&language_code=ja"
+        );
+
+        // Models without `language_code` support are never sent one
+        let model = AudioGenerationModel::new(client, "eleven_multilingual_v2")
+            .with_language_detection(Japanese);
+        assert_eq!(model.detect_language("こんにちは").await, None);
+    }
+}
@@ -0,0 +1,169 @@
+//! Content-addressed response caching for ElevenLabs text-to-speech.
+//!
+//! Repeated generation of identical strings (a common pattern in IVR and menu flows) is billed per character every time.
+//! Attaching a cache to an [`AudioGenerationModel`](super::audiogen::AudioGenerationModel) means that identical requests are only sent to ElevenLabs once.
+use bytes::Bytes;
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    path::PathBuf,
+    sync::{Arc, RwLock},
+};
+
+use super::audiogen::AudioGenerationRequest;
+
+/// A cache key for a generated audio clip.
+/// Keyed on the model, the voice, the text and a hash of any additional parameters used.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct CacheKey {
+    pub model_id: String,
+    pub voice_id: String,
+    pub text: String,
+    pub params_hash: u64,
+}
+
+impl CacheKey {
+    /// Creates a cache key from an ElevenLabs audio generation request.
+    pub fn from_request(req: &AudioGenerationRequest) -> Self {
+        let params = serde_json::to_string(&req.params).unwrap_or_default();
+
+        Self {
+            model_id: req.model_id.clone(),
+            voice_id: req.voice_id.clone(),
+            text: req.text.clone(),
+            params_hash: fnv1a(params.as_bytes()),
+        }
+    }
+
+    /// A stable hex digest of the whole key. This is stable across program runs, so it can be used as a file name.
+    pub fn digest(&self) -> String {
+        let mut buf = Vec::new();
+        for part in [&self.model_id, &self.voice_id, &self.text] {
+            buf.extend_from_slice(part.as_bytes());
+            // Separator so that ("ab", "c") and ("a", "bc") don't collide
+            buf.push(0);
+        }
+        buf.extend_from_slice(&self.params_hash.to_le_bytes());
+
+        format!("{:016x}", fnv1a(&buf))
+    }
+}
+
+/// 64-bit FNV-1a. Used over `DefaultHasher` because the output needs to be stable between builds for the on-disk cache.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+    const PRIME: u64 = 0x100000001b3;
+
+    bytes.iter().fold(OFFSET_BASIS, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(PRIME)
+    })
+}
+
+/// A cache for generated audio.
+/// Implement this if you want to use your own storage backend (Redis, S3, etc).
+pub trait AudioCache: Debug + Send + Sync {
+    /// Retrieve a cached audio clip, if one exists.
+    fn get(&self, key: &CacheKey) -> Option<Bytes>;
+
+    /// Store an audio clip.
+    fn insert(&self, key: CacheKey, audio: Bytes);
+}
+
+/// An in-memory audio cache. Cheaply cloneable; clones share the same storage.
+#[derive(Clone, Debug, Default)]
+pub struct InMemoryAudioCache {
+    inner: Arc<RwLock<HashMap<CacheKey, Bytes>>>,
+}
+
+impl InMemoryAudioCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The number of cached audio clips.
+    pub fn len(&self) -> usize {
+        self.inner.read().map(|x| x.len()).unwrap_or_default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl AudioCache for InMemoryAudioCache {
+    fn get(&self, key: &CacheKey) -> Option<Bytes> {
+        self.inner.read().ok()?.get(key).cloned()
+    }
+
+    fn insert(&self, key: CacheKey, audio: Bytes) {
+        if let Ok(mut cache) = self.inner.write() {
+            cache.insert(key, audio);
+        }
+    }
+}
+
+/// An on-disk audio cache. Each audio clip is stored as a file named after the digest of its key.
+#[derive(Clone, Debug)]
+pub struct DiskAudioCache {
+    dir: PathBuf,
+}
+
+impl DiskAudioCache {
+    /// Creates a new on-disk cache in the given directory. The directory will be created if it does not exist.
+    pub fn new<P>(dir: P) -> std::io::Result<Self>
+    where
+        P: Into<PathBuf>,
+    {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)?;
+
+        Ok(Self { dir })
+    }
+
+    fn path(&self, key: &CacheKey) -> PathBuf {
+        self.dir.join(format!("{}.bin", key.digest()))
+    }
+}
+
+impl AudioCache for DiskAudioCache {
+    fn get(&self, key: &CacheKey) -> Option<Bytes> {
+        std::fs::read(self.path(key)).ok().map(Bytes::from)
+    }
+
+    fn insert(&self, key: CacheKey, audio: Bytes) {
+        if let Err(err) = std::fs::write(self.path(&key), &audio) {
+            tracing::warn!("Failed to write audio to disk cache: {err}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AudioCache, CacheKey, InMemoryAudioCache};
+    use bytes::Bytes;
+
+    fn key(text: &str) -> CacheKey {
+        CacheKey {
+            model_id: "eleven_multilingual_v2".to_string(),
+            voice_id: "voice".to_string(),
+            text: text.to_string(),
+            params_hash: 0,
+        }
+    }
+
+    #[test]
+    fn digest_is_stable_and_distinct() {
+        assert_eq!(key("hello").digest(), key("hello").digest());
+        assert_ne!(key("hello").digest(), key("world").digest());
+    }
+
+    #[test]
+    fn in_memory_cache_roundtrip() {
+        let cache = InMemoryAudioCache::new();
+        assert!(cache.get(&key("hello")).is_none());
+
+        cache.insert(key("hello"), Bytes::from_static(b"audio"));
+        assert_eq!(cache.get(&key("hello")), Some(Bytes::from_static(b"audio")));
+        assert_eq!(cache.len(), 1);
+    }
+}
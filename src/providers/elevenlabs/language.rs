@@ -0,0 +1,173 @@
+//! Language detection for ElevenLabs requests.
+//!
+//! The turbo and flash models can only enforce a language when `language_code` is set.
+//! Attaching a [`LanguageDetector`] to an [`AudioGenerationModel`](super::audiogen::AudioGenerationModel) will detect the language of the input text and fill in `language_code` automatically for these models.
+use futures::{FutureExt, future::BoxFuture};
+use rig::{
+    agent::Agent,
+    completion::{CompletionModel, Prompt},
+};
+use std::fmt;
+
+/// A language detector. Returns an ISO 639-1 language code (ie `en`, `ja`), or `None` if the language could not be determined.
+pub trait LanguageDetector: fmt::Debug + Send + Sync {
+    fn detect<'a>(&'a self, text: &'a str) -> BoxFuture<'a, Option<String>>;
+}
+
+/// Returns whether or not the given model supports (and benefits from) having `language_code` set.
+pub fn model_supports_language_code(model: &str) -> bool {
+    model.contains("turbo") || model.contains("flash")
+}
+
+/// A lightweight, dependency-free language detector.
+/// Languages with a distinctive script are detected from the script used; Latin-script languages are detected from common stopwords.
+#[derive(Clone, Debug, Default)]
+pub struct ScriptLanguageDetector;
+
+impl ScriptLanguageDetector {
+    pub fn new() -> Self {
+        Self
+    }
+
+    fn detect_script(text: &str) -> Option<&'static str> {
+        let mut han = false;
+
+        for c in text.chars() {
+            let lang = match c as u32 {
+                0x3040..=0x30FF => "ja",
+                0xAC00..=0xD7AF | 0x1100..=0x11FF => "ko",
+                0x4E00..=0x9FFF => {
+                    han = true;
+                    continue;
+                }
+                0x0400..=0x04FF => "ru",
+                0x0370..=0x03FF => "el",
+                0x0600..=0x06FF => "ar",
+                0x0590..=0x05FF => "he",
+                0x0E00..=0x0E7F => "th",
+                0x0900..=0x097F => "hi",
+                _ => continue,
+            };
+
+            return Some(lang);
+        }
+
+        // Han characters without any kana are most likely Chinese
+        han.then_some("zh")
+    }
+
+    fn detect_latin(text: &str) -> Option<&'static str> {
+        const STOPWORDS: &[(&str, &[&str])] = &[
+            ("en", &["the", "and", "is", "you", "of", "to", "with"]),
+            ("es", &["el", "los", "las", "es", "y", "que", "con"]),
+            ("fr", &["le", "les", "est", "et", "vous", "avec", "une"]),
+            ("de", &["der", "die", "das", "und", "ist", "nicht", "mit"]),
+            ("it", &["il", "gli", "è", "e", "che", "con", "non"]),
+            ("pt", &["o", "os", "é", "e", "que", "com", "não"]),
+        ];
+
+        let words: Vec<String> = text
+            .split(|c: char| !c.is_alphabetic())
+            .filter(|x| !x.is_empty())
+            .map(str::to_lowercase)
+            .collect();
+
+        STOPWORDS
+            .iter()
+            .map(|(lang, stopwords)| {
+                let hits = words
+                    .iter()
+                    .filter(|word| stopwords.contains(&word.as_str()))
+                    .count();
+                (lang, hits)
+            })
+            .filter(|(_, hits)| *hits > 0)
+            .max_by_key(|(_, hits)| *hits)
+            .map(|(lang, _)| *lang)
+    }
+}
+
+impl LanguageDetector for ScriptLanguageDetector {
+    fn detect<'a>(&'a self, text: &'a str) -> BoxFuture<'a, Option<String>> {
+        let lang = Self::detect_script(text).or_else(|| Self::detect_latin(text));
+
+        futures::future::ready(lang.map(ToString::to_string)).boxed()
+    }
+}
+
+/// A language detector that asks a Rig agent to detect the language.
+/// More accurate than [`ScriptLanguageDetector`], at the cost of an extra completion call per request.
+pub struct CompletionLanguageDetector<M>
+where
+    M: CompletionModel,
+{
+    agent: Agent<M>,
+}
+
+impl<M> CompletionLanguageDetector<M>
+where
+    M: CompletionModel,
+{
+    pub fn new(agent: Agent<M>) -> Self {
+        Self { agent }
+    }
+}
+
+impl<M> fmt::Debug for CompletionLanguageDetector<M>
+where
+    M: CompletionModel,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CompletionLanguageDetector").finish()
+    }
+}
+
+impl<M> LanguageDetector for CompletionLanguageDetector<M>
+where
+    M: CompletionModel,
+{
+    fn detect<'a>(&'a self, text: &'a str) -> BoxFuture<'a, Option<String>> {
+        async move {
+            let prompt = format!(
+                "Respond with only the ISO 639-1 code of the language the following text is written in, and nothing else.\n\n{text}"
+            );
+
+            let res = self
+                .agent
+                .prompt(prompt)
+                .await
+                .inspect_err(|err| tracing::warn!("Language detection failed: {err}"))
+                .ok()?;
+
+            let code = res.trim().trim_matches('.').to_lowercase();
+
+            (code.len() == 2 && code.chars().all(|c| c.is_ascii_alphabetic())).then_some(code)
+        }
+        .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ScriptLanguageDetector;
+
+    #[test]
+    fn detects_languages() {
+        assert_eq!(
+            ScriptLanguageDetector::detect_script("こんにちは世界"),
+            Some("ja")
+        );
+        assert_eq!(
+            ScriptLanguageDetector::detect_script("你好世界"),
+            Some("zh")
+        );
+        assert_eq!(
+            ScriptLanguageDetector::detect_latin("The cat is on the mat"),
+            Some("en")
+        );
+        assert_eq!(
+            ScriptLanguageDetector::detect_latin("Der Hund ist nicht hier"),
+            Some("de")
+        );
+    }
+}
@@ -1,4 +1,7 @@
 pub mod audiogen;
+pub mod cache;
+pub mod language;
+pub mod voices;
 
 /// The ElevenLabs eleven_multilingual_v2 model.
 pub const ELEVEN_MULTILINGUAL_V2: &str = "eleven_multilingual_v2";
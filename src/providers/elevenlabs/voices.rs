@@ -0,0 +1,61 @@
+//! Voice management for ElevenLabs.
+//! Currently supports listing the samples attached to a voice and downloading their raw audio.
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+
+use super::audiogen::Client;
+
+/// A sample audio clip attached to an ElevenLabs voice.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct VoiceSample {
+    pub sample_id: String,
+    #[serde(default)]
+    pub file_name: Option<String>,
+    #[serde(default)]
+    pub mime_type: Option<String>,
+    #[serde(default)]
+    pub size_bytes: Option<u64>,
+    #[serde(default)]
+    pub hash: Option<String>,
+}
+
+/// The subset of an ElevenLabs voice that is relevant to retrieving samples.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Voice {
+    pub voice_id: String,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub samples: Option<Vec<VoiceSample>>,
+}
+
+impl Client {
+    /// Retrieve a voice by its ID.
+    pub async fn voice(&self, voice_id: &str) -> Result<Voice, reqwest::Error> {
+        self.get(&format!("/voices/{voice_id}"))
+            .await?
+            .error_for_status()?
+            .json()
+            .await
+    }
+
+    /// List the samples attached to a voice.
+    pub async fn voice_samples(&self, voice_id: &str) -> Result<Vec<VoiceSample>, reqwest::Error> {
+        let voice = self.voice(voice_id).await?;
+
+        Ok(voice.samples.unwrap_or_default())
+    }
+
+    /// Download the raw audio of a voice sample.
+    pub async fn download_voice_sample(
+        &self,
+        voice_id: &str,
+        sample_id: &str,
+    ) -> Result<Bytes, reqwest::Error> {
+        self.get(&format!("/voices/{voice_id}/samples/{sample_id}/audio"))
+            .await?
+            .error_for_status()?
+            .bytes()
+            .await
+    }
+}
@@ -0,0 +1,274 @@
+//! A higher-level realtime agent that automatically executes Rig tools.
+//!
+//! When the model makes function calls, the matching tools are executed once the response is done, the outputs are sent back to the conversation and a new response is requested.
+//! This gives realtime sessions the same tool loop that regular Rig agents have.
+use std::{collections::HashMap, sync::Arc};
+
+use futures::{StreamExt, stream::BoxStream};
+use rig::tool::{Tool, ToolDyn};
+use tokio::sync::mpsc::Sender;
+
+use super::realtime::{
+    ConversationItem, InputEvent, RealtimeError, RealtimeModel, RealtimeTool, RealtimeVoice,
+    RealtimeVoiceRequest, ReceivedEvent, ReceivedEventKind, ResponseEvent, Session,
+};
+
+type Tools = Arc<HashMap<String, Box<dyn ToolDyn>>>;
+
+/// A realtime agent that owns a set of Rig tools.
+pub struct RealtimeAgent<V = RealtimeModel> {
+    model: V,
+    session: Session,
+    tools: Tools,
+}
+
+impl<V> RealtimeAgent<V>
+where
+    V: RealtimeVoice,
+{
+    /// Create an instance of [`RealtimeAgentBuilder`].
+    pub fn builder(model: V) -> RealtimeAgentBuilder<V> {
+        RealtimeAgentBuilder::new(model)
+    }
+
+    /// Open a realtime session.
+    /// The session config will be sent with the definitions of all of this agent's tools.
+    /// Function calls will be executed automatically, but all events (including function call events) are still emitted on the returned stream.
+    pub async fn connect(
+        &self,
+    ) -> Result<(Sender<InputEvent>, BoxStream<'_, ReceivedEvent>), RealtimeError> {
+        // Keep any MCP servers set on the session, as they're executed server-side
+        let mut tool_definitions: Vec<RealtimeTool> = self
+            .session
+            .tools
+            .iter()
+            .flatten()
+            .filter(|x| matches!(x, RealtimeTool::Mcp(_)))
+            .cloned()
+            .collect();
+        for tool in self.tools.values() {
+            tool_definitions.push(RealtimeTool::from(tool.definition(String::new()).await));
+        }
+
+        let session = self.session.clone().tools(tool_definitions);
+        let req = RealtimeVoiceRequest::with_session(session);
+
+        let (sender, stream) = self.model.realtime_voice(req).await?;
+
+        let tools = Arc::clone(&self.tools);
+        let tool_sender = sender.clone();
+        let stream = stream
+            .inspect(move |evt| {
+                let ReceivedEventKind::Response(ResponseEvent::ResponseDone { response }) =
+                    &evt.data
+                else {
+                    return;
+                };
+
+                let calls: Vec<(String, String, String)> = response
+                    .output
+                    .iter()
+                    .filter_map(|item| match item {
+                        ConversationItem::FunctionCall {
+                            call_id,
+                            name,
+                            arguments,
+                            ..
+                        } => Some((call_id.clone(), name.clone(), arguments.clone())),
+                        _ => None,
+                    })
+                    .collect();
+
+                if calls.is_empty() {
+                    return;
+                }
+
+                tokio::spawn(execute_tool_calls(
+                    Arc::clone(&tools),
+                    tool_sender.clone(),
+                    calls,
+                ));
+            })
+            .boxed();
+
+        Ok((sender, stream))
+    }
+}
+
+async fn execute_tool_calls(
+    tools: Tools,
+    sender: Sender<InputEvent>,
+    calls: Vec<(String, String, String)>,
+) {
+    for (call_id, name, arguments) in calls {
+        let output = match tools.get(&name) {
+            Some(tool) => match tool.call(arguments).await {
+                Ok(output) => output,
+                Err(err) => {
+                    tracing::warn!("Tool {name} returned an error: {err}");
+                    format!("Error: {err}")
+                }
+            },
+            None => {
+                tracing::warn!("Model called a tool that does not exist: {name}");
+                format!("Error: no tool named {name} exists")
+            }
+        };
+
+        if sender
+            .send(InputEvent::function_call_output(&call_id, &output))
+            .await
+            .is_err()
+        {
+            return;
+        }
+    }
+
+    let _ = sender.send(InputEvent::create_response()).await;
+}
+
+/// A builder for [`RealtimeAgent`].
+pub struct RealtimeAgentBuilder<V = RealtimeModel> {
+    model: V,
+    session: Session,
+    tools: HashMap<String, Box<dyn ToolDyn>>,
+}
+
+impl<V> RealtimeAgentBuilder<V>
+where
+    V: RealtimeVoice,
+{
+    pub fn new(model: V) -> Self {
+        Self {
+            model,
+            session: Session::new(),
+            tools: HashMap::new(),
+        }
+    }
+
+    /// Set the session config. Any function tools set on the session will be overwritten by the agent's tools, but MCP tools are kept.
+    pub fn session(mut self, session: Session) -> Self {
+        self.session = session;
+        self
+    }
+
+    /// Set the preamble ("system prompt") of the agent.
+    pub fn instructions(mut self, instructions: &str) -> Self {
+        self.session = self.session.instructions(instructions);
+        self
+    }
+
+    /// Add a Rig tool to the agent.
+    pub fn tool<T>(mut self, tool: T) -> Self
+    where
+        T: Tool + 'static,
+    {
+        self.tools.insert(Tool::name(&tool), Box::new(tool));
+        self
+    }
+
+    pub fn build(self) -> RealtimeAgent<V> {
+        RealtimeAgent {
+            model: self.model,
+            session: self.session,
+            tools: Arc::new(self.tools),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use futures::StreamExt;
+    use rig::{completion::ToolDefinition, tool::Tool};
+    use serde::Deserialize;
+    use serde_json::json;
+
+    use super::RealtimeAgent;
+    use crate::providers::openai_realtime::recording::{
+        Direction, MockRealtimeVoice, RecordedEvent,
+    };
+
+    #[derive(Debug, thiserror::Error)]
+    #[error("Math error")]
+    struct MathError;
+
+    #[derive(Deserialize)]
+    struct AddArgs {
+        x: i64,
+        y: i64,
+    }
+
+    struct Add;
+
+    impl Tool for Add {
+        const NAME: &'static str = "add";
+        type Error = MathError;
+        type Args = AddArgs;
+        type Output = i64;
+
+        async fn definition(&self, _prompt: String) -> ToolDefinition {
+            ToolDefinition {
+                name: Self::NAME.to_string(),
+                description: "Add two numbers".to_string(),
+                parameters: json!({
+                    "type": "object",
+                    "properties": { "x": { "type": "number" }, "y": { "type": "number" } }
+                }),
+            }
+        }
+
+        async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+            Ok(args.x + args.y)
+        }
+    }
+
+    #[tokio::test]
+    async fn executes_tool_calls() {
+        let mock = MockRealtimeVoice::new(vec![RecordedEvent {
+            timestamp_ms: 0,
+            direction: Direction::Inbound,
+            event: json!({
+                "event_id": "e1",
+                "type": "response.done",
+                "response": {
+                    "id": "r1",
+                    "status": "completed",
+                    "output": [{
+                        "type": "function_call",
+                        "id": "item1",
+                        "call_id": "call1",
+                        "name": "add",
+                        "arguments": "{\"x\":1,\"y\":2}"
+                    }]
+                }
+            }),
+        }]);
+
+        let agent = RealtimeAgent::builder(mock.clone()).tool(Add).build();
+        let (_sender, stream) = agent.connect().await.unwrap();
+        assert_eq!(stream.collect::<Vec<_>>().await.len(), 1);
+
+        // The tool is executed in the background, so wait for the outputs to be sent
+        let mut sent = Vec::new();
+        for _ in 0..100 {
+            sent = mock.sent_events();
+            if sent.len() == 2 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        let sent: Vec<serde_json::Value> = sent
+            .iter()
+            .map(|x| serde_json::to_value(x).unwrap())
+            .collect();
+        assert_eq!(sent.len(), 2);
+        assert_eq!(sent[0]["type"], "conversation.item.create");
+        assert_eq!(sent[0]["item"]["type"], "function_call_output");
+        assert_eq!(sent[0]["item"]["call_id"], "call1");
+        assert_eq!(sent[0]["item"]["output"], "3");
+        assert_eq!(sent[1]["type"], "response.create");
+    }
+}
@@ -0,0 +1,275 @@
+//! Helpers for decoding audio received from the realtime API.
+//!
+//! Audio deltas are sent as base64-encoded chunks. [`AudioOutput`] turns a received event stream into a stream of decoded `i16` samples,
+//! and (with the `openai_realtime_playback` feature) into a [`rodio::Source`] that can be played back directly.
+use base64::{Engine, prelude::BASE64_STANDARD};
+use futures::{StreamExt, stream::BoxStream};
+
+use super::realtime::{
+    AudioFormat, ReceivedEvent, ReceivedEventKind, ReceivedItemEventKind, Session,
+};
+
+/// Decodes audio output from the realtime API. All audio is mono.
+#[derive(Debug, Clone, Copy)]
+pub struct AudioOutput {
+    format: AudioFormat,
+}
+
+impl Default for AudioOutput {
+    fn default() -> Self {
+        Self::new(AudioFormat::Pcm16)
+    }
+}
+
+impl AudioOutput {
+    pub fn new(format: AudioFormat) -> Self {
+        Self { format }
+    }
+
+    /// Uses the output audio format from the session config, falling back to the OpenAI default (PCM16) if not set.
+    pub fn from_session(session: &Session) -> Self {
+        session
+            .output_audio_format
+            .map(Self::new)
+            .unwrap_or_default()
+    }
+
+    pub fn format(&self) -> AudioFormat {
+        self.format
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.format.sample_rate()
+    }
+
+    /// Decode a single base64-encoded audio delta into samples.
+    pub fn decode(&self, delta: &str) -> Result<Vec<i16>, base64::DecodeError> {
+        let bytes = BASE64_STANDARD.decode(delta)?;
+
+        let samples = match self.format {
+            AudioFormat::Pcm16 => bytes
+                .chunks_exact(2)
+                .map(|x| i16::from_le_bytes([x[0], x[1]]))
+                .collect(),
+            AudioFormat::G711Ulaw => bytes.into_iter().map(ulaw_to_linear).collect(),
+            AudioFormat::G711Alaw => bytes.into_iter().map(alaw_to_linear).collect(),
+        };
+
+        Ok(samples)
+    }
+
+    /// Turn a received event stream into a stream of decoded sample chunks. Any events that aren't audio deltas are skipped.
+    pub fn stream<'a>(self, events: BoxStream<'a, ReceivedEvent>) -> BoxStream<'a, Vec<i16>> {
+        events
+            .filter_map(move |evt| async move {
+                let ReceivedEventKind::Item {
+                    data: ReceivedItemEventKind::AudioDelta { delta },
+                    ..
+                } = evt.data
+                else {
+                    return None;
+                };
+
+                match self.decode(&delta) {
+                    Ok(samples) => Some(samples),
+                    Err(err) => {
+                        tracing::warn!("Failed to decode audio delta: {err}");
+                        None
+                    }
+                }
+            })
+            .boxed()
+    }
+
+    /// Creates a playable source along with a handle for pushing samples into it.
+    #[cfg(feature = "openai_realtime_playback")]
+    pub fn source(&self) -> (playback::StreamingSource, playback::AudioBuffer) {
+        playback::StreamingSource::new(self.sample_rate())
+    }
+}
+
+/// Decodes a G.711 µ-law sample into 16-bit linear PCM.
+pub fn ulaw_to_linear(sample: u8) -> i16 {
+    const BIAS: i16 = 0x84;
+
+    let sample = !sample;
+    let exponent = (sample >> 4) & 0x07;
+    let mantissa = i16::from(sample & 0x0F);
+
+    let magnitude = (((mantissa << 3) + BIAS) << exponent) - BIAS;
+
+    if sample & 0x80 != 0 {
+        -magnitude
+    } else {
+        magnitude
+    }
+}
+
+/// Decodes a G.711 A-law sample into 16-bit linear PCM.
+pub fn alaw_to_linear(sample: u8) -> i16 {
+    let sample = sample ^ 0x55;
+    let exponent = (sample >> 4) & 0x07;
+    let mantissa = i16::from(sample & 0x0F);
+
+    let magnitude = match exponent {
+        0 => (mantissa << 4) + 0x08,
+        exponent => ((mantissa << 4) + 0x108) << (exponent - 1),
+    };
+
+    if sample & 0x80 != 0 {
+        magnitude
+    } else {
+        -magnitude
+    }
+}
+
+/// Encodes a 16-bit linear PCM sample as G.711 µ-law.
+pub fn linear_to_ulaw(sample: i16) -> u8 {
+    const BIAS: i32 = 0x84;
+    const CLIP: i32 = 32635;
+
+    let sample = i32::from(sample);
+    let sign = if sample < 0 { 0x80 } else { 0x00 };
+    let magnitude = sample.abs().min(CLIP) + BIAS;
+
+    // The position of the highest set bit above bit 7
+    let exponent = 31 - ((magnitude >> 7) as u32).leading_zeros();
+    let mantissa = (magnitude >> (exponent + 3)) & 0x0F;
+
+    !(sign | (exponent << 4) as i32 | mantissa) as u8
+}
+
+/// Encodes a 16-bit linear PCM sample as G.711 A-law.
+pub fn linear_to_alaw(sample: i16) -> u8 {
+    const SEGMENT_ENDS: [i32; 8] = [0x1F, 0x3F, 0x7F, 0xFF, 0x1FF, 0x3FF, 0x7FF, 0xFFF];
+
+    let sample = i32::from(sample);
+    let (mask, magnitude) = if sample >= 0 {
+        (0xD5, sample >> 3)
+    } else {
+        (0x55, (-sample - 1) >> 3)
+    };
+
+    let Some(segment) = SEGMENT_ENDS.iter().position(|end| magnitude <= *end) else {
+        return 0x7F ^ mask;
+    };
+
+    let mantissa = if segment < 2 {
+        (magnitude >> 1) & 0x0F
+    } else {
+        (magnitude >> segment) & 0x0F
+    };
+
+    (((segment as i32) << 4) | mantissa) as u8 ^ mask
+}
+
+#[cfg(feature = "openai_realtime_playback")]
+pub mod playback {
+    use std::{
+        collections::VecDeque,
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    /// A handle to the sample buffer backing a [`StreamingSource`].
+    #[derive(Debug, Clone, Default)]
+    pub struct AudioBuffer {
+        inner: Arc<Mutex<VecDeque<i16>>>,
+    }
+
+    impl AudioBuffer {
+        /// Queue samples for playback.
+        pub fn push(&self, samples: &[i16]) {
+            if let Ok(mut buf) = self.inner.lock() {
+                buf.extend(samples);
+            }
+        }
+
+        /// Drop all queued samples (ie when the user interrupts the assistant).
+        pub fn clear(&self) {
+            if let Ok(mut buf) = self.inner.lock() {
+                buf.clear();
+            }
+        }
+
+        /// The number of queued samples.
+        pub fn len(&self) -> usize {
+            self.inner.lock().map(|x| x.len()).unwrap_or_default()
+        }
+
+        pub fn is_empty(&self) -> bool {
+            self.len() == 0
+        }
+    }
+
+    /// A never-ending mono `rodio::Source` that plays queued samples, and silence when no samples are queued.
+    /// Playing audio deltas as they come in can result in choppy audio, so samples are buffered instead.
+    #[derive(Debug)]
+    pub struct StreamingSource {
+        buffer: AudioBuffer,
+        sample_rate: u32,
+    }
+
+    impl StreamingSource {
+        pub fn new(sample_rate: u32) -> (Self, AudioBuffer) {
+            let buffer = AudioBuffer::default();
+
+            (
+                Self {
+                    buffer: buffer.clone(),
+                    sample_rate,
+                },
+                buffer,
+            )
+        }
+    }
+
+    impl Iterator for StreamingSource {
+        type Item = i16;
+
+        fn next(&mut self) -> Option<Self::Item> {
+            let sample = self
+                .buffer
+                .inner
+                .try_lock()
+                .ok()
+                .and_then(|mut buf| buf.pop_front());
+
+            // Return silence on underrun
+            Some(sample.unwrap_or(0))
+        }
+    }
+
+    impl rodio::Source for StreamingSource {
+        fn current_frame_len(&self) -> Option<usize> {
+            None
+        }
+
+        fn channels(&self) -> u16 {
+            1
+        }
+
+        fn sample_rate(&self) -> u32 {
+            self.sample_rate
+        }
+
+        fn total_duration(&self) -> Option<Duration> {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{alaw_to_linear, ulaw_to_linear};
+
+    #[test]
+    fn decodes_g711() {
+        assert_eq!(ulaw_to_linear(0xFF), 0);
+        assert_eq!(ulaw_to_linear(0x00), -32124);
+        assert_eq!(ulaw_to_linear(0x80), 32124);
+        assert_eq!(alaw_to_linear(0xD5), 8);
+        assert_eq!(alaw_to_linear(0x55), -8);
+        assert_eq!(alaw_to_linear(0xAA), 32256);
+    }
+}
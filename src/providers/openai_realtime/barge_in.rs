@@ -0,0 +1,311 @@
+//! Handling of user interruptions ("barge-in").
+//!
+//! When the user starts speaking while the assistant's audio is still playing, the in-flight response should be cancelled
+//! and the assistant's audio item should be truncated to what the user actually heard, so that the model doesn't think the user heard the whole response.
+//! As audio is generated faster than it is played back, this requires feedback on the playback position, which is what [`BargeInController`] uses.
+use std::time::Duration;
+
+use tokio::sync::mpsc::Sender;
+
+use super::realtime::{
+    AudioFormat, InputAudioBufferEvent, InputEvent, RealtimeError, ReceivedEvent,
+    ReceivedEventKind, ReceivedItemEventKind, ResponseEvent,
+};
+
+/// An interruption that has been handled by a [`BargeInController`].
+/// Applications should stop playback and drop any queued audio when this is returned.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Interruption {
+    /// The assistant item that was interrupted.
+    pub item_id: String,
+    /// How much of the item's audio was played (and kept in the conversation).
+    pub audio_end_ms: u64,
+}
+
+/// The assistant audio item currently being played back.
+#[derive(Debug, Clone)]
+struct PlayingItem {
+    item_id: String,
+    content_index: u64,
+    /// How many bytes of audio have been received for this item.
+    received_bytes: usize,
+    /// How much audio has been played for this item.
+    played: Duration,
+}
+
+/// Automatically cancels and truncates assistant responses when the user interrupts.
+///
+/// Feed every received event into [`BargeInController::observe`], and report playback progress with [`BargeInController::played_samples`]
+/// (or [`BargeInController::set_playback_position`]).
+#[derive(Debug, Clone)]
+pub struct BargeInController {
+    sender: Sender<InputEvent>,
+    format: AudioFormat,
+    response_in_progress: Option<String>,
+    playing: Option<PlayingItem>,
+}
+
+impl BargeInController {
+    pub fn new(sender: Sender<InputEvent>) -> Self {
+        Self {
+            sender,
+            format: AudioFormat::Pcm16,
+            response_in_progress: None,
+            playing: None,
+        }
+    }
+
+    /// Set the output audio format of the session (defaults to PCM16). Used to convert sample counts and audio deltas into durations.
+    pub fn with_format(mut self, format: AudioFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Report that a number of samples of the current assistant item have been played.
+    pub fn played_samples(&mut self, samples: usize) {
+        let played = Duration::from_secs_f64(samples as f64 / self.format.sample_rate() as f64);
+
+        if let Some(playing) = &mut self.playing {
+            playing.played += played;
+        }
+    }
+
+    /// Set the playback position within the current assistant item.
+    pub fn set_playback_position(&mut self, position: Duration) {
+        if let Some(playing) = &mut self.playing {
+            playing.played = position;
+        }
+    }
+
+    /// Whether assistant audio is (as far as the controller knows) still playing.
+    pub fn is_playing(&self) -> bool {
+        self.playing
+            .as_ref()
+            .is_some_and(|x| x.played < self.received(x))
+    }
+
+    /// How much audio has been received for an item.
+    /// This is computed from the total number of bytes, so that rounding errors don't add up over many small deltas.
+    fn received(&self, playing: &PlayingItem) -> Duration {
+        Duration::from_secs_f64(
+            playing.received_bytes as f64 / (self.format.bytes_per_ms() * 1000) as f64,
+        )
+    }
+
+    /// Observe a received event. If the user has interrupted the assistant, the in-flight response is cancelled,
+    /// the assistant item is truncated and the interruption is returned.
+    pub async fn observe(
+        &mut self,
+        evt: &ReceivedEvent,
+    ) -> Result<Option<Interruption>, RealtimeError> {
+        match &evt.data {
+            ReceivedEventKind::Response(ResponseEvent::ResponseCreated { response }) => {
+                self.response_in_progress = Some(response.id.clone());
+            }
+            ReceivedEventKind::Response(ResponseEvent::ResponseDone { response })
+                if self.response_in_progress.as_deref() == Some(response.id.as_str()) =>
+            {
+                self.response_in_progress = None;
+            }
+            ReceivedEventKind::Item {
+                item_id,
+                content_index,
+                data: ReceivedItemEventKind::AudioDelta { delta },
+                ..
+            } => {
+                let is_new_item = self
+                    .playing
+                    .as_ref()
+                    .is_none_or(|x| x.item_id != *item_id || x.content_index != *content_index);
+
+                if is_new_item {
+                    self.playing = Some(PlayingItem {
+                        item_id: item_id.clone(),
+                        content_index: *content_index,
+                        received_bytes: 0,
+                        played: Duration::ZERO,
+                    });
+                }
+
+                if let Some(playing) = &mut self.playing {
+                    playing.received_bytes += decoded_len(delta);
+                }
+            }
+            ReceivedEventKind::InputAudioBuffer(InputAudioBufferEvent::SpeechStarted {
+                ..
+            }) => return self.interrupt().await,
+            _ => {}
+        }
+
+        Ok(None)
+    }
+
+    /// Cancel the in-flight response (if any) and truncate the playing assistant item (if any) to what has been played.
+    pub async fn interrupt(&mut self) -> Result<Option<Interruption>, RealtimeError> {
+        if let Some(response_id) = self.response_in_progress.take() {
+            self.send(InputEvent::cancel_response(Some(&response_id)))
+                .await?;
+        }
+
+        let Some(playing) = self.playing.take() else {
+            return Ok(None);
+        };
+
+        // If everything has already been played, there's nothing to truncate
+        if playing.played >= self.received(&playing) {
+            return Ok(None);
+        }
+
+        let audio_end_ms = playing.played.as_millis() as u64;
+        self.send(InputEvent::truncate_item(
+            &playing.item_id,
+            playing.content_index,
+            audio_end_ms,
+        ))
+        .await?;
+
+        Ok(Some(Interruption {
+            item_id: playing.item_id,
+            audio_end_ms,
+        }))
+    }
+
+    async fn send(&self, event: InputEvent) -> Result<(), RealtimeError> {
+        self.sender
+            .send(event)
+            .await
+            .map_err(|_| RealtimeError::Closed)
+    }
+}
+
+/// The number of bytes encoded by a base64 string. Base64 encodes 3 bytes as 4 characters, with `=` padding at the end.
+fn decoded_len(base64: &str) -> usize {
+    let padding = base64.bytes().rev().take_while(|x| *x == b'=').count();
+
+    (base64.len() / 4 * 3).saturating_sub(padding)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use base64::{Engine, prelude::BASE64_STANDARD};
+    use serde_json::json;
+    use tokio::sync::mpsc;
+
+    use super::{BargeInController, Interruption, decoded_len};
+    use crate::providers::openai_realtime::realtime::{AudioFormat, InputEvent, ReceivedEvent};
+
+    fn event(value: serde_json::Value) -> ReceivedEvent {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn response_created(response_id: &str) -> ReceivedEvent {
+        event(json!({
+            "event_id": "e",
+            "type": "response.created",
+            "response": { "id": response_id, "status": "in_progress", "output": [] }
+        }))
+    }
+
+    /// An audio delta with the given number of bytes of audio.
+    fn audio_delta(item_id: &str, bytes: usize) -> ReceivedEvent {
+        event(json!({
+            "event_id": "e",
+            "type": "response.audio.delta",
+            "response_id": "r1",
+            "item_id": item_id,
+            "output_index": 0,
+            "content_index": 0,
+            "delta": BASE64_STANDARD.encode(vec![0u8; bytes])
+        }))
+    }
+
+    fn speech_started() -> ReceivedEvent {
+        event(json!({
+            "event_id": "e",
+            "type": "input_audio_buffer.speech_started",
+            "audio_start_ms": 0,
+            "item_id": "user"
+        }))
+    }
+
+    fn sent(rx: &mut mpsc::Receiver<InputEvent>) -> Vec<serde_json::Value> {
+        std::iter::from_fn(|| rx.try_recv().ok())
+            .map(|x| serde_json::to_value(x).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn counts_base64_padding() {
+        for len in 0..10 {
+            assert_eq!(decoded_len(&BASE64_STANDARD.encode(vec![0u8; len])), len);
+        }
+    }
+
+    #[tokio::test]
+    async fn truncates_to_the_played_audio() {
+        let (tx, mut rx) = mpsc::channel(8);
+        let mut controller = BargeInController::new(tx);
+
+        controller.observe(&response_created("r1")).await.unwrap();
+        // 1000 deltas of 25 bytes each is 25000 bytes, or ~520.8ms of PCM16 audio
+        for _ in 0..1000 {
+            controller.observe(&audio_delta("i1", 25)).await.unwrap();
+        }
+        // 0.25s at 24kHz
+        controller.played_samples(6000);
+        assert!(controller.is_playing());
+
+        let interruption = controller.observe(&speech_started()).await.unwrap();
+        assert_eq!(
+            interruption,
+            Some(Interruption {
+                item_id: "i1".to_string(),
+                audio_end_ms: 250
+            })
+        );
+
+        let sent = sent(&mut rx);
+        assert_eq!(sent.len(), 2);
+        assert_eq!(sent[0]["type"], "response.cancel");
+        assert_eq!(sent[0]["response_id"], "r1");
+        assert_eq!(sent[1]["type"], "conversation.item.truncate");
+        assert_eq!(sent[1]["item_id"], "i1");
+        assert_eq!(sent[1]["audio_end_ms"], 250);
+
+        // The interruption has been handled, so there's nothing left to interrupt
+        assert_eq!(controller.interrupt().await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn does_not_truncate_fully_played_audio() {
+        let (tx, mut rx) = mpsc::channel(8);
+        let mut controller = BargeInController::new(tx).with_format(AudioFormat::G711Ulaw);
+
+        // 800 bytes is 100ms of G.711 audio
+        controller.observe(&audio_delta("i1", 800)).await.unwrap();
+        controller.set_playback_position(Duration::from_millis(100));
+        assert!(!controller.is_playing());
+
+        assert_eq!(controller.observe(&speech_started()).await.unwrap(), None);
+        assert!(sent(&mut rx).is_empty());
+    }
+
+    #[tokio::test]
+    async fn resets_playback_for_new_items() {
+        let (tx, mut rx) = mpsc::channel(8);
+        let mut controller = BargeInController::new(tx).with_format(AudioFormat::G711Ulaw);
+
+        controller.observe(&audio_delta("i1", 800)).await.unwrap();
+        controller.played_samples(800);
+        controller.observe(&audio_delta("i2", 800)).await.unwrap();
+        // 40ms of the new item
+        controller.played_samples(320);
+
+        let interruption = controller.interrupt().await.unwrap().unwrap();
+        assert_eq!(interruption.item_id, "i2");
+        assert_eq!(interruption.audio_end_ms, 40);
+        assert_eq!(sent(&mut rx).len(), 1);
+    }
+}
@@ -1,15 +1,33 @@
 use reqwest_websocket::RequestBuilderExt;
+use serde::{Deserialize, Serialize};
 use std::fmt;
 
-use super::realtime::{RealtimeClient, RealtimeModel};
+use super::realtime::{RealtimeClient, RealtimeError, RealtimeModel, Session};
 
 const OPENAI_WSS_BASE_URL: &str = "wss://api.openai.com/v1";
 
+/// The default Azure OpenAI API version to use for realtime connections.
+pub const AZURE_DEFAULT_API_VERSION: &str = "2024-10-01-preview";
+
+/// The version of the realtime API protocol to use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RealtimeApiVersion {
+    /// The beta protocol (`OpenAI-Beta: realtime=v1`), used by the `gpt-4o-realtime-preview` models.
+    #[default]
+    Beta,
+    /// The GA protocol, used by the `gpt-realtime` models.
+    /// Events are translated to and from the types in this crate transparently.
+    Ga,
+}
+
 #[derive(Clone)]
 pub struct Client {
     api_key: String,
     base_url: String,
     http_client: reqwest::Client,
+    api_version: RealtimeApiVersion,
+    /// The Azure API version (ie `2024-10-01-preview`), if this client is for an Azure OpenAI resource.
+    azure_api_version: Option<String>,
 }
 
 impl fmt::Debug for Client {
@@ -18,6 +36,8 @@ impl fmt::Debug for Client {
             .field("api_key", b"<REDACTED>")
             .field("base_url", &self.base_url)
             .field("http_client", &self.http_client)
+            .field("api_version", &self.api_version)
+            .field("azure_api_version", &self.azure_api_version)
             .finish()
     }
 }
@@ -34,28 +54,163 @@ impl Client {
             http_client: reqwest::Client::builder()
                 .build()
                 .expect("This should build!"),
+            api_version: RealtimeApiVersion::default(),
+            azure_api_version: None,
+        }
+    }
+
+    /// Create a client for an Azure OpenAI resource, ie `https://my-resource.openai.azure.com`.
+    /// When using an Azure client, the model name passed to [`RealtimeClient::realtime_client`] should be the name of your deployment.
+    pub fn azure(api_key: &str, endpoint: &str, api_version: &str) -> Self {
+        let endpoint = endpoint.trim_end_matches('/');
+        let endpoint = if let Some(rest) = endpoint.strip_prefix("https://") {
+            format!("wss://{rest}")
+        } else if let Some(rest) = endpoint.strip_prefix("http://") {
+            format!("ws://{rest}")
+        } else {
+            endpoint.to_string()
+        };
+
+        Self {
+            azure_api_version: Some(api_version.to_string()),
+            ..Self::from_url(api_key, &format!("{endpoint}/openai"))
+        }
+    }
+
+    /// Set the version of the realtime API protocol to use.
+    pub fn with_api_version(mut self, api_version: RealtimeApiVersion) -> Self {
+        self.api_version = api_version;
+        self
+    }
+
+    pub fn api_version(&self) -> RealtimeApiVersion {
+        self.api_version
+    }
+
+    pub fn is_azure(&self) -> bool {
+        self.azure_api_version.is_some()
+    }
+
+    /// The websocket path (including query) for a realtime connection to the given model.
+    /// For Azure, the model is the deployment name.
+    pub(crate) fn realtime_path(&self, model: &str, transcription: bool) -> String {
+        let mut path = match (transcription, &self.azure_api_version) {
+            (true, _) => "/realtime?intent=transcription".to_string(),
+            (false, Some(_)) => format!("/realtime?deployment={model}"),
+            (false, None) => format!("/realtime?model={model}"),
+        };
+
+        if let Some(api_version) = &self.azure_api_version {
+            path.push_str(&format!("&api-version={api_version}"));
+        }
+
+        path
+    }
+
+    /// Azure uses an `api-key` header rather than bearer auth.
+    fn authenticate(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        if self.is_azure() {
+            request.header("api-key", &self.api_key)
+        } else {
+            request.bearer_auth(&self.api_key)
         }
     }
 
     pub async fn initiate_websocket(
         &self,
         path: &str,
-    ) -> Result<reqwest_websocket::WebSocket, reqwest_websocket::Error> {
+    ) -> Result<reqwest_websocket::WebSocket, RealtimeError> {
         let url = format!("{base_url}{path}", base_url = self.base_url);
 
-        let response = self
-            .http_client
-            .get(url)
-            .bearer_auth(&self.api_key)
-            .header("OpenAI-Beta", "realtime=v1")
+        let mut request = self.authenticate(self.http_client.get(url));
+
+        if self.api_version == RealtimeApiVersion::Beta {
+            request = request.header("OpenAI-Beta", "realtime=v1");
+        }
+
+        let response = request
             .upgrade()
             .send()
+            .await
+            .map_err(RealtimeError::from_handshake)?;
+
+        response
+            .into_websocket()
+            .await
+            .map_err(RealtimeError::from_handshake)
+    }
+
+    /// The HTTP(S) equivalent of the websocket base URL, for use with the REST API.
+    fn http_base_url(&self) -> String {
+        if let Some(rest) = self.base_url.strip_prefix("wss://") {
+            format!("https://{rest}")
+        } else if let Some(rest) = self.base_url.strip_prefix("ws://") {
+            format!("http://{rest}")
+        } else {
+            self.base_url.clone()
+        }
+    }
+
+    /// Mint an ephemeral session token for the given model and session config.
+    /// The returned client secret can be handed to browser/WebRTC clients so that they can connect to the realtime API directly without exposing your API key.
+    pub async fn create_ephemeral_session(
+        &self,
+        model: &str,
+        session: &Session,
+    ) -> Result<EphemeralSession, RealtimeError> {
+        let url = match &self.azure_api_version {
+            Some(api_version) => format!(
+                "{base_url}/realtimeapi/sessions?api-version={api_version}",
+                base_url = self.http_base_url()
+            ),
+            None => format!(
+                "{base_url}/realtime/sessions",
+                base_url = self.http_base_url()
+            ),
+        };
+
+        let response = self
+            .authenticate(self.http_client.post(url))
+            .json(&CreateSessionRequest { model, session })
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
             .await?;
 
-        response.into_websocket().await
+        Ok(response)
     }
 }
 
+#[derive(Serialize)]
+struct CreateSessionRequest<'a> {
+    model: &'a str,
+    #[serde(flatten)]
+    session: &'a Session,
+}
+
+/// A session created through the REST API, containing an ephemeral client secret.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct EphemeralSession {
+    #[serde(default)]
+    pub id: Option<String>,
+    #[serde(default)]
+    pub model: Option<String>,
+    pub client_secret: ClientSecret,
+    /// The session config the session was created with.
+    #[serde(flatten)]
+    pub session: Session,
+}
+
+/// An ephemeral API token.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ClientSecret {
+    /// The ephemeral key. Use this in place of an API key when authenticating client-side.
+    pub value: String,
+    /// The unix timestamp (in seconds) at which the key expires.
+    pub expires_at: u64,
+}
+
 impl RealtimeClient for super::client::Client {
     type Output = RealtimeModel;
 
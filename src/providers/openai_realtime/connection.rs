@@ -0,0 +1,581 @@
+//! The background task that owns the realtime websocket.
+//! Input events are forwarded to the websocket, and received events are parsed and forwarded to the output channel.
+use std::collections::HashMap;
+use std::time::Duration;
+
+use futures::{SinkExt, StreamExt};
+use reqwest_websocket::{Message, WebSocket};
+use serde::Deserialize;
+use serde_json::Value;
+use tokio::sync::mpsc::{Receiver, Sender};
+use tokio::time::Instant;
+use tracing::Instrument;
+
+use super::client::RealtimeApiVersion;
+use super::ga;
+use super::realtime::{
+    AudioFormat, CloseReason, ConnectionEvent, InputAudioBufferEvent, InputEvent, InputEventKind,
+    MAX_APPEND_AUDIO_BYTES, RealtimeError, RealtimeModel, ReceivedEvent, ReceivedEventKind,
+    ReceivedItemEventKind, ResponseEvent, SessionEvent,
+};
+use super::recording::Direction;
+
+/// A reconnection policy for realtime connections.
+/// When the websocket drops, the connection will be re-opened with exponential backoff and the last known session config will be replayed.
+///
+/// Note that the conversation itself is not restored, as OpenAI does not persist conversations between connections.
+#[derive(Debug, Clone)]
+pub struct ReconnectPolicy {
+    max_attempts: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(10),
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The maximum number of consecutive reconnection attempts before giving up.
+    pub fn max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts;
+        self
+    }
+
+    /// The delay before the first reconnection attempt. This doubles with every failed attempt.
+    pub fn initial_backoff(mut self, initial_backoff: Duration) -> Self {
+        self.initial_backoff = initial_backoff;
+        self
+    }
+
+    /// The maximum delay between reconnection attempts.
+    pub fn max_backoff(mut self, max_backoff: Duration) -> Self {
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+
+        self.initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+    }
+}
+
+/// How long to wait for the server to close the connection after sending a close frame.
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Keepalive settings for realtime connections.
+/// Long quiet periods (ie when nobody is speaking) can cause the websocket to be silently closed by proxies or the server.
+/// Sending periodic pings keeps the connection alive, and lets a dead connection be detected if nothing (including pongs) has been received for a while.
+#[derive(Debug, Clone)]
+pub struct KeepAlive {
+    interval: Duration,
+    timeout: Duration,
+}
+
+impl Default for KeepAlive {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(20),
+            timeout: Duration::from_secs(60),
+        }
+    }
+}
+
+impl KeepAlive {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// How often to send a ping frame.
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// How long the connection can go without receiving any messages before it is considered dead.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+pub(super) struct Connection {
+    model: RealtimeModel,
+    websocket: WebSocket,
+    /// The last known session config, as a session update event. Replayed on reconnection.
+    session_update: Option<InputEvent>,
+    /// The input audio format of the session, used to work out how large audio append chunks should be.
+    input_format: AudioFormat,
+    responses: ResponseSpans,
+}
+
+impl Connection {
+    pub(super) fn new(
+        model: RealtimeModel,
+        websocket: WebSocket,
+        session_update: Option<InputEvent>,
+    ) -> Self {
+        let mut connection = Self {
+            model,
+            websocket,
+            session_update: None,
+            input_format: AudioFormat::Pcm16,
+            responses: ResponseSpans::default(),
+        };
+
+        if let Some(session_update) = session_update {
+            connection.track_session(&session_update);
+        }
+
+        connection
+    }
+
+    /// Records a session update, so that it can be replayed on reconnection. Any other events are ignored.
+    fn track_session(&mut self, event: &InputEvent) {
+        let format = match &event.data {
+            InputEventKind::UpdateSession { session } => session.input_audio_format,
+            InputEventKind::UpdateTranscriptionSession { session } => session.input_audio_format,
+            _ => return,
+        };
+
+        if let Some(format) = format {
+            self.input_format = format;
+        }
+
+        self.session_update = Some(event.clone());
+    }
+
+    /// The maximum number of base64 characters in a single audio append.
+    fn max_append_chars(&self) -> usize {
+        let max_bytes = self
+            .model
+            .append_chunk_duration()
+            .map(|x| x.as_millis() as usize * self.input_format.bytes_per_ms())
+            .unwrap_or(MAX_APPEND_AUDIO_BYTES)
+            .min(MAX_APPEND_AUDIO_BYTES);
+
+        // Every 3 bytes is encoded as 4 characters, so chunks are kept to a multiple of 4 characters to stay valid base64 on their own
+        (max_bytes / 3).max(1) * 4
+    }
+
+    /// Sends an input event, splitting audio appends that are too large into multiple events.
+    async fn send_input(&mut self, event: &InputEvent) -> Result<(), RealtimeError> {
+        if matches!(event.data, InputEventKind::CommitAudioInputBuffer) {
+            self.responses.committed();
+        }
+
+        let InputEventKind::AppendAudioInput { audio } = &event.data else {
+            return self.send(event).await;
+        };
+
+        let max_chars = self.max_append_chars();
+        if audio.len() <= max_chars || !audio.is_ascii() {
+            return self.send(event).await;
+        }
+
+        for (idx, chunk) in audio.as_bytes().chunks(max_chars).enumerate() {
+            // This can't fail as the audio is ASCII
+            let chunk = String::from_utf8_lossy(chunk);
+            let mut chunk_event = InputEvent::append_audio(&chunk);
+
+            // Only the first chunk keeps the event ID, as event IDs should be unique
+            if idx == 0
+                && let Some(id) = event.event_id()
+            {
+                chunk_event = chunk_event.with_id(id);
+            }
+
+            self.send(&chunk_event).await?;
+        }
+
+        Ok(())
+    }
+
+    pub(super) async fn run(
+        mut self,
+        mut input: Receiver<InputEvent>,
+        output: Sender<ReceivedEvent>,
+    ) {
+        let keepalive = self.model.keepalive().cloned();
+        // If keepalive is disabled, the ticker branch is never polled so the period doesn't matter
+        let period = keepalive
+            .as_ref()
+            .map(|x| x.interval)
+            .unwrap_or(Duration::from_secs(60));
+        let mut ticker = tokio::time::interval_at(Instant::now() + period, period);
+        let mut last_seen = Instant::now();
+
+        tracing::info!("Realtime connection opened");
+
+        let reason = loop {
+            tokio::select! {
+                event = input.recv() => {
+                    let Some(event) = event else {
+                        tracing::debug!("All senders dropped, closing realtime connection");
+                        let _ = SinkExt::close(&mut self.websocket).await;
+                        break CloseReason::Client;
+                    };
+
+                    if matches!(event.data, InputEventKind::Close) {
+                        tracing::debug!("Gracefully closing realtime connection");
+                        self.close_gracefully(&output).await;
+                        break CloseReason::Client;
+                    }
+
+                    self.track_session(&event);
+
+                    if let Err(err) = self.send_input(&event).await {
+                        tracing::warn!("Failed to send event: {err}");
+                        let _ = output
+                            .send(ReceivedEvent::connection(ConnectionEvent::SendFailed {
+                                event_id: event.event_id().map(str::to_string),
+                                error: err.to_string(),
+                            }))
+                            .await;
+
+                        // A serialization error means the event itself is bad rather than the connection
+                        if !matches!(err, RealtimeError::Serde(_)) {
+                            if !self.reconnect(&output).await {
+                                break CloseReason::Error { message: err.to_string() };
+                            }
+                            last_seen = Instant::now();
+                        }
+                    }
+                }
+                _ = ticker.tick(), if keepalive.is_some() => {
+                    let timeout = keepalive.as_ref().map(|x| x.timeout).unwrap_or_default();
+
+                    if last_seen.elapsed() > timeout {
+                        tracing::warn!("No messages received from the realtime API in {timeout:?}, assuming the connection is dead");
+                        if !self.reconnect(&output).await {
+                            break CloseReason::IdleTimeout;
+                        }
+                        last_seen = Instant::now();
+                    } else if let Err(err) = self.websocket.send(Message::Ping(Default::default())).await {
+                        tracing::warn!("Failed to send ping: {err}");
+                        if !self.reconnect(&output).await {
+                            break CloseReason::Error { message: err.to_string() };
+                        }
+                        last_seen = Instant::now();
+                    }
+                }
+                message = self.websocket.next() => {
+                    if matches!(message, Some(Ok(_))) {
+                        last_seen = Instant::now();
+                    }
+
+                    match message {
+                        Some(Ok(Message::Text(txt))) => {
+                            tracing::trace!("Received text: {txt}");
+                            let event = self.parse_event(txt);
+                            self.responses.observe(&event);
+
+                            match &event.data {
+                                ReceivedEventKind::Session(SessionEvent::SessionUpdated { session }) => {
+                                    self.track_session(&InputEvent::update_session(session.clone()));
+                                }
+                                ReceivedEventKind::Session(SessionEvent::TranscriptionSessionUpdated { session }) => {
+                                    self.track_session(&InputEvent::update_transcription_session(session.clone()));
+                                }
+                                _ => {}
+                            }
+
+                            if output.send(event).await.is_err() {
+                                tracing::debug!("Event stream dropped, closing realtime connection");
+                                let _ = SinkExt::close(&mut self.websocket).await;
+                                break CloseReason::Client;
+                            }
+                        }
+                        Some(Ok(thing)) => {
+                            tracing::debug!("Got thing that was not a text message: {thing:?}");
+                        }
+                        Some(Err(err)) => {
+                            let err = RealtimeError::Receive(err);
+                            tracing::warn!("{err}");
+                            if !self.reconnect(&output).await {
+                                break CloseReason::Error { message: err.to_string() };
+                            }
+                            last_seen = Instant::now();
+                        }
+                        None => {
+                            tracing::debug!("Websocket closed");
+                            if !self.reconnect(&output).await {
+                                break CloseReason::Server;
+                            }
+                            last_seen = Instant::now();
+                        }
+                    }
+                }
+            }
+        };
+
+        tracing::info!(?reason, "Realtime connection closed");
+
+        // Let the application know why the stream is ending (if it's still listening)
+        let _ = output
+            .send(ReceivedEvent::connection(
+                ConnectionEvent::ConnectionClosed { reason },
+            ))
+            .await;
+    }
+
+    async fn send(&mut self, event: &InputEvent) -> Result<(), RealtimeError> {
+        if let Some(recorder) = self.model.recorder() {
+            recorder.record(Direction::Outbound, event);
+        }
+
+        let mut value = serde_json::to_value(event)?;
+        if self.model.api_version() == RealtimeApiVersion::Ga {
+            value = ga::outbound(value);
+        }
+
+        for hook in self.model.outbound_hooks() {
+            hook.call(&mut value);
+        }
+
+        let json = serde_json::to_string(&value)?;
+        let event_type = value.get("type").and_then(Value::as_str);
+        let span = tracing::debug_span!(
+            "realtime_send",
+            event_type,
+            event_id = event.event_id(),
+            item_id = item_id(&value),
+            bytes = json.len(),
+        );
+
+        self.websocket
+            .send(Message::Text(json))
+            .instrument(span)
+            .await
+            .map_err(RealtimeError::Send)?;
+
+        Ok(())
+    }
+
+    /// Attempts to re-open the websocket according to the model's reconnect policy.
+    /// Returns whether or not the connection was re-established.
+    #[tracing::instrument(name = "realtime_reconnect", skip_all)]
+    async fn reconnect(&mut self, output: &Sender<ReceivedEvent>) -> bool {
+        let Some(policy) = self.model.reconnect_policy().cloned() else {
+            return false;
+        };
+
+        // Responses that were in progress won't be resumed on the new connection
+        self.responses = ResponseSpans::default();
+
+        for attempt in 1..=policy.max_attempts {
+            tokio::time::sleep(policy.backoff(attempt)).await;
+
+            let websocket = match self.model.connect().await {
+                Ok(websocket) => websocket,
+                Err(err) => {
+                    tracing::warn!("Reconnection attempt {attempt} failed: {err}");
+                    continue;
+                }
+            };
+
+            self.websocket = websocket;
+
+            if let Some(session_update) = self.session_update.clone()
+                && let Err(err) = self.send(&session_update).await
+            {
+                tracing::warn!("Failed to restore session after reconnecting: {err}");
+                continue;
+            }
+
+            tracing::info!("Reconnected to realtime API after {attempt} attempt(s)");
+            let _ = output
+                .send(ReceivedEvent::connection(ConnectionEvent::Reconnected {
+                    attempts: attempt,
+                }))
+                .await;
+
+            return true;
+        }
+
+        false
+    }
+
+    /// Sends a close frame, then forwards any remaining events until the server closes the connection (or a timeout elapses).
+    async fn close_gracefully(&mut self, output: &Sender<ReceivedEvent>) {
+        if let Err(err) = SinkExt::close(&mut self.websocket).await {
+            tracing::warn!("Failed to send close frame: {err}");
+            return;
+        }
+
+        let drain = async {
+            while let Some(Ok(message)) = self.websocket.next().await {
+                if let Message::Text(txt) = message
+                    && output.send(self.parse_event(txt)).await.is_err()
+                {
+                    break;
+                }
+            }
+        };
+
+        if tokio::time::timeout(CLOSE_TIMEOUT, drain).await.is_err() {
+            tracing::debug!("Timed out waiting for the server to close the connection");
+        }
+    }
+
+    fn parse_event(&self, txt: String) -> ReceivedEvent {
+        let bytes = txt.len();
+        let mut value = match serde_json::from_str::<serde_json::Value>(&txt) {
+            Ok(value) => value,
+            Err(err) => {
+                tracing::warn!("Failed to parse event: {err}");
+                return ReceivedEvent::unknown(serde_json::Value::String(txt));
+            }
+        };
+
+        for hook in self.model.inbound_hooks() {
+            hook.call(&mut value);
+        }
+
+        if self.model.api_version() == RealtimeApiVersion::Ga {
+            value = ga::inbound(value);
+        }
+
+        let event_type = value.get("type").and_then(Value::as_str);
+        tracing::debug!(
+            event_type,
+            item_id = item_id(&value),
+            bytes,
+            "Received event"
+        );
+
+        // Deserialize from a reference so the (potentially large) value isn't cloned for every event
+        let event = ReceivedEvent::deserialize(&value).unwrap_or_else(|err| {
+            tracing::warn!("Failed to parse event: {err}");
+            ReceivedEvent::unknown(value)
+        });
+
+        if let Some(recorder) = self.model.recorder() {
+            recorder.record(Direction::Inbound, &event);
+        }
+
+        event
+    }
+}
+
+/// The ID of the item an event refers to, if any.
+fn item_id(event: &Value) -> Option<&str> {
+    event
+        .get("item_id")
+        .or_else(|| event.pointer("/item/id"))
+        .and_then(Value::as_str)
+}
+
+/// Tracing spans for in-progress responses, used to profile response latency.
+#[derive(Default)]
+struct ResponseSpans {
+    /// When the input audio buffer was last committed, either by us or by server VAD.
+    committed_at: Option<Instant>,
+    in_progress: HashMap<String, ResponseSpan>,
+}
+
+struct ResponseSpan {
+    span: tracing::Span,
+    created_at: Instant,
+    committed_at: Option<Instant>,
+    received_audio: bool,
+    audio_bytes: usize,
+}
+
+impl ResponseSpans {
+    /// Records that the input audio buffer has been committed. The first commit before a response is used to measure latency.
+    fn committed(&mut self) {
+        self.committed_at.get_or_insert_with(Instant::now);
+    }
+
+    fn observe(&mut self, event: &ReceivedEvent) {
+        match &event.data {
+            ReceivedEventKind::InputAudioBuffer(InputAudioBufferEvent::Committed { .. }) => {
+                self.committed();
+            }
+            ReceivedEventKind::Response(ResponseEvent::ResponseCreated { response }) => {
+                let span = tracing::info_span!(
+                    "realtime_response",
+                    response_id = %response.id,
+                    status = tracing::field::Empty,
+                    audio_bytes = tracing::field::Empty,
+                    output_tokens = tracing::field::Empty,
+                    first_audio_ms = tracing::field::Empty,
+                    commit_to_first_audio_ms = tracing::field::Empty,
+                    duration_ms = tracing::field::Empty,
+                );
+
+                self.in_progress.insert(
+                    response.id.clone(),
+                    ResponseSpan {
+                        span,
+                        created_at: Instant::now(),
+                        committed_at: self.committed_at.take(),
+                        received_audio: false,
+                        audio_bytes: 0,
+                    },
+                );
+            }
+            ReceivedEventKind::Item {
+                item_id,
+                response_id,
+                data: ReceivedItemEventKind::AudioDelta { delta },
+                ..
+            } => {
+                let Some(response) = self.in_progress.get_mut(response_id) else {
+                    return;
+                };
+
+                response.audio_bytes += delta.len() / 4 * 3;
+
+                if !response.received_audio {
+                    response.received_audio = true;
+                    response.span.record(
+                        "first_audio_ms",
+                        response.created_at.elapsed().as_millis() as u64,
+                    );
+                    if let Some(committed_at) = response.committed_at {
+                        response.span.record(
+                            "commit_to_first_audio_ms",
+                            committed_at.elapsed().as_millis() as u64,
+                        );
+                    }
+                    response.span.in_scope(|| {
+                        tracing::debug!(item_id = item_id.as_str(), "Received first audio delta")
+                    });
+                }
+            }
+            ReceivedEventKind::Response(ResponseEvent::ResponseDone { response: done }) => {
+                // Dropping the span closes it
+                let Some(response) = self.in_progress.remove(&done.id) else {
+                    return;
+                };
+
+                response.span.record("status", done.status.as_str());
+                response.span.record("audio_bytes", response.audio_bytes);
+                response.span.record(
+                    "duration_ms",
+                    response.created_at.elapsed().as_millis() as u64,
+                );
+                if let Some(usage) = &done.usage {
+                    response.span.record("output_tokens", usage.output_tokens);
+                }
+                response.span.in_scope(|| tracing::debug!("Response done"));
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,353 @@
+//! A local mirror of the server-side conversation.
+//!
+//! The realtime API only sends the conversation as a series of events and deltas.
+//! [`ConversationState`] consumes these events and maintains an ordered list of conversation items (user messages and transcripts, assistant text and audio transcripts, function calls) that can be queried at any time.
+use std::sync::{Arc, RwLock};
+
+use futures::{StreamExt, stream::BoxStream};
+
+use super::realtime::{
+    ContentPart, ConversationEvent, ConversationItem, ItemRole, McpEvent, ReceivedEvent,
+    ReceivedEventKind, ReceivedItemEventKind, ResponseEvent, TranscriptionEvent,
+};
+
+/// An ordered mirror of the items in a realtime conversation.
+#[derive(Debug, Clone, Default)]
+pub struct ConversationState {
+    items: Vec<ConversationItem>,
+}
+
+impl ConversationState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Wrap an event stream so that every event is applied to a shared conversation state before being passed on.
+    pub fn track<'a>(
+        stream: BoxStream<'a, ReceivedEvent>,
+        state: Arc<RwLock<ConversationState>>,
+    ) -> BoxStream<'a, ReceivedEvent> {
+        stream
+            .inspect(move |evt| {
+                if let Ok(mut state) = state.write() {
+                    state.apply(evt);
+                }
+            })
+            .boxed()
+    }
+
+    /// All items in the conversation, in order.
+    pub fn items(&self) -> &[ConversationItem] {
+        &self.items
+    }
+
+    /// Retrieve an item by its ID.
+    pub fn get(&self, item_id: &str) -> Option<&ConversationItem> {
+        self.items.iter().find(|x| x.id() == Some(item_id))
+    }
+
+    /// A plain text view of the conversation messages, using transcripts for audio content.
+    /// Messages without any text (ie audio that has not been transcribed) are skipped.
+    pub fn transcript(&self) -> Vec<(ItemRole, String)> {
+        self.items
+            .iter()
+            .filter_map(|item| {
+                let ConversationItem::Message { role, content, .. } = item else {
+                    return None;
+                };
+
+                let text = content
+                    .iter()
+                    .filter_map(|part| match part {
+                        ContentPart::InputText { text } | ContentPart::Text { text } => {
+                            Some(text.as_str())
+                        }
+                        ContentPart::InputAudio { transcript, .. }
+                        | ContentPart::Audio { transcript, .. } => transcript.as_deref(),
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" ");
+
+                (!text.is_empty()).then_some((*role, text))
+            })
+            .collect()
+    }
+
+    /// Apply a received event to the conversation state. Events that don't affect the conversation are ignored.
+    pub fn apply(&mut self, evt: &ReceivedEvent) {
+        match &evt.data {
+            ReceivedEventKind::Conversation(ConversationEvent::ItemCreated {
+                previous_item_id,
+                item,
+            }) => self.insert(item.clone(), previous_item_id.as_deref()),
+            ReceivedEventKind::Conversation(ConversationEvent::ItemDeleted { item_id }) => {
+                self.items.retain(|x| x.id() != Some(item_id.as_str()));
+            }
+            ReceivedEventKind::Conversation(ConversationEvent::ItemTruncated {
+                item_id,
+                content_index,
+                ..
+            }) => {
+                // Truncating an item's audio also removes its transcript server-side
+                if let Some(ContentPart::Audio { transcript, .. }) =
+                    self.content_part(item_id, *content_index, || ContentPart::Audio {
+                        audio: None,
+                        transcript: None,
+                    })
+                {
+                    *transcript = None;
+                }
+            }
+            ReceivedEventKind::Response(ResponseEvent::OutputItemAdded { item, .. }) => {
+                self.insert(item.clone(), None);
+            }
+            ReceivedEventKind::Response(ResponseEvent::FunctionCallArgumentsDone {
+                item_id,
+                arguments: new_arguments,
+                ..
+            })
+            | ReceivedEventKind::Mcp(McpEvent::CallArgumentsDone {
+                item_id,
+                arguments: new_arguments,
+                ..
+            }) => {
+                if let Some(
+                    ConversationItem::FunctionCall { arguments, .. }
+                    | ConversationItem::McpCall { arguments, .. },
+                ) = self.item_mut(item_id)
+                {
+                    *arguments = new_arguments.clone();
+                }
+            }
+            ReceivedEventKind::Response(ResponseEvent::ResponseDone { response }) => {
+                // The finished response contains the final state of each output item
+                for item in &response.output {
+                    self.insert(item.clone(), None);
+                }
+            }
+            ReceivedEventKind::Item {
+                item_id,
+                content_index,
+                data,
+                ..
+            } => self.apply_item_event(item_id, *content_index, data),
+            ReceivedEventKind::Transcription(TranscriptionEvent::Completed {
+                item_id,
+                content_index,
+                transcript: new_transcript,
+            }) => {
+                if let Some(ContentPart::InputAudio { transcript, .. }) =
+                    self.content_part(item_id, *content_index, || ContentPart::InputAudio {
+                        audio: None,
+                        transcript: None,
+                    })
+                {
+                    *transcript = Some(new_transcript.clone());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn apply_item_event(
+        &mut self,
+        item_id: &str,
+        content_index: u64,
+        data: &ReceivedItemEventKind,
+    ) {
+        let empty_text = || ContentPart::Text {
+            text: String::new(),
+        };
+        let empty_audio = || ContentPart::Audio {
+            audio: None,
+            transcript: None,
+        };
+
+        match data {
+            ReceivedItemEventKind::TextDelta { delta } => {
+                if let Some(ContentPart::Text { text }) =
+                    self.content_part(item_id, content_index, empty_text)
+                {
+                    text.push_str(delta);
+                }
+            }
+            ReceivedItemEventKind::TextDone { text: done } => {
+                if let Some(ContentPart::Text { text }) =
+                    self.content_part(item_id, content_index, empty_text)
+                {
+                    *text = done.clone();
+                }
+            }
+            ReceivedItemEventKind::AudioTranscriptDelta { delta } => {
+                if let Some(ContentPart::Audio { transcript, .. }) =
+                    self.content_part(item_id, content_index, empty_audio)
+                {
+                    transcript.get_or_insert_with(String::new).push_str(delta);
+                }
+            }
+            ReceivedItemEventKind::AudioTranscriptDone { transcript: done } => {
+                if let Some(ContentPart::Audio { transcript, .. }) =
+                    self.content_part(item_id, content_index, empty_audio)
+                {
+                    *transcript = Some(done.clone());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Inserts an item after the item with the given ID (or at the end), replacing any existing item with the same ID.
+    fn insert(&mut self, item: ConversationItem, previous_item_id: Option<&str>) {
+        if let Some(existing) = item.id().and_then(|id| self.item_mut(id)) {
+            *existing = item;
+            return;
+        }
+
+        let position = previous_item_id
+            .and_then(|prev| self.items.iter().position(|x| x.id() == Some(prev)))
+            .map(|idx| idx + 1)
+            .unwrap_or(self.items.len());
+
+        self.items.insert(position, item);
+    }
+
+    fn item_mut(&mut self, item_id: &str) -> Option<&mut ConversationItem> {
+        self.items.iter_mut().find(|x| x.id() == Some(item_id))
+    }
+
+    /// Retrieve a content part of a message, creating it (and any missing parts before it) with `empty` if it does not exist yet.
+    fn content_part(
+        &mut self,
+        item_id: &str,
+        content_index: u64,
+        empty: impl Fn() -> ContentPart,
+    ) -> Option<&mut ContentPart> {
+        let ConversationItem::Message { content, .. } = self.item_mut(item_id)? else {
+            return None;
+        };
+
+        let idx = content_index as usize;
+        while content.len() <= idx {
+            content.push(empty());
+        }
+
+        content.get_mut(idx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::ConversationState;
+    use crate::providers::openai_realtime::realtime::{ItemRole, ReceivedEvent};
+
+    fn apply(state: &mut ConversationState, value: serde_json::Value) {
+        let evt: ReceivedEvent = serde_json::from_value(value).unwrap();
+        state.apply(&evt);
+    }
+
+    fn message(id: &str, role: &str, text: &str) -> serde_json::Value {
+        let kind = if role == "user" { "input_text" } else { "text" };
+        json!({ "id": id, "type": "message", "role": role, "content": [{ "type": kind, "text": text }] })
+    }
+
+    fn ids(state: &ConversationState) -> Vec<&str> {
+        state.items().iter().filter_map(|x| x.id()).collect()
+    }
+
+    #[test]
+    fn inserts_items_after_their_previous_item() {
+        let mut state = ConversationState::new();
+        apply(
+            &mut state,
+            json!({ "event_id": "e1", "type": "conversation.item.created", "previous_item_id": null, "item": message("i1", "user", "One") }),
+        );
+        apply(
+            &mut state,
+            json!({ "event_id": "e2", "type": "conversation.item.created", "previous_item_id": "i1", "item": message("i3", "user", "Three") }),
+        );
+        apply(
+            &mut state,
+            json!({ "event_id": "e3", "type": "conversation.item.created", "previous_item_id": "i1", "item": message("i2", "user", "Two") }),
+        );
+        assert_eq!(ids(&state), ["i1", "i2", "i3"]);
+
+        // An item with an existing ID replaces the existing item in place
+        apply(
+            &mut state,
+            json!({ "event_id": "e4", "type": "conversation.item.created", "previous_item_id": null, "item": message("i2", "user", "Two again") }),
+        );
+        assert_eq!(ids(&state), ["i1", "i2", "i3"]);
+        assert_eq!(
+            state.transcript()[1],
+            (ItemRole::User, "Two again".to_string())
+        );
+
+        apply(
+            &mut state,
+            json!({ "event_id": "e5", "type": "conversation.item.deleted", "item_id": "i1" }),
+        );
+        assert_eq!(ids(&state), ["i2", "i3"]);
+    }
+
+    #[test]
+    fn accumulates_deltas() {
+        let mut state = ConversationState::new();
+        apply(
+            &mut state,
+            json!({ "event_id": "e1", "type": "response.output_item.added", "response_id": "r1", "output_index": 0, "item": { "id": "i1", "type": "message", "role": "assistant", "content": [] } }),
+        );
+
+        for (idx, delta) in ["Hel", "lo"].into_iter().enumerate() {
+            apply(
+                &mut state,
+                json!({ "event_id": format!("t{idx}"), "type": "response.text.delta", "response_id": "r1", "item_id": "i1", "output_index": 0, "content_index": 0, "delta": delta }),
+            );
+        }
+        assert_eq!(
+            state.transcript(),
+            [(ItemRole::Assistant, "Hello".to_string())]
+        );
+
+        apply(
+            &mut state,
+            json!({ "event_id": "e2", "type": "response.text.done", "response_id": "r1", "item_id": "i1", "output_index": 0, "content_index": 0, "text": "Hello!" }),
+        );
+        assert_eq!(
+            state.transcript(),
+            [(ItemRole::Assistant, "Hello!".to_string())]
+        );
+
+        // Audio transcripts are accumulated into their own content part
+        apply(
+            &mut state,
+            json!({ "event_id": "e3", "type": "response.audio_transcript.delta", "response_id": "r1", "item_id": "i1", "output_index": 0, "content_index": 1, "delta": "Bye" }),
+        );
+        assert_eq!(
+            state.transcript(),
+            [(ItemRole::Assistant, "Hello! Bye".to_string())]
+        );
+    }
+
+    #[test]
+    fn truncating_audio_removes_its_transcript() {
+        let mut state = ConversationState::new();
+        apply(
+            &mut state,
+            json!({ "event_id": "e1", "type": "response.output_item.added", "response_id": "r1", "output_index": 0, "item": { "id": "i1", "type": "message", "role": "assistant", "content": [] } }),
+        );
+        apply(
+            &mut state,
+            json!({ "event_id": "e2", "type": "response.audio_transcript.done", "response_id": "r1", "item_id": "i1", "output_index": 0, "content_index": 0, "transcript": "A long answer" }),
+        );
+        assert_eq!(state.transcript().len(), 1);
+
+        apply(
+            &mut state,
+            json!({ "event_id": "e3", "type": "conversation.item.truncated", "item_id": "i1", "content_index": 0, "audio_end_ms": 1500 }),
+        );
+        assert!(state.transcript().is_empty());
+        assert!(state.get("i1").is_some());
+    }
+}
@@ -0,0 +1,190 @@
+//! Cost estimation for realtime sessions.
+//!
+//! [`CostTracker`] consumes the usage reported on `response.done` events and converts it to an estimated cost in US dollars using a [`Pricing`] table.
+//! Estimates are only as accurate as the pricing table, so make sure it matches what you're actually billed.
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
+use super::realtime::{ReceivedEvent, ReceivedEventKind, ResponseEvent, Usage};
+
+/// Prices in US dollars per million tokens.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pricing {
+    pub text_input: f64,
+    pub cached_text_input: f64,
+    pub text_output: f64,
+    pub audio_input: f64,
+    pub cached_audio_input: f64,
+    pub audio_output: f64,
+}
+
+impl Pricing {
+    /// Pricing for `gpt-realtime`.
+    pub const GPT_REALTIME: Pricing = Pricing {
+        text_input: 4.0,
+        cached_text_input: 0.4,
+        text_output: 16.0,
+        audio_input: 32.0,
+        cached_audio_input: 0.4,
+        audio_output: 64.0,
+    };
+
+    /// Pricing for the `gpt-4o-realtime-preview` models.
+    pub const GPT_4O_REALTIME_PREVIEW: Pricing = Pricing {
+        text_input: 5.0,
+        cached_text_input: 2.5,
+        text_output: 20.0,
+        audio_input: 40.0,
+        cached_audio_input: 2.5,
+        audio_output: 80.0,
+    };
+
+    /// Pricing for the `gpt-4o-mini-realtime-preview` models.
+    pub const GPT_4O_MINI_REALTIME_PREVIEW: Pricing = Pricing {
+        text_input: 0.6,
+        cached_text_input: 0.3,
+        text_output: 2.4,
+        audio_input: 10.0,
+        cached_audio_input: 0.3,
+        audio_output: 20.0,
+    };
+
+    /// Look up the pricing for a known model.
+    pub fn for_model(model: &str) -> Option<Self> {
+        if model.starts_with("gpt-4o-mini-realtime") {
+            Some(Self::GPT_4O_MINI_REALTIME_PREVIEW)
+        } else if model.starts_with("gpt-4o-realtime") {
+            Some(Self::GPT_4O_REALTIME_PREVIEW)
+        } else if model.starts_with("gpt-realtime") {
+            Some(Self::GPT_REALTIME)
+        } else {
+            None
+        }
+    }
+
+    /// The estimated cost (in US dollars) of the given usage.
+    pub fn cost(&self, usage: &Usage) -> f64 {
+        let input = &usage.input_token_details;
+        let cached = &input.cached_tokens_details;
+        let output = &usage.output_token_details;
+
+        let tokens_and_prices = [
+            (
+                input.text_tokens.saturating_sub(cached.text_tokens),
+                self.text_input,
+            ),
+            (cached.text_tokens, self.cached_text_input),
+            (
+                input.audio_tokens.saturating_sub(cached.audio_tokens),
+                self.audio_input,
+            ),
+            (cached.audio_tokens, self.cached_audio_input),
+            (output.text_tokens, self.text_output),
+            (output.audio_tokens, self.audio_output),
+        ];
+
+        tokens_and_prices
+            .into_iter()
+            .map(|(tokens, price)| tokens as f64 * price / 1_000_000.0)
+            .sum()
+    }
+}
+
+/// The usage and estimated cost of a single session.
+#[derive(Debug, Clone, Default)]
+pub struct SessionCost {
+    pub usage: Usage,
+    /// The estimated cost in US dollars.
+    pub cost: f64,
+    pub responses: u64,
+}
+
+/// Tracks the estimated cost of realtime sessions, keyed by session id. Clones share their totals, so one tracker can
+/// be handed to every session in an application.
+#[derive(Debug, Clone)]
+pub struct CostTracker {
+    pricing: Pricing,
+    sessions: Arc<RwLock<HashMap<String, SessionCost>>>,
+}
+
+impl CostTracker {
+    pub fn new(pricing: Pricing) -> Self {
+        Self {
+            pricing,
+            sessions: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    /// Observe an event for a session, returning the estimated cost of the response if the event is a `response.done` event with usage.
+    pub fn observe(&self, session_id: &str, evt: &ReceivedEvent) -> Option<f64> {
+        let ReceivedEventKind::Response(ResponseEvent::ResponseDone { response }) = &evt.data
+        else {
+            return None;
+        };
+
+        let usage = response.usage.as_ref()?;
+        let cost = self.pricing.cost(usage);
+
+        let mut sessions = self.sessions.write().ok()?;
+        let session = sessions.entry(session_id.to_string()).or_default();
+        session.usage += usage;
+        session.cost += cost;
+        session.responses += 1;
+
+        Some(cost)
+    }
+
+    /// The usage and estimated cost of a session.
+    pub fn session(&self, session_id: &str) -> Option<SessionCost> {
+        self.sessions.read().ok()?.get(session_id).cloned()
+    }
+
+    /// The estimated cost of all sessions (in US dollars).
+    pub fn total_cost(&self) -> f64 {
+        self.sessions
+            .read()
+            .map(|x| x.values().map(|session| session.cost).sum())
+            .unwrap_or_default()
+    }
+
+    /// Stop tracking a session (ie once it has closed), returning its final usage and cost.
+    /// Note that this removes the session's cost from the total.
+    pub fn remove_session(&self, session_id: &str) -> Option<SessionCost> {
+        self.sessions.write().ok()?.remove(session_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Pricing;
+    use crate::providers::openai_realtime::realtime::{
+        CachedTokenDetails, InputTokenDetails, OutputTokenDetails, Usage,
+    };
+
+    #[test]
+    fn prices_cached_tokens_separately() {
+        let usage = Usage {
+            total_tokens: 3_000_000,
+            input_tokens: 2_000_000,
+            output_tokens: 1_000_000,
+            input_token_details: InputTokenDetails {
+                text_tokens: 0,
+                audio_tokens: 2_000_000,
+                cached_tokens: 1_000_000,
+                cached_tokens_details: CachedTokenDetails {
+                    text_tokens: 0,
+                    audio_tokens: 1_000_000,
+                },
+            },
+            output_token_details: OutputTokenDetails {
+                text_tokens: 0,
+                audio_tokens: 1_000_000,
+            },
+        };
+
+        let cost = Pricing::GPT_REALTIME.cost(&usage);
+        assert!((cost - (32.0 + 0.4 + 64.0)).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,286 @@
+//! Translation between the beta realtime protocol (which the types in this crate model) and the GA realtime protocol.
+//!
+//! The GA protocol mostly renames events and content part types, and nests the audio configuration of a session under `audio.input` and `audio.output`.
+use serde::Deserialize;
+use serde_json::{Map, Value, json};
+
+use super::realtime::{AudioFormat, GaAudioFormat};
+
+/// Event types that were renamed in the GA protocol, as `(beta, ga)` pairs.
+const RENAMED_EVENTS: &[(&str, &str)] = &[
+    ("response.audio.delta", "response.output_audio.delta"),
+    ("response.audio.done", "response.output_audio.done"),
+    ("response.text.delta", "response.output_text.delta"),
+    ("response.text.done", "response.output_text.done"),
+    (
+        "response.audio_transcript.delta",
+        "response.output_audio_transcript.delta",
+    ),
+    (
+        "response.audio_transcript.done",
+        "response.output_audio_transcript.done",
+    ),
+    ("conversation.item.created", "conversation.item.added"),
+];
+
+/// Content part types that were renamed in the GA protocol, as `(beta, ga)` pairs.
+const RENAMED_CONTENT_PARTS: &[(&str, &str)] =
+    &[("text", "output_text"), ("audio", "output_audio")];
+
+/// Converts an outbound (beta) event into its GA equivalent.
+pub(super) fn outbound(mut event: Value) -> Value {
+    match event.get("type").and_then(Value::as_str) {
+        Some("session.update") => {
+            if let Some(session) = event.get_mut("session") {
+                *session = session_to_ga(session.take());
+            }
+        }
+        Some("transcription_session.update") => {
+            // The GA protocol uses a regular session update with a session type of "transcription"
+            event["type"] = Value::String("session.update".into());
+            if let Some(session) = event.get_mut("session") {
+                let mut ga = session_to_ga(session.take());
+                ga["type"] = Value::String("transcription".into());
+                *session = ga;
+            }
+        }
+        Some("response.create") => {
+            if let Some(response) = event.get_mut("response") {
+                *response = response_to_ga(response.take());
+            }
+        }
+        Some("conversation.item.create") => {
+            if let Some(content) = event.pointer_mut("/item/content") {
+                rename_content_parts(content, true);
+            }
+        }
+        _ => {}
+    }
+
+    event
+}
+
+/// Converts an inbound GA event into its beta equivalent, so that it can be deserialized into a [`ReceivedEvent`](super::realtime::ReceivedEvent).
+pub(super) fn inbound(mut event: Value) -> Value {
+    if let Some(kind) = event.get("type").and_then(Value::as_str)
+        && let Some((beta, _)) = RENAMED_EVENTS.iter().find(|(_, ga)| *ga == kind)
+    {
+        event["type"] = Value::String(beta.to_string());
+    }
+
+    if let Some(session) = event.get_mut("session") {
+        *session = session_from_ga(session.take());
+    }
+
+    for pointer in ["/item/content", "/part"] {
+        if let Some(content) = event.pointer_mut(pointer) {
+            rename_content_parts(content, false);
+        }
+    }
+
+    if let Some(Value::Array(output)) = event.pointer_mut("/response/output") {
+        for item in output {
+            if let Some(content) = item.get_mut("content") {
+                rename_content_parts(content, false);
+            }
+        }
+    }
+
+    event
+}
+
+/// Renames the `type` of every content part in `content`, either from beta to GA or vice versa.
+fn rename_content_parts(content: &mut Value, to_ga: bool) {
+    let parts = match content {
+        Value::Array(parts) => parts.iter_mut().collect::<Vec<_>>(),
+        part => vec![part],
+    };
+
+    for part in parts {
+        let Some(kind) = part.get("type").and_then(Value::as_str) else {
+            continue;
+        };
+
+        if let Some((_, to)) = RENAMED_CONTENT_PARTS
+            .iter()
+            .map(|&(beta, ga)| if to_ga { (beta, ga) } else { (ga, beta) })
+            .find(|(from, _)| *from == kind)
+        {
+            part["type"] = Value::String(to.to_string());
+        }
+    }
+}
+
+fn format_to_ga(format: Value) -> Value {
+    match AudioFormat::deserialize(&format) {
+        Ok(beta) => json!(GaAudioFormat::from(beta)),
+        Err(_) => format,
+    }
+}
+
+fn format_from_ga(format: Value) -> Value {
+    let beta = GaAudioFormat::deserialize(&format)
+        .ok()
+        .and_then(|ga| AudioFormat::try_from(ga).ok());
+
+    match beta {
+        Some(beta) => json!(beta),
+        None => format,
+    }
+}
+
+fn session_to_ga(session: Value) -> Value {
+    let Value::Object(mut session) = session else {
+        return session;
+    };
+
+    let mut input = Map::new();
+    let mut output = Map::new();
+
+    if let Some(format) = session.remove("input_audio_format") {
+        input.insert("format".into(), format_to_ga(format));
+    }
+    if let Some(transcription) = session.remove("input_audio_transcription") {
+        input.insert("transcription".into(), transcription);
+    }
+    if let Some(turn_detection) = session.remove("turn_detection") {
+        input.insert("turn_detection".into(), turn_detection);
+    }
+    if let Some(format) = session.remove("output_audio_format") {
+        output.insert("format".into(), format_to_ga(format));
+    }
+    if let Some(voice) = session.remove("voice") {
+        output.insert("voice".into(), voice);
+    }
+    if let Some(speed) = session.remove("speed") {
+        output.insert("speed".into(), speed);
+    }
+    if let Some(modalities) = session.remove("modalities") {
+        session.insert("output_modalities".into(), modalities);
+    }
+    if let Some(max_tokens) = session.remove("max_response_output_tokens") {
+        session.insert("max_output_tokens".into(), max_tokens);
+    }
+    // Temperature is not configurable in the GA protocol
+    session.remove("temperature");
+
+    let mut audio = Map::new();
+    if !input.is_empty() {
+        audio.insert("input".into(), Value::Object(input));
+    }
+    if !output.is_empty() {
+        audio.insert("output".into(), Value::Object(output));
+    }
+    if !audio.is_empty() {
+        session.insert("audio".into(), Value::Object(audio));
+    }
+
+    session.insert("type".into(), Value::String("realtime".into()));
+
+    Value::Object(session)
+}
+
+fn session_from_ga(session: Value) -> Value {
+    let Value::Object(mut session) = session else {
+        return session;
+    };
+
+    session.remove("type");
+
+    if let Some(modalities) = session.remove("output_modalities") {
+        session.insert("modalities".into(), modalities);
+    }
+    if let Some(max_tokens) = session.remove("max_output_tokens") {
+        session.insert("max_response_output_tokens".into(), max_tokens);
+    }
+
+    if let Some(Value::Object(mut audio)) = session.remove("audio") {
+        if let Some(Value::Object(mut input)) = audio.remove("input") {
+            if let Some(format) = input.remove("format") {
+                session.insert("input_audio_format".into(), format_from_ga(format));
+            }
+            if let Some(transcription) = input.remove("transcription") {
+                session.insert("input_audio_transcription".into(), transcription);
+            }
+            if let Some(turn_detection) = input.remove("turn_detection") {
+                session.insert("turn_detection".into(), turn_detection);
+            }
+        }
+
+        if let Some(Value::Object(mut output)) = audio.remove("output") {
+            if let Some(format) = output.remove("format") {
+                session.insert("output_audio_format".into(), format_from_ga(format));
+            }
+            if let Some(voice) = output.remove("voice") {
+                session.insert("voice".into(), voice);
+            }
+            if let Some(speed) = output.remove("speed") {
+                session.insert("speed".into(), speed);
+            }
+        }
+    }
+
+    Value::Object(session)
+}
+
+fn response_to_ga(response: Value) -> Value {
+    let Value::Object(mut response) = response else {
+        return response;
+    };
+
+    if let Some(modalities) = response.remove("modalities") {
+        response.insert("output_modalities".into(), modalities);
+    }
+    response.remove("temperature");
+
+    let mut output = Map::new();
+    if let Some(format) = response.remove("output_audio_format") {
+        output.insert("format".into(), format_to_ga(format));
+    }
+    if let Some(voice) = response.remove("voice") {
+        output.insert("voice".into(), voice);
+    }
+    if !output.is_empty() {
+        response.insert("audio".into(), json!({ "output": output }));
+    }
+
+    Value::Object(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    #[test]
+    fn session_roundtrips_through_ga() {
+        let beta = json!({
+            "modalities": ["text", "audio"],
+            "voice": "marin",
+            "input_audio_format": "pcm16",
+            "instructions": "Be helpful"
+        });
+
+        let ga = super::session_to_ga(beta.clone());
+        assert_eq!(ga["type"], "realtime");
+        assert_eq!(ga["audio"]["output"]["voice"], "marin");
+        assert_eq!(ga["audio"]["input"]["format"]["type"], "audio/pcm");
+
+        assert_eq!(super::session_from_ga(ga), beta);
+    }
+
+    #[test]
+    fn renames_inbound_events() {
+        let evt = super::inbound(json!({
+            "type": "response.output_audio.delta",
+            "delta": "AAAA"
+        }));
+        assert_eq!(evt["type"], "response.audio.delta");
+
+        let evt = super::inbound(json!({
+            "type": "conversation.item.added",
+            "item": { "type": "message", "role": "assistant", "content": [{ "type": "output_text", "text": "Hi" }] }
+        }));
+        assert_eq!(evt["type"], "conversation.item.created");
+        assert_eq!(evt["item"]["content"][0]["type"], "text");
+    }
+}
@@ -0,0 +1,311 @@
+//! Management of many concurrent realtime sessions.
+//!
+//! Server-side voice applications (ie a phone line) typically hold one realtime session per caller.
+//! [`RealtimeSessionManager`] keeps track of these sessions by ID, enforces a limit on concurrent sessions and forgets sessions once they are closed.
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+use futures::{StreamExt, stream::BoxStream};
+use tokio::sync::mpsc::Sender;
+
+use super::realtime::{
+    ConnectionEvent, InputEvent, RealtimeError, RealtimeSenderExt, RealtimeVoice,
+    RealtimeVoiceRequest, ReceivedEvent, ReceivedEventKind,
+};
+
+/// Sessions by ID. A session that is still connecting has no sender yet.
+type Sessions = Arc<Mutex<HashMap<String, ManagedSession>>>;
+
+#[derive(Debug)]
+struct ManagedSession {
+    /// Distinguishes between sessions that reuse the same ID, so that a closed session can't remove its replacement.
+    generation: u64,
+    sender: Option<Sender<InputEvent>>,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum SessionManagerError {
+    #[error("The maximum number of concurrent sessions ({0}) has been reached")]
+    LimitReached(usize),
+    #[error("A session with ID `{0}` already exists")]
+    AlreadyExists(String),
+    /// Boxed, as realtime errors are much larger than the other variants.
+    #[error(transparent)]
+    Realtime(#[from] Box<RealtimeError>),
+}
+
+impl From<RealtimeError> for SessionManagerError {
+    fn from(err: RealtimeError) -> Self {
+        Self::Realtime(Box::new(err))
+    }
+}
+
+/// Owns multiple concurrent realtime sessions, keyed by an ID.
+#[derive(Debug, Clone)]
+pub struct RealtimeSessionManager<V> {
+    model: V,
+    max_sessions: Option<usize>,
+    sessions: Sessions,
+    generation: Arc<AtomicU64>,
+}
+
+impl<V> RealtimeSessionManager<V>
+where
+    V: RealtimeVoice,
+{
+    pub fn new(model: V) -> Self {
+        Self {
+            model,
+            max_sessions: None,
+            sessions: Arc::new(Mutex::new(HashMap::new())),
+            generation: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Limit the number of concurrent sessions. Opening a session past the limit returns [`SessionManagerError::LimitReached`].
+    pub fn max_sessions(mut self, max_sessions: usize) -> Self {
+        self.max_sessions = Some(max_sessions);
+        self
+    }
+
+    /// Open a new session with the given ID.
+    /// The session is removed from the manager once its event stream is dropped or the connection closes.
+    pub async fn open(
+        &self,
+        id: &str,
+        req: RealtimeVoiceRequest,
+    ) -> Result<(Sender<InputEvent>, BoxStream<'_, ReceivedEvent>), SessionManagerError> {
+        let generation = self.reserve(id)?;
+
+        let (sender, stream) = match self.model.realtime_voice(req).await {
+            Ok(res) => res,
+            Err(err) => {
+                remove(&self.sessions, id, generation);
+                return Err(err.into());
+            }
+        };
+
+        if let Ok(mut sessions) = self.sessions.lock()
+            && let Some(session) = sessions.get_mut(id)
+            && session.generation == generation
+        {
+            session.sender = Some(sender.clone());
+        }
+
+        let guard = SessionGuard {
+            id: id.to_string(),
+            generation,
+            sessions: Arc::clone(&self.sessions),
+        };
+
+        let stream = stream
+            .map(move |evt| {
+                if matches!(
+                    evt.data,
+                    ReceivedEventKind::Connection(ConnectionEvent::ConnectionClosed { .. })
+                ) {
+                    remove(&guard.sessions, &guard.id, guard.generation);
+                }
+
+                evt
+            })
+            .boxed();
+
+        Ok((sender, stream))
+    }
+
+    /// The input event sender of a session, if the session exists and is connected.
+    pub fn sender(&self, id: &str) -> Option<Sender<InputEvent>> {
+        self.sessions.lock().ok()?.get(id)?.sender.clone()
+    }
+
+    /// Gracefully shut down a session. Returns whether the session existed.
+    pub async fn close(&self, id: &str) -> bool {
+        let sender = self
+            .sessions
+            .lock()
+            .ok()
+            .and_then(|mut sessions| sessions.remove(id))
+            .and_then(|x| x.sender);
+
+        match sender {
+            Some(sender) => {
+                sender.shutdown().await;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Gracefully shut down all sessions.
+    pub async fn close_all(&self) {
+        let senders: Vec<_> = self
+            .sessions
+            .lock()
+            .map(|mut sessions| sessions.drain().filter_map(|(_, x)| x.sender).collect())
+            .unwrap_or_default();
+
+        futures::future::join_all(senders.iter().map(|x| x.shutdown())).await;
+    }
+
+    /// The IDs of all open sessions.
+    pub fn ids(&self) -> Vec<String> {
+        self.sessions
+            .lock()
+            .map(|x| x.keys().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    pub fn contains(&self, id: &str) -> bool {
+        self.sessions
+            .lock()
+            .is_ok_and(|sessions| sessions.contains_key(id))
+    }
+
+    /// The number of open (or connecting) sessions.
+    pub fn len(&self) -> usize {
+        self.sessions.lock().map(|x| x.len()).unwrap_or_default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Reserve an ID before connecting, so that concurrent opens can't exceed the limit or reuse an ID.
+    fn reserve(&self, id: &str) -> Result<u64, SessionManagerError> {
+        let Ok(mut sessions) = self.sessions.lock() else {
+            return Err(RealtimeError::Closed.into());
+        };
+
+        if sessions.contains_key(id) {
+            return Err(SessionManagerError::AlreadyExists(id.to_string()));
+        }
+
+        if let Some(max_sessions) = self.max_sessions
+            && sessions.len() >= max_sessions
+        {
+            return Err(SessionManagerError::LimitReached(max_sessions));
+        }
+
+        let generation = self.generation.fetch_add(1, Ordering::Relaxed);
+        sessions.insert(
+            id.to_string(),
+            ManagedSession {
+                generation,
+                sender: None,
+            },
+        );
+
+        Ok(generation)
+    }
+}
+
+fn remove(sessions: &Sessions, id: &str, generation: u64) {
+    if let Ok(mut sessions) = sessions.lock()
+        && sessions.get(id).is_some_and(|x| x.generation == generation)
+    {
+        sessions.remove(id);
+    }
+}
+
+/// Removes a session from the manager when its event stream is dropped.
+struct SessionGuard {
+    id: String,
+    generation: u64,
+    sessions: Sessions,
+}
+
+impl Drop for SessionGuard {
+    fn drop(&mut self) {
+        remove(&self.sessions, &self.id, self.generation);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use serde_json::json;
+
+    use super::{RealtimeSessionManager, SessionManagerError};
+    use crate::providers::openai_realtime::{
+        realtime::RealtimeVoiceRequest,
+        recording::{Direction, MockRealtimeVoice, RecordedEvent},
+    };
+
+    fn manager() -> RealtimeSessionManager<MockRealtimeVoice> {
+        RealtimeSessionManager::new(MockRealtimeVoice::new(vec![RecordedEvent {
+            timestamp_ms: 0,
+            direction: Direction::Inbound,
+            event: json!({ "event_id": "e1", "type": "input_audio_buffer.committed", "item_id": "i1" }),
+        }]))
+    }
+
+    #[tokio::test]
+    async fn limits_concurrent_sessions() {
+        let manager = manager().max_sessions(1);
+
+        let (_sender, stream) = manager
+            .open("a", RealtimeVoiceRequest::new())
+            .await
+            .unwrap();
+        assert!(matches!(
+            manager.open("b", RealtimeVoiceRequest::new()).await,
+            Err(SessionManagerError::LimitReached(1))
+        ));
+
+        // Dropping the event stream frees up the slot
+        drop(stream);
+        assert!(manager.is_empty());
+        assert!(manager.open("b", RealtimeVoiceRequest::new()).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn rejects_duplicate_ids() {
+        let manager = manager();
+
+        let _session = manager
+            .open("a", RealtimeVoiceRequest::new())
+            .await
+            .unwrap();
+        assert!(matches!(
+            manager.open("a", RealtimeVoiceRequest::new()).await,
+            Err(SessionManagerError::AlreadyExists(id)) if id == "a"
+        ));
+        assert_eq!(manager.ids(), ["a"]);
+    }
+
+    #[tokio::test]
+    async fn closed_sessions_do_not_remove_their_replacement() {
+        let manager = manager();
+
+        let (_sender, old_stream) = manager
+            .open("a", RealtimeVoiceRequest::new())
+            .await
+            .unwrap();
+        let closed = tokio::time::timeout(Duration::from_secs(1), manager.close("a"))
+            .await
+            .unwrap();
+        assert!(closed);
+        assert!(!manager.contains("a"));
+
+        let (_sender, new_stream) = manager
+            .open("a", RealtimeVoiceRequest::new())
+            .await
+            .unwrap();
+        assert!(manager.sender("a").is_some());
+
+        // The old session's stream belongs to a previous generation
+        drop(old_stream);
+        assert!(manager.contains("a"));
+
+        drop(new_stream);
+        assert!(!manager.contains("a"));
+        assert!(!manager.close("a").await);
+    }
+}
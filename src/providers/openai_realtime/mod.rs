@@ -1,4 +1,17 @@
+pub mod agent;
+pub mod audio;
+pub mod barge_in;
 pub mod client;
+mod connection;
+pub mod conversation;
+pub mod cost;
+mod ga;
+pub mod manager;
 pub mod realtime;
+pub mod recording;
+pub mod sequence;
+pub mod text;
+pub mod transcript;
+pub mod twilio;
 
-pub use client::Client;
+pub use client::{Client, RealtimeApiVersion};
@@ -1,29 +1,36 @@
-use futures::{SinkExt, StreamExt, stream::BoxStream};
-use reqwest_websocket::Message;
-use rig::providers::openai::ToolDefinition;
+use futures::{StreamExt, stream::BoxStream};
 use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, sync::Arc};
 use tokio::sync::mpsc::{self, Sender};
+use tracing::Instrument;
+
+use super::connection::Connection;
+pub use super::connection::{KeepAlive, ReconnectPolicy};
+use super::recording::Recorder;
+use crate::prompt_templating::PromptTemplate;
 
 pub trait RealtimeVoice: Clone {
     fn realtime_voice(
         &self,
         req: RealtimeVoiceRequest,
     ) -> impl Future<
-        Output = Result<
-            (Sender<InputEvent>, BoxStream<'_, ReceivedEvent>),
-            Box<dyn std::error::Error>,
-        >,
+        Output = Result<(Sender<InputEvent>, BoxStream<'_, ReceivedEvent>), RealtimeError>,
     > + Send;
 }
 
+/// The default capacity of the input and output event channels.
+pub const DEFAULT_CHANNEL_CAPACITY: usize = 9999;
+
 #[derive(Debug, Clone, Deserialize, Serialize, Default)]
 pub struct RealtimeVoiceRequest {
     session: Option<Session>,
+    #[serde(skip)]
+    channel_capacity: Option<usize>,
 }
 
 impl RealtimeVoiceRequest {
     pub fn new() -> Self {
-        Self { session: None }
+        Self::default()
     }
 
     pub fn session_data(mut self, session: Session) -> Self {
@@ -32,8 +39,34 @@ impl RealtimeVoiceRequest {
     }
 
     pub fn with_session(session: Session) -> Self {
-        Self {
-            session: Some(session),
+        Self::new().session_data(session)
+    }
+
+    /// Set the capacity of the input and output event channels (defaults to [`DEFAULT_CHANNEL_CAPACITY`]).
+    /// When the input channel is full, sending an input event will wait until there is space, so a smaller capacity applies backpressure to audio producers sooner.
+    ///
+    /// Panics if the capacity is zero.
+    pub fn channel_capacity(mut self, capacity: usize) -> Self {
+        assert!(capacity > 0, "Channel capacity must be greater than zero");
+        self.channel_capacity = Some(capacity);
+        self
+    }
+}
+
+/// Extension methods for the input event sender of a realtime connection.
+pub trait RealtimeSenderExt {
+    /// Gracefully shut down the connection.
+    /// Any input events sent before this are sent to OpenAI, then a close frame is sent and any remaining events from OpenAI are forwarded to the event stream,
+    /// which ends with a [`ConnectionEvent::ConnectionClosed`] event. Resolves once the background connection task has finished.
+    fn shutdown(&self) -> impl Future<Output = ()> + Send;
+}
+
+impl RealtimeSenderExt for Sender<InputEvent> {
+    async fn shutdown(&self) {
+        // If sending fails, the connection has already been closed
+        if self.send(InputEvent::close()).await.is_ok() {
+            // The input receiver is dropped when the connection task finishes
+            self.closed().await;
         }
     }
 }
@@ -44,10 +77,41 @@ pub trait RealtimeClient {
     fn realtime_client(&self, model_name: &str) -> Self::Output;
 }
 
+/// A hook that receives the raw JSON of a realtime event, as sent or received on the wire.
+/// Hooks can inspect the event (for logging or metrics) or modify it in place.
+#[derive(Clone)]
+pub struct EventHook(Arc<dyn Fn(&mut serde_json::Value) + Send + Sync>);
+
+impl EventHook {
+    pub fn new<F>(hook: F) -> Self
+    where
+        F: Fn(&mut serde_json::Value) + Send + Sync + 'static,
+    {
+        Self(Arc::new(hook))
+    }
+
+    pub(crate) fn call(&self, event: &mut serde_json::Value) {
+        (self.0)(event)
+    }
+}
+
+impl std::fmt::Debug for EventHook {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("EventHook")
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct RealtimeModel {
     client: super::client::Client,
     model: String,
+    reconnect: Option<ReconnectPolicy>,
+    keepalive: Option<KeepAlive>,
+    append_chunk_duration: Option<std::time::Duration>,
+    recorder: Option<Recorder>,
+    inbound_hooks: Vec<EventHook>,
+    outbound_hooks: Vec<EventHook>,
+    transcription: bool,
 }
 
 impl RealtimeModel {
@@ -55,73 +119,180 @@ impl RealtimeModel {
         Self {
             client,
             model: model.to_string(),
+            reconnect: None,
+            keepalive: None,
+            append_chunk_duration: None,
+            recorder: None,
+            inbound_hooks: Vec::new(),
+            outbound_hooks: Vec::new(),
+            transcription: false,
         }
     }
-}
 
-impl RealtimeVoice for RealtimeModel {
-    async fn realtime_voice(
-        &self,
-        req: RealtimeVoiceRequest,
-    ) -> Result<(Sender<InputEvent>, BoxStream<'_, ReceivedEvent>), Box<dyn std::error::Error>>
+    /// Automatically reconnect (and restore the session config) if the websocket drops.
+    /// A [`ConnectionEvent::Reconnected`] event will be emitted on the event stream when the connection has been re-established.
+    pub fn with_reconnect(mut self, policy: ReconnectPolicy) -> Self {
+        self.reconnect = Some(policy);
+        self
+    }
+
+    /// Send periodic pings to keep the connection alive, and treat the connection as dead if nothing has been received for the configured timeout.
+    /// If a reconnect policy is set, a dead connection will be re-opened. Otherwise, the event stream ends with a [`ConnectionEvent::ConnectionClosed`] event.
+    pub fn with_keepalive(mut self, keepalive: KeepAlive) -> Self {
+        self.keepalive = Some(keepalive);
+        self
+    }
+
+    /// Add a hook that is called with every received event before it is deserialized.
+    /// Hooks are called in the order they were added. For the GA API, hooks see the event before it is translated.
+    pub fn with_inbound_hook<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&mut serde_json::Value) + Send + Sync + 'static,
+    {
+        self.inbound_hooks.push(EventHook::new(hook));
+        self
+    }
+
+    /// Add a hook that is called with every input event after it has been serialized, just before it is sent.
+    /// Hooks are called in the order they were added. For the GA API, hooks see the event after it has been translated.
+    pub fn with_outbound_hook<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&mut serde_json::Value) + Send + Sync + 'static,
     {
-        let path = format!("/realtime?model={model_id}", model_id = self.model);
-        let websocket = self
-            .client
-            .initiate_websocket(&path)
+        self.outbound_hooks.push(EventHook::new(hook));
+        self
+    }
+
+    pub(crate) fn inbound_hooks(&self) -> &[EventHook] {
+        &self.inbound_hooks
+    }
+
+    pub(crate) fn outbound_hooks(&self) -> &[EventHook] {
+        &self.outbound_hooks
+    }
+
+    pub(crate) fn reconnect_policy(&self) -> Option<&ReconnectPolicy> {
+        self.reconnect.as_ref()
+    }
+
+    /// Split audio appends into chunks of at most the given duration of audio, which are sent sequentially.
+    /// Appends that exceed OpenAI's maximum event size are always split, regardless of this setting.
+    pub fn with_append_chunking(mut self, chunk_duration: std::time::Duration) -> Self {
+        self.append_chunk_duration = Some(chunk_duration);
+        self
+    }
+
+    /// Record every event sent and received to a JSONL file. Recordings can be replayed with [`MockRealtimeVoice`](super::recording::MockRealtimeVoice).
+    pub fn with_recorder(mut self, recorder: Recorder) -> Self {
+        self.recorder = Some(recorder);
+        self
+    }
+
+    pub(crate) fn recorder(&self) -> Option<&Recorder> {
+        self.recorder.as_ref()
+    }
+
+    pub(crate) fn append_chunk_duration(&self) -> Option<std::time::Duration> {
+        self.append_chunk_duration
+    }
+
+    pub(crate) fn keepalive(&self) -> Option<&KeepAlive> {
+        self.keepalive.as_ref()
+    }
+
+    pub(crate) fn api_version(&self) -> super::client::RealtimeApiVersion {
+        self.client.api_version()
+    }
+
+    pub(crate) async fn connect(&self) -> Result<reqwest_websocket::WebSocket, RealtimeError> {
+        let path = self.client.realtime_path(&self.model, self.transcription);
+
+        self.client.initiate_websocket(&path).await
+    }
+
+    /// Open a transcription-only session. Instead of responses, the server will send [`TranscriptionEvent`]s for committed user audio.
+    /// The transcription model is set in the session config rather than using the model of this [`RealtimeModel`].
+    pub async fn transcription_session(
+        &self,
+        session: TranscriptionSession,
+    ) -> Result<(Sender<InputEvent>, BoxStream<'_, ReceivedEvent>), RealtimeError> {
+        let model = Self {
+            transcription: true,
+            ..self.clone()
+        };
+
+        model
+            .open(
+                Some(InputEvent::update_transcription_session(session)),
+                DEFAULT_CHANNEL_CAPACITY,
+            )
             .await
-            .inspect_err(|x| println!("Error: {x}"))
-            .unwrap();
+    }
 
-        let (mut ws_tx, ws_rx) = websocket.split();
+    async fn open(
+        &self,
+        session_update: Option<InputEvent>,
+        channel_capacity: usize,
+    ) -> Result<(Sender<InputEvent>, BoxStream<'static, ReceivedEvent>), RealtimeError> {
+        // Spans the whole lifetime of the websocket, including any reconnections
+        let span = tracing::info_span!(
+            "realtime_connection",
+            model = %self.model,
+            api_version = ?self.api_version(),
+            transcription = self.transcription,
+        );
 
-        let (tx, mut rx) = mpsc::channel::<InputEvent>(9999);
+        let websocket = self.connect().instrument(span.clone()).await?;
 
-        tokio::spawn(async move {
-            while let Some(message) = rx.recv().await {
-                let json = serde_json::to_string(&message).unwrap();
-                ws_tx.send(Message::Text(json)).await.unwrap();
-            }
-        });
-
-        // Convert `ws_rx` (Stream of WebSocket messages) into a stream of `ReceivedEvent`
-        let mapped_stream = ws_rx
-            .filter_map(|msg_result| async {
-                match msg_result {
-                    Ok(reqwest_websocket::Message::Text(txt)) => {
-                        tracing::debug!("Received text: {txt}");
-                        serde_json::from_str::<ReceivedEvent>(&txt).ok()
-                    }
-                    Err(err) => {
-                        tracing::debug!("Received error: {err}");
-                        None
-                    }
-                    Ok(thing) => {
-                        tracing::debug!(
-                            "Got thing that was neither a text message nor an error: {thing:?}"
-                        );
-                        None
-                    }
-                }
-            })
-            .boxed();
-
-        if let Some(session) = req.session {
-            tx.send(InputEvent::update_session(session))
+        let (tx, rx) = mpsc::channel::<InputEvent>(channel_capacity);
+        let (event_tx, event_rx) = mpsc::channel::<ReceivedEvent>(channel_capacity);
+
+        let connection = Connection::new(self.clone(), websocket, session_update.clone());
+        tokio::spawn(connection.run(rx, event_tx).instrument(span));
+
+        // Convert the received events into a stream of `ReceivedEvent`
+        let mapped_stream = futures::stream::unfold(event_rx, |mut rx| async move {
+            rx.recv().await.map(|evt| (evt, rx))
+        })
+        .boxed();
+
+        if let Some(session_update) = session_update {
+            tx.send(session_update)
                 .await
-                .expect("If this closes, there was a malformed JSON object sent to OpenAI");
+                .map_err(|_| RealtimeError::Closed)?;
         }
 
         Ok((tx, mapped_stream))
     }
 }
 
+impl RealtimeVoice for RealtimeModel {
+    async fn realtime_voice(
+        &self,
+        req: RealtimeVoiceRequest,
+    ) -> Result<(Sender<InputEvent>, BoxStream<'_, ReceivedEvent>), RealtimeError> {
+        if let Some(session) = &req.session {
+            session.validate()?;
+        }
+
+        self.open(
+            req.session.map(InputEvent::update_session),
+            req.channel_capacity.unwrap_or(DEFAULT_CHANNEL_CAPACITY),
+        )
+        .await
+    }
+}
+
+/// The maximum number of raw audio bytes in a single append event.
+/// OpenAI accepts up to 15MiB of (base64-encoded) audio per event, and base64 encoding inflates the size by a third.
+pub(crate) const MAX_APPEND_AUDIO_BYTES: usize = 15 * 1024 * 1024 / 4 * 3;
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct InputEvent {
     #[serde(skip_serializing_if = "Option::is_none")]
     event_id: Option<String>,
     #[serde(flatten)]
-    data: InputEventKind,
+    pub(crate) data: InputEventKind,
 }
 
 impl InputEvent {
@@ -146,14 +317,133 @@ impl InputEvent {
         })
     }
 
+    /// Append raw audio bytes (in the session's input audio format) to the input audio buffer.
+    /// Buffers that are too large for a single event are split across multiple events, which should be sent in order.
+    pub fn append_audio_bytes(bytes: &[u8]) -> Vec<Self> {
+        use base64::{Engine, prelude::BASE64_STANDARD};
+
+        bytes
+            .chunks(MAX_APPEND_AUDIO_BYTES)
+            .map(|chunk| Self::append_audio(&BASE64_STANDARD.encode(chunk)))
+            .collect()
+    }
+
+    /// Append PCM16 samples to the input audio buffer.
+    /// Buffers that are too large for a single event are split across multiple events, which should be sent in order.
+    pub fn append_samples(samples: &[i16]) -> Vec<Self> {
+        let bytes: Vec<u8> = samples.iter().flat_map(|x| x.to_le_bytes()).collect();
+
+        Self::append_audio_bytes(&bytes)
+    }
+
     pub fn with_id(mut self, id: &str) -> Self {
         self.event_id = Some(id.to_string());
         self
     }
 
-    pub fn update_session(session: Session) -> Self {
+    /// The client-assigned ID of this event, if one was set.
+    pub fn event_id(&self) -> Option<&str> {
+        self.event_id.as_deref()
+    }
+
+    /// Update the session. If the session has an instructions template, the instructions are re-rendered first.
+    pub fn update_session(mut session: Session) -> Self {
+        session.render_instructions();
         Self::new(InputEventKind::UpdateSession { session })
     }
+
+    /// Update a transcription session.
+    pub fn update_transcription_session(session: TranscriptionSession) -> Self {
+        Self::new(InputEventKind::UpdateTranscriptionSession { session })
+    }
+
+    /// Instruct the server to create a response, using the current session configuration.
+    /// This is required when turn detection is disabled or after submitting a function call output.
+    pub fn create_response() -> Self {
+        Self::new(InputEventKind::CreateResponse { response: None })
+    }
+
+    /// Instruct the server to create a response, overriding the session configuration for this response only.
+    pub fn create_response_with(config: ResponseConfig) -> Self {
+        Self::new(InputEventKind::CreateResponse {
+            response: Some(config),
+        })
+    }
+
+    /// Add an item to the conversation.
+    pub fn create_item(item: ConversationItem) -> Self {
+        Self::new(InputEventKind::CreateConversationItem {
+            previous_item_id: None,
+            item,
+        })
+    }
+
+    /// Add an item to the conversation, directly after the item with the given ID.
+    pub fn create_item_after(item: ConversationItem, previous_item_id: &str) -> Self {
+        Self::new(InputEventKind::CreateConversationItem {
+            previous_item_id: Some(previous_item_id.to_string()),
+            item,
+        })
+    }
+
+    /// Add a user text message to the conversation.
+    pub fn user_message(text: &str) -> Self {
+        Self::create_item(ConversationItem::user_text(text))
+    }
+
+    /// Add a system text message to the conversation. Useful for seeding context mid-session.
+    pub fn system_message(text: &str) -> Self {
+        Self::create_item(ConversationItem::system_text(text))
+    }
+
+    /// Truncate an assistant message's audio. Use this when the user interrupts the assistant, so that the server's understanding of the conversation matches what the user actually heard.
+    pub fn truncate_item(item_id: &str, content_index: u64, audio_end_ms: u64) -> Self {
+        Self::new(InputEventKind::TruncateConversationItem {
+            item_id: item_id.to_string(),
+            content_index,
+            audio_end_ms,
+        })
+    }
+
+    /// Remove an item from the conversation history.
+    pub fn delete_item(item_id: &str) -> Self {
+        Self::new(InputEventKind::DeleteConversationItem {
+            item_id: item_id.to_string(),
+        })
+    }
+
+    /// Cancel an in-progress response. If no response ID is given, the current response is cancelled.
+    pub fn cancel_response(response_id: Option<&str>) -> Self {
+        Self::new(InputEventKind::CancelResponse {
+            response_id: response_id.map(str::to_string),
+        })
+    }
+
+    /// Gracefully close the connection once all previously sent events have been sent.
+    /// Prefer [`RealtimeSenderExt::shutdown`], which also waits for the connection to finish closing.
+    pub fn close() -> Self {
+        Self::new(InputEventKind::Close)
+    }
+
+    /// Approve or reject an MCP tool call that requires approval.
+    pub fn mcp_approval(approval_request_id: &str, approve: bool) -> Self {
+        Self::create_item(ConversationItem::McpApprovalResponse {
+            id: None,
+            approval_request_id: approval_request_id.to_string(),
+            approve,
+            reason: None,
+        })
+    }
+
+    /// Submit the output of a function call to the conversation.
+    /// Send [`InputEvent::create_response`] afterwards to have the model respond to the output.
+    pub fn function_call_output(call_id: &str, output: &str) -> Self {
+        Self::create_item(ConversationItem::FunctionCallOutput {
+            id: None,
+            call_id: call_id.to_string(),
+            output: output.to_string(),
+        })
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -171,6 +461,41 @@ pub enum InputEventKind {
     /// Update a session. Note that only fields with Some will be updated - anything else will be left blank.
     #[serde(rename = "session.update")]
     UpdateSession { session: Session },
+    /// Update a transcription session.
+    #[serde(rename = "transcription_session.update")]
+    UpdateTranscriptionSession { session: TranscriptionSession },
+    /// Instruct the server to create a response. Optionally overrides the session configuration for this response only.
+    #[serde(rename = "response.create")]
+    CreateResponse {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        response: Option<ResponseConfig>,
+    },
+    /// Cancel an in-progress response.
+    #[serde(rename = "response.cancel")]
+    CancelResponse {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        response_id: Option<String>,
+    },
+    /// Add a new item to the conversation.
+    #[serde(rename = "conversation.item.create")]
+    CreateConversationItem {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        previous_item_id: Option<String>,
+        item: ConversationItem,
+    },
+    /// Truncate a previous assistant message's audio.
+    #[serde(rename = "conversation.item.truncate")]
+    TruncateConversationItem {
+        item_id: String,
+        content_index: u64,
+        audio_end_ms: u64,
+    },
+    /// Remove an item from the conversation history.
+    #[serde(rename = "conversation.item.delete")]
+    DeleteConversationItem { item_id: String },
+    /// Gracefully close the connection. This is handled locally and is never sent to OpenAI.
+    #[serde(skip)]
+    Close,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -181,6 +506,27 @@ pub struct ReceivedEvent {
     pub data: ReceivedEventKind,
 }
 
+impl ReceivedEvent {
+    pub(crate) fn unknown(value: serde_json::Value) -> Self {
+        Self {
+            event_id: None,
+            data: ReceivedEventKind::Unknown(value),
+        }
+    }
+
+    pub(crate) fn connection(event: ConnectionEvent) -> Self {
+        Self {
+            event_id: None,
+            data: ReceivedEventKind::Connection(event),
+        }
+    }
+
+    /// The server-assigned ID of this event.
+    pub fn event_id(&self) -> Option<&str> {
+        self.event_id.as_deref()
+    }
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(untagged)]
 pub enum ReceivedEventKind {
@@ -193,6 +539,53 @@ pub enum ReceivedEventKind {
         #[serde(flatten)]
         data: ReceivedItemEventKind,
     },
+    Response(ResponseEvent),
+    Conversation(ConversationEvent),
+    InputAudioBuffer(InputAudioBufferEvent),
+    Transcription(TranscriptionEvent),
+    RateLimits(RateLimitsEvent),
+    Mcp(McpEvent),
+    Error(ErrorEvent),
+    /// Events emitted by this crate about the state of the underlying connection. These are never sent by OpenAI.
+    #[serde(skip_deserializing)]
+    Connection(ConnectionEvent),
+    /// Any event that isn't (yet) modelled by this crate. Contains the raw JSON of the event.
+    Unknown(serde_json::Value),
+}
+
+impl ReceivedEventKind {
+    /// The ID of the response this event belongs to, if any.
+    pub fn response_id(&self) -> Option<&str> {
+        match self {
+            Self::Item { response_id, .. } => Some(response_id),
+            Self::Response(
+                ResponseEvent::ResponseCreated { response }
+                | ResponseEvent::ResponseDone { response },
+            ) => Some(&response.id),
+            Self::Response(
+                ResponseEvent::OutputItemAdded { response_id, .. }
+                | ResponseEvent::FunctionCallArgumentsDelta { response_id, .. }
+                | ResponseEvent::FunctionCallArgumentsDone { response_id, .. },
+            ) => Some(response_id),
+            Self::Mcp(
+                McpEvent::CallArgumentsDelta { response_id, .. }
+                | McpEvent::CallArgumentsDone { response_id, .. },
+            ) => Some(response_id),
+            _ => None,
+        }
+    }
+
+    /// The `type` field of the event as sent by OpenAI, if one exists.
+    pub fn event_type(&self) -> Option<String> {
+        match self {
+            Self::Unknown(value) => value.get("type")?.as_str().map(ToString::to_string),
+            other => serde_json::to_value(other)
+                .ok()?
+                .get("type")?
+                .as_str()
+                .map(ToString::to_string),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -202,6 +595,10 @@ pub enum SessionEvent {
     SessionCreated { session: Session },
     #[serde(rename = "session.updated")]
     SessionUpdated { session: Session },
+    #[serde(rename = "transcription_session.created")]
+    TranscriptionSessionCreated { session: TranscriptionSession },
+    #[serde(rename = "transcription_session.updated")]
+    TranscriptionSessionUpdated { session: TranscriptionSession },
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -213,88 +610,1099 @@ pub enum ReceivedItemEventKind {
     /// Clears all audio bytes from the input buffer.
     #[serde(rename = "response.audio.done")]
     AudioDone,
+    /// A text delta.
+    #[serde(rename = "response.text.delta")]
+    TextDelta { delta: String },
+    /// The final text of a text content part.
+    #[serde(rename = "response.text.done")]
+    TextDone { text: String },
+    /// A delta of the transcript of the model's audio output.
+    #[serde(rename = "response.audio_transcript.delta")]
+    AudioTranscriptDelta { delta: String },
+    /// The final transcript of the model's audio output.
+    #[serde(rename = "response.audio_transcript.done")]
+    AudioTranscriptDone { transcript: String },
 }
 
-/// The gpt-4o-realtime-preview-2025-06-03 model. For use with the OpenAI realtime API.
-pub const GPT_4O_REALTIME_PREVIEW_20250603: &str = "gpt-4o-realtime-preview-2025-06-03";
-
-/// OpenAI's realtime API session data. You can use this to update the realtime session at any time.
-#[derive(Debug, Clone, Deserialize, Serialize, Default)]
-pub struct Session {
-    /// The modality you want to use. Can either be text or audio (and you can have both in the same vec!)
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub modalities: Option<Vec<Modality>>,
-    /// The preamble ("system prompt") that you want to use.
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub instructions: Option<String>,
-    /// The OpenAI voice you want to use.
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub voice: Option<String>,
-    /// Turn detection. Instead of manually committing, this allows OpenAI to just figure it out for you.
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub turn_detection: Option<TurnDetection>,
-    /// The input audio format to be used.
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub input_audio_format: Option<AudioFormat>,
-    /// The output audio format to be used.
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub output_audio_format: Option<AudioFormat>,
-    /// The model you want to use for input audio transcription.
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub input_audio_transcription: Option<InputAudioTranscription>,
-    /// The tools you want to use.
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub tools: Option<Vec<ToolDefinition>>,
-    /// The temperature you want to use. Set higher for more creative responses.
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub temperature: Option<f64>,
-    /// Playback speed.
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub speed: Option<f64>,
+/// Events relating to the lifecycle of a response.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "type")]
+pub enum ResponseEvent {
+    /// A new response has been created. The response will be in the `in_progress` state.
+    #[serde(rename = "response.created")]
+    ResponseCreated { response: RealtimeResponse },
+    /// A response has finished streaming (regardless of final state).
+    #[serde(rename = "response.done")]
+    ResponseDone { response: RealtimeResponse },
+    /// A new output item was created during response generation.
+    #[serde(rename = "response.output_item.added")]
+    OutputItemAdded {
+        response_id: String,
+        output_index: u64,
+        item: ConversationItem,
+    },
+    /// A delta of the arguments of a function call.
+    #[serde(rename = "response.function_call_arguments.delta")]
+    FunctionCallArgumentsDelta {
+        response_id: String,
+        item_id: String,
+        output_index: u64,
+        call_id: String,
+        delta: String,
+    },
+    /// The finalised arguments of a function call, as a JSON string.
+    #[serde(rename = "response.function_call_arguments.done")]
+    FunctionCallArgumentsDone {
+        response_id: String,
+        item_id: String,
+        output_index: u64,
+        call_id: String,
+        arguments: String,
+    },
 }
 
-impl Session {
-    pub fn new() -> Self {
-        Self::default()
-    }
-
-    pub fn voice(mut self, voice: &str) -> Self {
-        self.voice = Some(voice.to_string());
-        self
-    }
-
-    pub fn instructions(mut self, instructions: &str) -> Self {
-        self.instructions = Some(instructions.to_string());
-        self
-    }
+/// Events relating to the conversation.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "type")]
+pub enum ConversationEvent {
+    /// A conversation item has been created.
+    #[serde(rename = "conversation.item.created")]
+    ItemCreated {
+        #[serde(default)]
+        previous_item_id: Option<String>,
+        item: ConversationItem,
+    },
+    /// An assistant message's audio has been truncated.
+    #[serde(rename = "conversation.item.truncated")]
+    ItemTruncated {
+        item_id: String,
+        content_index: u64,
+        audio_end_ms: u64,
+    },
+    /// A conversation item has been deleted.
+    #[serde(rename = "conversation.item.deleted")]
+    ItemDeleted { item_id: String },
+}
 
-    pub fn turn_detection(mut self, cfg: TurnDetection) -> Self {
-        self.turn_detection = Some(cfg);
-        self
-    }
+/// An error sent by the server. Most errors are recoverable and the session will stay open.
+///
+/// This is tagged on `type`, so that other events with an `error` field (ie failed transcriptions) aren't mistaken for it.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "type")]
+pub enum ErrorEvent {
+    #[serde(rename = "error")]
+    Error { error: RealtimeApiError },
+}
 
-    pub fn input_audio_format(mut self, format: AudioFormat) -> Self {
-        self.input_audio_format = Some(format);
-        self
+impl ErrorEvent {
+    /// The details of the error.
+    pub fn error(&self) -> &RealtimeApiError {
+        match self {
+            Self::Error { error } => error,
+        }
     }
+}
 
-    pub fn output_audio_format(mut self, format: AudioFormat) -> Self {
-        self.output_audio_format = Some(format);
-        self
-    }
+/// Remote MCP server events. Only sent by the GA API, when MCP tools are set on the session.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "type")]
+pub enum McpEvent {
+    /// The server is listing the tools available on an MCP server.
+    #[serde(rename = "mcp_list_tools.in_progress")]
+    ListToolsInProgress { item_id: String },
+    /// The tools available on an MCP server have been listed. The tools are added to the conversation as an `mcp_list_tools` item.
+    #[serde(rename = "mcp_list_tools.completed")]
+    ListToolsCompleted { item_id: String },
+    #[serde(rename = "mcp_list_tools.failed")]
+    ListToolsFailed { item_id: String },
+    #[serde(rename = "response.mcp_call_arguments.delta")]
+    CallArgumentsDelta {
+        response_id: String,
+        item_id: String,
+        output_index: u64,
+        delta: String,
+    },
+    #[serde(rename = "response.mcp_call_arguments.done")]
+    CallArgumentsDone {
+        response_id: String,
+        item_id: String,
+        output_index: u64,
+        arguments: String,
+    },
+    #[serde(rename = "response.mcp_call.in_progress")]
+    CallInProgress { item_id: String, output_index: u64 },
+    /// An MCP tool call has completed. The output can be found on the `mcp_call` item.
+    #[serde(rename = "response.mcp_call.completed")]
+    CallCompleted { item_id: String, output_index: u64 },
+    #[serde(rename = "response.mcp_call.failed")]
+    CallFailed { item_id: String, output_index: u64 },
+}
 
-    pub fn modalities(mut self, arr: Vec<Modality>) -> Self {
-        self.modalities = Some(arr);
-        self
-    }
+/// Events about the state of the underlying websocket connection.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "type")]
+pub enum ConnectionEvent {
+    /// The connection dropped and has been re-established. The last known session config has been replayed.
+    #[serde(rename = "connection.reconnected")]
+    Reconnected { attempts: u32 },
+    /// An input event could not be sent. If the websocket itself failed, a reconnection will be attempted (if configured).
+    #[serde(rename = "connection.send_failed")]
+    SendFailed {
+        /// The client-assigned ID of the event that failed to send, if one was set.
+        event_id: Option<String>,
+        error: String,
+    },
+    /// The connection has been closed and no more events will be received. This is always the last event on the stream.
+    #[serde(rename = "connection.closed")]
+    ConnectionClosed { reason: CloseReason },
+}
 
-    pub fn speed(mut self, speed: f64) -> Self {
-        self.speed = Some(speed);
-        self
-    }
+/// Why a realtime connection was closed.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum CloseReason {
+    /// The connection was closed from our side (ie the sender was dropped).
+    Client,
+    /// The server closed the connection.
+    Server,
+    /// Nothing was received from the server within the keepalive timeout.
+    IdleTimeout,
+    /// The connection failed.
+    Error { message: String },
 }
 
-/// Turn detection config.
+/// Input audio transcription events. Sent when `input_audio_transcription` is set on the session, or in a transcription session.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "type")]
+pub enum TranscriptionEvent {
+    /// A delta of the transcript of a user audio item. Only sent by streaming transcription models (ie `gpt-4o-transcribe`).
+    #[serde(rename = "conversation.item.input_audio_transcription.delta")]
+    Delta {
+        item_id: String,
+        content_index: u64,
+        delta: String,
+    },
+    /// The final transcript of a user audio item.
+    #[serde(rename = "conversation.item.input_audio_transcription.completed")]
+    Completed {
+        item_id: String,
+        content_index: u64,
+        transcript: String,
+    },
+    /// Transcription of a user audio item failed. This doesn't affect the rest of the session.
+    #[serde(rename = "conversation.item.input_audio_transcription.failed")]
+    Failed {
+        item_id: String,
+        content_index: u64,
+        error: RealtimeApiError,
+    },
+}
+
+/// Rate limit events. Emitted at the beginning of a response to indicate the updated rate limits.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "type")]
+pub enum RateLimitsEvent {
+    #[serde(rename = "rate_limits.updated")]
+    Updated { rate_limits: Vec<RateLimit> },
+}
+
+impl RateLimitsEvent {
+    /// All rate limits in this event.
+    pub fn rate_limits(&self) -> &[RateLimit] {
+        match self {
+            Self::Updated { rate_limits } => rate_limits,
+        }
+    }
+
+    /// The request rate limit, if present.
+    pub fn requests(&self) -> Option<&RateLimit> {
+        self.rate_limits().iter().find(|x| x.name == "requests")
+    }
+
+    /// The token rate limit, if present.
+    pub fn tokens(&self) -> Option<&RateLimit> {
+        self.rate_limits().iter().find(|x| x.name == "tokens")
+    }
+}
+
+/// A single rate limit.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct RateLimit {
+    /// The name of the rate limit (`requests` or `tokens`).
+    pub name: String,
+    /// The maximum allowed value for the rate limit.
+    pub limit: u64,
+    /// The remaining value before the limit is reached.
+    pub remaining: u64,
+    /// Seconds until the rate limit resets.
+    pub reset_seconds: f64,
+}
+
+impl RateLimit {
+    /// The duration until the rate limit resets.
+    pub fn reset_after(&self) -> std::time::Duration {
+        std::time::Duration::from_secs_f64(self.reset_seconds.max(0.0))
+    }
+}
+
+/// The details of an error event sent by the server.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct RealtimeApiError {
+    /// The type of error (ie `invalid_request_error`, `server_error`).
+    #[serde(rename = "type")]
+    pub kind: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub param: Option<String>,
+    /// The ID of the client event that caused the error, if applicable.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub event_id: Option<String>,
+}
+
+impl std::fmt::Display for RealtimeApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.code {
+            Some(code) => write!(f, "{} ({code}): {}", self.kind, self.message),
+            None => write!(f, "{}: {}", self.kind, self.message),
+        }
+    }
+}
+
+/// Events relating to the input audio buffer.
+/// When server VAD is enabled, these can be used to duck playback or show a "listening" indicator.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "type")]
+pub enum InputAudioBufferEvent {
+    /// Speech has been detected in the audio buffer.
+    #[serde(rename = "input_audio_buffer.speech_started")]
+    SpeechStarted {
+        /// Milliseconds from the start of all audio written to the buffer during the session.
+        audio_start_ms: u64,
+        /// The ID of the user message item that will be created when speech stops.
+        item_id: String,
+    },
+    /// The end of speech has been detected in the audio buffer.
+    #[serde(rename = "input_audio_buffer.speech_stopped")]
+    SpeechStopped { audio_end_ms: u64, item_id: String },
+    /// The input audio buffer has been committed, either by the client or automatically by server VAD.
+    #[serde(rename = "input_audio_buffer.committed")]
+    Committed {
+        #[serde(default)]
+        previous_item_id: Option<String>,
+        item_id: String,
+    },
+    /// The input audio buffer has been cleared.
+    #[serde(rename = "input_audio_buffer.cleared")]
+    Cleared,
+}
+
+/// A response as returned by the realtime API.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct RealtimeResponse {
+    pub id: String,
+    /// The status of the response: `in_progress`, `completed`, `cancelled`, `failed` or `incomplete`.
+    pub status: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status_details: Option<serde_json::Value>,
+    #[serde(default)]
+    pub output: Vec<ConversationItem>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<Usage>,
+    /// The metadata set on the response when it was created, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<HashMap<String, String>>,
+    /// The conversation the response was added to. `None` for out-of-band responses.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub conversation_id: Option<String>,
+}
+
+/// Token usage for a single response.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Usage {
+    #[serde(default)]
+    pub total_tokens: u64,
+    #[serde(default)]
+    pub input_tokens: u64,
+    #[serde(default)]
+    pub output_tokens: u64,
+    #[serde(default)]
+    pub input_token_details: InputTokenDetails,
+    #[serde(default)]
+    pub output_token_details: OutputTokenDetails,
+}
+
+/// A breakdown of the input tokens used by a response.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct InputTokenDetails {
+    #[serde(default)]
+    pub text_tokens: u64,
+    #[serde(default)]
+    pub audio_tokens: u64,
+    /// The number of input tokens that were served from the prompt cache.
+    #[serde(default)]
+    pub cached_tokens: u64,
+    #[serde(default)]
+    pub cached_tokens_details: CachedTokenDetails,
+}
+
+/// A breakdown of the cached input tokens used by a response.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct CachedTokenDetails {
+    #[serde(default)]
+    pub text_tokens: u64,
+    #[serde(default)]
+    pub audio_tokens: u64,
+}
+
+/// A breakdown of the output tokens used by a response.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct OutputTokenDetails {
+    #[serde(default)]
+    pub text_tokens: u64,
+    #[serde(default)]
+    pub audio_tokens: u64,
+}
+
+impl std::ops::AddAssign<&Usage> for Usage {
+    fn add_assign(&mut self, rhs: &Usage) {
+        self.total_tokens += rhs.total_tokens;
+        self.input_tokens += rhs.input_tokens;
+        self.output_tokens += rhs.output_tokens;
+
+        let (input, rhs_input) = (&mut self.input_token_details, &rhs.input_token_details);
+        input.text_tokens += rhs_input.text_tokens;
+        input.audio_tokens += rhs_input.audio_tokens;
+        input.cached_tokens += rhs_input.cached_tokens;
+        input.cached_tokens_details.text_tokens += rhs_input.cached_tokens_details.text_tokens;
+        input.cached_tokens_details.audio_tokens += rhs_input.cached_tokens_details.audio_tokens;
+
+        let (output, rhs_output) = (&mut self.output_token_details, &rhs.output_token_details);
+        output.text_tokens += rhs_output.text_tokens;
+        output.audio_tokens += rhs_output.audio_tokens;
+    }
+}
+
+/// Keeps a running total of the token usage of a realtime session.
+#[derive(Debug, Clone, Default)]
+pub struct UsageTracker {
+    total: Usage,
+    responses: u64,
+}
+
+impl UsageTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Observe an event, returning the usage of the response if the event is a `response.done` event.
+    pub fn observe<'a>(&mut self, evt: &'a ReceivedEvent) -> Option<&'a Usage> {
+        let ReceivedEventKind::Response(ResponseEvent::ResponseDone { response }) = &evt.data
+        else {
+            return None;
+        };
+
+        let usage = response.usage.as_ref()?;
+        self.total += usage;
+        self.responses += 1;
+
+        Some(usage)
+    }
+
+    /// The total token usage of all responses observed so far.
+    pub fn total(&self) -> &Usage {
+        &self.total
+    }
+
+    /// The number of responses (with usage) observed so far.
+    pub fn responses(&self) -> u64 {
+        self.responses
+    }
+}
+
+/// An item in a realtime conversation.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ConversationItem {
+    /// A message from the user, the assistant or the system.
+    Message {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        status: Option<String>,
+        role: ItemRole,
+        #[serde(default)]
+        content: Vec<ContentPart>,
+    },
+    /// A function call made by the model.
+    FunctionCall {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        status: Option<String>,
+        call_id: String,
+        name: String,
+        /// The arguments of the function call, as a JSON string.
+        arguments: String,
+    },
+    /// The output of a function call.
+    FunctionCallOutput {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+        call_id: String,
+        output: String,
+    },
+    /// The tools available on an MCP server.
+    McpListTools {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+        server_label: String,
+        #[serde(default)]
+        tools: Vec<McpToolInfo>,
+    },
+    /// A call to an MCP server tool made by the model.
+    McpCall {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+        server_label: String,
+        name: String,
+        /// The arguments of the tool call, as a JSON string.
+        arguments: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        output: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        error: Option<serde_json::Value>,
+    },
+    /// A request from the model to call an MCP server tool that requires approval.
+    McpApprovalRequest {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+        server_label: String,
+        name: String,
+        arguments: String,
+    },
+    /// A response to an MCP approval request.
+    McpApprovalResponse {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+        approval_request_id: String,
+        approve: bool,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        reason: Option<String>,
+    },
+}
+
+/// A tool listed by an MCP server.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct McpToolInfo {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub input_schema: serde_json::Value,
+}
+
+impl ConversationItem {
+    /// A text message from the user.
+    pub fn user_text(text: &str) -> Self {
+        Self::message(
+            ItemRole::User,
+            ContentPart::InputText {
+                text: text.to_string(),
+            },
+        )
+    }
+
+    /// A text message from the system.
+    pub fn system_text(text: &str) -> Self {
+        Self::message(
+            ItemRole::System,
+            ContentPart::InputText {
+                text: text.to_string(),
+            },
+        )
+    }
+
+    /// A text message from the assistant.
+    pub fn assistant_text(text: &str) -> Self {
+        Self::message(
+            ItemRole::Assistant,
+            ContentPart::Text {
+                text: text.to_string(),
+            },
+        )
+    }
+
+    fn message(role: ItemRole, content: ContentPart) -> Self {
+        Self::Message {
+            id: None,
+            status: None,
+            role,
+            content: vec![content],
+        }
+    }
+
+    /// The ID of the item, if one has been assigned.
+    pub fn id(&self) -> Option<&str> {
+        match self {
+            Self::Message { id, .. }
+            | Self::FunctionCall { id, .. }
+            | Self::FunctionCallOutput { id, .. }
+            | Self::McpListTools { id, .. }
+            | Self::McpCall { id, .. }
+            | Self::McpApprovalRequest { id, .. }
+            | Self::McpApprovalResponse { id, .. } => id.as_deref(),
+        }
+    }
+}
+
+/// The role of a conversation message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ItemRole {
+    User,
+    Assistant,
+    System,
+}
+
+/// A content part of a conversation message.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ContentPart {
+    /// Text input from the user (or system).
+    InputText { text: String },
+    /// Audio input from the user, with an optional transcript.
+    InputAudio {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        audio: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        transcript: Option<String>,
+    },
+    /// Text output from the assistant.
+    Text { text: String },
+    /// Audio output from the assistant, with an optional transcript.
+    Audio {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        audio: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        transcript: Option<String>,
+    },
+}
+
+/// The gpt-realtime model. Requires [`RealtimeApiVersion::Ga`](super::client::RealtimeApiVersion::Ga).
+pub const GPT_REALTIME: &str = "gpt-realtime";
+
+/// The gpt-4o-realtime-preview-2025-06-03 model. For use with the OpenAI realtime API.
+pub const GPT_4O_REALTIME_PREVIEW_20250603: &str = "gpt-4o-realtime-preview-2025-06-03";
+
+/// A realtime API voice.
+/// Voices that aren't (yet) listed here can be used with [`Voice::Custom`], or by passing the name as a string.
+///
+/// Note that the voice cannot be changed once the model has responded with audio in a session.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(from = "String", into = "String")]
+pub enum Voice {
+    Alloy,
+    Ash,
+    Ballad,
+    Coral,
+    Echo,
+    Sage,
+    Shimmer,
+    Verse,
+    /// Only available with the `gpt-realtime` models.
+    Marin,
+    /// Only available with the `gpt-realtime` models.
+    Cedar,
+    Custom(String),
+}
+
+impl Voice {
+    /// All of the voices listed by this crate (excluding custom voices).
+    pub const ALL: [Voice; 10] = [
+        Voice::Alloy,
+        Voice::Ash,
+        Voice::Ballad,
+        Voice::Coral,
+        Voice::Echo,
+        Voice::Sage,
+        Voice::Shimmer,
+        Voice::Verse,
+        Voice::Marin,
+        Voice::Cedar,
+    ];
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Alloy => "alloy",
+            Self::Ash => "ash",
+            Self::Ballad => "ballad",
+            Self::Coral => "coral",
+            Self::Echo => "echo",
+            Self::Sage => "sage",
+            Self::Shimmer => "shimmer",
+            Self::Verse => "verse",
+            Self::Marin => "marin",
+            Self::Cedar => "cedar",
+            Self::Custom(voice) => voice,
+        }
+    }
+}
+
+impl std::fmt::Display for Voice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl From<&str> for Voice {
+    fn from(value: &str) -> Self {
+        Self::ALL
+            .into_iter()
+            .find(|x| x.as_str() == value)
+            .unwrap_or_else(|| Self::Custom(value.to_string()))
+    }
+}
+
+impl From<String> for Voice {
+    fn from(value: String) -> Self {
+        Self::from(value.as_str())
+    }
+}
+
+impl From<Voice> for String {
+    fn from(value: Voice) -> Self {
+        match value {
+            Voice::Custom(voice) => voice,
+            voice => voice.as_str().to_string(),
+        }
+    }
+}
+
+/// OpenAI's realtime API session data. You can use this to update the realtime session at any time.
+#[derive(Debug, Clone, Deserialize, Serialize, Default)]
+pub struct Session {
+    /// The modality you want to use. Can either be text or audio (and you can have both in the same vec!)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub modalities: Option<Vec<Modality>>,
+    /// The preamble ("system prompt") that you want to use.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub instructions: Option<String>,
+    /// The OpenAI voice you want to use.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub voice: Option<Voice>,
+    /// Turn detection. Instead of manually committing, this allows OpenAI to just figure it out for you.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub turn_detection: Option<TurnDetection>,
+    /// The input audio format to be used.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub input_audio_format: Option<AudioFormat>,
+    /// The output audio format to be used.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_audio_format: Option<AudioFormat>,
+    /// The model you want to use for input audio transcription.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub input_audio_transcription: Option<InputAudioTranscription>,
+    /// The tools you want to use.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tools: Option<Vec<RealtimeTool>>,
+    /// The temperature you want to use. Set higher for more creative responses.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f64>,
+    /// Playback speed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub speed: Option<f64>,
+    /// The maximum number of output tokens for a single response, inclusive of tool calls.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_response_output_tokens: Option<MaxOutputTokens>,
+    /// How the model chooses tools.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_choice: Option<ToolChoice>,
+    /// A template that the instructions are rendered from. Not sent to OpenAI.
+    #[serde(skip)]
+    pub instructions_template: Option<PromptTemplate>,
+}
+
+impl Session {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the voice. Accepts either a [`Voice`] or a voice name.
+    pub fn voice(mut self, voice: impl Into<Voice>) -> Self {
+        self.voice = Some(voice.into());
+        self
+    }
+
+    pub fn instructions(mut self, instructions: &str) -> Self {
+        self.instructions = Some(instructions.to_string());
+        self
+    }
+
+    /// Render the instructions from a prompt template. The template is re-rendered whenever the session is sent as a session update,
+    /// so variables can be changed mid-session with [`Session::set_template_variable`].
+    pub fn instructions_template(mut self, template: PromptTemplate) -> Self {
+        self.instructions_template = Some(template);
+        self.render_instructions();
+        self
+    }
+
+    /// Set a variable on the instructions template (if there is one) and re-render the instructions.
+    pub fn set_template_variable<V>(&mut self, k: &str, v: V)
+    where
+        V: Serialize,
+    {
+        if let Some(template) = self.instructions_template.take() {
+            self.instructions_template = Some(template.with_variable(k, v));
+            self.render_instructions();
+        }
+    }
+
+    /// Re-render the instructions from the instructions template, if there is one.
+    /// If rendering fails, the previous instructions are kept (and [`Session::validate`] will return an error).
+    pub fn render_instructions(&mut self) {
+        let Some(template) = &self.instructions_template else {
+            return;
+        };
+
+        match template.try_render_to_string() {
+            Ok(instructions) => self.instructions = Some(instructions),
+            Err(err) => tracing::warn!("Failed to render instructions template: {err}"),
+        }
+    }
+
+    pub fn turn_detection(mut self, cfg: TurnDetection) -> Self {
+        self.turn_detection = Some(cfg);
+        self
+    }
+
+    pub fn input_audio_format(mut self, format: AudioFormat) -> Self {
+        self.input_audio_format = Some(format);
+        self
+    }
+
+    pub fn output_audio_format(mut self, format: AudioFormat) -> Self {
+        self.output_audio_format = Some(format);
+        self
+    }
+
+    pub fn modalities(mut self, arr: Vec<Modality>) -> Self {
+        self.modalities = Some(arr);
+        self
+    }
+
+    pub fn speed(mut self, speed: f64) -> Self {
+        self.speed = Some(speed);
+        self
+    }
+
+    pub fn tools(mut self, tools: Vec<RealtimeTool>) -> Self {
+        self.tools = Some(tools);
+        self
+    }
+
+    pub fn max_response_output_tokens(mut self, max_tokens: MaxOutputTokens) -> Self {
+        self.max_response_output_tokens = Some(max_tokens);
+        self
+    }
+
+    pub fn tool_choice(mut self, tool_choice: ToolChoice) -> Self {
+        self.tool_choice = Some(tool_choice);
+        self
+    }
+
+    /// Check the session config for mistakes that OpenAI would otherwise reject with an error event after connecting.
+    /// This is called automatically before opening a connection with a session config.
+    pub fn validate(&self) -> Result<(), SessionValidationError> {
+        if let Some(template) = &self.instructions_template
+            && let Err(err) = template.try_render_to_string()
+        {
+            return Err(SessionValidationError::InvalidInstructionsTemplate(
+                err.to_string(),
+            ));
+        }
+
+        if let Some(speed) = self.speed
+            && !(0.25..=1.5).contains(&speed)
+        {
+            return Err(SessionValidationError::OutOfRange {
+                field: "speed",
+                value: speed,
+                min: 0.25,
+                max: 1.5,
+            });
+        }
+
+        if let Some(temperature) = self.temperature
+            && !(0.6..=1.2).contains(&temperature)
+        {
+            return Err(SessionValidationError::OutOfRange {
+                field: "temperature",
+                value: temperature,
+                min: 0.6,
+                max: 1.2,
+            });
+        }
+
+        if let Some(threshold) = self.turn_detection.as_ref().and_then(|x| x.threshold)
+            && !(0.0..=1.0).contains(&threshold)
+        {
+            return Err(SessionValidationError::OutOfRange {
+                field: "turn_detection.threshold",
+                value: threshold,
+                min: 0.0,
+                max: 1.0,
+            });
+        }
+
+        if let Some(MaxOutputTokens::Limited(max_tokens)) = self.max_response_output_tokens
+            && !(1..=4096).contains(&max_tokens)
+        {
+            return Err(SessionValidationError::OutOfRange {
+                field: "max_response_output_tokens",
+                value: max_tokens as f64,
+                min: 1.0,
+                max: 4096.0,
+            });
+        }
+
+        let mut tool_names = std::collections::HashSet::new();
+        for tool in self.tools.iter().flatten() {
+            let RealtimeTool::Function { name, .. } = tool else {
+                continue;
+            };
+
+            if !tool_names.insert(name.as_str()) {
+                return Err(SessionValidationError::DuplicateTool(name.clone()));
+            }
+        }
+
+        if let Some(ToolChoice::Function { name, .. }) = &self.tool_choice
+            && !tool_names.contains(name.as_str())
+        {
+            return Err(SessionValidationError::UnknownToolChoice(name.clone()));
+        }
+
+        Ok(())
+    }
+}
+
+/// A problem with a [`Session`] config, found before sending it to OpenAI.
+#[derive(thiserror::Error, Debug, Clone, PartialEq)]
+pub enum SessionValidationError {
+    #[error("`{field}` must be between {min} and {max} (got {value})")]
+    OutOfRange {
+        field: &'static str,
+        value: f64,
+        min: f64,
+        max: f64,
+    },
+    #[error("Failed to render instructions template: {0}")]
+    InvalidInstructionsTemplate(String),
+    #[error("Tool `{0}` is defined more than once")]
+    DuplicateTool(String),
+    #[error("Tool choice references tool `{0}`, which is not defined in the session")]
+    UnknownToolChoice(String),
+}
+
+/// The maximum number of output tokens for a response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MaxOutputTokens {
+    /// A limit between 1 and 4096.
+    Limited(u64),
+    /// No limit (other than the model's maximum). Serialized as `"inf"`.
+    Infinite,
+}
+
+impl Serialize for MaxOutputTokens {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        match self {
+            Self::Limited(tokens) => serializer.serialize_u64(*tokens),
+            Self::Infinite => serializer.serialize_str("inf"),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for MaxOutputTokens {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Limited(u64),
+            Sentinel(String),
+        }
+
+        match Raw::deserialize(deserializer)? {
+            Raw::Limited(tokens) => Ok(Self::Limited(tokens)),
+            Raw::Sentinel(str) if str == "inf" => Ok(Self::Infinite),
+            Raw::Sentinel(str) => Err(serde::de::Error::custom(format!(
+                "expected a number or \"inf\", got \"{str}\""
+            ))),
+        }
+    }
+}
+
+/// How the model chooses tools.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum ToolChoice {
+    Mode(ToolChoiceMode),
+    /// Force the model to call a specific function.
+    Function {
+        #[serde(rename = "type")]
+        kind: String,
+        name: String,
+    },
+}
+
+impl ToolChoice {
+    /// The model decides whether or not to call tools.
+    pub fn auto() -> Self {
+        Self::Mode(ToolChoiceMode::Auto)
+    }
+
+    /// The model will not call any tools.
+    pub fn none() -> Self {
+        Self::Mode(ToolChoiceMode::None)
+    }
+
+    /// The model must call at least one tool.
+    pub fn required() -> Self {
+        Self::Mode(ToolChoiceMode::Required)
+    }
+
+    /// The model must call the function with the given name.
+    pub fn function(name: &str) -> Self {
+        Self::Function {
+            kind: "function".to_string(),
+            name: name.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ToolChoiceMode {
+    Auto,
+    None,
+    Required,
+}
+
+/// A tool that can be used by the model in a realtime session.
+/// Note that unlike the chat completions API, function definitions are not nested under a `function` key.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RealtimeTool {
+    Function {
+        name: String,
+        description: String,
+        parameters: serde_json::Value,
+    },
+    /// A remote MCP server. Only supported by the GA API.
+    Mcp(McpTool),
+}
+
+/// A remote MCP server that the model can call tools on.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct McpTool {
+    /// A label for the server, used to identify it in tool calls.
+    pub server_label: String,
+    pub server_url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server_description: Option<String>,
+    /// An OAuth access token for the server.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub authorization: Option<String>,
+    /// Additional headers to send to the server.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub headers: Option<HashMap<String, String>>,
+    /// Restrict the tools that the model can use from this server. All tools are allowed if not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allowed_tools: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub require_approval: Option<McpApproval>,
+}
+
+impl McpTool {
+    pub fn new(server_label: &str, server_url: &str) -> Self {
+        Self {
+            server_label: server_label.to_string(),
+            server_url: server_url.to_string(),
+            server_description: None,
+            authorization: None,
+            headers: None,
+            allowed_tools: None,
+            require_approval: None,
+        }
+    }
+
+    pub fn server_description(mut self, description: &str) -> Self {
+        self.server_description = Some(description.to_string());
+        self
+    }
+
+    pub fn authorization(mut self, token: &str) -> Self {
+        self.authorization = Some(token.to_string());
+        self
+    }
+
+    pub fn header(mut self, k: &str, v: &str) -> Self {
+        self.headers
+            .get_or_insert_with(HashMap::new)
+            .insert(k.to_string(), v.to_string());
+        self
+    }
+
+    pub fn allowed_tools(mut self, tools: Vec<String>) -> Self {
+        self.allowed_tools = Some(tools);
+        self
+    }
+
+    pub fn require_approval(mut self, approval: McpApproval) -> Self {
+        self.require_approval = Some(approval);
+        self
+    }
+}
+
+impl From<McpTool> for RealtimeTool {
+    fn from(value: McpTool) -> Self {
+        Self::Mcp(value)
+    }
+}
+
+/// Whether MCP tool calls require approval. When approval is required, the model will add an `mcp_approval_request` item to the conversation,
+/// which must be answered with [`InputEvent::mcp_approval`] before the tool is called.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum McpApproval {
+    Always,
+    Never,
+}
+
+impl From<rig::completion::ToolDefinition> for RealtimeTool {
+    fn from(value: rig::completion::ToolDefinition) -> Self {
+        let rig::completion::ToolDefinition {
+            name,
+            description,
+            parameters,
+        } = value;
+
+        Self::Function {
+            name,
+            description,
+            parameters,
+        }
+    }
+}
+
+/// Turn detection config.
 /// If you don't want to configure manual audio commits, this is a useful convenience method for having OpenAI do it entirely for you.
 /// Instead of manually committing, you simply append audio inputs and OpenAI figures it out.
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -302,10 +1710,18 @@ pub struct TurnDetection {
     /// The turn detection kind. T
     #[serde(rename = "type")]
     kind: Option<TurnDetectionKind>,
+    // Unset fields are skipped rather than sent as null, as semantic VAD rejects the server VAD-only fields
+    #[serde(skip_serializing_if = "Option::is_none")]
     threshold: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     prefix_padding_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     silence_duration_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     create_response: Option<bool>,
+    /// How eager the model is to respond. Only applies to semantic VAD.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    eagerness: Option<Eagerness>,
 }
 
 impl TurnDetection {
@@ -317,6 +1733,20 @@ impl TurnDetection {
             prefix_padding_ms: Some(300),
             silence_duration_ms: Some(500),
             create_response: Some(true),
+            eagerness: None,
+        }
+    }
+
+    /// Creates a new semantic VAD config.
+    /// Semantic VAD uses a model to estimate whether the user has finished speaking, which results in far fewer interruptions than server VAD.
+    pub fn semantic_vad() -> Self {
+        Self {
+            kind: Some(TurnDetectionKind::SemanticVad),
+            threshold: None,
+            prefix_padding_ms: None,
+            silence_duration_ms: None,
+            create_response: None,
+            eagerness: None,
         }
     }
 
@@ -328,6 +1758,7 @@ impl TurnDetection {
             prefix_padding_ms: None,
             silence_duration_ms: None,
             create_response: None,
+            eagerness: None,
         }
     }
 
@@ -354,6 +1785,12 @@ impl TurnDetection {
         self.create_response = Some(create_response);
         self
     }
+
+    /// Set how eager the model is to respond (semantic VAD only). `Low` will let the user take their time, `High` will respond as soon as possible.
+    pub fn eagerness(mut self, eagerness: Eagerness) -> Self {
+        self.eagerness = Some(eagerness);
+        self
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize, Default)]
@@ -362,25 +1799,282 @@ pub enum TurnDetectionKind {
     #[serde(rename = "server_vad")]
     #[default]
     ServerVad,
+    #[serde(rename = "semantic_vad")]
+    SemanticVad,
 }
 
+/// Semantic VAD eagerness.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Eagerness {
+    Low,
+    Medium,
+    High,
+    #[default]
+    Auto,
+}
+
+/// The whisper-1 transcription model.
+pub const WHISPER_1: &str = "whisper-1";
+
+/// The gpt-4o-transcribe transcription model. Supports streaming transcription deltas.
+pub const GPT_4O_TRANSCRIBE: &str = "gpt-4o-transcribe";
+
+/// The gpt-4o-mini-transcribe transcription model. Supports streaming transcription deltas.
+pub const GPT_4O_MINI_TRANSCRIBE: &str = "gpt-4o-mini-transcribe";
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct InputAudioTranscription {
     model: String,
+    /// An optional prompt to guide the transcription (ie expected vocabulary).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    prompt: Option<String>,
+    /// The language of the input audio (ISO 639-1). Improves accuracy and latency.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    language: Option<String>,
 }
 
 impl Default for InputAudioTranscription {
     fn default() -> Self {
+        Self::new(WHISPER_1)
+    }
+}
+
+impl InputAudioTranscription {
+    pub fn new(model: &str) -> Self {
         Self {
-            model: "whisper-1".to_string(),
+            model: model.to_string(),
+            prompt: None,
+            language: None,
         }
     }
+
+    pub fn prompt(mut self, prompt: &str) -> Self {
+        self.prompt = Some(prompt.to_string());
+        self
+    }
+
+    pub fn language(mut self, language: &str) -> Self {
+        self.language = Some(language.to_string());
+        self
+    }
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
-#[serde(rename_all = "lowercase")]
+/// The config for a transcription-only session. See [`RealtimeModel::transcription_session`].
+#[derive(Debug, Clone, Deserialize, Serialize, Default)]
+pub struct TranscriptionSession {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub input_audio_format: Option<AudioFormat>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub input_audio_transcription: Option<InputAudioTranscription>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub turn_detection: Option<TurnDetection>,
+}
+
+impl TranscriptionSession {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn input_audio_format(mut self, format: AudioFormat) -> Self {
+        self.input_audio_format = Some(format);
+        self
+    }
+
+    pub fn transcription(mut self, transcription: InputAudioTranscription) -> Self {
+        self.input_audio_transcription = Some(transcription);
+        self
+    }
+
+    pub fn turn_detection(mut self, cfg: TurnDetection) -> Self {
+        self.turn_detection = Some(cfg);
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum AudioFormat {
+    /// 16-bit little-endian PCM at 24kHz.
     Pcm16,
+    /// G.711 µ-law at 8kHz. Used by most telephony providers in North America and Japan (ie Twilio).
+    #[serde(rename = "g711_ulaw")]
+    G711Ulaw,
+    /// G.711 A-law at 8kHz. Used by most telephony providers outside of North America.
+    #[serde(rename = "g711_alaw")]
+    G711Alaw,
+}
+
+impl AudioFormat {
+    /// The sample rate (in Hz) of audio in this format.
+    pub fn sample_rate(&self) -> u32 {
+        match self {
+            Self::Pcm16 => 24_000,
+            Self::G711Ulaw | Self::G711Alaw => 8_000,
+        }
+    }
+
+    /// The number of bytes per millisecond of (mono) audio in this format.
+    pub fn bytes_per_ms(&self) -> usize {
+        match self {
+            // 24 samples per ms, 2 bytes per sample
+            Self::Pcm16 => 48,
+            // 8 samples per ms, 1 byte per sample
+            Self::G711Ulaw | Self::G711Alaw => 8,
+        }
+    }
+
+    /// The MIME type of this format, as used by the GA API.
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            Self::Pcm16 => "audio/pcm",
+            Self::G711Ulaw => "audio/pcmu",
+            Self::G711Alaw => "audio/pcma",
+        }
+    }
+}
+
+/// An audio format as represented in the GA API, where formats are objects with a MIME type and (for PCM) a sample rate.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct GaAudioFormat {
+    #[serde(rename = "type")]
+    pub mime_type: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rate: Option<u32>,
+}
+
+impl From<AudioFormat> for GaAudioFormat {
+    fn from(value: AudioFormat) -> Self {
+        Self {
+            mime_type: value.mime_type().to_string(),
+            // Only PCM has a configurable sample rate, G.711 is always 8kHz
+            rate: matches!(value, AudioFormat::Pcm16).then(|| value.sample_rate()),
+        }
+    }
+}
+
+impl TryFrom<GaAudioFormat> for AudioFormat {
+    type Error = GaAudioFormat;
+
+    fn try_from(value: GaAudioFormat) -> Result<Self, Self::Error> {
+        [Self::Pcm16, Self::G711Ulaw, Self::G711Alaw]
+            .into_iter()
+            .find(|x| x.mime_type() == value.mime_type)
+            .ok_or(value)
+    }
+}
+
+/// Per-response configuration for `response.create`. Any fields that are set override the session configuration for this response only.
+#[derive(Debug, Clone, Deserialize, Serialize, Default)]
+pub struct ResponseConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub modalities: Option<Vec<Modality>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub instructions: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub voice: Option<Voice>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_audio_format: Option<AudioFormat>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tools: Option<Vec<RealtimeTool>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f64>,
+    /// Which conversation the response is added to. Set this to [`ResponseConversation::None`] for an out-of-band response.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub conversation: Option<ResponseConversation>,
+    /// Custom input items for the response. If set, these are used as the context instead of the default conversation.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub input: Option<Vec<ConversationItem>>,
+    /// Key-value metadata, returned on the response events. Useful for identifying out-of-band responses.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<HashMap<String, String>>,
+}
+
+impl ResponseConfig {
+    /// A response that is not added to the default conversation. Use this for side-calls (ie classification or moderation) that shouldn't pollute the conversation.
+    pub fn out_of_band() -> Self {
+        Self {
+            conversation: Some(ResponseConversation::None),
+            ..Default::default()
+        }
+    }
+
+    /// Set custom input items for the response.
+    pub fn input(mut self, items: Vec<ConversationItem>) -> Self {
+        self.input = Some(items);
+        self
+    }
+
+    /// Add a metadata entry to the response.
+    pub fn metadata(mut self, k: &str, v: &str) -> Self {
+        self.metadata
+            .get_or_insert_with(HashMap::new)
+            .insert(k.to_string(), v.to_string());
+        self
+    }
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn modalities(mut self, arr: Vec<Modality>) -> Self {
+        self.modalities = Some(arr);
+        self
+    }
+
+    pub fn instructions(mut self, instructions: &str) -> Self {
+        self.instructions = Some(instructions.to_string());
+        self
+    }
+
+    pub fn voice(mut self, voice: impl Into<Voice>) -> Self {
+        self.voice = Some(voice.into());
+        self
+    }
+
+    pub fn temperature(mut self, temperature: f64) -> Self {
+        self.temperature = Some(temperature);
+        self
+    }
+}
+
+/// Which conversation a response is added to.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ResponseConversation {
+    /// The default conversation.
+    Auto,
+    /// No conversation (an out-of-band response).
+    None,
+}
+
+/// Tracks the metadata of in-flight responses, so that events for a response (ie text deltas) can be matched to the metadata the response was created with.
+#[derive(Debug, Clone, Default)]
+pub struct ResponseMetadataTracker {
+    responses: HashMap<String, HashMap<String, String>>,
+}
+
+impl ResponseMetadataTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Observe an event, returning the metadata of the response the event belongs to (if any).
+    /// Metadata is forgotten once the response is done.
+    pub fn observe(&mut self, evt: &ReceivedEvent) -> Option<HashMap<String, String>> {
+        match &evt.data {
+            ReceivedEventKind::Response(ResponseEvent::ResponseCreated { response }) => {
+                let metadata = response.metadata.clone()?;
+                self.responses.insert(response.id.clone(), metadata.clone());
+                Some(metadata)
+            }
+            ReceivedEventKind::Response(ResponseEvent::ResponseDone { response }) => self
+                .responses
+                .remove(&response.id)
+                .or_else(|| response.metadata.clone()),
+            data => self.responses.get(data.response_id()?).cloned(),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -389,3 +2083,176 @@ pub enum Modality {
     Text,
     Audio,
 }
+
+#[derive(thiserror::Error, Debug)]
+pub enum RealtimeError {
+    #[error("Websocket handshake failed: {0}")]
+    Handshake(reqwest_websocket::Error),
+    #[error("Authentication failed (HTTP {0}), check your API key")]
+    Auth(reqwest::StatusCode),
+    #[error("HTTP error: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("(De)serialization error: {0}")]
+    Serde(#[from] serde_json::Error),
+    #[error("Failed to send event: {0}")]
+    Send(reqwest_websocket::Error),
+    #[error("Failed to receive event: {0}")]
+    Receive(reqwest_websocket::Error),
+    #[error("The realtime connection has been closed")]
+    Closed,
+    #[error("OpenAI returned an error: {0}")]
+    Api(RealtimeApiError),
+    #[error("Invalid session config: {0}")]
+    InvalidSession(#[from] SessionValidationError),
+}
+
+impl RealtimeError {
+    pub(crate) fn from_handshake(err: reqwest_websocket::Error) -> Self {
+        match err {
+            reqwest_websocket::Error::Handshake(
+                reqwest_websocket::HandshakeError::UnexpectedStatusCode(status),
+            ) if status == reqwest::StatusCode::UNAUTHORIZED
+                || status == reqwest::StatusCode::FORBIDDEN =>
+            {
+                Self::Auth(status)
+            }
+            err => Self::Handshake(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        AudioFormat, ErrorEvent, InputAudioBufferEvent, MaxOutputTokens, Modality, ReceivedEvent,
+        ReceivedEventKind, ReceivedItemEventKind, ResponseEvent, Session, SessionValidationError,
+        TranscriptionEvent, UsageTracker, Voice,
+    };
+
+    fn parse(json: &str) -> ReceivedEventKind {
+        serde_json::from_str::<ReceivedEvent>(json).unwrap().data
+    }
+
+    #[test]
+    fn parses_received_events() {
+        let evt = parse(
+            r#"{"event_id":"e1","type":"response.audio.delta","response_id":"r1","item_id":"i1","output_index":0,"content_index":0,"delta":"AAAA"}"#,
+        );
+        assert!(matches!(
+            evt,
+            ReceivedEventKind::Item {
+                data: ReceivedItemEventKind::AudioDelta { .. },
+                ..
+            }
+        ));
+
+        let evt = parse(
+            r#"{"event_id":"e2","type":"response.function_call_arguments.done","response_id":"r1","item_id":"i1","output_index":0,"call_id":"c1","arguments":"{}"}"#,
+        );
+        assert!(matches!(
+            evt,
+            ReceivedEventKind::Response(ResponseEvent::FunctionCallArgumentsDone { .. })
+        ));
+
+        let evt = parse(
+            r#"{"event_id":"e3","type":"input_audio_buffer.speech_started","audio_start_ms":100,"item_id":"i2"}"#,
+        );
+        assert!(matches!(
+            evt,
+            ReceivedEventKind::InputAudioBuffer(InputAudioBufferEvent::SpeechStarted { .. })
+        ));
+    }
+
+    #[test]
+    fn max_output_tokens_serde() {
+        assert_eq!(
+            serde_json::to_string(&MaxOutputTokens::Infinite).unwrap(),
+            r#""inf""#
+        );
+        assert_eq!(
+            serde_json::from_str::<MaxOutputTokens>("4096").unwrap(),
+            MaxOutputTokens::Limited(4096)
+        );
+        assert!(serde_json::from_str::<MaxOutputTokens>(r#""lots""#).is_err());
+    }
+
+    #[test]
+    fn parses_errors_and_unknown_events() {
+        let evt = parse(
+            r#"{"event_id":"e4","type":"error","error":{"type":"invalid_request_error","code":"invalid_value","message":"Bad","param":null,"event_id":null}}"#,
+        );
+        assert!(matches!(
+            evt,
+            ReceivedEventKind::Error(ErrorEvent::Error { .. })
+        ));
+        assert_eq!(evt.event_type().as_deref(), Some("error"));
+
+        // Only events with the `error` type are errors, even if other events carry an `error` field
+        let evt = parse(
+            r#"{"event_id":"e6","type":"something.failed","error":{"type":"server_error","code":null,"message":"Bad","param":null}}"#,
+        );
+        assert!(matches!(evt, ReceivedEventKind::Unknown(_)));
+        assert_eq!(evt.event_type().as_deref(), Some("something.failed"));
+
+        let evt = parse(
+            r#"{"event_id":"e7","type":"conversation.item.input_audio_transcription.failed","item_id":"i1","content_index":0,"error":{"type":"transcription_error","code":"audio_unintelligible","message":"Bad audio","param":null}}"#,
+        );
+        assert!(matches!(
+            evt,
+            ReceivedEventKind::Transcription(TranscriptionEvent::Failed { .. })
+        ));
+
+        let evt = parse(r#"{"event_id":"e5","type":"something.new","foo":1}"#);
+        assert!(matches!(evt, ReceivedEventKind::Unknown(_)));
+        assert_eq!(evt.event_type().as_deref(), Some("something.new"));
+    }
+
+    #[test]
+    fn tracks_usage_totals() {
+        let done = r#"{"event_id":"e1","type":"response.done","response":{"id":"r1","status":"completed","output":[],"usage":{"total_tokens":30,"input_tokens":20,"output_tokens":10,"input_token_details":{"text_tokens":5,"audio_tokens":15,"cached_tokens":4,"cached_tokens_details":{"text_tokens":4,"audio_tokens":0}},"output_token_details":{"text_tokens":2,"audio_tokens":8}}}}"#;
+        let evt = serde_json::from_str::<ReceivedEvent>(done).unwrap();
+
+        let mut tracker = UsageTracker::new();
+        // The returned usage borrows from the event, not the tracker, so it can be held across further observations.
+        let usage = tracker.observe(&evt).unwrap();
+        tracker.observe(&evt);
+        assert_eq!(usage.output_token_details.audio_tokens, 8);
+
+        assert_eq!(tracker.responses(), 2);
+        assert_eq!(tracker.total().total_tokens, 60);
+        assert_eq!(tracker.total().input_token_details.cached_tokens, 8);
+    }
+
+    #[test]
+    fn validates_sessions() {
+        let session = Session::new()
+            .modalities(vec![Modality::Text, Modality::Audio])
+            .output_audio_format(AudioFormat::Pcm16)
+            .speed(1.0);
+        assert_eq!(session.validate(), Ok(()));
+
+        // Audio-only sessions are valid
+        let session = Session::new().modalities(vec![Modality::Audio]);
+        assert_eq!(session.validate(), Ok(()));
+
+        // The output audio format defaults to PCM16 when not set
+        let session = Session::new().modalities(vec![Modality::Text, Modality::Audio]);
+        assert_eq!(session.validate(), Ok(()));
+
+        let session = Session::new().speed(3.0);
+        assert!(matches!(
+            session.validate(),
+            Err(SessionValidationError::OutOfRange { field: "speed", .. })
+        ));
+    }
+
+    #[test]
+    fn voice_serde() {
+        assert_eq!(serde_json::to_string(&Voice::Marin).unwrap(), r#""marin""#);
+        assert_eq!(Voice::from("sage"), Voice::Sage);
+        assert_eq!(
+            serde_json::from_str::<Voice>(r#""new-voice""#).unwrap(),
+            Voice::Custom("new-voice".to_string())
+        );
+    }
+}
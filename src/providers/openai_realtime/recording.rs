@@ -0,0 +1,278 @@
+//! Recording and replaying realtime sessions.
+//!
+//! A [`Recorder`] attached to a [`RealtimeModel`](super::realtime::RealtimeModel) writes every event sent and received to a JSONL file.
+//! [`MockRealtimeVoice`] replays such a file, so realtime integrations can be tested in CI without an API key or audio hardware.
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::Path,
+    sync::{Arc, Mutex, mpsc::Receiver},
+    time::{Duration, Instant},
+};
+
+use futures::{StreamExt, stream::BoxStream};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::{self, Sender};
+
+use super::realtime::{
+    InputEvent, InputEventKind, RealtimeError, RealtimeVoice, RealtimeVoiceRequest, ReceivedEvent,
+};
+
+/// Whether an event was sent to or received from OpenAI.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    Inbound,
+    Outbound,
+}
+
+/// A single line of a recording.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct RecordedEvent {
+    /// Milliseconds since the recording was started.
+    pub timestamp_ms: u64,
+    pub direction: Direction,
+    pub event: serde_json::Value,
+}
+
+/// Records realtime events to a JSONL file, timestamped relative to when the recording was created. Lines are written
+/// on a background thread, and write errors are logged rather than interrupting the session.
+#[derive(Debug, Clone)]
+pub struct Recorder {
+    started: Instant,
+    lines: std::sync::mpsc::Sender<String>,
+}
+
+impl Recorder {
+    /// Creates a new recording, overwriting the file if it already exists.
+    pub fn create<P>(path: P) -> std::io::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let file = BufWriter::new(File::create(path)?);
+        let (lines, rx) = std::sync::mpsc::channel();
+        std::thread::spawn(move || write_lines(rx, file));
+
+        Ok(Self {
+            started: Instant::now(),
+            lines,
+        })
+    }
+
+    pub(crate) fn record<T>(&self, direction: Direction, event: &T)
+    where
+        T: Serialize,
+    {
+        let event = match serde_json::to_value(event) {
+            Ok(event) => event,
+            Err(err) => {
+                tracing::warn!("Failed to serialize event for recording: {err}");
+                return;
+            }
+        };
+
+        let record = RecordedEvent {
+            timestamp_ms: self.started.elapsed().as_millis() as u64,
+            direction,
+            event,
+        };
+
+        let Ok(mut line) = serde_json::to_string(&record) else {
+            return;
+        };
+        line.push('\n');
+
+        if self.lines.send(line).is_err() {
+            tracing::warn!("Failed to write event to recording: the writer has stopped");
+        }
+    }
+}
+
+/// Writes recorded lines on a dedicated thread, so that recording events never blocks the async runtime.
+/// The file is flushed whenever there are no more pending lines, and the thread exits once every [`Recorder`] clone is dropped.
+fn write_lines(lines: Receiver<String>, mut file: BufWriter<File>) {
+    while let Ok(line) = lines.recv() {
+        let mut res = file.write_all(line.as_bytes());
+        for line in lines.try_iter() {
+            res = res.and_then(|_| file.write_all(line.as_bytes()));
+        }
+
+        if let Err(err) = res.and_then(|_| file.flush()) {
+            tracing::warn!("Failed to write event to recording: {err}");
+        }
+    }
+}
+
+/// A [`RealtimeVoice`] implementation that replays the inbound events of a recording.
+/// Input events sent to the mock are not forwarded anywhere, but are kept so that they can be inspected with [`MockRealtimeVoice::sent_events`].
+#[derive(Debug, Clone)]
+pub struct MockRealtimeVoice {
+    events: Arc<Vec<RecordedEvent>>,
+    realtime: bool,
+    sent: Arc<Mutex<Vec<InputEvent>>>,
+}
+
+impl MockRealtimeVoice {
+    pub fn new(events: Vec<RecordedEvent>) -> Self {
+        Self {
+            events: Arc::new(events),
+            realtime: false,
+            sent: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    /// Load a recording made with a [`Recorder`].
+    pub fn from_file<P>(path: P) -> std::io::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let reader = BufReader::new(File::open(path)?);
+
+        let mut events = Vec::new();
+        for line in reader.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            events.push(serde_json::from_str(&line)?);
+        }
+
+        Ok(Self::new(events))
+    }
+
+    /// Replay events with the same timing as they were recorded with. By default, events are replayed as fast as possible.
+    pub fn with_realtime_playback(mut self) -> Self {
+        self.realtime = true;
+        self
+    }
+
+    /// All input events sent to the mock so far.
+    pub fn sent_events(&self) -> Vec<InputEvent> {
+        self.sent.lock().map(|x| x.clone()).unwrap_or_default()
+    }
+}
+
+impl RealtimeVoice for MockRealtimeVoice {
+    async fn realtime_voice(
+        &self,
+        _req: RealtimeVoiceRequest,
+    ) -> Result<(Sender<InputEvent>, BoxStream<'_, ReceivedEvent>), RealtimeError> {
+        let (tx, mut rx) = mpsc::channel::<InputEvent>(100);
+
+        let sent = Arc::clone(&self.sent);
+        tokio::spawn(async move {
+            while let Some(event) = rx.recv().await {
+                let is_close = matches!(event.data, InputEventKind::Close);
+
+                if let Ok(mut sent) = sent.lock() {
+                    sent.push(event);
+                }
+
+                // Like a real connection, stop receiving once closed so that `shutdown` resolves
+                if is_close {
+                    break;
+                }
+            }
+        });
+
+        let realtime = self.realtime;
+        let mut last_timestamp = 0;
+        let stream = futures::stream::iter(
+            self.events
+                .iter()
+                .filter(|x| x.direction == Direction::Inbound),
+        )
+        .then(move |record| {
+            let delay = record.timestamp_ms.saturating_sub(last_timestamp);
+            last_timestamp = record.timestamp_ms;
+
+            async move {
+                if realtime {
+                    tokio::time::sleep(Duration::from_millis(delay)).await;
+                }
+
+                ReceivedEvent::deserialize(&record.event).unwrap_or_else(|err| {
+                    tracing::warn!("Failed to parse recorded event: {err}");
+                    ReceivedEvent::unknown(record.event.clone())
+                })
+            }
+        })
+        .boxed();
+
+        Ok((tx, stream))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::StreamExt;
+    use serde_json::json;
+
+    use super::{Direction, MockRealtimeVoice, RecordedEvent, Recorder};
+    use crate::providers::openai_realtime::realtime::{
+        InputEvent, RealtimeVoice, RealtimeVoiceRequest, ReceivedEventKind,
+    };
+
+    #[tokio::test]
+    async fn replays_inbound_events() {
+        let mock = MockRealtimeVoice::new(vec![
+            RecordedEvent {
+                timestamp_ms: 0,
+                direction: Direction::Outbound,
+                event: json!({ "type": "input_audio_buffer.commit" }),
+            },
+            RecordedEvent {
+                timestamp_ms: 10,
+                direction: Direction::Inbound,
+                event: json!({ "event_id": "e1", "type": "input_audio_buffer.committed", "item_id": "i1" }),
+            },
+        ]);
+
+        let (sender, stream) = mock
+            .realtime_voice(RealtimeVoiceRequest::new())
+            .await
+            .unwrap();
+        sender.send(InputEvent::commit_audio()).await.unwrap();
+
+        let events = stream.collect::<Vec<_>>().await;
+        assert_eq!(events.len(), 1);
+        assert!(matches!(
+            events[0].data,
+            ReceivedEventKind::InputAudioBuffer(_)
+        ));
+    }
+
+    #[tokio::test]
+    async fn records_events_to_file() {
+        let path =
+            std::env::temp_dir().join(format!("realtime-recording-{}.jsonl", std::process::id()));
+        let recorder = Recorder::create(&path).unwrap();
+        recorder.record(Direction::Outbound, &InputEvent::commit_audio());
+        recorder.record(
+            Direction::Inbound,
+            &json!({ "event_id": "e1", "type": "input_audio_buffer.committed", "item_id": "i1" }),
+        );
+
+        // Lines are written in the background
+        let mut events = Vec::new();
+        for _ in 0..100 {
+            events = std::fs::read_to_string(&path)
+                .unwrap_or_default()
+                .lines()
+                .map(|x| serde_json::from_str::<RecordedEvent>(x).unwrap())
+                .collect();
+            if events.len() == 2 {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].direction, Direction::Outbound);
+        assert_eq!(events[0].event["type"], "input_audio_buffer.commit");
+        assert_eq!(events[1].direction, Direction::Inbound);
+
+        let _ = std::fs::remove_file(&path);
+    }
+}
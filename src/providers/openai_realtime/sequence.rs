@@ -0,0 +1,375 @@
+//! Validation of the order of received events.
+//!
+//! The realtime API follows a strict lifecycle: a response is created, output items are added to it, each content part streams deltas and then a final "done" event, and finally the response is done.
+//! Events that break this lifecycle (ie deltas arriving after a part is done, or events for a response that was never created) are normally just ignored by the trackers in this crate.
+//! [`SequenceValidator`] instead flags them as [`SequenceWarning`]s, which makes protocol issues much easier to debug.
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use futures::{StreamExt, stream::BoxStream};
+
+use super::realtime::{
+    ConnectionEvent, McpEvent, ReceivedEvent, ReceivedEventKind, ReceivedItemEventKind,
+    ResponseEvent,
+};
+
+/// How many finished responses (and server event IDs) are remembered, so that late events can still be flagged.
+const HISTORY_LEN: usize = 256;
+
+/// An event that arrived out of order or could not be matched to the response or item it belongs to.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum SequenceWarning {
+    #[error("Received duplicate event ID {event_id}")]
+    DuplicateEventId { event_id: String },
+    #[error("Response {response_id} was created more than once")]
+    DuplicateResponse { response_id: String },
+    #[error("Received {event_type} for unknown response {response_id}")]
+    UnknownResponse {
+        response_id: String,
+        event_type: &'static str,
+    },
+    #[error("Received {event_type} for response {response_id} after it was done")]
+    ResponseAlreadyDone {
+        response_id: String,
+        event_type: &'static str,
+    },
+    #[error(
+        "Received {event_type} for item {item_id}, which was never added to response {response_id}"
+    )]
+    UnknownItem {
+        response_id: String,
+        item_id: String,
+        event_type: &'static str,
+    },
+    #[error(
+        "Received {event_type} for content part {content_index} of item {item_id} after it was done"
+    )]
+    PartAlreadyDone {
+        item_id: String,
+        content_index: u64,
+        event_type: &'static str,
+    },
+}
+
+/// The kind of streamed data of a content part (or function call). Each has its own delta/done lifecycle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum PartStream {
+    Audio,
+    Text,
+    Transcript,
+    Arguments,
+}
+
+#[derive(Debug, Clone, Default)]
+struct ResponseState {
+    items: HashSet<String>,
+    /// Streams that have received their "done" event, keyed by item ID and content index.
+    finished: HashSet<(String, u64, PartStream)>,
+}
+
+/// Tracks response lifecycles and flags events that arrive out of order.
+///
+/// The validator should observe every event from the start of a session, otherwise events for responses that were created before it was attached will be flagged as unknown.
+/// In-flight responses are forgotten when the connection is re-established, as the server does not resume them.
+#[derive(Debug, Clone, Default)]
+pub struct SequenceValidator {
+    responses: HashMap<String, ResponseState>,
+    done: VecDeque<String>,
+    event_ids: VecDeque<String>,
+}
+
+impl SequenceValidator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Wrap an event stream so that every event is validated before being passed on.
+    /// `on_warning` is called for every warning, ie to log it or collect it for a test assertion.
+    pub fn validate<'a, F>(
+        events: BoxStream<'a, ReceivedEvent>,
+        mut on_warning: F,
+    ) -> BoxStream<'a, ReceivedEvent>
+    where
+        F: FnMut(SequenceWarning) + Send + 'a,
+    {
+        let mut validator = Self::new();
+
+        events
+            .inspect(move |evt| {
+                for warning in validator.observe(evt) {
+                    on_warning(warning);
+                }
+            })
+            .boxed()
+    }
+
+    /// Observe an event, returning any warnings about its place in the sequence.
+    pub fn observe(&mut self, evt: &ReceivedEvent) -> Vec<SequenceWarning> {
+        let mut warnings = Vec::new();
+
+        if let Some(event_id) = evt.event_id() {
+            if self.event_ids.iter().any(|x| x == event_id) {
+                warnings.push(SequenceWarning::DuplicateEventId {
+                    event_id: event_id.to_string(),
+                });
+            } else {
+                remember(&mut self.event_ids, event_id);
+            }
+        }
+
+        match &evt.data {
+            ReceivedEventKind::Response(ResponseEvent::ResponseCreated { response }) => {
+                if self.responses.contains_key(&response.id) || self.is_done(&response.id) {
+                    warnings.push(SequenceWarning::DuplicateResponse {
+                        response_id: response.id.clone(),
+                    });
+                } else {
+                    self.responses
+                        .insert(response.id.clone(), ResponseState::default());
+                }
+            }
+            ReceivedEventKind::Response(ResponseEvent::ResponseDone { response }) => {
+                match self.responses.remove(&response.id) {
+                    Some(_) => remember(&mut self.done, &response.id),
+                    None => warnings.push(self.missing_response(&response.id, "response.done")),
+                }
+            }
+            ReceivedEventKind::Response(ResponseEvent::OutputItemAdded {
+                response_id,
+                item,
+                ..
+            }) => match self.responses.get_mut(response_id) {
+                Some(state) => {
+                    if let Some(item_id) = item.id() {
+                        state.items.insert(item_id.to_string());
+                    }
+                }
+                None => {
+                    warnings.push(self.missing_response(response_id, "response.output_item.added"))
+                }
+            },
+            ReceivedEventKind::Item {
+                item_id,
+                response_id,
+                content_index,
+                data,
+                ..
+            } => {
+                let (stream, is_done, event_type) = match data {
+                    ReceivedItemEventKind::AudioDelta { .. } => {
+                        (PartStream::Audio, false, "response.audio.delta")
+                    }
+                    ReceivedItemEventKind::AudioDone => {
+                        (PartStream::Audio, true, "response.audio.done")
+                    }
+                    ReceivedItemEventKind::TextDelta { .. } => {
+                        (PartStream::Text, false, "response.text.delta")
+                    }
+                    ReceivedItemEventKind::TextDone { .. } => {
+                        (PartStream::Text, true, "response.text.done")
+                    }
+                    ReceivedItemEventKind::AudioTranscriptDelta { .. } => (
+                        PartStream::Transcript,
+                        false,
+                        "response.audio_transcript.delta",
+                    ),
+                    ReceivedItemEventKind::AudioTranscriptDone { .. } => (
+                        PartStream::Transcript,
+                        true,
+                        "response.audio_transcript.done",
+                    ),
+                };
+
+                warnings.extend(self.check_stream(
+                    response_id,
+                    item_id,
+                    *content_index,
+                    stream,
+                    is_done,
+                    event_type,
+                ));
+            }
+            ReceivedEventKind::Response(ResponseEvent::FunctionCallArgumentsDelta {
+                response_id,
+                item_id,
+                ..
+            }) => warnings.extend(self.check_stream(
+                response_id,
+                item_id,
+                0,
+                PartStream::Arguments,
+                false,
+                "response.function_call_arguments.delta",
+            )),
+            ReceivedEventKind::Response(ResponseEvent::FunctionCallArgumentsDone {
+                response_id,
+                item_id,
+                ..
+            }) => warnings.extend(self.check_stream(
+                response_id,
+                item_id,
+                0,
+                PartStream::Arguments,
+                true,
+                "response.function_call_arguments.done",
+            )),
+            ReceivedEventKind::Mcp(McpEvent::CallArgumentsDelta {
+                response_id,
+                item_id,
+                ..
+            }) => warnings.extend(self.check_stream(
+                response_id,
+                item_id,
+                0,
+                PartStream::Arguments,
+                false,
+                "response.mcp_call_arguments.delta",
+            )),
+            ReceivedEventKind::Mcp(McpEvent::CallArgumentsDone {
+                response_id,
+                item_id,
+                ..
+            }) => warnings.extend(self.check_stream(
+                response_id,
+                item_id,
+                0,
+                PartStream::Arguments,
+                true,
+                "response.mcp_call_arguments.done",
+            )),
+            ReceivedEventKind::Connection(ConnectionEvent::Reconnected { .. }) => {
+                self.responses.clear();
+            }
+            _ => {}
+        }
+
+        warnings
+    }
+
+    fn is_done(&self, response_id: &str) -> bool {
+        self.done.iter().any(|x| x == response_id)
+    }
+
+    fn missing_response(&self, response_id: &str, event_type: &'static str) -> SequenceWarning {
+        let response_id = response_id.to_string();
+
+        if self.is_done(&response_id) {
+            SequenceWarning::ResponseAlreadyDone {
+                response_id,
+                event_type,
+            }
+        } else {
+            SequenceWarning::UnknownResponse {
+                response_id,
+                event_type,
+            }
+        }
+    }
+
+    fn check_stream(
+        &mut self,
+        response_id: &str,
+        item_id: &str,
+        content_index: u64,
+        stream: PartStream,
+        is_done: bool,
+        event_type: &'static str,
+    ) -> Option<SequenceWarning> {
+        let Some(state) = self.responses.get_mut(response_id) else {
+            return Some(self.missing_response(response_id, event_type));
+        };
+
+        if !state.items.contains(item_id) {
+            return Some(SequenceWarning::UnknownItem {
+                response_id: response_id.to_string(),
+                item_id: item_id.to_string(),
+                event_type,
+            });
+        }
+
+        let key = (item_id.to_string(), content_index, stream);
+        if state.finished.contains(&key) {
+            return Some(SequenceWarning::PartAlreadyDone {
+                item_id: item_id.to_string(),
+                content_index,
+                event_type,
+            });
+        }
+
+        if is_done {
+            state.finished.insert(key);
+        }
+
+        None
+    }
+}
+
+/// Pushes a value onto a bounded history, dropping the oldest value if it's full.
+fn remember(history: &mut VecDeque<String>, value: &str) {
+    if history.len() == HISTORY_LEN {
+        history.pop_front();
+    }
+    history.push_back(value.to_string());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SequenceValidator, SequenceWarning};
+    use crate::providers::openai_realtime::realtime::ReceivedEvent;
+
+    fn observe(validator: &mut SequenceValidator, json: &str) -> Vec<SequenceWarning> {
+        validator.observe(&serde_json::from_str::<ReceivedEvent>(json).unwrap())
+    }
+
+    #[test]
+    fn flags_out_of_order_events() {
+        let mut validator = SequenceValidator::new();
+
+        let created = r#"{"event_id":"e1","type":"response.created","response":{"id":"r1","status":"in_progress","output":[]}}"#;
+        let added = r#"{"event_id":"e2","type":"response.output_item.added","response_id":"r1","output_index":0,"item":{"id":"i1","type":"message","role":"assistant","content":[]}}"#;
+        let done = r#"{"event_id":"e4","type":"response.audio.done","response_id":"r1","item_id":"i1","output_index":0,"content_index":0}"#;
+
+        assert!(observe(&mut validator, created).is_empty());
+        assert!(observe(&mut validator, added).is_empty());
+        assert!(observe(&mut validator, r#"{"event_id":"e3","type":"response.audio.delta","response_id":"r1","item_id":"i1","output_index":0,"content_index":0,"delta":"AAAA"}"#).is_empty());
+        assert!(observe(&mut validator, done).is_empty());
+
+        assert_eq!(
+            observe(
+                &mut validator,
+                r#"{"event_id":"e5","type":"response.audio.delta","response_id":"r1","item_id":"i1","output_index":0,"content_index":0,"delta":"AAAA"}"#
+            ),
+            vec![SequenceWarning::PartAlreadyDone {
+                item_id: "i1".into(),
+                content_index: 0,
+                event_type: "response.audio.delta",
+            }]
+        );
+
+        assert_eq!(
+            observe(
+                &mut validator,
+                r#"{"event_id":"e6","type":"response.text.delta","response_id":"r1","item_id":"i2","output_index":1,"content_index":0,"delta":"Hi"}"#
+            ),
+            vec![SequenceWarning::UnknownItem {
+                response_id: "r1".into(),
+                item_id: "i2".into(),
+                event_type: "response.text.delta",
+            }]
+        );
+
+        assert!(observe(&mut validator, r#"{"event_id":"e7","type":"response.done","response":{"id":"r1","status":"completed","output":[]}}"#).is_empty());
+
+        assert_eq!(
+            observe(&mut validator, done),
+            vec![
+                SequenceWarning::DuplicateEventId {
+                    event_id: "e4".into()
+                },
+                SequenceWarning::ResponseAlreadyDone {
+                    response_id: "r1".into(),
+                    event_type: "response.audio.done",
+                },
+            ]
+        );
+    }
+}
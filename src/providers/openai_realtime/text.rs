@@ -0,0 +1,336 @@
+//! Text conversations over a realtime connection.
+//!
+//! This allows one connection to serve both voice turns and fast text turns:
+//! wrap the sender/stream pair returned by [`RealtimeVoice::realtime_voice`](super::realtime::RealtimeVoice::realtime_voice), then use [`RealtimeTextChat::stream`] or [`RealtimeTextChat::prompt`] for text turns.
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures::{Stream, StreamExt, stream::BoxStream};
+use rig::{
+    OneOrMany,
+    completion::{CompletionError, CompletionResponse},
+    message::AssistantContent,
+    streaming::RawStreamingChoice,
+};
+use tokio::sync::mpsc::Sender;
+
+use super::realtime::{
+    ErrorEvent, InputEvent, Modality, RealtimeError, RealtimeResponse, ReceivedEvent,
+    ReceivedEventKind, ReceivedItemEventKind, ResponseConfig, ResponseEvent,
+};
+
+/// The response metadata key used to tag the responses of text turns.
+const TEXT_TURN_METADATA_KEY: &str = "rig_text_turn";
+
+/// A text chat helper over a realtime connection.
+pub struct RealtimeTextChat<'a> {
+    sender: Sender<InputEvent>,
+    stream: BoxStream<'a, ReceivedEvent>,
+    turn: u64,
+}
+
+impl<'a> RealtimeTextChat<'a> {
+    pub fn new(sender: Sender<InputEvent>, stream: BoxStream<'a, ReceivedEvent>) -> Self {
+        Self {
+            sender,
+            stream,
+            turn: 0,
+        }
+    }
+
+    /// The input event sender. Can be used to send audio between text turns.
+    pub fn sender(&self) -> &Sender<InputEvent> {
+        &self.sender
+    }
+
+    /// Return the underlying sender/stream pair.
+    pub fn into_inner(self) -> (Sender<InputEvent>, BoxStream<'a, ReceivedEvent>) {
+        (self.sender, self.stream)
+    }
+
+    /// Send a user text message and stream back the (text-only) response.
+    /// The stream ends when the response is done. Events not belonging to the response are discarded.
+    ///
+    /// The response is tagged with metadata, so that responses created by other requests on the same connection are not mistaken for it.
+    /// Likewise, only errors caused by the events sent for this turn end the stream.
+    pub async fn stream(&mut self, text: &str) -> Result<RealtimeTextStream<'_>, CompletionError> {
+        self.turn += 1;
+        let tag = self.turn.to_string();
+        let event_ids = [
+            format!("rig_text_{tag}_message"),
+            format!("rig_text_{tag}_response"),
+        ];
+
+        self.sender
+            .send(InputEvent::user_message(text).with_id(&event_ids[0]))
+            .await
+            .map_err(|_| completion_error(RealtimeError::Closed))?;
+
+        self.sender
+            .send(
+                InputEvent::create_response_with(
+                    ResponseConfig::new()
+                        .modalities(vec![Modality::Text])
+                        .metadata(TEXT_TURN_METADATA_KEY, &tag),
+                )
+                .with_id(&event_ids[1]),
+            )
+            .await
+            .map_err(|_| completion_error(RealtimeError::Closed))?;
+
+        let state = Some((&mut self.stream, tag, event_ids, None::<String>));
+
+        let stream = futures::stream::unfold(state, |state| async move {
+            let (stream, tag, event_ids, mut response_id) = state?;
+
+            loop {
+                let Some(evt) = stream.next().await else {
+                    return Some((Err(completion_error(RealtimeError::Closed)), None));
+                };
+
+                match evt.data {
+                    ReceivedEventKind::Response(ResponseEvent::ResponseCreated { response })
+                        if response_id.is_none()
+                            && response
+                                .metadata
+                                .as_ref()
+                                .and_then(|metadata| metadata.get(TEXT_TURN_METADATA_KEY))
+                                == Some(&tag) =>
+                    {
+                        response_id = Some(response.id);
+                    }
+                    ReceivedEventKind::Item {
+                        response_id: id,
+                        data: ReceivedItemEventKind::TextDelta { delta },
+                        ..
+                    } if response_id.as_ref() == Some(&id) => {
+                        return Some((
+                            Ok(RawStreamingChoice::Message(delta)),
+                            Some((stream, tag, event_ids, response_id)),
+                        ));
+                    }
+                    ReceivedEventKind::Response(ResponseEvent::ResponseDone { response })
+                        if response_id.as_ref() == Some(&response.id) =>
+                    {
+                        return Some((Ok(RawStreamingChoice::FinalResponse(response)), None));
+                    }
+                    ReceivedEventKind::Error(ErrorEvent::Error { error })
+                        if error
+                            .event_id
+                            .as_ref()
+                            .is_some_and(|id| event_ids.contains(id)) =>
+                    {
+                        return Some((Err(completion_error(RealtimeError::Api(error))), None));
+                    }
+                    _ => {}
+                }
+            }
+        })
+        .boxed();
+
+        Ok(RealtimeTextStream::new(stream))
+    }
+
+    /// Send a user text message and wait for the full text response.
+    pub async fn prompt(&mut self, text: &str) -> Result<String, CompletionError> {
+        let mut stream = self.stream(text).await?;
+        while let Some(content) = stream.next().await {
+            content?;
+        }
+
+        Ok(stream.text)
+    }
+}
+
+fn completion_error(err: RealtimeError) -> CompletionError {
+    CompletionError::ProviderError(err.to_string())
+}
+
+/// A streamed text response, which works like Rig's [`StreamingCompletionResponse`](rig::streaming::StreamingCompletionResponse).
+/// Text is yielded as it arrives. Once the stream has ended, `choice` holds the full message and `response` holds the finished realtime response.
+pub struct RealtimeTextStream<'a> {
+    inner: BoxStream<'a, Result<RawStreamingChoice<RealtimeResponse>, CompletionError>>,
+    text: String,
+    /// The final aggregated message from the stream.
+    pub choice: OneOrMany<AssistantContent>,
+    /// The finished realtime response. `None` until the stream has ended.
+    pub response: Option<RealtimeResponse>,
+}
+
+impl<'a> RealtimeTextStream<'a> {
+    fn new(
+        inner: BoxStream<'a, Result<RawStreamingChoice<RealtimeResponse>, CompletionError>>,
+    ) -> Self {
+        Self {
+            inner,
+            text: String::new(),
+            choice: OneOrMany::one(AssistantContent::text("")),
+            response: None,
+        }
+    }
+}
+
+impl Stream for RealtimeTextStream<'_> {
+    type Item = Result<AssistantContent, CompletionError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let stream = self.get_mut();
+
+        loop {
+            return match stream.inner.poll_next_unpin(cx) {
+                Poll::Pending => Poll::Pending,
+                Poll::Ready(None) => {
+                    stream.choice = OneOrMany::one(AssistantContent::text(stream.text.clone()));
+                    Poll::Ready(None)
+                }
+                Poll::Ready(Some(Err(err))) => Poll::Ready(Some(Err(err))),
+                Poll::Ready(Some(Ok(RawStreamingChoice::Message(text)))) => {
+                    stream.text.push_str(&text);
+                    Poll::Ready(Some(Ok(AssistantContent::text(text))))
+                }
+                Poll::Ready(Some(Ok(RawStreamingChoice::FinalResponse(response)))) => {
+                    stream.response = Some(response);
+                    continue;
+                }
+                // Text turns never make tool calls
+                Poll::Ready(Some(Ok(RawStreamingChoice::ToolCall { .. }))) => continue,
+            };
+        }
+    }
+}
+
+impl From<RealtimeTextStream<'_>> for CompletionResponse<Option<RealtimeResponse>> {
+    fn from(value: RealtimeTextStream<'_>) -> Self {
+        CompletionResponse {
+            choice: value.choice,
+            raw_response: value.response,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::StreamExt;
+
+    use super::*;
+
+    fn event(value: serde_json::Value) -> ReceivedEvent {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[tokio::test]
+    async fn streams_only_its_own_response() {
+        let (sender, mut receiver) = tokio::sync::mpsc::channel(8);
+        let events = vec![
+            event(serde_json::json!({
+                "event_id": "e1",
+                "type": "response.created",
+                "response": {"object": "realtime.response", "id": "resp_other", "status": "in_progress", "metadata": {"topic": "moderation"}}
+            })),
+            event(serde_json::json!({
+                "event_id": "e2",
+                "type": "response.text.delta",
+                "response_id": "resp_other",
+                "item_id": "item_other",
+                "output_index": 0,
+                "content_index": 0,
+                "delta": "Other"
+            })),
+            event(serde_json::json!({
+                "event_id": "e3",
+                "type": "response.created",
+                "response": {"object": "realtime.response", "id": "resp_ours", "status": "in_progress", "metadata": {TEXT_TURN_METADATA_KEY: "1"}}
+            })),
+            event(serde_json::json!({
+                "event_id": "e4",
+                "type": "response.text.delta",
+                "response_id": "resp_ours",
+                "item_id": "item_ours",
+                "output_index": 0,
+                "content_index": 0,
+                "delta": "Hello"
+            })),
+            event(serde_json::json!({
+                "event_id": "e5",
+                "type": "response.done",
+                "response": {"object": "realtime.response", "id": "resp_ours", "status": "completed", "metadata": {TEXT_TURN_METADATA_KEY: "1"}}
+            })),
+        ];
+
+        let mut chat = RealtimeTextChat::new(sender, futures::stream::iter(events).boxed());
+        let mut stream = chat.stream("Hi").await.unwrap();
+        let mut chunks = Vec::new();
+        while let Some(content) = stream.next().await {
+            chunks.push(content.unwrap());
+        }
+        assert!(
+            matches!(chunks.as_slice(), [AssistantContent::Text(text)] if text.text == "Hello")
+        );
+        assert!(
+            matches!(stream.choice.first(), AssistantContent::Text(text) if text.text == "Hello")
+        );
+        assert_eq!(stream.response.unwrap().id, "resp_ours");
+
+        // The user message, then the tagged response request.
+        assert!(receiver.recv().await.is_some());
+        let create = serde_json::to_value(receiver.recv().await.unwrap()).unwrap();
+        assert_eq!(create["response"]["metadata"][TEXT_TURN_METADATA_KEY], "1");
+    }
+
+    fn error(event_id: Option<&str>) -> ReceivedEvent {
+        event(serde_json::json!({
+            "event_id": "e_error",
+            "type": "error",
+            "error": {"type": "invalid_request_error", "code": null, "message": "Bad", "param": null, "event_id": event_id}
+        }))
+    }
+
+    fn response_events() -> Vec<ReceivedEvent> {
+        vec![
+            event(serde_json::json!({
+                "event_id": "e1",
+                "type": "response.created",
+                "response": {"object": "realtime.response", "id": "resp_ours", "status": "in_progress", "metadata": {TEXT_TURN_METADATA_KEY: "1"}}
+            })),
+            event(serde_json::json!({
+                "event_id": "e2",
+                "type": "response.text.delta",
+                "response_id": "resp_ours",
+                "item_id": "item_ours",
+                "output_index": 0,
+                "content_index": 0,
+                "delta": "Hello"
+            })),
+            event(serde_json::json!({
+                "event_id": "e3",
+                "type": "response.done",
+                "response": {"object": "realtime.response", "id": "resp_ours", "status": "completed", "metadata": {TEXT_TURN_METADATA_KEY: "1"}}
+            })),
+        ]
+    }
+
+    #[tokio::test]
+    async fn ignores_errors_from_other_events() {
+        let (sender, _receiver) = tokio::sync::mpsc::channel(8);
+        let mut events = vec![error(Some("voice_append")), error(None)];
+        events.extend(response_events());
+
+        let mut chat = RealtimeTextChat::new(sender, futures::stream::iter(events).boxed());
+        assert_eq!(chat.prompt("Hi").await.unwrap(), "Hello");
+    }
+
+    #[tokio::test]
+    async fn ends_on_errors_from_its_own_events() {
+        let (sender, _receiver) = tokio::sync::mpsc::channel(8);
+        let mut events = vec![error(Some("rig_text_1_response"))];
+        events.extend(response_events());
+
+        let mut chat = RealtimeTextChat::new(sender, futures::stream::iter(events).boxed());
+        assert!(matches!(
+            chat.prompt("Hi").await,
+            Err(CompletionError::ProviderError(_))
+        ));
+    }
+}
@@ -0,0 +1,345 @@
+//! Assembly of transcripts from realtime transcript deltas.
+//!
+//! The transcript of the model's audio output is sent as a series of deltas, potentially spread across multiple items in a response.
+//! [`TranscriptAssembler`] accumulates these and produces a [`TranscriptReady`] once the response is done,
+//! so applications can log or display what the assistant said without re-transcribing its audio.
+//!
+//! Similarly, [`UserCaptions`] turns user input transcription events into live captions of what the user said.
+use std::collections::HashMap;
+
+use futures::{StreamExt, stream::BoxStream};
+use serde::{Deserialize, Serialize};
+
+use super::realtime::{
+    ReceivedEvent, ReceivedEventKind, ReceivedItemEventKind, ResponseEvent, TranscriptionEvent,
+};
+
+/// The full transcript of the audio output of a response.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct TranscriptReady {
+    pub response_id: String,
+    pub text: String,
+}
+
+/// Accumulates assistant audio transcript deltas per response.
+#[derive(Debug, Clone, Default)]
+pub struct TranscriptAssembler {
+    /// In-progress transcripts, keyed by response ID. Each content part of each item has its own transcript.
+    responses: HashMap<String, Vec<PartTranscript>>,
+}
+
+#[derive(Debug, Clone)]
+struct PartTranscript {
+    item_id: String,
+    output_index: u64,
+    content_index: u64,
+    text: String,
+    /// Whether the final transcript of this part has been received.
+    done: bool,
+}
+
+impl TranscriptAssembler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Turn a received event stream into a stream of completed transcripts. Responses without any audio are skipped.
+    pub fn stream<'a>(events: BoxStream<'a, ReceivedEvent>) -> BoxStream<'a, TranscriptReady> {
+        let mut assembler = Self::new();
+
+        events
+            .filter_map(move |evt| futures::future::ready(assembler.observe(&evt)))
+            .boxed()
+    }
+
+    /// Observe an event, returning the full transcript of a response once it's done.
+    pub fn observe(&mut self, evt: &ReceivedEvent) -> Option<TranscriptReady> {
+        match &evt.data {
+            ReceivedEventKind::Item {
+                item_id,
+                response_id,
+                output_index,
+                content_index,
+                data,
+            } => {
+                let (text, replace) = match data {
+                    ReceivedItemEventKind::AudioTranscriptDelta { delta } => (delta, false),
+                    // The final transcript is authoritative, so it replaces the accumulated deltas
+                    ReceivedItemEventKind::AudioTranscriptDone { transcript } => (transcript, true),
+                    _ => return None,
+                };
+
+                let parts = self.responses.entry(response_id.clone()).or_default();
+                let part = match parts
+                    .iter_mut()
+                    .position(|x| x.item_id == *item_id && x.content_index == *content_index)
+                {
+                    Some(idx) => &mut parts[idx],
+                    None => {
+                        parts.push(PartTranscript {
+                            item_id: item_id.clone(),
+                            output_index: *output_index,
+                            content_index: *content_index,
+                            text: String::new(),
+                            done: false,
+                        });
+                        parts.last_mut()?
+                    }
+                };
+
+                if replace {
+                    part.text = text.clone();
+                    part.done = true;
+                } else if !part.done {
+                    // Deltas arriving after the final transcript are already part of it
+                    part.text.push_str(text);
+                }
+
+                None
+            }
+            ReceivedEventKind::Response(ResponseEvent::ResponseDone { response }) => {
+                let mut parts = self.responses.remove(&response.id)?;
+                // Deltas for different items can be interleaved, so order the parts by their position in the response
+                parts.sort_by_key(|x| (x.output_index, x.content_index));
+
+                let text = parts
+                    .into_iter()
+                    .map(|x| x.text)
+                    .filter(|x| !x.is_empty())
+                    .collect::<Vec<_>>()
+                    .join(" ");
+
+                (!text.is_empty()).then(|| TranscriptReady {
+                    response_id: response.id.clone(),
+                    text,
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+/// A live caption of a user audio item.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Caption {
+    pub item_id: String,
+    /// The transcript so far.
+    pub text: String,
+    /// Whether this is the final transcript of the item.
+    pub is_final: bool,
+}
+
+/// Builds live captions from user input transcription events.
+/// Requires `input_audio_transcription` to be set on the session.
+#[derive(Debug, Clone, Default)]
+pub struct UserCaptions {
+    in_progress: HashMap<String, String>,
+}
+
+impl UserCaptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Turn a received event stream into a stream of caption updates.
+    pub fn stream<'a>(events: BoxStream<'a, ReceivedEvent>) -> BoxStream<'a, Caption> {
+        let mut captions = Self::new();
+
+        events
+            .filter_map(move |evt| futures::future::ready(captions.observe(&evt)))
+            .boxed()
+    }
+
+    /// Observe an event, returning the updated caption of a user audio item if the event is a transcription event.
+    /// Failed transcriptions are logged and produce a final caption with whatever was transcribed so far.
+    pub fn observe(&mut self, evt: &ReceivedEvent) -> Option<Caption> {
+        let ReceivedEventKind::Transcription(evt) = &evt.data else {
+            return None;
+        };
+
+        match evt {
+            TranscriptionEvent::Delta { item_id, delta, .. } => {
+                let text = self.in_progress.entry(item_id.clone()).or_default();
+                text.push_str(delta);
+
+                Some(Caption {
+                    item_id: item_id.clone(),
+                    text: text.clone(),
+                    is_final: false,
+                })
+            }
+            TranscriptionEvent::Completed {
+                item_id,
+                transcript,
+                ..
+            } => {
+                self.in_progress.remove(item_id);
+
+                Some(Caption {
+                    item_id: item_id.clone(),
+                    text: transcript.clone(),
+                    is_final: true,
+                })
+            }
+            TranscriptionEvent::Failed { item_id, error, .. } => {
+                tracing::warn!("Failed to transcribe user audio item {item_id}: {error}");
+
+                Some(Caption {
+                    item_id: item_id.clone(),
+                    text: self.in_progress.remove(item_id).unwrap_or_default(),
+                    is_final: true,
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{Caption, TranscriptAssembler, TranscriptReady, UserCaptions};
+    use crate::providers::openai_realtime::realtime::ReceivedEvent;
+
+    fn event(value: serde_json::Value) -> ReceivedEvent {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn transcript_delta(
+        response_id: &str,
+        item_id: &str,
+        output_index: u64,
+        delta: &str,
+    ) -> ReceivedEvent {
+        event(json!({
+            "event_id": "e",
+            "type": "response.audio_transcript.delta",
+            "response_id": response_id,
+            "item_id": item_id,
+            "output_index": output_index,
+            "content_index": 0,
+            "delta": delta
+        }))
+    }
+
+    fn transcript_done(
+        response_id: &str,
+        item_id: &str,
+        output_index: u64,
+        transcript: &str,
+    ) -> ReceivedEvent {
+        event(json!({
+            "event_id": "e",
+            "type": "response.audio_transcript.done",
+            "response_id": response_id,
+            "item_id": item_id,
+            "output_index": output_index,
+            "content_index": 0,
+            "transcript": transcript
+        }))
+    }
+
+    fn response_done(response_id: &str) -> ReceivedEvent {
+        event(json!({
+            "event_id": "e",
+            "type": "response.done",
+            "response": { "id": response_id, "status": "completed", "output": [] }
+        }))
+    }
+
+    #[test]
+    fn assembles_interleaved_transcripts() {
+        let mut assembler = TranscriptAssembler::new();
+        let events = [
+            transcript_delta("r1", "i2", 1, "World"),
+            transcript_delta("r1", "i1", 0, "Hel"),
+            transcript_delta("r2", "i3", 0, "Other"),
+            transcript_delta("r1", "i1", 0, "lo"),
+        ];
+        for evt in &events {
+            assert_eq!(assembler.observe(evt), None);
+        }
+
+        assert_eq!(
+            assembler.observe(&response_done("r1")),
+            Some(TranscriptReady {
+                response_id: "r1".to_string(),
+                text: "Hello World".to_string()
+            })
+        );
+        // Responses without any transcript are skipped
+        assert_eq!(assembler.observe(&response_done("r3")), None);
+    }
+
+    #[test]
+    fn final_transcript_replaces_deltas() {
+        let mut assembler = TranscriptAssembler::new();
+        assembler.observe(&transcript_delta("r1", "i1", 0, "Helo"));
+        assembler.observe(&transcript_done("r1", "i1", 0, "Hello"));
+        // A late delta is already included in the final transcript
+        assembler.observe(&transcript_delta("r1", "i1", 0, "lo"));
+
+        assert_eq!(
+            assembler.observe(&response_done("r1")).unwrap().text,
+            "Hello"
+        );
+    }
+
+    #[test]
+    fn captions_user_audio() {
+        let mut captions = UserCaptions::new();
+        let delta = |item_id: &str, delta: &str| {
+            event(json!({
+                "event_id": "e",
+                "type": "conversation.item.input_audio_transcription.delta",
+                "item_id": item_id,
+                "content_index": 0,
+                "delta": delta
+            }))
+        };
+
+        captions.observe(&delta("i1", "Hi "));
+        captions.observe(&delta("i2", "Hey"));
+        assert_eq!(
+            captions.observe(&delta("i1", "there")),
+            Some(Caption {
+                item_id: "i1".to_string(),
+                text: "Hi there".to_string(),
+                is_final: false
+            })
+        );
+
+        let completed = event(json!({
+            "event_id": "e",
+            "type": "conversation.item.input_audio_transcription.completed",
+            "item_id": "i1",
+            "content_index": 0,
+            "transcript": "Hi there!"
+        }));
+        assert_eq!(
+            captions.observe(&completed),
+            Some(Caption {
+                item_id: "i1".to_string(),
+                text: "Hi there!".to_string(),
+                is_final: true
+            })
+        );
+
+        // A failed transcription finalizes the caption with what was transcribed so far
+        let failed = event(json!({
+            "event_id": "e",
+            "type": "conversation.item.input_audio_transcription.failed",
+            "item_id": "i2",
+            "content_index": 0,
+            "error": { "type": "transcription_error", "code": null, "message": "Bad audio", "param": null }
+        }));
+        assert_eq!(
+            captions.observe(&failed),
+            Some(Caption {
+                item_id: "i2".to_string(),
+                text: "Hey".to_string(),
+                is_final: true
+            })
+        );
+    }
+}
@@ -0,0 +1,459 @@
+//! A bridge between Twilio Media Streams and a realtime session.
+//!
+//! Twilio sends the audio of a phone call as 8kHz µ-law frames over a websocket, and plays back any audio sent the other way.
+//! [`TwilioBridge`] forwards caller audio to OpenAI and assistant audio to Twilio (transcoding if the session isn't using µ-law),
+//! and handles barge-in by clearing Twilio's playback buffer and truncating the assistant's audio to what the caller actually heard.
+//!
+//! The bridge is independent of the websocket server used to accept Twilio's connection:
+//! pass it a stream of the text messages received from Twilio, and a channel for the messages that should be sent back.
+use std::collections::VecDeque;
+
+use base64::{Engine, prelude::BASE64_STANDARD};
+use futures::{Stream, StreamExt, stream::BoxStream};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::Sender;
+
+use super::audio::{alaw_to_linear, linear_to_alaw, linear_to_ulaw, ulaw_to_linear};
+use super::realtime::{
+    AudioFormat, ConnectionEvent, InputAudioBufferEvent, InputEvent, RealtimeError,
+    RealtimeSenderExt, ReceivedEvent, ReceivedEventKind, ReceivedItemEventKind, Session,
+};
+
+/// A message received from Twilio.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "event", rename_all = "lowercase")]
+pub enum TwilioMessage {
+    Connected,
+    Start {
+        #[serde(rename = "streamSid")]
+        stream_sid: String,
+    },
+    Media {
+        media: TwilioMedia,
+    },
+    /// A mark sent to Twilio has been reached in playback.
+    Mark {
+        mark: TwilioMark,
+    },
+    Stop,
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TwilioMedia {
+    /// Milliseconds since the start of the stream. Sent as a string by Twilio.
+    #[serde(default)]
+    pub timestamp: Option<String>,
+    /// Base64-encoded 8kHz µ-law audio.
+    pub payload: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TwilioMark {
+    pub name: String,
+}
+
+/// A message sent to Twilio.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "lowercase")]
+pub enum TwilioCommand {
+    /// Queue audio for playback.
+    Media {
+        #[serde(rename = "streamSid")]
+        stream_sid: String,
+        media: TwilioOutboundMedia,
+    },
+    /// Ask Twilio to send back a mark message once all audio queued before it has been played.
+    Mark {
+        #[serde(rename = "streamSid")]
+        stream_sid: String,
+        mark: TwilioMark,
+    },
+    /// Drop all queued audio.
+    Clear {
+        #[serde(rename = "streamSid")]
+        stream_sid: String,
+    },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TwilioOutboundMedia {
+    pub payload: String,
+}
+
+/// Bridges a Twilio Media Stream to a realtime session.
+#[derive(Debug, Clone, Copy)]
+pub struct TwilioBridge {
+    format: AudioFormat,
+}
+
+impl Default for TwilioBridge {
+    fn default() -> Self {
+        Self {
+            format: AudioFormat::G711Ulaw,
+        }
+    }
+}
+
+impl TwilioBridge {
+    /// Create a bridge for a session using µ-law audio, which avoids any transcoding.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the audio format used by the realtime session. Audio is transcoded between this and Twilio's 8kHz µ-law.
+    pub fn with_format(mut self, format: AudioFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Set the input and output audio formats of a session to the format used by this bridge.
+    pub fn configure_session(&self, session: Session) -> Session {
+        session
+            .input_audio_format(self.format)
+            .output_audio_format(self.format)
+    }
+
+    /// Run the bridge until either side closes.
+    /// When the Twilio stream ends, the realtime session is shut down.
+    pub async fn run<S>(
+        self,
+        mut twilio_in: S,
+        twilio_out: Sender<String>,
+        sender: Sender<InputEvent>,
+        mut events: BoxStream<'_, ReceivedEvent>,
+    ) -> Result<(), RealtimeError>
+    where
+        S: Stream<Item = String> + Unpin,
+    {
+        let mut state = BridgeState::default();
+
+        loop {
+            tokio::select! {
+                message = twilio_in.next() => {
+                    let Some(message) = message else {
+                        sender.shutdown().await;
+                        return Ok(());
+                    };
+
+                    let message = match serde_json::from_str::<TwilioMessage>(&message) {
+                        Ok(message) => message,
+                        Err(err) => {
+                            tracing::warn!("Failed to parse Twilio message: {err}");
+                            continue;
+                        }
+                    };
+
+                    match message {
+                        TwilioMessage::Start { stream_sid } => {
+                            state = BridgeState {
+                                stream_sid: Some(stream_sid),
+                                ..Default::default()
+                            };
+                        }
+                        TwilioMessage::Media { media } => {
+                            if let Some(timestamp) = media.timestamp.and_then(|x| x.parse().ok()) {
+                                state.latest_media_timestamp = timestamp;
+                            }
+
+                            let Some(audio) = self.decode_twilio(&media.payload) else {
+                                continue;
+                            };
+
+                            sender
+                                .send(InputEvent::append_audio(&audio))
+                                .await
+                                .map_err(|_| RealtimeError::Closed)?;
+                        }
+                        TwilioMessage::Mark { .. } => state.mark_reached(),
+                        TwilioMessage::Stop => {
+                            sender.shutdown().await;
+                            return Ok(());
+                        }
+                        TwilioMessage::Connected | TwilioMessage::Other => {}
+                    }
+                }
+                evt = events.next() => {
+                    let Some(evt) = evt else {
+                        return Ok(());
+                    };
+
+                    match evt.data {
+                        ReceivedEventKind::Item {
+                            item_id,
+                            data: ReceivedItemEventKind::AudioDelta { delta },
+                            ..
+                        } => {
+                            let Some(stream_sid) = state.stream_sid.clone() else {
+                                continue;
+                            };
+                            let Some(payload) = self.encode_twilio(&delta) else {
+                                continue;
+                            };
+
+                            send_command(&twilio_out, TwilioCommand::Media {
+                                stream_sid: stream_sid.clone(),
+                                media: TwilioOutboundMedia { payload },
+                            }).await?;
+
+                            // Marks let us know when Twilio has finished playing the audio sent so far
+                            let mark = state.audio_sent(&item_id);
+                            send_command(&twilio_out, TwilioCommand::Mark { stream_sid, mark }).await?;
+                        }
+                        ReceivedEventKind::InputAudioBuffer(InputAudioBufferEvent::SpeechStarted { .. }) => {
+                            self.interrupt(&mut state, &twilio_out, &sender).await?;
+                        }
+                        ReceivedEventKind::Connection(ConnectionEvent::ConnectionClosed { .. }) => {
+                            return Ok(());
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+    }
+
+    /// Truncate the assistant's audio to what the caller has heard, and clear Twilio's playback buffer.
+    async fn interrupt(
+        &self,
+        state: &mut BridgeState,
+        twilio_out: &Sender<String>,
+        sender: &Sender<InputEvent>,
+    ) -> Result<(), RealtimeError> {
+        // No marks outstanding means that all audio has been played, so there's nothing to interrupt
+        if state.marks.is_empty() {
+            return Ok(());
+        }
+
+        if let Some((item_id, played_ms)) = state.take_interruption() {
+            sender
+                .send(InputEvent::truncate_item(&item_id, 0, played_ms))
+                .await
+                .map_err(|_| RealtimeError::Closed)?;
+        }
+
+        if let Some(stream_sid) = state.stream_sid.clone() {
+            send_command(twilio_out, TwilioCommand::Clear { stream_sid }).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Converts base64 µ-law audio from Twilio into base64 audio in the session format.
+    fn decode_twilio(&self, payload: &str) -> Option<String> {
+        let samples = || -> Option<Vec<i16>> {
+            let bytes = BASE64_STANDARD.decode(payload).ok()?;
+            Some(bytes.into_iter().map(ulaw_to_linear).collect())
+        };
+
+        let encoded: Vec<u8> = match self.format {
+            AudioFormat::G711Ulaw => return Some(payload.to_string()),
+            AudioFormat::Pcm16 => upsample(&samples()?, 3)
+                .into_iter()
+                .flat_map(i16::to_le_bytes)
+                .collect(),
+            AudioFormat::G711Alaw => samples()?.into_iter().map(linear_to_alaw).collect(),
+        };
+
+        Some(BASE64_STANDARD.encode(encoded))
+    }
+
+    /// Converts base64 audio in the session format into base64 µ-law audio for Twilio.
+    fn encode_twilio(&self, delta: &str) -> Option<String> {
+        let bytes = || BASE64_STANDARD.decode(delta).ok();
+
+        let samples: Vec<i16> = match self.format {
+            AudioFormat::G711Ulaw => return Some(delta.to_string()),
+            AudioFormat::Pcm16 => {
+                let samples: Vec<i16> = bytes()?
+                    .chunks_exact(2)
+                    .map(|x| i16::from_le_bytes([x[0], x[1]]))
+                    .collect();
+                downsample(&samples, 3)
+            }
+            AudioFormat::G711Alaw => bytes()?.into_iter().map(alaw_to_linear).collect(),
+        };
+
+        let encoded: Vec<u8> = samples.into_iter().map(linear_to_ulaw).collect();
+        Some(BASE64_STANDARD.encode(encoded))
+    }
+}
+
+#[derive(Debug, Default)]
+struct BridgeState {
+    stream_sid: Option<String>,
+    /// The timestamp of the latest media frame received from Twilio, in milliseconds.
+    latest_media_timestamp: u64,
+    /// The media timestamp at which playback of the current assistant item started.
+    response_start_timestamp: Option<u64>,
+    last_assistant_item: Option<String>,
+    /// Marks that have been sent to Twilio, but not yet reached in playback.
+    marks: VecDeque<String>,
+}
+
+impl BridgeState {
+    /// Track assistant audio sent to Twilio, returning the mark to send after it.
+    /// Truncation is relative to the start of an item, so playback is timed from the first audio of each item.
+    fn audio_sent(&mut self, item_id: &str) -> TwilioMark {
+        if self.last_assistant_item.as_deref() != Some(item_id) {
+            self.last_assistant_item = Some(item_id.to_string());
+            self.response_start_timestamp = Some(self.latest_media_timestamp);
+        }
+
+        let mark = TwilioMark {
+            name: "response_part".to_string(),
+        };
+        self.marks.push_back(mark.name.clone());
+
+        mark
+    }
+
+    /// Twilio has played the audio sent before a mark.
+    fn mark_reached(&mut self) {
+        self.marks.pop_front();
+
+        // Everything sent so far has been played, so any later audio starts a new playback
+        if self.marks.is_empty() {
+            self.last_assistant_item = None;
+            self.response_start_timestamp = None;
+        }
+    }
+
+    /// The assistant item that is playing and how many milliseconds of it have been played, clearing the playback state.
+    fn take_interruption(&mut self) -> Option<(String, u64)> {
+        self.marks.clear();
+
+        let item_id = self.last_assistant_item.take()?;
+        let start = self.response_start_timestamp.take()?;
+
+        Some((item_id, self.latest_media_timestamp.saturating_sub(start)))
+    }
+}
+
+async fn send_command(
+    twilio_out: &Sender<String>,
+    command: TwilioCommand,
+) -> Result<(), RealtimeError> {
+    let message = serde_json::to_string(&command)?;
+
+    twilio_out
+        .send(message)
+        .await
+        .map_err(|_| RealtimeError::Closed)
+}
+
+/// Upsamples by an integer factor using linear interpolation.
+fn upsample(samples: &[i16], factor: usize) -> Vec<i16> {
+    let mut output = Vec::with_capacity(samples.len() * factor);
+
+    for (idx, sample) in samples.iter().enumerate() {
+        let current = f32::from(*sample);
+        let next = f32::from(*samples.get(idx + 1).unwrap_or(sample));
+
+        for step in 0..factor {
+            let t = step as f32 / factor as f32;
+            output.push((current + (next - current) * t) as i16);
+        }
+    }
+
+    output
+}
+
+/// Downsamples by an integer factor by averaging each group of samples.
+fn downsample(samples: &[i16], factor: usize) -> Vec<i16> {
+    samples
+        .chunks(factor)
+        .map(|chunk| {
+            let sum: i32 = chunk.iter().map(|x| i32::from(*x)).sum();
+            (sum / chunk.len() as i32) as i16
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use base64::{Engine, prelude::BASE64_STANDARD};
+
+    use super::{BridgeState, TwilioBridge, downsample, upsample};
+    use crate::providers::openai_realtime::realtime::AudioFormat;
+
+    #[test]
+    fn resamples_audio() {
+        assert_eq!(upsample(&[0, 300], 3), [0, 100, 200, 300, 300, 300]);
+        assert_eq!(downsample(&[1, 2, 3, 4, 5, 6, 7], 3), [2, 5, 7]);
+        assert_eq!(downsample(&upsample(&[-90, 90, 30], 3), 3), [-30, 70, 30]);
+    }
+
+    #[test]
+    fn transcodes_audio() {
+        // A constant µ-law level, which survives resampling unchanged
+        let payload = BASE64_STANDARD.encode([0x9A; 160]);
+
+        let bridge = TwilioBridge::new();
+        assert_eq!(bridge.decode_twilio(&payload).unwrap(), payload);
+        assert_eq!(bridge.encode_twilio(&payload).unwrap(), payload);
+
+        let bridge = TwilioBridge::new().with_format(AudioFormat::Pcm16);
+        let pcm = BASE64_STANDARD
+            .decode(bridge.decode_twilio(&payload).unwrap())
+            .unwrap();
+        // 8kHz µ-law to 24kHz PCM16: 3 samples of 2 bytes for every byte
+        assert_eq!(pcm.len(), 160 * 6);
+        assert_eq!(
+            bridge.encode_twilio(&BASE64_STANDARD.encode(pcm)).unwrap(),
+            payload
+        );
+
+        let bridge = TwilioBridge::new().with_format(AudioFormat::G711Alaw);
+        let alaw = bridge.decode_twilio(&payload).unwrap();
+        assert_eq!(BASE64_STANDARD.decode(&alaw).unwrap().len(), 160);
+        assert_eq!(bridge.encode_twilio(&alaw).unwrap(), payload);
+
+        assert_eq!(bridge.decode_twilio("not base64!"), None);
+    }
+
+    #[test]
+    fn times_interruptions_from_the_start_of_each_item() {
+        let mut state = BridgeState {
+            latest_media_timestamp: 1000,
+            ..Default::default()
+        };
+
+        state.audio_sent("i1");
+        state.latest_media_timestamp = 1400;
+        state.audio_sent("i1");
+        state.latest_media_timestamp = 1700;
+        assert_eq!(state.take_interruption(), Some(("i1".to_string(), 700)));
+        assert!(state.marks.is_empty());
+
+        // A new item is timed from its own first audio
+        state.latest_media_timestamp = 2000;
+        state.audio_sent("i2");
+        state.latest_media_timestamp = 2100;
+        state.audio_sent("i3");
+        state.latest_media_timestamp = 2350;
+        assert_eq!(state.take_interruption(), Some(("i3".to_string(), 250)));
+    }
+
+    #[test]
+    fn resets_playback_once_all_audio_is_played() {
+        let mut state = BridgeState {
+            latest_media_timestamp: 1000,
+            ..Default::default()
+        };
+
+        state.audio_sent("i1");
+        state.audio_sent("i1");
+        state.mark_reached();
+        assert_eq!(state.last_assistant_item.as_deref(), Some("i1"));
+        state.mark_reached();
+        assert_eq!(state.last_assistant_item, None);
+
+        // The next response's audio doesn't inherit the previous start time
+        state.latest_media_timestamp = 5000;
+        state.audio_sent("i1");
+        state.latest_media_timestamp = 5200;
+        assert_eq!(state.take_interruption(), Some(("i1".to_string(), 200)));
+    }
+}
@@ -0,0 +1,148 @@
+//! Caching of route candidates, so that repeated queries skip the embedding call and vector store lookup.
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use super::RouteMatch;
+
+#[derive(Debug, Default)]
+struct CacheState {
+    entries: HashMap<String, (Vec<RouteMatch>, Instant)>,
+    /// Keys from least to most recently used.
+    order: VecDeque<String>,
+    hits: u64,
+    misses: u64,
+}
+
+/// An LRU cache of the candidate routes of recent queries.
+///
+/// Queries are normalized (trimmed, lowercased and with whitespace collapsed) before lookup, so near-identical queries share an entry.
+/// Only the candidates are cached: thresholds, guardrails and fallbacks are still applied to every query.
+#[derive(Debug)]
+pub struct RouteCache {
+    capacity: usize,
+    ttl: Option<Duration>,
+    state: Mutex<CacheState>,
+}
+
+impl RouteCache {
+    /// Create a cache that holds the candidates of up to `capacity` queries.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            ttl: None,
+            state: Mutex::new(CacheState::default()),
+        }
+    }
+
+    /// Expire entries after a while, ie so that routes added at runtime are picked up by cached queries.
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// The number of lookups that were (and weren't) served from the cache, as `(hits, misses)`.
+    pub fn stats(&self) -> (u64, u64) {
+        self.state
+            .lock()
+            .map(|x| (x.hits, x.misses))
+            .unwrap_or_default()
+    }
+
+    /// Remove every entry.
+    pub fn clear(&self) {
+        if let Ok(mut state) = self.state.lock() {
+            state.entries.clear();
+            state.order.clear();
+        }
+    }
+
+    pub(super) fn get(&self, query: &str) -> Option<Vec<RouteMatch>> {
+        let key = normalize(query);
+        let mut state = self.state.lock().ok()?;
+
+        let expired = match state.entries.get(&key) {
+            Some((_, inserted)) => self.ttl.is_some_and(|ttl| inserted.elapsed() > ttl),
+            None => {
+                state.misses += 1;
+                return None;
+            }
+        };
+
+        if expired {
+            state.entries.remove(&key);
+            state.order.retain(|x| *x != key);
+            state.misses += 1;
+            return None;
+        }
+
+        state.hits += 1;
+        touch(&mut state.order, &key);
+
+        state
+            .entries
+            .get(&key)
+            .map(|(candidates, _)| candidates.clone())
+    }
+
+    pub(super) fn insert(&self, query: &str, candidates: Vec<RouteMatch>) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        let key = normalize(query);
+        let Ok(mut state) = self.state.lock() else {
+            return;
+        };
+
+        touch(&mut state.order, &key);
+        state.entries.insert(key, (candidates, Instant::now()));
+
+        while state.entries.len() > self.capacity {
+            let Some(oldest) = state.order.pop_front() else {
+                break;
+            };
+            state.entries.remove(&oldest);
+        }
+    }
+}
+
+/// Move a key to the most recently used end of the order.
+fn touch(order: &mut VecDeque<String>, key: &str) {
+    order.retain(|x| x != key);
+    order.push_back(key.to_string());
+}
+
+fn normalize(query: &str) -> String {
+    query
+        .split_whitespace()
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RouteCache;
+    use crate::routing::RouteMatch;
+
+    #[test]
+    fn caches_recent_queries() {
+        let cache = RouteCache::new(2);
+
+        cache.insert("Where is my refund?", vec![RouteMatch::new("billing", 0.9)]);
+        cache.insert("hello", vec![]);
+
+        let hit = cache.get("  where is  my REFUND? ").unwrap();
+        assert_eq!(hit[0].tag, "billing");
+
+        // "hello" is now the least recently used entry, so it's evicted
+        cache.insert("goodbye", vec![]);
+        assert!(cache.get("hello").is_none());
+        assert!(cache.get("where is my refund?").is_some());
+
+        assert_eq!(cache.stats(), (2, 1));
+    }
+}
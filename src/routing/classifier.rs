@@ -0,0 +1,96 @@
+//! An LLM-based route classifier, used as a fallback when no route scores above the threshold.
+use std::sync::Arc;
+
+use super::RouteAgent;
+
+/// Classifies queries by prompting an agent with a description of every route.
+#[derive(Clone)]
+pub(super) struct LlmClassifier {
+    agent: Arc<dyn RouteAgent>,
+    /// Route tags and their descriptions, in the order they were added.
+    routes: Vec<(String, String)>,
+}
+
+/// The answer the classifier should give when no route fits.
+const NO_ROUTE: &str = "none";
+
+impl LlmClassifier {
+    pub(super) fn new(agent: Arc<dyn RouteAgent>, routes: Vec<(String, String)>) -> Self {
+        Self { agent, routes }
+    }
+
+    /// Ask the classifier agent which route a query belongs to.
+    /// Returns `None` if the agent doesn't pick a known route, or if prompting fails.
+    pub(super) async fn classify(&self, query: &str) -> Option<String> {
+        if self.routes.is_empty() {
+            return None;
+        }
+
+        let response = match self.agent.dyn_prompt(self.prompt(query), 0).await {
+            Ok(response) => response,
+            Err(err) => {
+                tracing::warn!("LLM route classification failed: {err}");
+                return None;
+            }
+        };
+
+        let tag = self.parse(&response);
+        tracing::info!("LLM classifier picked route: {tag:?}");
+
+        tag
+    }
+
+    fn prompt(&self, query: &str) -> String {
+        let routes = self
+            .routes
+            .iter()
+            .map(|(tag, description)| format!("- {tag}: {description}"))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        format!(
+            "Classify the query into one of the following routes:\n{routes}\n\nRespond with only the name of the route. If no route fits the query, respond with \"{NO_ROUTE}\".\n\nQuery: {query}"
+        )
+    }
+
+    /// Matches the agent's response against the known routes, ignoring case, surrounding whitespace, quotes and punctuation.
+    fn parse(&self, response: &str) -> Option<String> {
+        let answer = response
+            .trim()
+            .trim_matches(|c: char| c == '"' || c == '\'' || c == '`' || c == '.')
+            .trim();
+
+        if answer.eq_ignore_ascii_case(NO_ROUTE) {
+            return None;
+        }
+
+        self.routes
+            .iter()
+            .find(|(tag, _)| tag.eq_ignore_ascii_case(answer))
+            .map(|(tag, _)| tag.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::LlmClassifier;
+    use crate::test_utils::Echo;
+
+    #[test]
+    fn parses_classifier_responses() {
+        let classifier = LlmClassifier::new(
+            Arc::new(Echo),
+            vec![
+                ("billing".into(), "Invoices and payments".into()),
+                ("support".into(), "Technical issues".into()),
+            ],
+        );
+
+        assert_eq!(classifier.parse(" Billing.\n"), Some("billing".into()));
+        assert_eq!(classifier.parse("`support`"), Some("support".into()));
+        assert_eq!(classifier.parse("\"none\""), None);
+        assert_eq!(classifier.parse("weather"), None);
+    }
+}
@@ -0,0 +1,182 @@
+//! Router configuration files, so that the routing table can be maintained without recompiling.
+//!
+//! A config describes routes (their utterances, thresholds and lexical patterns), scoring settings and fallback behaviour.
+//! Anything that isn't data (the store, agents, the LLM classifier's agent, hooks and metrics) is still set up in code.
+//!
+//! ```yaml
+//! threshold: 0.8
+//! default_route: general
+//! routes:
+//!   - name: billing
+//!     description: Questions about invoices and payments
+//!     utterances: ["Why was I charged twice?", "Where can I find my invoice?"]
+//!     keywords: [refund, invoice]
+//!     rules: ["^/billing$"]
+//!   - name: general
+//!     utterances: ["Hi there", "Can you help me?"]
+//! ```
+use std::{fs, path::Path};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use super::{
+    Aggregation, ScoreTransform, SemanticRoute, SemanticRouterBuilder, SemanticRouterError,
+};
+
+/// The contents of a router config file. See [`SemanticRouterBuilder::from_config`].
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RouterConfig {
+    /// The global score threshold. Defaults to the builder's default.
+    #[serde(default)]
+    pub threshold: Option<f64>,
+    /// The route used when no route scores above its threshold.
+    #[serde(default)]
+    pub default_route: Option<String>,
+    #[serde(default)]
+    pub score_transform: ScoreTransform,
+    #[serde(default)]
+    pub aggregation: Aggregation,
+    /// The share of the combined score that comes from lexical matching.
+    #[serde(default)]
+    pub lexical_weight: Option<f64>,
+    pub routes: Vec<RouteConfig>,
+}
+
+/// A single route in a [`RouterConfig`].
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RouteConfig {
+    pub name: String,
+    /// Example utterances, which are embedded when the router is built.
+    #[serde(default)]
+    pub utterances: Vec<String>,
+    /// A threshold for this route, overriding the global threshold.
+    #[serde(default)]
+    pub threshold: Option<f64>,
+    /// Used by the LLM classifier and rerankers.
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub metadata: Map<String, Value>,
+    /// If set, the route is a guardrail and queries that match it are refused with this response.
+    #[serde(default)]
+    pub refusal: Option<String>,
+    #[serde(default)]
+    pub phrases: Vec<String>,
+    /// Regex patterns that raise the route's score.
+    #[serde(default)]
+    pub patterns: Vec<String>,
+    #[serde(default)]
+    pub keywords: Vec<String>,
+    /// Regex patterns that route queries straight to this route, without a vector query.
+    #[serde(default)]
+    pub rules: Vec<String>,
+}
+
+impl RouterConfig {
+    /// Read a config from a YAML (`.yaml` or `.yml`) or JSON file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SemanticRouterError> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path)?;
+
+        match path.extension().and_then(|x| x.to_str()) {
+            Some("yaml" | "yml") => Ok(serde_yaml::from_str(&contents)?),
+            _ => Ok(serde_json::from_str(&contents)?),
+        }
+    }
+}
+
+impl<V> SemanticRouterBuilder<V> {
+    /// Create a builder from a YAML or JSON config file (see [`RouterConfig`]). The store still needs to be set, and routes
+    /// are only embedded and inserted by [`Self::build_with_routes`].
+    pub fn from_config(path: impl AsRef<Path>) -> Result<Self, SemanticRouterError> {
+        Ok(Self::new().config(RouterConfig::load(path)?))
+    }
+
+    /// Apply the settings and routes of a config to the builder.
+    pub fn config(mut self, config: RouterConfig) -> Self {
+        if let Some(threshold) = config.threshold {
+            self = self.threshold(threshold);
+        }
+        if let Some(tag) = &config.default_route {
+            self = self.default_route(tag);
+        }
+        if let Some(weight) = config.lexical_weight {
+            self = self.lexical_weight(weight);
+        }
+        self = self
+            .score_transform(config.score_transform)
+            .aggregation(config.aggregation);
+
+        for route in config.routes {
+            let tag = route.name.as_str();
+
+            if let Some(threshold) = route.threshold {
+                self = self.route_threshold(tag, threshold);
+            }
+            if let Some(description) = &route.description {
+                self = self.route_description(tag, description);
+            }
+            if let Some(refusal) = &route.refusal {
+                self = self.guardrail(tag, refusal);
+            }
+            for phrase in &route.phrases {
+                self = self.phrase(tag, phrase);
+            }
+            for pattern in &route.patterns {
+                self = self.pattern(tag, pattern);
+            }
+            for rule in &route.rules {
+                self = self.rule_pattern(tag, rule);
+            }
+            if !route.keywords.is_empty() {
+                self = self.keywords(tag, &route.keywords);
+            }
+            if !route.utterances.is_empty() {
+                let semantic_route = SemanticRoute {
+                    tag: route.name,
+                    metadata: route.metadata,
+                };
+                self = self.route(semantic_route, route.utterances);
+            }
+        }
+
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RouterConfig;
+    use crate::routing::Aggregation;
+
+    #[test]
+    fn parses_yaml_config() {
+        let config: RouterConfig = serde_yaml::from_str(
+            r#"
+threshold: 0.75
+default_route: general
+aggregation:
+  type: mean
+  top_n: 3
+routes:
+  - name: billing
+    utterances: ["Why was I charged twice?"]
+    threshold: 0.9
+    rules: ["^/billing$"]
+    metadata:
+      team: payments
+  - name: general
+    utterances: ["Hi there"]
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(config.threshold, Some(0.75));
+        assert_eq!(config.aggregation, Aggregation::Mean { top_n: 3 });
+        assert_eq!(config.routes.len(), 2);
+        assert_eq!(config.routes[0].threshold, Some(0.9));
+        assert_eq!(config.routes[0].metadata["team"], "payments");
+        assert!(config.routes[1].keywords.is_empty());
+    }
+}
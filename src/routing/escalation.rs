@@ -0,0 +1,154 @@
+//! Cost-aware escalation: easy queries go to a cheap (ie local) agent, and only ambiguous or complex queries go to an expensive one.
+use std::sync::Arc;
+
+use super::{RouteAgent, RouteContext};
+
+/// Which agent of an [`EscalationPolicy`] served a query, and why.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Escalation {
+    /// The query was unambiguous and not flagged as complex, so the cheap agent served it.
+    Cheap,
+    /// The matched route's score was too close to the next best route's, so the expensive agent served it.
+    Ambiguous,
+    /// The complexity classifier flagged the query, so the expensive agent served it.
+    Complex,
+}
+
+impl Escalation {
+    pub fn is_escalated(&self) -> bool {
+        !matches!(self, Self::Cheap)
+    }
+}
+
+/// The answer the complexity classifier gives for queries that need the expensive agent.
+const COMPLEX: &str = "complex";
+
+/// Decides whether a route's queries go to a cheap or an expensive agent. See [`SemanticRouterWithAgents::escalation`](super::SemanticRouterWithAgents::escalation).
+///
+/// Queries are escalated if the route's score margin over the next best route is below [`Self::min_margin`],
+/// or if the [`Self::complexity_classifier`] says they're complex. Otherwise, the cheap agent serves them.
+#[derive(Clone)]
+pub struct EscalationPolicy {
+    cheap: Arc<dyn RouteAgent>,
+    expensive: Arc<dyn RouteAgent>,
+    min_margin: Option<f64>,
+    classifier: Option<Arc<dyn RouteAgent>>,
+}
+
+impl EscalationPolicy {
+    pub fn new<C, E>(cheap: C, expensive: E) -> Self
+    where
+        C: RouteAgent + 'static,
+        E: RouteAgent + 'static,
+    {
+        Self {
+            cheap: Arc::new(cheap),
+            expensive: Arc::new(expensive),
+            min_margin: None,
+            classifier: None,
+        }
+    }
+
+    /// Escalate queries whose matched route scores less than `margin` above the next best route.
+    pub fn min_margin(mut self, margin: f64) -> Self {
+        self.min_margin = Some(margin);
+
+        self
+    }
+
+    /// Ask a (small) agent whether each unambiguous query is simple or complex, and escalate complex ones.
+    /// If prompting the classifier fails, the query isn't escalated.
+    pub fn complexity_classifier<A>(mut self, agent: A) -> Self
+    where
+        A: RouteAgent + 'static,
+    {
+        self.classifier = Some(Arc::new(agent));
+
+        self
+    }
+
+    /// Decide which agent serves a query. The margin is checked first, so the classifier is only prompted when needed.
+    pub(super) async fn decide(&self, ctx: &RouteContext) -> Escalation {
+        if let (Some(min_margin), Some(margin)) = (self.min_margin, ctx.margin)
+            && margin < min_margin
+        {
+            return Escalation::Ambiguous;
+        }
+
+        let Some(classifier) = &self.classifier else {
+            return Escalation::Cheap;
+        };
+
+        match classifier.dyn_prompt(prompt(&ctx.query), 0).await {
+            Ok(answer) if is_complex(&answer) => Escalation::Complex,
+            Ok(_) => Escalation::Cheap,
+            Err(err) => {
+                tracing::warn!("Complexity classification failed: {err}");
+                Escalation::Cheap
+            }
+        }
+    }
+
+    pub(super) fn agent(&self, escalation: Escalation) -> &Arc<dyn RouteAgent> {
+        if escalation.is_escalated() {
+            &self.expensive
+        } else {
+            &self.cheap
+        }
+    }
+}
+
+fn prompt(query: &str) -> String {
+    format!(
+        "Decide whether the following query is simple enough for a small model to answer well, or complex (ie it needs multi-step reasoning, specialist knowledge or careful writing).\n\nRespond with only \"simple\" or \"{COMPLEX}\".\n\nQuery: {query}"
+    )
+}
+
+/// Matches the classifier's answer, ignoring case, surrounding whitespace, quotes and punctuation.
+fn is_complex(answer: &str) -> bool {
+    answer
+        .trim()
+        .trim_matches(|c: char| c == '"' || c == '\'' || c == '`' || c == '.')
+        .eq_ignore_ascii_case(COMPLEX)
+}
+
+impl std::fmt::Debug for EscalationPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EscalationPolicy")
+            .field("min_margin", &self.min_margin)
+            .field("classifier", &self.classifier.is_some())
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Escalation, EscalationPolicy};
+    use crate::routing::RouteContext;
+    use crate::test_utils::Fixed;
+
+    fn ctx(margin: Option<f64>) -> RouteContext {
+        RouteContext {
+            query: "Compare the tax implications of both options".into(),
+            route: None,
+            history: Vec::new(),
+            turns: 0,
+            margin,
+        }
+    }
+
+    #[tokio::test]
+    async fn escalates_ambiguous_and_complex_queries() {
+        let policy = EscalationPolicy::new(Fixed("cheap"), Fixed("expensive")).min_margin(0.05);
+        assert_eq!(policy.decide(&ctx(Some(0.01))).await, Escalation::Ambiguous);
+        assert_eq!(policy.decide(&ctx(Some(0.2))).await, Escalation::Cheap);
+        assert_eq!(policy.decide(&ctx(None)).await, Escalation::Cheap);
+
+        let policy = policy.complexity_classifier(Fixed(" Complex.\n"));
+        assert_eq!(policy.decide(&ctx(Some(0.2))).await, Escalation::Complex);
+
+        let policy = EscalationPolicy::new(Fixed("cheap"), Fixed("expensive"))
+            .complexity_classifier(Fixed("simple"));
+        assert_eq!(policy.decide(&ctx(None)).await, Escalation::Cheap);
+    }
+}
@@ -0,0 +1,234 @@
+//! Evaluation of routers against labelled queries, so that thresholds can be tuned with data rather than guesswork.
+use std::collections::HashMap;
+
+use rig::vector_store::{VectorStoreError, VectorStoreIndex};
+use serde::{Deserialize, Serialize};
+
+use super::{RouteMatch, SemanticRouter};
+
+/// A labelled query: the route it should land on, or `None` if it shouldn't match any route.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GoldenQuery {
+    pub query: String,
+    pub expected: Option<String>,
+}
+
+/// How a single golden query was routed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EvaluationResult {
+    pub query: String,
+    pub expected: Option<String>,
+    /// The route the router picked, including fallbacks.
+    pub predicted: Option<String>,
+    /// The best scoring candidate, whether or not it scored above its threshold.
+    pub best: Option<RouteMatch>,
+}
+
+impl EvaluationResult {
+    pub fn is_correct(&self) -> bool {
+        self.expected == self.predicted
+    }
+}
+
+/// A kind of mistake the router made, and how often.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Confusion {
+    pub expected: Option<String>,
+    pub predicted: Option<String>,
+    pub count: usize,
+}
+
+/// The results of running golden queries through a router. See [`RouterEvaluator`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EvaluationReport {
+    pub results: Vec<EvaluationResult>,
+    /// The share of queries that were routed as expected, between 0 and 1.
+    pub accuracy: f64,
+    /// The mistakes the router made, most frequent first.
+    pub confusions: Vec<Confusion>,
+    /// The global threshold that would have routed the most queries correctly on score alone, if there were any scores.
+    /// Route thresholds and fallbacks aren't taken into account.
+    pub suggested_threshold: Option<f64>,
+}
+
+/// Runs labelled (golden) queries through a router and reports how well it routes them.
+///
+/// Include queries that shouldn't match any route (see [`Self::unroutable`]), otherwise the suggested threshold only ever gets lower.
+#[derive(Debug, Clone, Default)]
+pub struct RouterEvaluator {
+    queries: Vec<GoldenQuery>,
+}
+
+impl RouterEvaluator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a query that should land on a route.
+    pub fn case(mut self, query: &str, expected: &str) -> Self {
+        self.queries.push(GoldenQuery {
+            query: query.to_string(),
+            expected: Some(expected.to_string()),
+        });
+
+        self
+    }
+
+    /// Add a query that shouldn't match any route.
+    pub fn unroutable(mut self, query: &str) -> Self {
+        self.queries.push(GoldenQuery {
+            query: query.to_string(),
+            expected: None,
+        });
+
+        self
+    }
+
+    /// Add golden queries, ie loaded from a fixtures file.
+    pub fn queries<I>(mut self, queries: I) -> Self
+    where
+        I: IntoIterator<Item = GoldenQuery>,
+    {
+        self.queries.extend(queries);
+
+        self
+    }
+
+    /// Route every golden query and report the results. Queries are routed like with [`SemanticRouter::route`],
+    /// so decisions are also recorded in the router's metrics sink, if it has one.
+    pub async fn evaluate<V>(
+        &self,
+        router: &SemanticRouter<V>,
+    ) -> Result<EvaluationReport, VectorStoreError>
+    where
+        V: VectorStoreIndex,
+    {
+        let mut results = Vec::with_capacity(self.queries.len());
+
+        for GoldenQuery { query, expected } in &self.queries {
+            let decision = router.decision(&router.middleware.rewrite(query)).await?;
+
+            results.push(EvaluationResult {
+                query: query.clone(),
+                expected: expected.clone(),
+                predicted: decision.route.map(|x| x.tag),
+                best: decision.best,
+            });
+        }
+
+        Ok(EvaluationReport::new(results))
+    }
+}
+
+impl EvaluationReport {
+    fn new(results: Vec<EvaluationResult>) -> Self {
+        let correct = results.iter().filter(|x| x.is_correct()).count();
+        let accuracy = if results.is_empty() {
+            0.0
+        } else {
+            correct as f64 / results.len() as f64
+        };
+
+        let mut counts: HashMap<(Option<String>, Option<String>), usize> = HashMap::new();
+        for result in results.iter().filter(|x| !x.is_correct()) {
+            *counts
+                .entry((result.expected.clone(), result.predicted.clone()))
+                .or_default() += 1;
+        }
+        let mut confusions: Vec<Confusion> = counts
+            .into_iter()
+            .map(|((expected, predicted), count)| Confusion {
+                expected,
+                predicted,
+                count,
+            })
+            .collect();
+        confusions.sort_by(|a, b| {
+            b.count
+                .cmp(&a.count)
+                .then_with(|| a.expected.cmp(&b.expected))
+                .then_with(|| a.predicted.cmp(&b.predicted))
+        });
+
+        Self {
+            suggested_threshold: suggest_threshold(&results),
+            results,
+            accuracy,
+            confusions,
+        }
+    }
+}
+
+/// Try every best-candidate score as the threshold, and pick the one that routes the most queries correctly on score alone.
+/// Ties go to the lowest threshold.
+fn suggest_threshold(results: &[EvaluationResult]) -> Option<f64> {
+    let mut thresholds: Vec<f64> = results
+        .iter()
+        .filter_map(|x| x.best.as_ref().map(|best| best.score))
+        .collect();
+    thresholds.sort_by(f64::total_cmp);
+    thresholds.dedup();
+
+    // Just above the highest score, for when no query should match
+    if let Some(highest) = thresholds.last() {
+        thresholds.push(highest.next_up());
+    }
+
+    let correct_at = |threshold: f64| {
+        results
+            .iter()
+            .filter(|x| {
+                let predicted = x
+                    .best
+                    .as_ref()
+                    .filter(|best| best.score >= threshold)
+                    .map(|best| &best.tag);
+
+                predicted == x.expected.as_ref()
+            })
+            .count()
+    };
+
+    thresholds
+        .into_iter()
+        .map(|threshold| (threshold, correct_at(threshold)))
+        .max_by(|(a, a_correct), (b, b_correct)| a_correct.cmp(b_correct).then(b.total_cmp(a)))
+        .map(|(threshold, _)| threshold)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EvaluationReport, EvaluationResult};
+    use crate::routing::RouteMatch;
+
+    fn result(
+        expected: Option<&str>,
+        predicted: Option<&str>,
+        best: (&str, f64),
+    ) -> EvaluationResult {
+        EvaluationResult {
+            query: String::new(),
+            expected: expected.map(str::to_string),
+            predicted: predicted.map(str::to_string),
+            best: Some(RouteMatch::new(best.0, best.1)),
+        }
+    }
+
+    #[test]
+    fn reports_accuracy_and_confusions() {
+        let report = EvaluationReport::new(vec![
+            result(Some("billing"), Some("billing"), ("billing", 0.9)),
+            result(Some("billing"), None, ("billing", 0.75)),
+            result(Some("support"), Some("billing"), ("billing", 0.85)),
+            result(None, None, ("support", 0.6)),
+        ]);
+
+        assert_eq!(report.accuracy, 0.5);
+        assert_eq!(report.confusions.len(), 2);
+        assert_eq!(report.confusions[0].expected.as_deref(), Some("billing"));
+        assert_eq!(report.confusions[0].predicted, None);
+
+        // 0.75 routes both billing queries, and leaves the unroutable query unmatched
+        assert_eq!(report.suggested_threshold, Some(0.75));
+    }
+}
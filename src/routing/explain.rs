@@ -0,0 +1,147 @@
+//! Explanations of routing decisions, for debugging routes.
+use rig::vector_store::{VectorStoreError, VectorStoreIndex};
+use serde::{Deserialize, Serialize};
+
+use super::{Aggregation, HYBRID_CANDIDATES, SemanticRouter};
+
+/// A stored utterance, as far as explanations are concerned.
+#[derive(Debug, Deserialize)]
+struct StoredUtterance {
+    tag: String,
+    #[serde(default, rename = "utterance")]
+    text: Option<String>,
+}
+
+/// A retrieved utterance and its score.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UtteranceScore {
+    /// The text of the utterance, if the store returns it in the [`UTTERANCE_FIELD`](super::UTTERANCE_FIELD) of the document.
+    pub utterance: Option<String>,
+    /// The score as returned by the store.
+    pub raw_score: f64,
+    /// The score after the router's [`ScoreTransform`](super::ScoreTransform).
+    pub score: f64,
+}
+
+/// How a single route scored for a query.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RouteExplanation {
+    pub tag: String,
+    /// The best matching utterances of the route, highest score first.
+    pub utterances: Vec<UtteranceScore>,
+    /// The utterance scores aggregated into a single score.
+    pub semantic_score: f64,
+    /// The lexical score, if the route has lexical patterns.
+    pub lexical_score: Option<f64>,
+    /// The semantic score with the lexical score blended in. This is the score that's compared to the threshold.
+    pub score: f64,
+    pub threshold: f64,
+    pub guardrail: bool,
+}
+
+impl RouteExplanation {
+    pub fn above_threshold(&self) -> bool {
+        self.score >= self.threshold
+    }
+}
+
+/// Why a query landed (or failed to land) on a route. See [`SemanticRouter::explain`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Explanation {
+    pub query: String,
+    /// How utterance scores are combined into route scores.
+    pub aggregation: Aggregation,
+    /// Candidate routes, highest score first.
+    pub routes: Vec<RouteExplanation>,
+    /// The route the query matches on rules or score alone. Fallbacks (the LLM classifier and the default route) aren't evaluated.
+    pub matched: Option<String>,
+}
+
+impl<V> SemanticRouter<V>
+where
+    V: VectorStoreIndex,
+{
+    /// Explain how a query scores against each candidate route, with up to `n` of the best matching utterances per route.
+    /// The query is rewritten by the pre-route hooks first, like when routing.
+    pub async fn explain(&self, query: &str, n: usize) -> Result<Explanation, VectorStoreError> {
+        let query = &self.middleware.rewrite(query);
+        let res = self
+            .store
+            .top_n::<StoredUtterance>(query, self.candidate_count(HYBRID_CANDIDATES))
+            .await?;
+
+        let mut routes: Vec<RouteExplanation> = Vec::new();
+
+        for (raw_score, _, StoredUtterance { tag, text }) in res {
+            let utterance = UtteranceScore {
+                utterance: text,
+                raw_score,
+                score: self.score_transform.apply(raw_score),
+            };
+
+            match routes.iter_mut().find(|x| x.tag == tag) {
+                Some(route) => route.utterances.push(utterance),
+                None => routes.push(RouteExplanation {
+                    threshold: self.threshold(&tag),
+                    guardrail: self.is_guardrail(&tag),
+                    tag,
+                    utterances: vec![utterance],
+                    semantic_score: 0.0,
+                    lexical_score: None,
+                    score: 0.0,
+                }),
+            }
+        }
+
+        if let Some(lexical) = &self.lexical {
+            for tag in lexical.tags() {
+                if !routes.iter().any(|x| x.tag == tag) {
+                    routes.push(RouteExplanation {
+                        tag: tag.to_string(),
+                        utterances: Vec::new(),
+                        semantic_score: 0.0,
+                        lexical_score: None,
+                        score: 0.0,
+                        threshold: self.threshold(tag),
+                        guardrail: self.is_guardrail(tag),
+                    });
+                }
+            }
+        }
+
+        for route in &mut routes {
+            route.utterances.sort_by(|a, b| b.score.total_cmp(&a.score));
+            let scores: Vec<f64> = route.utterances.iter().map(|x| x.score).collect();
+            route.semantic_score = self.aggregation.apply(&scores);
+            route.utterances.truncate(n);
+
+            route.score = route.semantic_score;
+            if let Some(lexical) = &self.lexical {
+                route.lexical_score = Some(lexical.score(&route.tag, query));
+                route.score = lexical.combine(&route.tag, query, route.semantic_score);
+            }
+        }
+
+        routes.sort_by(|a, b| b.score.total_cmp(&a.score));
+
+        let matched = self.rules.matches(query).map(str::to_string).or_else(|| {
+            routes
+                .iter()
+                .find(|x| x.guardrail && x.above_threshold())
+                .or_else(|| {
+                    routes
+                        .iter()
+                        .find(|x| !x.guardrail)
+                        .filter(|x| x.above_threshold())
+                })
+                .map(|x| x.tag.clone())
+        });
+
+        Ok(Explanation {
+            query: query.to_string(),
+            aggregation: self.aggregation,
+            routes,
+            matched,
+        })
+    }
+}
@@ -0,0 +1,148 @@
+//! Timeouts and failover for route targets, so that one misbehaving provider doesn't hang or fail the whole routed request.
+use std::{sync::Arc, time::Duration};
+
+use super::handler::RouteTarget;
+use super::{RouteAgent, RouteContext, RoutedResponse, SemanticRouterError};
+
+/// How a route handles a target that errors or doesn't respond in time.
+///
+/// The route's target is tried first (and retried, if retries are set), then each fallback in the order it was added.
+/// The error of the last attempt is returned if every attempt fails.
+#[derive(Debug, Clone, Default)]
+pub struct FailoverPolicy {
+    /// How long each attempt may take.
+    timeout: Option<Duration>,
+    /// How many times the route's own target is retried.
+    retries: usize,
+    fallbacks: Vec<RouteTarget>,
+}
+
+impl FailoverPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fail an attempt (and move on to the next one) if it takes longer than `timeout`.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+
+        self
+    }
+
+    /// Retry the route's own target up to `retries` times before using the fallbacks.
+    pub fn retries(mut self, retries: usize) -> Self {
+        self.retries = retries;
+
+        self
+    }
+
+    /// Send the query to another agent (ie one using a different provider) if the previous attempts failed.
+    pub fn fallback_agent<A>(mut self, agent: A) -> Self
+    where
+        A: RouteAgent + 'static,
+    {
+        self.fallbacks.push(RouteTarget::Agent(Arc::new(agent)));
+
+        self
+    }
+
+    /// Respond with a fixed message if the previous attempts failed. This can't fail, so any fallbacks added after it are never used.
+    pub fn fallback_response(mut self, response: &str) -> Self {
+        self.fallbacks
+            .push(RouteTarget::Refusal(response.to_string()));
+
+        self
+    }
+
+    /// Send a query to a target, applying the timeout, retries and fallbacks.
+    pub(super) async fn respond(
+        &self,
+        target: &RouteTarget,
+        ctx: RouteContext,
+    ) -> Result<Option<RoutedResponse>, SemanticRouterError> {
+        let attempts = std::iter::repeat_n(target, self.retries + 1).chain(&self.fallbacks);
+        let mut last_err = None;
+
+        for (attempt, target) in attempts.enumerate() {
+            if let Some(err) = &last_err {
+                tracing::warn!("Route target failed, trying attempt {}: {err}", attempt + 1);
+            }
+
+            match self.attempt(target, ctx.clone()).await {
+                Ok(res) => return Ok(res),
+                Err(err) => last_err = Some(err),
+            }
+        }
+
+        Err(last_err.expect("the route's target is always attempted"))
+    }
+
+    async fn attempt(
+        &self,
+        target: &RouteTarget,
+        ctx: RouteContext,
+    ) -> Result<Option<RoutedResponse>, SemanticRouterError> {
+        let Some(timeout) = self.timeout else {
+            return target.respond(ctx).await;
+        };
+
+        tokio::time::timeout(timeout, target.respond(ctx))
+            .await
+            .map_err(|_| SemanticRouterError::Timeout(timeout))?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            Arc,
+            atomic::{AtomicUsize, Ordering},
+        },
+        time::Duration,
+    };
+
+    use super::FailoverPolicy;
+    use crate::routing::{RouteContext, SemanticRouterError, handler::RouteTarget};
+
+    fn ctx() -> RouteContext {
+        RouteContext {
+            query: "hello".into(),
+            route: None,
+            history: Vec::new(),
+            turns: 0,
+            margin: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn retries_then_falls_back() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let failing = RouteTarget::handler(move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            async { Err(anyhow::anyhow!("provider down")) }
+        });
+
+        let policy = FailoverPolicy::new()
+            .retries(2)
+            .fallback_response("Sorry, try again later.");
+        let res = policy.respond(&failing, ctx()).await.unwrap().unwrap();
+
+        assert_eq!(res.response, "Sorry, try again later.");
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn times_out() {
+        let slow = RouteTarget::handler(|_| async {
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            Ok("too late".to_string())
+        });
+
+        let policy = FailoverPolicy::new().timeout(Duration::from_millis(10));
+        let res = policy.respond(&slow, ctx()).await;
+
+        assert!(matches!(res, Err(SemanticRouterError::Timeout(_))));
+    }
+}
@@ -0,0 +1,200 @@
+//! What routes send their queries to: agents, async handlers or other routers.
+use std::{future::Future, sync::Arc, time::Duration};
+
+use futures::{FutureExt, StreamExt, future::BoxFuture, stream};
+use rig::message::Message;
+
+use super::variants::{self, AgentVariant};
+use super::{
+    EscalationPolicy, RouteAgent, RouteMatch, RouteStream, RoutedResponse, SemanticRouterError,
+};
+
+/// Everything a route handler gets to know about the query it's handling.
+#[derive(Debug, Clone)]
+pub struct RouteContext {
+    pub query: String,
+    /// The matched route, or `None` if the query was sent to the default handler.
+    pub route: Option<RouteMatch>,
+    /// The chat history, if the query is part of a [`RouterSession`](super::RouterSession).
+    pub history: Vec<Message>,
+    /// How many tool-calling turns the query was sent with.
+    pub turns: usize,
+    /// How far the matched route's score is ahead of the next best route's. `None` if the route wasn't picked on score,
+    /// or if no other route was a candidate.
+    pub margin: Option<f64>,
+}
+
+/// The agent ID of responses from the default agent.
+const DEFAULT_AGENT_ID: &str = "default";
+
+/// A type-erased async function that handles a route.
+pub type RouteHandler =
+    Arc<dyn Fn(RouteContext) -> BoxFuture<'static, anyhow::Result<String>> + Send + Sync>;
+
+/// A type-erased router that can be the target of a route on another router.
+pub(super) trait NestedRouter: Send + Sync {
+    /// Route a query with this router's own routes and thresholds, then send it to the matched target.
+    fn respond_nested(
+        &self,
+        ctx: RouteContext,
+    ) -> BoxFuture<'_, Result<Option<RoutedResponse>, SemanticRouterError>>;
+
+    fn stream_nested(
+        &self,
+        ctx: RouteContext,
+    ) -> BoxFuture<'_, Result<Option<RouteStream>, SemanticRouterError>>;
+}
+
+/// What a route sends its queries to.
+#[derive(Clone)]
+pub(super) enum RouteTarget {
+    Agent(Arc<dyn RouteAgent>),
+    Handler(RouteHandler),
+    /// Another router, which narrows the query down to a more specific route.
+    Router(Arc<dyn NestedRouter>),
+    /// A fixed refusal, for guardrail routes.
+    Refusal(String),
+    /// Several agents, one of which is picked for each request according to their weights.
+    Variants(Vec<AgentVariant>),
+    /// A cheap and an expensive agent, picked by how ambiguous or complex the query is.
+    Escalation(Arc<EscalationPolicy>),
+}
+
+impl RouteTarget {
+    pub(super) fn handler<F, Fut>(handler: F) -> Self
+    where
+        F: Fn(RouteContext) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = anyhow::Result<String>> + Send + 'static,
+    {
+        Self::Handler(Arc::new(move |ctx| handler(ctx).boxed()))
+    }
+
+    /// Send a query to the agent, handler or router. The route in the context is prepended to the trace of the response.
+    /// Agents are prompted with the history as a chat if there is any, and with the number of turns otherwise.
+    /// Returns `Ok(None)` if a nested router found no route for the query, or if there are no variants to pick from.
+    pub(super) async fn respond(
+        &self,
+        ctx: RouteContext,
+    ) -> Result<Option<RoutedResponse>, SemanticRouterError> {
+        let route = ctx.route.clone();
+        let mut variant = None;
+        let mut escalation = None;
+
+        let response = match self {
+            Self::Agent(agent) if ctx.history.is_empty() => {
+                agent.dyn_prompt(ctx.query, ctx.turns).await?
+            }
+            Self::Agent(agent) => agent.dyn_chat(ctx.query, ctx.history).await?,
+            Self::Handler(handler) => handler(ctx)
+                .await
+                .map_err(SemanticRouterError::HandlerError)?,
+            Self::Refusal(refusal) => refusal.clone(),
+            Self::Variants(variants) => {
+                let Some(picked) = variants::pick(variants) else {
+                    return Ok(None);
+                };
+                variant = Some(picked.name.clone());
+
+                if ctx.history.is_empty() {
+                    picked.agent.dyn_prompt(ctx.query, ctx.turns).await?
+                } else {
+                    picked.agent.dyn_chat(ctx.query, ctx.history).await?
+                }
+            }
+            Self::Escalation(policy) => {
+                let picked = policy.decide(&ctx).await;
+                escalation = Some(picked);
+                let agent = policy.agent(picked);
+
+                if ctx.history.is_empty() {
+                    agent.dyn_prompt(ctx.query, ctx.turns).await?
+                } else {
+                    agent.dyn_chat(ctx.query, ctx.history).await?
+                }
+            }
+            Self::Router(router) => {
+                let Some(mut res) = router.respond_nested(ctx).await? else {
+                    return Ok(None);
+                };
+
+                if let Some(route) = route {
+                    res.trace.insert(0, route);
+                }
+
+                return Ok(Some(res));
+            }
+        };
+
+        let target_id = route.as_ref().map_or(DEFAULT_AGENT_ID, |x| x.tag.as_str());
+        let agent_id = match (&variant, escalation) {
+            (Some(variant), _) => format!("{target_id}/{variant}"),
+            (None, Some(escalation)) if escalation.is_escalated() => {
+                format!("{target_id}/expensive")
+            }
+            (None, Some(_)) => format!("{target_id}/cheap"),
+            (None, None) => target_id.to_string(),
+        };
+
+        Ok(Some(RoutedResponse {
+            response,
+            trace: route.into_iter().collect(),
+            variant,
+            escalation,
+            agent_id: Some(agent_id),
+            latency: Duration::ZERO,
+            parts: Vec::new(),
+        }))
+    }
+
+    /// Stream the response to a query. Handlers don't stream, so their response is yielded as a single chunk.
+    pub(super) async fn stream(
+        &self,
+        ctx: RouteContext,
+    ) -> Result<Option<RouteStream>, SemanticRouterError> {
+        match self {
+            Self::Agent(agent) => Ok(Some(agent.dyn_stream_prompt(ctx.query).await?)),
+            Self::Handler(handler) => {
+                let res = handler(ctx)
+                    .await
+                    .map_err(SemanticRouterError::HandlerError)?;
+
+                Ok(Some(stream::once(async move { Ok(res) }).boxed()))
+            }
+            Self::Router(router) => router.stream_nested(ctx).await,
+            Self::Refusal(refusal) => {
+                let res = refusal.clone();
+
+                Ok(Some(stream::once(async move { Ok(res) }).boxed()))
+            }
+            Self::Variants(variants) => {
+                let Some(picked) = variants::pick(variants) else {
+                    return Ok(None);
+                };
+                tracing::info!("Streaming from agent variant: {}", picked.name);
+
+                Ok(Some(picked.agent.dyn_stream_prompt(ctx.query).await?))
+            }
+            Self::Escalation(policy) => {
+                let picked = policy.decide(&ctx).await;
+                tracing::info!("Streaming with escalation: {picked:?}");
+
+                Ok(Some(
+                    policy.agent(picked).dyn_stream_prompt(ctx.query).await?,
+                ))
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for RouteTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Agent(_) => f.write_str("Agent"),
+            Self::Handler(_) => f.write_str("Handler"),
+            Self::Router(_) => f.write_str("Router"),
+            Self::Refusal(_) => f.write_str("Refusal"),
+            Self::Variants(variants) => f.debug_tuple("Variants").field(variants).finish(),
+            Self::Escalation(policy) => f.debug_tuple("Escalation").field(policy).finish(),
+        }
+    }
+}
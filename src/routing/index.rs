@@ -0,0 +1,368 @@
+//! An in-memory route index that supports adding routes at runtime.
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Write},
+    path::Path,
+    sync::RwLock,
+};
+
+use rig::{
+    embeddings::EmbeddingModel,
+    vector_store::{VectorStoreError, VectorStoreIndex},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use super::{SemanticRoute, SemanticRouterError, UTTERANCE_FIELD};
+
+/// A route store that supports inserting new routes at runtime.
+pub trait InsertRoutes: VectorStoreIndex {
+    /// Embed the example utterances of a route and add them to the store, along with the route's tag and metadata.
+    fn insert_route(
+        &self,
+        route: &SemanticRoute,
+        utterances: Vec<String>,
+    ) -> impl Future<Output = Result<(), VectorStoreError>> + Send;
+}
+
+/// Retrieved utterances as `(score, id, route)` tuples.
+pub type RouteResults = Vec<(f64, String, SemanticRoute)>;
+
+/// A route store that can retrieve routes for many queries at once, embedding the queries in batches.
+pub trait BatchRouteQuery: VectorStoreIndex {
+    /// The `n` utterances most similar to each query. Results are in the same order as the queries.
+    fn top_n_batch(
+        &self,
+        queries: Vec<String>,
+        n: usize,
+    ) -> impl Future<Output = Result<Vec<RouteResults>, VectorStoreError>> + Send;
+}
+
+/// An example utterance of a route, along with its embedding.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RouteUtterance {
+    pub tag: String,
+    #[serde(default)]
+    pub metadata: Map<String, Value>,
+    pub utterance: String,
+    pub embedding: Vec<f64>,
+}
+
+/// A route index that keeps every utterance (and its embedding) in memory, and ranks them by cosine similarity.
+pub struct InMemoryRouteIndex<M> {
+    model: M,
+    utterances: RwLock<Vec<RouteUtterance>>,
+}
+
+impl<M> InMemoryRouteIndex<M>
+where
+    M: EmbeddingModel,
+{
+    /// Create an empty index that embeds utterances (and queries) with the given model.
+    pub fn new(model: M) -> Self {
+        Self {
+            model,
+            utterances: RwLock::new(Vec::new()),
+        }
+    }
+
+    /// Create an index from utterances that were embedded earlier, ie with [`Self::load`].
+    /// Errors if an embedding doesn't have the number of dimensions of the model.
+    pub fn from_utterances(
+        model: M,
+        utterances: Vec<RouteUtterance>,
+    ) -> Result<Self, SemanticRouterError> {
+        let expected = model.ndims();
+
+        if let Some(utterance) = utterances.iter().find(|x| x.embedding.len() != expected) {
+            return Err(SemanticRouterError::DimensionMismatch {
+                expected,
+                found: utterance.embedding.len(),
+            });
+        }
+
+        Ok(Self {
+            model,
+            utterances: RwLock::new(utterances),
+        })
+    }
+
+    /// Load an index saved with [`Self::save`], so that the utterances don't have to be embedded again.
+    /// The model should be the one the utterances were embedded with.
+    pub fn load(model: M, path: impl AsRef<Path>) -> Result<Self, SemanticRouterError> {
+        let utterances = serde_json::from_reader(BufReader::new(File::open(path)?))?;
+
+        Self::from_utterances(model, utterances)
+    }
+
+    /// Save every utterance (and its embedding) to a JSON file.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), SemanticRouterError> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer(&mut writer, &self.utterances())?;
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    /// All utterances in the index.
+    pub fn utterances(&self) -> Vec<RouteUtterance> {
+        self.utterances
+            .read()
+            .map(|x| x.clone())
+            .unwrap_or_default()
+    }
+
+    /// Re-embed every utterance with a different embedding model (ie after upgrading models), keeping tags and metadata.
+    /// The index isn't changed, so it can keep serving queries until the new index replaces it.
+    pub async fn reembed<N>(&self, model: N) -> Result<InMemoryRouteIndex<N>, VectorStoreError>
+    where
+        N: EmbeddingModel,
+    {
+        let index = InMemoryRouteIndex::new(model);
+        let mut utterances = self.utterances();
+        let embeddings = index
+            .embed(utterances.iter().map(|x| x.utterance.clone()).collect())
+            .await?;
+
+        for (utterance, embedding) in utterances.iter_mut().zip(embeddings) {
+            utterance.embedding = embedding;
+        }
+        *index
+            .utterances
+            .write()
+            .map_err(|err| VectorStoreError::DatastoreError(err.to_string().into()))? = utterances;
+
+        Ok(index)
+    }
+
+    /// Embed a list of texts, batched according to the model's document limit.
+    async fn embed(&self, texts: Vec<String>) -> Result<Vec<Vec<f64>>, VectorStoreError> {
+        let mut embeddings = Vec::with_capacity(texts.len());
+
+        for batch in texts.chunks(M::MAX_DOCUMENTS.max(1)) {
+            let batch = self.model.embed_texts(batch.to_vec()).await?;
+            embeddings.extend(batch.into_iter().map(|x| x.vec));
+        }
+
+        Ok(embeddings)
+    }
+}
+
+impl<M> InsertRoutes for InMemoryRouteIndex<M>
+where
+    M: EmbeddingModel,
+{
+    async fn insert_route(
+        &self,
+        route: &SemanticRoute,
+        utterances: Vec<String>,
+    ) -> Result<(), VectorStoreError> {
+        let embeddings = self.embed(utterances.clone()).await?;
+
+        let mut stored = self
+            .utterances
+            .write()
+            .map_err(|err| VectorStoreError::DatastoreError(err.to_string().into()))?;
+
+        stored.extend(
+            utterances
+                .into_iter()
+                .zip(embeddings)
+                .map(|(utterance, embedding)| RouteUtterance {
+                    tag: route.tag.clone(),
+                    metadata: route.metadata.clone(),
+                    utterance,
+                    embedding,
+                }),
+        );
+
+        Ok(())
+    }
+}
+
+impl<M> BatchRouteQuery for InMemoryRouteIndex<M>
+where
+    M: EmbeddingModel,
+{
+    async fn top_n_batch(
+        &self,
+        queries: Vec<String>,
+        n: usize,
+    ) -> Result<Vec<RouteResults>, VectorStoreError> {
+        self.embed(queries)
+            .await?
+            .iter()
+            .map(|query| {
+                Ok(self
+                    .rank(query, n)?
+                    .into_iter()
+                    .map(|(score, id, route, _)| (score, id, route))
+                    .collect())
+            })
+            .collect()
+    }
+}
+
+impl<M> InMemoryRouteIndex<M> {
+    /// The `n` utterances most similar to an embedded query, as `(score, id, route, utterance)` tuples.
+    /// The ID of an utterance is its position in the index.
+    fn rank(
+        &self,
+        query: &[f64],
+        n: usize,
+    ) -> Result<Vec<(f64, String, SemanticRoute, String)>, VectorStoreError> {
+        let stored = self
+            .utterances
+            .read()
+            .map_err(|err| VectorStoreError::DatastoreError(err.to_string().into()))?;
+
+        let mut ranked: Vec<(f64, usize)> = stored
+            .iter()
+            .enumerate()
+            .map(|(idx, x)| (cosine_similarity(query, &x.embedding), idx))
+            .collect();
+
+        ranked.sort_by(|a, b| b.0.total_cmp(&a.0));
+        ranked.truncate(n);
+
+        Ok(ranked
+            .into_iter()
+            .map(|(score, idx)| {
+                let route = SemanticRoute {
+                    tag: stored[idx].tag.clone(),
+                    metadata: stored[idx].metadata.clone(),
+                };
+
+                (score, idx.to_string(), route, stored[idx].utterance.clone())
+            })
+            .collect())
+    }
+}
+
+impl<M> VectorStoreIndex for InMemoryRouteIndex<M>
+where
+    M: EmbeddingModel,
+{
+    async fn top_n<T: for<'a> Deserialize<'a> + Send>(
+        &self,
+        query: &str,
+        n: usize,
+    ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
+        let query = self.model.embed_text(query).await?;
+
+        self.rank(&query.vec, n)?
+            .into_iter()
+            .map(
+                |(score, id, route, utterance)| -> Result<_, VectorStoreError> {
+                    // Documents have the same shape as a route stored in an external store, with the utterance alongside the tag
+                    let mut doc = serde_json::to_value(route)?;
+                    if let Value::Object(fields) = &mut doc {
+                        fields.insert(UTTERANCE_FIELD.to_string(), Value::String(utterance));
+                    }
+
+                    Ok((score, id, serde_json::from_value(doc)?))
+                },
+            )
+            .collect()
+    }
+
+    async fn top_n_ids(
+        &self,
+        query: &str,
+        n: usize,
+    ) -> Result<Vec<(f64, String)>, VectorStoreError> {
+        let query = self.model.embed_text(query).await?;
+
+        Ok(self
+            .rank(&query.vec, n)?
+            .into_iter()
+            .map(|(score, id, ..)| (score, id))
+            .collect())
+    }
+}
+
+fn cosine_similarity(a: &[f64], b: &[f64]) -> f64 {
+    let dot: f64 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a = a.iter().map(|x| x * x).sum::<f64>().sqrt();
+    let norm_b = b.iter().map(|x| x * x).sum::<f64>().sqrt();
+
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return 0.0;
+    }
+
+    dot / (norm_a * norm_b)
+}
+
+#[cfg(test)]
+mod tests {
+    use rig::vector_store::VectorStoreIndex;
+    use serde_json::Value;
+
+    use super::{InMemoryRouteIndex, InsertRoutes, RouteUtterance, cosine_similarity};
+    use crate::routing::{SemanticRoute, SemanticRouter, SemanticRouterError, UTTERANCE_FIELD};
+    use crate::test_utils::WordCounts;
+
+    const WORDS: &[&str] = &["invoice", "payment", "error"];
+
+    #[test]
+    fn computes_cosine_similarity() {
+        assert!((cosine_similarity(&[1.0, 2.0], &[2.0, 4.0]) - 1.0).abs() < 1e-9);
+        assert!((cosine_similarity(&[3.0, 4.0], &[4.0, 3.0]) - 0.96).abs() < 1e-9);
+        assert_eq!(cosine_similarity(&[1.0, 0.0], &[0.0, 3.0]), 0.0);
+        assert!((cosine_similarity(&[1.0, 1.0], &[-1.0, -1.0]) + 1.0).abs() < 1e-9);
+        assert_eq!(cosine_similarity(&[0.0, 0.0], &[1.0, 1.0]), 0.0);
+    }
+
+    #[test]
+    fn rejects_embeddings_of_other_models() {
+        let utterance = RouteUtterance {
+            tag: "billing".into(),
+            metadata: Default::default(),
+            utterance: "invoice".into(),
+            embedding: vec![1.0, 0.0],
+        };
+
+        let res = InMemoryRouteIndex::from_utterances(WordCounts(WORDS), vec![utterance]);
+        assert!(matches!(
+            res,
+            Err(SemanticRouterError::DimensionMismatch {
+                expected: 3,
+                found: 2
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn stores_utterances_alongside_routes() {
+        let index = InMemoryRouteIndex::new(WordCounts(WORDS));
+        let route = SemanticRoute::new("billing").with_metadata("team", "finance");
+        index
+            .insert_route(
+                &route,
+                vec!["invoice".into(), "payment".into(), "error".into()],
+            )
+            .await
+            .unwrap();
+        index
+            .insert_route(&"support".into(), vec!["error".into()])
+            .await
+            .unwrap();
+
+        let res = index.top_n::<Value>("invoice", 2).await.unwrap();
+        assert_eq!(res.len(), 2);
+        let (score, id, doc) = &res[0];
+        assert_eq!((*score, id.as_str()), (1.0, "0"));
+        assert_eq!(doc["tag"], "billing");
+        assert_eq!(doc["team"], "finance");
+        assert_eq!(doc[UTTERANCE_FIELD], "invoice");
+
+        // The router treats everything but the utterance as metadata
+        let router = SemanticRouter::builder().store(index).build().unwrap();
+        let routes = router.top_routes("invoice error", 2).await.unwrap();
+        let tags: Vec<&str> = routes.iter().map(|x| x.tag.as_str()).collect();
+        assert_eq!(tags, ["billing", "support"]);
+        assert_eq!(routes[0].metadata.len(), 1);
+        assert_eq!(routes[0].metadata["team"], "finance");
+        assert!(routes[1].metadata.is_empty());
+    }
+}
@@ -0,0 +1,198 @@
+//! Splitting of multi-intent queries ("cancel my order and update my address"), so that each intent can be routed on its own.
+use std::sync::{Arc, LazyLock};
+
+use futures::future::BoxFuture;
+use regex::Regex;
+
+use super::RouteAgent;
+
+/// Splits a query into its separate intents.
+///
+/// [`ConjunctionSplitter`] is a cheap heuristic, and [`LlmSplitter`] asks a (small) agent to do the splitting.
+pub trait IntentSplitter: Send + Sync {
+    /// Split a query into intents, in the order they appear. A query with a single intent is returned as the only element.
+    fn split<'a>(&'a self, query: &'a str) -> BoxFuture<'a, Vec<String>>;
+}
+
+/// Conjunctions and separators that usually join separate requests.
+static SEPARATORS: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)\s*(?:;|\band then\b|\band also\b|\band\b|\balso\b)\s*")
+        .expect("separator pattern is valid")
+});
+
+/// Splits queries on conjunctions ("and", "also", "and then") and semicolons.
+///
+/// Fragments with fewer than `min_words` words are joined back onto the previous part, so that "salt and pepper" or
+/// "terms and conditions" aren't split up.
+#[derive(Debug, Clone)]
+pub struct ConjunctionSplitter {
+    min_words: usize,
+}
+
+impl Default for ConjunctionSplitter {
+    fn default() -> Self {
+        Self { min_words: 3 }
+    }
+}
+
+impl ConjunctionSplitter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set how many words a fragment needs to count as an intent of its own.
+    pub fn min_words(mut self, min_words: usize) -> Self {
+        self.min_words = min_words;
+
+        self
+    }
+
+    fn split_query(&self, query: &str) -> Vec<String> {
+        let mut fragments = Vec::new();
+        let mut last = 0;
+        for separator in SEPARATORS.find_iter(query) {
+            fragments.push(last..separator.start());
+            last = separator.end();
+        }
+        fragments.push(last..query.len());
+
+        // Spans of the original query, so that merged fragments keep the conjunction between them
+        let mut spans: Vec<(usize, usize)> = Vec::new();
+        for range in fragments {
+            let fragment = &query[range.clone()];
+            let trimmed = fragment.trim_start_matches(|c: char| c.is_whitespace() || c == ',');
+            if trimmed.trim_end().is_empty() {
+                continue;
+            }
+            let start = range.start + fragment.len() - trimmed.len();
+            let end = range.start + fragment.trim_end().len();
+
+            match spans.last_mut() {
+                Some(previous)
+                    if self.is_short(&query[start..end])
+                        || self.is_short(&query[previous.0..previous.1]) =>
+                {
+                    previous.1 = end
+                }
+                _ => spans.push((start, end)),
+            }
+        }
+
+        if spans.is_empty() {
+            return vec![query.trim().to_string()];
+        }
+
+        spans
+            .into_iter()
+            .map(|(start, end)| query[start..end].to_string())
+            .collect()
+    }
+
+    fn is_short(&self, fragment: &str) -> bool {
+        fragment.split_whitespace().count() < self.min_words
+    }
+}
+
+impl IntentSplitter for ConjunctionSplitter {
+    fn split<'a>(&'a self, query: &'a str) -> BoxFuture<'a, Vec<String>> {
+        Box::pin(async move { self.split_query(query) })
+    }
+}
+
+/// Splits queries by asking an agent to rewrite them as one request per line.
+/// If prompting fails, or the agent returns nothing, the query is kept whole.
+#[derive(Clone)]
+pub struct LlmSplitter {
+    agent: Arc<dyn RouteAgent>,
+}
+
+impl LlmSplitter {
+    pub fn new<A>(agent: A) -> Self
+    where
+        A: RouteAgent + 'static,
+    {
+        Self {
+            agent: Arc::new(agent),
+        }
+    }
+
+    fn prompt(query: &str) -> String {
+        format!(
+            "Split the following query into the separate requests it contains. Write each request as a standalone sentence on its own line, in the order they appear, with no numbering or other text. If the query contains a single request, repeat it unchanged.\n\nQuery: {query}"
+        )
+    }
+
+    /// Parse one intent per line, ignoring blank lines and list markers.
+    fn parse(response: &str) -> Vec<String> {
+        response
+            .lines()
+            .map(|line| {
+                line.trim()
+                    .trim_start_matches(|c: char| {
+                        c.is_ascii_digit() || matches!(c, '-' | '*' | '.' | ')')
+                    })
+                    .trim()
+                    .to_string()
+            })
+            .filter(|line| !line.is_empty())
+            .collect()
+    }
+}
+
+impl IntentSplitter for LlmSplitter {
+    fn split<'a>(&'a self, query: &'a str) -> BoxFuture<'a, Vec<String>> {
+        Box::pin(async move {
+            let parts = match self.agent.dyn_prompt(Self::prompt(query), 0).await {
+                Ok(response) => Self::parse(&response),
+                Err(err) => {
+                    tracing::warn!("Splitting query into intents failed: {err}");
+                    Vec::new()
+                }
+            };
+
+            if parts.is_empty() {
+                return vec![query.to_string()];
+            }
+
+            parts
+        })
+    }
+}
+
+impl std::fmt::Debug for LlmSplitter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LlmSplitter").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ConjunctionSplitter, LlmSplitter};
+
+    #[test]
+    fn splits_intents() {
+        let splitter = ConjunctionSplitter::new();
+
+        assert_eq!(
+            splitter.split_query("Cancel my order and update my address"),
+            vec!["Cancel my order", "update my address"]
+        );
+        assert_eq!(
+            splitter.split_query("Where is my refund; also, can I change my plan?"),
+            vec!["Where is my refund", "can I change my plan?"]
+        );
+        assert_eq!(
+            splitter.split_query("Do you sell salt and pepper grinders?"),
+            vec!["Do you sell salt and pepper grinders?"]
+        );
+        assert_eq!(
+            splitter.split_query("Hi and please reset my password"),
+            vec!["Hi and please reset my password"]
+        );
+
+        assert_eq!(
+            LlmSplitter::parse("1. Cancel my order\n\n- Update my address\n"),
+            vec!["Cancel my order", "Update my address"]
+        );
+    }
+}
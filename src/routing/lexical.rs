@@ -0,0 +1,184 @@
+//! Lexical (keyword) matching, combined with semantic scores for hybrid routing.
+//!
+//! Embeddings can be ambiguous for short queries or domain-specific terms. Routes with distinctive keywords ("refund", "invoice #")
+//! can be given exact phrases and regex patterns, which match the route outright, and keywords, which are scored against the query
+//! and blended with the embedding score.
+use std::collections::HashMap;
+
+use regex::Regex;
+
+/// BM25 term frequency saturation.
+const K1: f64 = 1.2;
+
+/// The lexical patterns of a single route.
+#[derive(Debug, Clone, Default)]
+struct RoutePatterns {
+    /// Lowercased phrases that match if they appear anywhere in the query.
+    phrases: Vec<String>,
+    patterns: Vec<Regex>,
+    /// Lowercased keywords, scored BM25-style.
+    keywords: Vec<String>,
+}
+
+impl RoutePatterns {
+    /// Whether one of the route's phrases appears in the query, or one of its patterns matches it.
+    fn is_exact_match(&self, query: &str) -> bool {
+        let lowercase = query.to_lowercase();
+
+        self.phrases.iter().any(|x| lowercase.contains(x.as_str()))
+            || self.patterns.iter().any(|x| x.is_match(query))
+    }
+}
+
+/// Scores queries against the lexical patterns of each route.
+#[derive(Debug, Clone)]
+pub(super) struct LexicalMatcher {
+    routes: HashMap<String, RoutePatterns>,
+    /// How much of the combined score comes from the lexical score, between 0 and 1.
+    weight: f64,
+}
+
+impl LexicalMatcher {
+    pub(super) fn new(weight: f64) -> Self {
+        Self {
+            routes: HashMap::new(),
+            weight: weight.clamp(0.0, 1.0),
+        }
+    }
+
+    pub(super) fn set_weight(&mut self, weight: f64) {
+        self.weight = weight.clamp(0.0, 1.0);
+    }
+
+    pub(super) fn is_empty(&self) -> bool {
+        self.routes.is_empty()
+    }
+
+    pub(super) fn add_phrase(&mut self, tag: &str, phrase: &str) {
+        self.route(tag).phrases.push(phrase.to_lowercase());
+    }
+
+    pub(super) fn add_pattern(&mut self, tag: &str, pattern: Regex) {
+        self.route(tag).patterns.push(pattern);
+    }
+
+    pub(super) fn add_keyword(&mut self, tag: &str, keyword: &str) {
+        self.route(tag).keywords.push(keyword.to_lowercase());
+    }
+
+    fn route(&mut self, tag: &str) -> &mut RoutePatterns {
+        self.routes.entry(tag.to_string()).or_default()
+    }
+
+    /// The routes that have lexical patterns.
+    pub(super) fn tags(&self) -> impl Iterator<Item = &str> {
+        self.routes.keys().map(String::as_str)
+    }
+
+    /// Combine a route's semantic score with its lexical score for a query.
+    /// A matching phrase or regex is a full match, scoring 1. Keyword scores are blended with the semantic score by weight.
+    /// The lexical score can only raise a route's score, so routes without (matching) patterns are unaffected.
+    pub(super) fn combine(&self, tag: &str, query: &str, semantic: f64) -> f64 {
+        let Some(route) = self.routes.get(tag) else {
+            return semantic;
+        };
+
+        if route.is_exact_match(query) {
+            return semantic.max(1.0);
+        }
+
+        let blended =
+            (1.0 - self.weight) * semantic + self.weight * self.keyword_score(route, query);
+
+        semantic.max(blended)
+    }
+
+    /// The lexical score of a route for a query, between 0 and 1.
+    /// A matching phrase or regex scores 1. Otherwise, keywords are scored with BM25-style term weighting,
+    /// normalised by the best possible score for the route.
+    pub(super) fn score(&self, tag: &str, query: &str) -> f64 {
+        let Some(route) = self.routes.get(tag) else {
+            return 0.0;
+        };
+
+        if route.is_exact_match(query) {
+            return 1.0;
+        }
+
+        self.keyword_score(route, query)
+    }
+
+    /// The BM25-style score of a route's keywords for a query, between 0 and 1.
+    fn keyword_score(&self, route: &RoutePatterns, query: &str) -> f64 {
+        if route.keywords.is_empty() {
+            return 0.0;
+        }
+
+        let lowercase = query.to_lowercase();
+        let terms: Vec<&str> = lowercase
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|x| !x.is_empty())
+            .collect();
+
+        let (score, max_score) =
+            route
+                .keywords
+                .iter()
+                .fold((0.0, 0.0), |(score, max_score), keyword| {
+                    let idf = self.idf(keyword);
+                    let tf = terms.iter().filter(|x| **x == keyword.as_str()).count() as f64;
+                    let saturated = tf * (K1 + 1.0) / (tf + K1);
+
+                    (score + idf * saturated, max_score + idf * (K1 + 1.0))
+                });
+
+        if max_score == 0.0 {
+            return 0.0;
+        }
+
+        (score / max_score).min(1.0)
+    }
+
+    /// Keywords shared by many routes are less distinctive, so they're weighted lower.
+    fn idf(&self, keyword: &str) -> f64 {
+        let routes = self.routes.len() as f64;
+        let containing = self
+            .routes
+            .values()
+            .filter(|x| x.keywords.iter().any(|k| k == keyword))
+            .count() as f64;
+
+        (1.0 + (routes - containing + 0.5) / (containing + 0.5)).ln()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use regex::Regex;
+
+    use super::LexicalMatcher;
+
+    #[test]
+    fn scores_lexical_matches() {
+        let mut matcher = LexicalMatcher::new(0.5);
+        matcher.add_phrase("billing", "Invoice #");
+        matcher.add_keyword("billing", "refund");
+        matcher.add_keyword("billing", "charge");
+        matcher.add_pattern("orders", Regex::new(r"\bORD-\d+\b").unwrap());
+        matcher.add_keyword("orders", "refund");
+
+        assert_eq!(matcher.score("billing", "Where is invoice #123?"), 1.0);
+        assert_eq!(matcher.score("orders", "What happened to ORD-42"), 1.0);
+        assert_eq!(matcher.score("billing", "What's the weather?"), 0.0);
+
+        // "charge" is unique to billing, so it outweighs the shared "refund"
+        let refund = matcher.score("billing", "I want a refund");
+        let charge = matcher.score("billing", "Why is there a charge?");
+        assert!(refund > 0.0 && charge > refund);
+
+        assert_eq!(matcher.combine("billing", "invoice #1", 0.4), 1.0);
+        assert_eq!(matcher.combine("billing", "hello", 0.4), 0.4);
+        assert!(matcher.combine("billing", "refund charge", 0.4) > 0.4);
+        assert_eq!(matcher.combine("support", "invoice #1", 0.4), 0.4);
+    }
+}
@@ -0,0 +1,240 @@
+//! Telemetry for routing decisions.
+//!
+//! Every decision made by [`SemanticRouter::route`](super::SemanticRouter::route) is recorded in a `route_decision` tracing span.
+//! For aggregate numbers (hit counts per route, score distributions, how often fallbacks are used and response latency),
+//! set a [`RouterMetrics`] sink on the router builder. [`InMemoryMetrics`] is a simple sink that keeps counters in memory.
+use std::{collections::HashMap, sync::Mutex, time::Duration};
+
+use super::RouteMatch;
+
+/// The number of buckets in a score histogram. Bucket `i` counts scores in `[i / SCORE_BUCKETS, (i + 1) / SCORE_BUCKETS)`.
+pub const SCORE_BUCKETS: usize = 10;
+
+/// How a route was picked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DecisionSource {
+    /// The best candidate scored above its threshold.
+    Matched,
+    /// No candidate scored above its threshold, so the LLM classifier picked the route.
+    LlmClassifier,
+    /// Neither a candidate nor the classifier matched, so the default route was used.
+    DefaultRoute,
+    /// Nothing matched.
+    NoMatch,
+    /// A guardrail route matched, so the query was refused.
+    Guardrail,
+    /// A rule fired, so the route was picked without querying the store.
+    Rule,
+    /// A [`RouterSession`](super::RouterSession) kept the route of the previous turn, as no other route scored high enough to switch to.
+    Session,
+}
+
+impl DecisionSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Matched => "matched",
+            Self::LlmClassifier => "llm_classifier",
+            Self::DefaultRoute => "default_route",
+            Self::NoMatch => "no_match",
+            Self::Guardrail => "guardrail",
+            Self::Rule => "rule",
+            Self::Session => "session",
+        }
+    }
+
+    /// Whether the route was picked by a fallback rather than by its score.
+    pub fn is_fallback(&self) -> bool {
+        matches!(self, Self::LlmClassifier | Self::DefaultRoute)
+    }
+}
+
+/// A single routing decision.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RouteDecision {
+    /// The route that was picked, if any.
+    pub route: Option<RouteMatch>,
+    /// The best scoring candidate, whether or not it scored above its threshold.
+    pub best: Option<RouteMatch>,
+    pub source: DecisionSource,
+    /// How far the picked route's score is ahead of the next best candidate's, if it was picked on score and had a competitor.
+    pub margin: Option<f64>,
+    /// How long the decision took, including any fallbacks.
+    pub duration: Duration,
+}
+
+/// A sink for routing metrics, ie to export them to Prometheus or OpenTelemetry.
+pub trait RouterMetrics: Send + Sync {
+    /// Called for every routing decision.
+    fn record_decision(&self, decision: &RouteDecision);
+
+    /// Called when a routed query has been answered (or has failed), with the end-to-end latency including routing.
+    /// `route` is `None` if the query was answered by the default agent.
+    fn record_response(&self, route: Option<&str>, latency: Duration, success: bool) {
+        let _ = (route, latency, success);
+    }
+
+    /// Called when a query to a route with several agent variants has been answered, with the variant that served it.
+    fn record_variant(&self, route: &str, variant: &str) {
+        let _ = (route, variant);
+    }
+}
+
+/// Metrics of a single route.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RouteStats {
+    /// How many queries were routed to the route.
+    pub hits: u64,
+    /// A histogram of the scores of the queries routed to the route.
+    pub scores: [u64; SCORE_BUCKETS],
+    pub responses: u64,
+    pub errors: u64,
+    pub total_latency: Duration,
+    pub max_latency: Duration,
+    /// How many responses each agent variant served, if the route has several.
+    pub variants: HashMap<String, u64>,
+}
+
+impl RouteStats {
+    pub fn mean_latency(&self) -> Option<Duration> {
+        (self.responses > 0).then(|| self.total_latency / self.responses as u32)
+    }
+}
+
+/// A snapshot of the metrics collected by [`InMemoryMetrics`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MetricsSnapshot {
+    pub decisions: u64,
+    /// Decisions where the route was picked by the LLM classifier or the default route.
+    pub fallbacks: u64,
+    /// Decisions where no route was picked at all.
+    pub unmatched: u64,
+    /// Metrics per route. Queries answered by the default agent are tracked under an empty tag.
+    pub routes: HashMap<String, RouteStats>,
+}
+
+impl MetricsSnapshot {
+    /// The share of decisions that needed a fallback, between 0 and 1.
+    pub fn fallback_rate(&self) -> f64 {
+        if self.decisions == 0 {
+            return 0.0;
+        }
+
+        self.fallbacks as f64 / self.decisions as f64
+    }
+}
+
+/// A [`RouterMetrics`] sink that keeps counters in memory.
+#[derive(Debug, Default)]
+pub struct InMemoryMetrics {
+    snapshot: Mutex<MetricsSnapshot>,
+}
+
+impl InMemoryMetrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The metrics collected so far.
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        self.snapshot.lock().map(|x| x.clone()).unwrap_or_default()
+    }
+}
+
+impl RouterMetrics for InMemoryMetrics {
+    fn record_decision(&self, decision: &RouteDecision) {
+        let Ok(mut snapshot) = self.snapshot.lock() else {
+            return;
+        };
+
+        snapshot.decisions += 1;
+        if decision.source.is_fallback() {
+            snapshot.fallbacks += 1;
+        }
+
+        let Some(route) = &decision.route else {
+            snapshot.unmatched += 1;
+            return;
+        };
+
+        let stats = snapshot.routes.entry(route.tag.clone()).or_default();
+        let bucket = (route.score.clamp(0.0, 1.0) * SCORE_BUCKETS as f64) as usize;
+
+        stats.hits += 1;
+        stats.scores[bucket.min(SCORE_BUCKETS - 1)] += 1;
+    }
+
+    fn record_response(&self, route: Option<&str>, latency: Duration, success: bool) {
+        let Ok(mut snapshot) = self.snapshot.lock() else {
+            return;
+        };
+
+        let stats = snapshot
+            .routes
+            .entry(route.unwrap_or_default().to_string())
+            .or_default();
+
+        stats.responses += 1;
+        if !success {
+            stats.errors += 1;
+        }
+        stats.total_latency += latency;
+        stats.max_latency = stats.max_latency.max(latency);
+    }
+
+    fn record_variant(&self, route: &str, variant: &str) {
+        let Ok(mut snapshot) = self.snapshot.lock() else {
+            return;
+        };
+
+        let stats = snapshot.routes.entry(route.to_string()).or_default();
+        *stats.variants.entry(variant.to_string()).or_default() += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{DecisionSource, InMemoryMetrics, RouteDecision, RouterMetrics};
+    use crate::routing::RouteMatch;
+
+    fn decision(tag: Option<&str>, score: f64, source: DecisionSource) -> RouteDecision {
+        let route = tag.map(|tag| RouteMatch::new(tag, score));
+
+        RouteDecision {
+            best: route.clone(),
+            route,
+            source,
+            margin: None,
+            duration: Duration::from_millis(5),
+        }
+    }
+
+    #[test]
+    fn aggregates_decisions() {
+        let metrics = InMemoryMetrics::new();
+
+        metrics.record_decision(&decision(Some("billing"), 0.93, DecisionSource::Matched));
+        metrics.record_decision(&decision(Some("billing"), 1.0, DecisionSource::Matched));
+        metrics.record_decision(&decision(
+            Some("support"),
+            0.4,
+            DecisionSource::DefaultRoute,
+        ));
+        metrics.record_decision(&decision(None, 0.2, DecisionSource::NoMatch));
+        metrics.record_response(Some("billing"), Duration::from_millis(100), true);
+        metrics.record_response(Some("billing"), Duration::from_millis(300), false);
+
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.decisions, 4);
+        assert_eq!(snapshot.unmatched, 1);
+        assert_eq!(snapshot.fallback_rate(), 0.25);
+
+        let billing = &snapshot.routes["billing"];
+        assert_eq!(billing.hits, 2);
+        assert_eq!(billing.scores[9], 2);
+        assert_eq!(billing.errors, 1);
+        assert_eq!(billing.mean_latency(), Some(Duration::from_millis(200)));
+        assert_eq!(snapshot.routes["support"].scores[4], 1);
+    }
+}
@@ -0,0 +1,52 @@
+//! Hooks that run before routing and after a routed query is answered.
+use std::sync::Arc;
+
+use super::RouteContext;
+
+/// Rewrites a query before it's routed, ie to scrub PII or expand abbreviations. The rewritten query is also what the agent receives.
+pub type PreRouteHook = Arc<dyn Fn(&str) -> String + Send + Sync>;
+
+/// Post-processes the response to a routed query, ie to format it. Receives the context the agent (or handler) was called with.
+pub type PostResponseHook = Arc<dyn Fn(&RouteContext, String) -> String + Send + Sync>;
+
+/// The hooks of a router, run in the order they were added.
+#[derive(Clone, Default)]
+pub(super) struct Middleware {
+    pre: Vec<PreRouteHook>,
+    post: Vec<PostResponseHook>,
+}
+
+impl Middleware {
+    pub(super) fn add_pre(&mut self, hook: PreRouteHook) {
+        self.pre.push(hook);
+    }
+
+    pub(super) fn add_post(&mut self, hook: PostResponseHook) {
+        self.post.push(hook);
+    }
+
+    pub(super) fn has_post(&self) -> bool {
+        !self.post.is_empty()
+    }
+
+    pub(super) fn rewrite(&self, query: &str) -> String {
+        self.pre
+            .iter()
+            .fold(query.to_string(), |query, hook| hook(&query))
+    }
+
+    pub(super) fn process(&self, ctx: &RouteContext, response: String) -> String {
+        self.post
+            .iter()
+            .fold(response, |response, hook| hook(ctx, response))
+    }
+}
+
+impl std::fmt::Debug for Middleware {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Middleware")
+            .field("pre", &self.pre.len())
+            .field("post", &self.post.len())
+            .finish()
+    }
+}
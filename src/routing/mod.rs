@@ -0,0 +1,1798 @@
+//! This module provides an abstraction for semantic routing.
+//!
+//! Example usage can be found in the `routing` example on the repository: <https://github.com/joshua-mo-143/rig-extra/blob/main/examples/routing.rs>
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+
+use crate::PromptTemplate;
+use serde_json::{Map, Value};
+use tracing::Instrument;
+
+use rig::{
+    completion::PromptError,
+    embeddings::EmbeddingModel,
+    vector_store::{VectorStoreError, VectorStoreIndex},
+};
+
+mod cache;
+mod classifier;
+pub mod config;
+mod escalation;
+pub mod eval;
+pub mod explain;
+mod failover;
+mod handler;
+pub mod index;
+mod intents;
+mod lexical;
+pub mod metrics;
+mod middleware;
+mod multi;
+pub mod rerank;
+mod rules;
+mod score;
+mod session;
+mod state;
+mod variants;
+
+pub use crate::agents::{AgentStream as RouteStream, DynAgent as RouteAgent};
+pub use cache::RouteCache;
+pub use config::RouterConfig;
+pub use escalation::{Escalation, EscalationPolicy};
+pub use eval::{EvaluationReport, RouterEvaluator};
+pub use explain::Explanation;
+pub use failover::FailoverPolicy;
+pub use handler::{RouteContext, RouteHandler};
+pub use index::{BatchRouteQuery, InMemoryRouteIndex, InsertRoutes, RouteUtterance};
+pub use intents::{ConjunctionSplitter, IntentSplitter, LlmSplitter};
+pub use metrics::{InMemoryMetrics, RouteDecision, RouterMetrics};
+pub use middleware::{PostResponseHook, PreRouteHook};
+pub use multi::MultiRouteIndex;
+pub use rerank::Reranker;
+pub use score::{Aggregation, ScoreTransform};
+pub use session::RouterSession;
+
+use classifier::LlmClassifier;
+use handler::{NestedRouter, RouteTarget};
+use lexical::LexicalMatcher;
+use metrics::DecisionSource;
+use middleware::Middleware;
+use rules::{RouteRules, RuleKind};
+use variants::AgentVariant;
+
+/// How many utterances to retrieve per requested route in [`SemanticRouter::top_routes`].
+const CANDIDATE_MULTIPLIER: usize = 4;
+
+/// How many semantic candidates are re-scored when lexical matching is enabled.
+const HYBRID_CANDIDATES: usize = 5;
+
+/// The default share of the combined score that comes from lexical matching.
+const DEFAULT_LEXICAL_WEIGHT: f64 = 0.3;
+
+/// The core semantic router abstraction.
+/// Contains a vector store index and a cosine similarity score threshold.
+pub struct SemanticRouter<V> {
+    store: V,
+    threshold: f64,
+    /// Thresholds for individual routes, overriding the global threshold.
+    route_thresholds: HashMap<String, f64>,
+    /// The route used when no route scores above the threshold.
+    default_route: Option<String>,
+    /// Classifies queries that don't score above the threshold, before falling back to the default route.
+    llm_fallback: Option<LlmClassifier>,
+    /// Keyword matching that is blended with the semantic score, if any lexical patterns were set.
+    lexical: Option<LexicalMatcher>,
+    /// Rules that route queries before the vector store is queried.
+    rules: RouteRules,
+    metrics: Option<Arc<dyn RouterMetrics>>,
+    /// Converts raw store scores into similarities before they're compared to thresholds.
+    score_transform: ScoreTransform,
+    /// Combines the scores of a route's utterances into the route's score.
+    aggregation: Aggregation,
+    /// Refusal responses of guardrail routes, by tag.
+    guardrails: HashMap<String, String>,
+    middleware: Middleware,
+    /// Candidates of recent queries, if caching is enabled.
+    cache: Option<RouteCache>,
+    /// Rescores candidates before thresholds are applied, if set.
+    reranker: Option<Arc<dyn Reranker>>,
+    /// Route descriptions, used as the documents for the reranker.
+    descriptions: HashMap<String, String>,
+}
+
+/// An abstraction over [`SemanticRouter`] that additionally contains Rig agents.
+/// Agents are stored type-erased as [`RouteAgent`]s, so each route can use a different completion model (or provider).
+/// Routes can also be handled by plain async functions (see [`SemanticRouterWithAgents::handler`]).
+pub struct SemanticRouterWithAgents<V> {
+    router: SemanticRouter<V>,
+    agents: HashMap<String, RouteTarget>,
+    /// The agent (or handler) used when no route matches.
+    default_agent: Option<RouteTarget>,
+    /// Templates that queries are rendered into before they're sent to the agent of a route.
+    templates: HashMap<String, PromptTemplate>,
+    /// Timeouts and fallbacks for the targets of routes, by tag.
+    failover: HashMap<String, FailoverPolicy>,
+    /// Splits multi-intent queries into parts that are routed separately, if set.
+    splitter: Option<Arc<dyn IntentSplitter>>,
+}
+
+impl<V> SemanticRouter<V> {
+    /// Create an instance of [`SemanticRouterBuilder`].
+    pub fn builder() -> SemanticRouterBuilder<V> {
+        SemanticRouterBuilder::new()
+    }
+}
+
+impl<V> SemanticRouter<V>
+where
+    V: VectorStoreIndex,
+{
+    pub async fn prompt(&self, query: &str) -> Option<String> {
+        self.route(query).await.ok()?.map(|x| x.tag)
+    }
+
+    /// The score threshold for a route. This is the global threshold unless a threshold was set for the route.
+    pub fn threshold(&self, tag: &str) -> f64 {
+        self.route_thresholds
+            .get(tag)
+            .copied()
+            .unwrap_or(self.threshold)
+    }
+
+    /// How raw store scores are converted into similarities. See [`ScoreTransform`].
+    pub fn score_transform(&self) -> ScoreTransform {
+        self.score_transform
+    }
+
+    /// How the scores of a route's utterances are combined. See [`Aggregation`].
+    pub fn aggregation(&self) -> Aggregation {
+        self.aggregation
+    }
+
+    /// How many utterances to retrieve to end up with `k` distinct routes, with enough utterances per route to aggregate.
+    fn candidate_count(&self, k: usize) -> usize {
+        k.saturating_mul(CANDIDATE_MULTIPLIER.max(self.aggregation.depth()))
+    }
+
+    /// Calibrate the score transform from a sample of typical queries, so that the lowest and highest scores of their
+    /// candidate routes map to 0 and 1. If the router was set up with [`ScoreTransform::DistanceToSimilarity`], the raw
+    /// scores are treated as distances. Returns the new transform.
+    pub async fn calibrate<I, S>(&mut self, queries: I) -> Result<ScoreTransform, VectorStoreError>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut samples = Vec::new();
+
+        for query in queries {
+            let res = self
+                .store
+                .top_n_ids(query.as_ref(), self.candidate_count(HYBRID_CANDIDATES))
+                .await?;
+
+            samples.extend(res.into_iter().map(|(score, _)| score));
+        }
+
+        let is_distance = self.score_transform == ScoreTransform::DistanceToSimilarity;
+        self.score_transform = ScoreTransform::calibrate(&samples, is_distance);
+
+        Ok(self.score_transform)
+    }
+
+    /// Set the score threshold for a single route, overriding the global threshold.
+    pub fn set_route_threshold(&mut self, tag: &str, threshold: f64) {
+        self.route_thresholds.insert(tag.to_string(), threshold);
+    }
+
+    /// The route used when no route scores above the threshold, if one is set.
+    pub fn default_route(&self) -> Option<&str> {
+        self.default_route.as_deref()
+    }
+
+    /// Add a route at runtime by embedding its example utterances and inserting them into the store.
+    /// The route can be a tag, or a [`SemanticRoute`] with metadata.
+    pub async fn add_route<R, I, S>(&self, route: R, utterances: I) -> Result<(), VectorStoreError>
+    where
+        V: InsertRoutes,
+        R: Into<SemanticRoute>,
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let utterances = utterances.into_iter().map(Into::into).collect();
+
+        self.store.insert_route(&route.into(), utterances).await?;
+
+        // Cached candidates don't include the new route
+        if let Some(cache) = &self.cache {
+            cache.clear();
+        }
+
+        Ok(())
+    }
+
+    /// Retrieve up to `k` candidate routes for a query, ranked by score (highest first).
+    /// Each route appears at most once, with the (transformed) score of its best matching utterance. Thresholds are not applied.
+    pub async fn top_routes(
+        &self,
+        query: &str,
+        k: usize,
+    ) -> Result<Vec<RouteMatch>, VectorStoreError> {
+        // Routes usually have several utterances, so fetch extra results to end up with `k` distinct routes
+        let res = self
+            .store
+            .top_n::<SemanticRoute>(query, self.candidate_count(k))
+            .await?;
+
+        Ok(self.aggregate(res, k))
+    }
+
+    /// Group retrieved utterances by route, combining the (transformed) scores of each route with the router's [`Aggregation`],
+    /// and return the top `k` routes.
+    fn aggregate(&self, res: Vec<(f64, String, SemanticRoute)>, k: usize) -> Vec<RouteMatch> {
+        let mut grouped: Vec<(RouteMatch, Vec<f64>)> = Vec::new();
+        for (score, _, SemanticRoute { tag, mut metadata }) in res {
+            let score = self.score_transform.apply(score);
+            metadata.remove(UTTERANCE_FIELD);
+
+            match grouped.iter_mut().find(|(x, _)| x.tag == tag) {
+                Some((_, scores)) => scores.push(score),
+                None => grouped.push((
+                    RouteMatch {
+                        tag,
+                        score: 0.0,
+                        metadata,
+                    },
+                    vec![score],
+                )),
+            }
+        }
+
+        let mut matches: Vec<RouteMatch> = grouped
+            .into_iter()
+            .map(|(mut route, scores)| {
+                route.score = self.aggregation.apply(&scores);
+                route
+            })
+            .collect();
+
+        matches.sort_by(|a, b| b.score.total_cmp(&a.score));
+        matches.truncate(k);
+
+        matches
+    }
+
+    /// Retrieve the best matching route (including its metadata), if its score is above the threshold.
+    /// Otherwise, falls back to the LLM classifier and then the default route (if set).
+    /// Routes picked by a fallback have no metadata, and the score of the best semantic match.
+    /// The query is rewritten by the pre-route hooks first, if any are set. If a rule fires for the rewritten query,
+    /// its route is used (with a score of 1) without querying the store.
+    pub async fn route(&self, query: &str) -> Result<Option<RouteMatch>, VectorStoreError> {
+        self.route_rewritten(&self.middleware.rewrite(query)).await
+    }
+
+    /// Route a query that the pre-route hooks have already been applied to.
+    async fn route_rewritten(&self, query: &str) -> Result<Option<RouteMatch>, VectorStoreError> {
+        Ok(self.decision(query).await?.route)
+    }
+
+    /// Make (and record) the routing decision for a query that the pre-route hooks have already been applied to.
+    async fn decision(&self, query: &str) -> Result<RouteDecision, VectorStoreError> {
+        let span = decision_span(query);
+        let start = Instant::now();
+
+        let decision = async {
+            if let Some(decision) = self.rule_decision(query, start) {
+                return Ok(decision);
+            }
+
+            let candidates = self.scored_routes(query).await?;
+
+            Ok::<_, VectorStoreError>(self.decide(query, candidates, start).await)
+        }
+        .instrument(span.clone())
+        .await?;
+
+        self.record_decision(&span, &decision);
+
+        Ok(decision)
+    }
+
+    /// Route a batch of queries, ie to classify logs or a dataset offline.
+    /// The queries are embedded in as few calls as possible, rather than one at a time. Each query is otherwise routed like with [`Self::route`].
+    pub async fn route_many<S>(
+        &self,
+        queries: &[S],
+    ) -> Result<Vec<Option<RouteMatch>>, VectorStoreError>
+    where
+        V: BatchRouteQuery,
+        S: AsRef<str>,
+    {
+        let queries: Vec<String> = queries
+            .iter()
+            .map(|x| self.middleware.rewrite(x.as_ref()))
+            .collect();
+
+        // Queries that a rule fires for don't need to be embedded
+        let unmatched: Vec<String> = queries
+            .iter()
+            .filter(|x| self.rules.matches(x).is_none())
+            .cloned()
+            .collect();
+        let mut results = self
+            .store
+            .top_n_batch(unmatched, self.candidate_count(HYBRID_CANDIDATES))
+            .await?
+            .into_iter();
+
+        let mut routes = Vec::with_capacity(queries.len());
+
+        for query in &queries {
+            let span = decision_span(query);
+            let start = Instant::now();
+
+            if let Some(decision) = self.rule_decision(query, start) {
+                self.record_decision(&span, &decision);
+                routes.push(decision.route);
+                continue;
+            }
+
+            let res = results.next().unwrap_or_default();
+            let candidates = self
+                .rerank(
+                    query,
+                    self.blend_lexical(query, self.aggregate(res, HYBRID_CANDIDATES)),
+                )
+                .await?;
+            let decision = self
+                .decide(query, candidates, start)
+                .instrument(span.clone())
+                .await;
+
+            self.record_decision(&span, &decision);
+            routes.push(decision.route);
+        }
+
+        Ok(routes)
+    }
+
+    /// The route of the first rule that fires for a query, if any.
+    fn rule_match(&self, query: &str) -> Option<RouteMatch> {
+        self.rules
+            .matches(query)
+            .map(|tag| RouteMatch::new(tag, 1.0))
+    }
+
+    /// A decision for a query that a rule fires for, if any.
+    fn rule_decision(&self, query: &str, start: Instant) -> Option<RouteDecision> {
+        let route = self.rule_match(query)?;
+        tracing::info!("Query matched rule for route: {}", route.tag);
+
+        Some(RouteDecision {
+            route: Some(route.clone()),
+            best: Some(route),
+            source: DecisionSource::Rule,
+            margin: None,
+            duration: start.elapsed(),
+        })
+    }
+
+    /// Record a decision in its tracing span and the metrics sink (if set).
+    fn record_decision(&self, span: &tracing::Span, decision: &RouteDecision) {
+        if let Some(route) = &decision.route {
+            span.record("route", route.tag.as_str());
+            span.record("score", route.score);
+        }
+        if let Some(best) = &decision.best {
+            span.record("best_route", best.tag.as_str());
+            span.record("best_score", best.score);
+        }
+        span.record("source", decision.source.as_str());
+        span.record("duration_ms", decision.duration.as_millis() as u64);
+
+        if let Some(metrics) = &self.metrics {
+            metrics.record_decision(decision);
+        }
+    }
+
+    /// Pick a route from the candidates for a query, keeping track of how it was picked.
+    async fn decide(
+        &self,
+        query: &str,
+        candidates: Vec<RouteMatch>,
+        start: Instant,
+    ) -> RouteDecision {
+        let best = candidates.first().cloned();
+        let best_score = best.as_ref().map(|x| x.score).unwrap_or_default();
+
+        if let Some(blocked) = self.guardrail_match(&candidates) {
+            tracing::warn!("Query blocked by guardrail route: {}", blocked.tag);
+
+            return RouteDecision {
+                route: Some(blocked),
+                best,
+                source: DecisionSource::Guardrail,
+                margin: None,
+                duration: start.elapsed(),
+            };
+        }
+
+        let mut scores = candidates
+            .iter()
+            .filter(|x| !self.is_guardrail(&x.tag))
+            .map(|x| x.score);
+        let margin = scores.next().zip(scores.next()).map(|(a, b)| a - b);
+
+        let mut source = DecisionSource::Matched;
+        let mut route = candidates
+            .into_iter()
+            .find(|x| !self.is_guardrail(&x.tag))
+            .filter(|RouteMatch { tag, score, .. }| {
+                tracing::info!("Retrieved route: {tag}, {score}");
+                *score >= self.threshold(tag)
+            });
+
+        if route.is_none()
+            && let Some(classifier) = &self.llm_fallback
+        {
+            source = DecisionSource::LlmClassifier;
+            route = classifier
+                .classify(query)
+                .await
+                .map(|tag| RouteMatch::new(&tag, best_score));
+        }
+
+        if route.is_none()
+            && let Some(default_route) = &self.default_route
+        {
+            tracing::info!("No route matched, using default route: {default_route}");
+            source = DecisionSource::DefaultRoute;
+            route = Some(RouteMatch::new(default_route, best_score));
+        }
+
+        if route.is_none() {
+            source = DecisionSource::NoMatch;
+        }
+
+        RouteDecision {
+            route,
+            best,
+            margin: margin.filter(|_| source == DecisionSource::Matched),
+            source,
+            duration: start.elapsed(),
+        }
+    }
+
+    /// Candidate routes for a query, ranked by score with lexical scores blended in (if configured).
+    /// Served from the cache if the query (or a near-identical one) was seen recently.
+    async fn scored_routes(&self, query: &str) -> Result<Vec<RouteMatch>, VectorStoreError> {
+        if let Some(candidates) = self.cache.as_ref().and_then(|x| x.get(query)) {
+            return Ok(candidates);
+        }
+
+        let candidates = self.top_routes(query, HYBRID_CANDIDATES).await?;
+        let candidates = self
+            .rerank(query, self.blend_lexical(query, candidates))
+            .await?;
+
+        if let Some(cache) = &self.cache {
+            cache.insert(query, candidates.clone());
+        }
+
+        Ok(candidates)
+    }
+
+    /// The route cache, if caching is enabled.
+    pub fn cache(&self) -> Option<&RouteCache> {
+        self.cache.as_ref()
+    }
+
+    /// Blend lexical scores into the semantic candidates for a query (if lexical matching is configured), then re-rank them.
+    fn blend_lexical(&self, query: &str, mut candidates: Vec<RouteMatch>) -> Vec<RouteMatch> {
+        let Some(lexical) = &self.lexical else {
+            return candidates;
+        };
+
+        // Routes with lexical patterns can match on their keywords alone, even if they aren't semantic candidates
+        for tag in lexical.tags() {
+            if !candidates.iter().any(|x| x.tag == tag) {
+                candidates.push(RouteMatch::new(tag, 0.0));
+            }
+        }
+
+        for candidate in &mut candidates {
+            candidate.score = lexical.combine(&candidate.tag, query, candidate.score);
+        }
+
+        candidates.sort_by(|a, b| b.score.total_cmp(&a.score));
+
+        candidates
+    }
+
+    /// Replace the scores of the candidates with the reranker's scores (if a reranker is set), then re-rank them.
+    async fn rerank(
+        &self,
+        query: &str,
+        mut candidates: Vec<RouteMatch>,
+    ) -> Result<Vec<RouteMatch>, VectorStoreError> {
+        let Some(reranker) = &self.reranker else {
+            return Ok(candidates);
+        };
+
+        if candidates.is_empty() {
+            return Ok(candidates);
+        }
+
+        let documents: Vec<String> = candidates
+            .iter()
+            .map(|x| {
+                self.descriptions
+                    .get(&x.tag)
+                    .cloned()
+                    .unwrap_or_else(|| x.tag.clone())
+            })
+            .collect();
+
+        let scores = reranker
+            .rerank(query, &documents)
+            .await
+            .map_err(VectorStoreError::DatastoreError)?;
+
+        if scores.len() != documents.len() {
+            return Err(VectorStoreError::DatastoreError(
+                format!(
+                    "Reranker returned {} scores for {} documents",
+                    scores.len(),
+                    documents.len()
+                )
+                .into(),
+            ));
+        }
+
+        for (candidate, score) in candidates.iter_mut().zip(scores) {
+            candidate.score = score;
+        }
+
+        candidates.sort_by(|a, b| b.score.total_cmp(&a.score));
+
+        Ok(candidates)
+    }
+
+    /// Whether a route is a guardrail. See [`SemanticRouterBuilder::guardrail`].
+    pub fn is_guardrail(&self, tag: &str) -> bool {
+        self.guardrails.contains_key(tag)
+    }
+
+    /// The refusal response of a guardrail route.
+    pub fn refusal(&self, tag: &str) -> Option<&str> {
+        self.guardrails.get(tag).map(String::as_str)
+    }
+
+    /// The best scoring guardrail route among the candidates that scored above its threshold, if any.
+    fn guardrail_match(&self, candidates: &[RouteMatch]) -> Option<RouteMatch> {
+        candidates
+            .iter()
+            .find(|x| self.is_guardrail(&x.tag) && x.score >= self.threshold(&x.tag))
+            .cloned()
+    }
+
+    /// Wrap the router so that agents can be registered on it. Guardrail routes respond with their refusal.
+    fn into_agents(self) -> SemanticRouterWithAgents<V> {
+        let agents = self
+            .guardrails
+            .iter()
+            .map(|(tag, refusal)| (tag.clone(), RouteTarget::Refusal(refusal.clone())))
+            .collect();
+
+        SemanticRouterWithAgents {
+            router: self,
+            agents,
+            default_agent: None,
+            templates: HashMap::new(),
+            failover: HashMap::new(),
+            splitter: None,
+        }
+    }
+
+    /// Register an agent for a route. Any type implementing [`RouteAgent`] can be used, including an [`Agent`](rig::agent::Agent) of any completion model.
+    pub fn agent<A>(self, route: &str, agent: A) -> SemanticRouterWithAgents<V>
+    where
+        A: RouteAgent + 'static,
+    {
+        self.into_agents().agent(route, agent)
+    }
+
+    /// Register an async handler for a route. See [`SemanticRouterWithAgents::handler`].
+    pub fn handler<F, Fut>(self, route: &str, handler: F) -> SemanticRouterWithAgents<V>
+    where
+        F: Fn(RouteContext) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = anyhow::Result<String>> + Send + 'static,
+    {
+        self.into_agents().handler(route, handler)
+    }
+}
+
+impl<V> SemanticRouterWithAgents<V>
+where
+    V: VectorStoreIndex,
+{
+    /// Route a query, then prompt the agent registered for the matched route. See [`Self::prompt_traced`] for the route and score as well.
+    /// Returns `Ok(None)` if no route matched and there is no default agent, and [`SemanticRouterError::RouteNotRegistered`] if the matched route has no agent.
+    /// If the route has a [`FailoverPolicy`], it's applied when the agent errors or times out.
+    pub async fn prompt<R>(&self, query: R) -> Result<Option<String>, SemanticRouterError>
+    where
+        R: Into<RouterRequest>,
+    {
+        Ok(self.prompt_traced(query).await?.map(|x| x.response))
+    }
+
+    /// Like [`Self::prompt`], but also returns the route that was matched (at each level of nested routers), its score,
+    /// the agent that responded and the end-to-end latency, ie for logging.
+    ///
+    /// If an [`IntentSplitter`] is set and the query has several intents, each intent is routed and answered separately.
+    /// The responses are then combined in order, with the response to each intent in [`RoutedResponse::parts`].
+    pub async fn prompt_traced<R>(
+        &self,
+        query: R,
+    ) -> Result<Option<RoutedResponse>, SemanticRouterError>
+    where
+        R: Into<RouterRequest>,
+    {
+        let start = Instant::now();
+        let RouterRequest { query, turns } = query.into();
+        let ctx = RouteContext {
+            query,
+            route: None,
+            history: Vec::new(),
+            turns: turns as usize,
+            margin: None,
+        };
+
+        let Some(splitter) = &self.splitter else {
+            return self.respond(ctx).await;
+        };
+        let intents = splitter.split(&ctx.query).await;
+        if intents.len() < 2 {
+            return self.respond(ctx).await;
+        }
+        tracing::info!("Split query into {} intents", intents.len());
+
+        let parts = futures::future::try_join_all(intents.into_iter().map(|query| {
+            self.respond(RouteContext {
+                query,
+                ..ctx.clone()
+            })
+        }))
+        .await?;
+
+        Ok(RoutedResponse::combine(
+            parts.into_iter().flatten().collect(),
+            start.elapsed(),
+        ))
+    }
+
+    /// Route a query, then stream the response of the agent registered for the matched route.
+    /// The stream yields text chunks as they arrive. Returns `Ok(None)` if no route matched and there is no default agent.
+    pub async fn prompt_stream(
+        &self,
+        query: &str,
+    ) -> Result<Option<RouteStream>, SemanticRouterError> {
+        self.stream(RouteContext {
+            query: query.to_string(),
+            route: None,
+            history: Vec::new(),
+            turns: 0,
+            margin: None,
+        })
+        .await
+    }
+
+    /// Route the query of a context, then send it to the matched target.
+    /// The end-to-end latency is recorded if a metrics sink is set.
+    async fn respond(
+        &self,
+        mut ctx: RouteContext,
+    ) -> Result<Option<RoutedResponse>, SemanticRouterError> {
+        let start = Instant::now();
+
+        ctx.query = self.router.middleware.rewrite(&ctx.query);
+        let decision = self.router.decision(&ctx.query).await?;
+        ctx.route = decision.route;
+        ctx.margin = decision.margin;
+        let tag = ctx.route.as_ref().map(|x| x.tag.clone());
+        let Some(target) = self.target_for(tag.as_deref())? else {
+            return Ok(None);
+        };
+
+        let mut res = self.respond_with(target, ctx).await;
+        if let Ok(Some(res)) = &mut res {
+            res.latency = start.elapsed();
+        }
+
+        if let Some(metrics) = &self.router.metrics {
+            metrics.record_response(tag.as_deref(), start.elapsed(), res.is_ok());
+
+            if let (
+                Some(tag),
+                Ok(Some(RoutedResponse {
+                    variant: Some(variant),
+                    ..
+                })),
+            ) = (&tag, &res)
+            {
+                metrics.record_variant(tag, variant);
+            }
+        }
+
+        res
+    }
+
+    /// Send a routed query to a target (applying the route's failover policy, if any), then apply the post-response hooks to the response.
+    async fn respond_with(
+        &self,
+        target: &RouteTarget,
+        ctx: RouteContext,
+    ) -> Result<Option<RoutedResponse>, SemanticRouterError> {
+        let middleware = &self.router.middleware;
+        if !middleware.has_post() {
+            return self.send(target, self.render_template(target, ctx)?).await;
+        }
+
+        let Some(mut res) = self
+            .send(target, self.render_template(target, ctx.clone())?)
+            .await?
+        else {
+            return Ok(None);
+        };
+        res.response = middleware.process(&ctx, res.response);
+
+        Ok(Some(res))
+    }
+
+    /// Send a query to a target, through the failover policy of the matched route if it has one.
+    async fn send(
+        &self,
+        target: &RouteTarget,
+        ctx: RouteContext,
+    ) -> Result<Option<RoutedResponse>, SemanticRouterError> {
+        let policy = ctx.route.as_ref().and_then(|x| self.failover.get(&x.tag));
+
+        match policy {
+            Some(policy) => policy.respond(target, ctx).await,
+            None => target.respond(ctx).await,
+        }
+    }
+
+    /// Route the query of a context, then stream the response of the matched target.
+    /// Post-response hooks aren't applied, as they work on complete responses.
+    async fn stream(
+        &self,
+        mut ctx: RouteContext,
+    ) -> Result<Option<RouteStream>, SemanticRouterError> {
+        ctx.query = self.router.middleware.rewrite(&ctx.query);
+        let decision = self.router.decision(&ctx.query).await?;
+        ctx.route = decision.route;
+        ctx.margin = decision.margin;
+        let Some(target) = self.target_for(ctx.route.as_ref().map(|x| x.tag.as_str()))? else {
+            return Ok(None);
+        };
+
+        target.stream(self.render_template(target, ctx)?).await
+    }
+
+    /// Render the query into the template of the matched route, if the target is an agent and the route has a template.
+    /// The template can use the raw query (`query`), the route's tag (`route`) and its metadata (`metadata`).
+    fn render_template(
+        &self,
+        target: &RouteTarget,
+        mut ctx: RouteContext,
+    ) -> Result<RouteContext, SemanticRouterError> {
+        let (
+            RouteTarget::Agent(_) | RouteTarget::Variants(_) | RouteTarget::Escalation(_),
+            Some(route),
+        ) = (target, &ctx.route)
+        else {
+            return Ok(ctx);
+        };
+        let Some(template) = self.templates.get(&route.tag) else {
+            return Ok(ctx);
+        };
+
+        ctx.query = template
+            .clone()
+            .with_variable("query", &ctx.query)
+            .with_variable("route", &route.tag)
+            .with_variable("metadata", &route.metadata)
+            .try_render_to_string()?;
+
+        Ok(ctx)
+    }
+
+    /// The agent (or handler, or router) registered for a route, or the default agent if there is no route.
+    /// Errors if a route matched that has nothing registered for it.
+    fn target_for(&self, tag: Option<&str>) -> Result<Option<&RouteTarget>, SemanticRouterError> {
+        match tag {
+            Some(tag) => self
+                .agents
+                .get(tag)
+                .map(Some)
+                .ok_or_else(|| SemanticRouterError::RouteNotRegistered(tag.to_string())),
+            None => Ok(self.default_agent.as_ref()),
+        }
+    }
+
+    /// Start a conversation. See [`RouterSession`].
+    pub fn session(&self) -> RouterSession<'_, V> {
+        RouterSession::new(self)
+    }
+
+    pub fn agent<A>(mut self, route: &str, agent: A) -> Self
+    where
+        A: RouteAgent + 'static,
+    {
+        self.agents
+            .insert(route.to_string(), RouteTarget::Agent(Arc::new(agent)));
+        self
+    }
+
+    /// Register an async function to handle a route, ie to call a tool, look something up in a database or return a canned response.
+    pub fn handler<F, Fut>(mut self, route: &str, handler: F) -> Self
+    where
+        F: Fn(RouteContext) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = anyhow::Result<String>> + Send + 'static,
+    {
+        self.agents
+            .insert(route.to_string(), RouteTarget::handler(handler));
+        self
+    }
+
+    /// Add a route at runtime, along with the agent that handles it. See [`SemanticRouter::add_route`].
+    pub async fn add_route<R, I, S, A>(
+        &mut self,
+        route: R,
+        utterances: I,
+        agent: A,
+    ) -> Result<(), VectorStoreError>
+    where
+        V: InsertRoutes,
+        R: Into<SemanticRoute>,
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+        A: RouteAgent + 'static,
+    {
+        let route = route.into();
+        let tag = route.tag.clone();
+
+        self.router.add_route(route, utterances).await?;
+        self.agents.insert(tag, RouteTarget::Agent(Arc::new(agent)));
+
+        Ok(())
+    }
+
+    /// Register one of several agents for a route, ie to gradually roll out a new model or prompt.
+    /// Each request to the route is served by one variant, picked in proportion to the weights (ie 90 and 10 for a 90/10 split).
+    /// The variant that served a request is recorded in [`RoutedResponse::variant`] and the metrics sink.
+    /// Registering a variant replaces a single agent registered with [`Self::agent`].
+    pub fn agent_variant<A>(mut self, route: &str, name: &str, agent: A, weight: u32) -> Self
+    where
+        A: RouteAgent + 'static,
+    {
+        let variant = AgentVariant {
+            name: name.to_string(),
+            weight,
+            agent: Arc::new(agent),
+        };
+
+        match self.agents.get_mut(route) {
+            Some(RouteTarget::Variants(variants)) => variants.push(variant),
+            _ => {
+                self.agents
+                    .insert(route.to_string(), RouteTarget::Variants(vec![variant]));
+            }
+        }
+        self
+    }
+
+    /// Serve a route with a cheap agent, escalating ambiguous or complex queries to an expensive one. See [`EscalationPolicy`].
+    /// Which agent served a request is recorded in [`RoutedResponse::escalation`].
+    pub fn escalation(mut self, route: &str, policy: EscalationPolicy) -> Self {
+        self.agents
+            .insert(route.to_string(), RouteTarget::Escalation(Arc::new(policy)));
+        self
+    }
+
+    /// Attach a prompt template to a route. Queries routed to the route's agent are rendered into the template first,
+    /// with the raw query, the route's tag and its metadata available as the `query`, `route` and `metadata` variables.
+    /// Any variables set on the template itself are kept.
+    pub fn template(mut self, route: &str, template: PromptTemplate) -> Self {
+        self.templates.insert(route.to_string(), template);
+        self
+    }
+
+    /// Split multi-intent queries ("cancel my order and update my address") before routing, so that each intent is
+    /// routed to its own agent. Only applies to [`Self::prompt`] and [`Self::prompt_traced`].
+    pub fn intent_splitter<S>(mut self, splitter: S) -> Self
+    where
+        S: IntentSplitter + 'static,
+    {
+        self.splitter = Some(Arc::new(splitter));
+        self
+    }
+
+    /// Set a timeout and fallbacks (retries, an alternate agent or a canned response) for a route's target.
+    /// The policy applies to complete responses, not to streamed ones.
+    pub fn failover(mut self, route: &str, policy: FailoverPolicy) -> Self {
+        self.failover.insert(route.to_string(), policy);
+        self
+    }
+
+    /// Register an agent for a route, with a score threshold specific to that route.
+    pub fn agent_with_threshold<A>(mut self, route: &str, agent: A, threshold: f64) -> Self
+    where
+        A: RouteAgent + 'static,
+    {
+        self.router.set_route_threshold(route, threshold);
+        self.agent(route, agent)
+    }
+
+    /// Route a query to another router, which then picks a more specific route with its own routes and thresholds.
+    /// For example, a "billing" route could be handed to a router with "refunds", "invoices" and "plans" routes.
+    pub fn router<R>(mut self, route: &str, router: SemanticRouterWithAgents<R>) -> Self
+    where
+        R: VectorStoreIndex + 'static,
+    {
+        self.agents
+            .insert(route.to_string(), RouteTarget::Router(Arc::new(router)));
+        self
+    }
+
+    /// Set a general-purpose agent that handles queries that don't match any route.
+    pub fn default_agent<A>(mut self, agent: A) -> Self
+    where
+        A: RouteAgent + 'static,
+    {
+        self.default_agent = Some(RouteTarget::Agent(Arc::new(agent)));
+        self
+    }
+
+    /// Set an async function that handles queries that don't match any route.
+    pub fn default_handler<F, Fut>(mut self, handler: F) -> Self
+    where
+        F: Fn(RouteContext) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = anyhow::Result<String>> + Send + 'static,
+    {
+        self.default_agent = Some(RouteTarget::handler(handler));
+        self
+    }
+}
+
+pub struct RouterRequest {
+    query: String,
+    turns: u64,
+}
+
+impl RouterRequest {
+    pub fn new(query: String) -> Self {
+        Self::from(query)
+    }
+
+    pub fn with_turns(mut self, turns: u64) -> Self {
+        self.turns = turns;
+        self
+    }
+}
+
+impl From<String> for RouterRequest {
+    fn from(value: String) -> Self {
+        Self {
+            query: value,
+            turns: 0,
+        }
+    }
+}
+
+impl From<&str> for RouterRequest {
+    fn from(value: &str) -> Self {
+        Self {
+            query: value.to_string(),
+            turns: 0,
+        }
+    }
+}
+
+impl From<(String, u64)> for RouterRequest {
+    fn from((query, turns): (String, u64)) -> Self {
+        Self { query, turns }
+    }
+}
+
+impl From<(&str, u64)> for RouterRequest {
+    fn from((query, turns): (&str, u64)) -> Self {
+        Self {
+            query: query.to_string(),
+            turns,
+        }
+    }
+}
+
+impl<V> NestedRouter for SemanticRouterWithAgents<V>
+where
+    V: VectorStoreIndex,
+{
+    fn respond_nested(
+        &self,
+        ctx: RouteContext,
+    ) -> BoxFuture<'_, Result<Option<RoutedResponse>, SemanticRouterError>> {
+        Box::pin(self.respond(ctx))
+    }
+
+    fn stream_nested(
+        &self,
+        ctx: RouteContext,
+    ) -> BoxFuture<'_, Result<Option<RouteStream>, SemanticRouterError>> {
+        Box::pin(self.stream(ctx))
+    }
+}
+
+/// The response to a routed query.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RoutedResponse {
+    pub response: String,
+    /// The route matched at each level, from the outermost router to the innermost. Levels that fell back to a default agent have no entry.
+    pub trace: Vec<RouteMatch>,
+    /// The name of the agent variant that served the query, if the route has several (see [`SemanticRouterWithAgents::agent_variant`]).
+    pub variant: Option<String>,
+    /// Whether the query was escalated to the expensive agent, if the route has an [`EscalationPolicy`].
+    pub escalation: Option<Escalation>,
+    /// The agent (or handler) that responded: the route it's registered for, or `default` for the default agent.
+    /// Variants are suffixed with their name (`billing/stable`), and escalating routes with the agent used (`billing/expensive`).
+    /// `None` if the query was split, as each part has its own.
+    pub agent_id: Option<String>,
+    /// How long routing and responding took, end to end.
+    pub latency: Duration,
+    /// The responses to each intent, in order, if the query was split (see [`SemanticRouterWithAgents::intent_splitter`]).
+    /// The response and trace of a split query combine those of its parts.
+    pub parts: Vec<RoutedResponse>,
+}
+
+impl RoutedResponse {
+    /// The innermost matched route, ie the one that picked the agent. `None` if the default agent responded.
+    pub fn route(&self) -> Option<&RouteMatch> {
+        self.trace.last()
+    }
+
+    /// The tag of the innermost matched route.
+    pub fn tag(&self) -> Option<&str> {
+        self.route().map(|x| x.tag.as_str())
+    }
+
+    /// The score of the innermost matched route.
+    pub fn score(&self) -> Option<f64> {
+        self.route().map(|x| x.score)
+    }
+
+    /// Combine the responses to the intents of a split query. Returns `None` if no intent got a response.
+    fn combine(parts: Vec<RoutedResponse>, latency: Duration) -> Option<Self> {
+        if parts.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            response: parts
+                .iter()
+                .map(|x| x.response.as_str())
+                .collect::<Vec<_>>()
+                .join("\n\n"),
+            trace: parts.iter().flat_map(|x| x.trace.clone()).collect(),
+            variant: None,
+            escalation: None,
+            agent_id: None,
+            latency,
+            parts,
+        })
+    }
+}
+
+/// The span every routing decision is recorded in. Fields other than the query length are recorded once the decision is made.
+fn decision_span(query: &str) -> tracing::Span {
+    tracing::info_span!(
+        "route_decision",
+        query_len = query.len(),
+        route = tracing::field::Empty,
+        score = tracing::field::Empty,
+        best_route = tracing::field::Empty,
+        best_score = tracing::field::Empty,
+        source = tracing::field::Empty,
+        duration_ms = tracing::field::Empty,
+    )
+}
+
+/// A candidate route for a query.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RouteMatch {
+    pub tag: String,
+    /// The similarity score of the route, aggregated from the scores of its matching utterances.
+    pub score: f64,
+    /// The metadata stored with the route.
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    pub metadata: Map<String, Value>,
+}
+
+impl RouteMatch {
+    fn new(tag: &str, score: f64) -> Self {
+        Self {
+            tag: tag.to_string(),
+            score,
+            metadata: Map::new(),
+        }
+    }
+
+    /// Deserialize the route's metadata into a type.
+    pub fn metadata_as<T: DeserializeOwned>(&self) -> Result<T, serde_json::Error> {
+        serde_json::from_value(Value::Object(self.metadata.clone()))
+    }
+}
+
+/// The field of a stored route document that holds the text of the example utterance. It isn't treated as route metadata.
+pub const UTTERANCE_FIELD: &str = "utterance";
+
+/// A route as stored alongside each of its utterances in a vector store.
+/// Any fields other than `tag` (and [`UTTERANCE_FIELD`]) are treated as route metadata (ie a description, locale or required auth level), and are returned in the [`RouteMatch`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SemanticRoute {
+    pub tag: String,
+    #[serde(flatten)]
+    pub metadata: Map<String, Value>,
+}
+
+impl SemanticRoute {
+    pub fn new(tag: &str) -> Self {
+        Self {
+            tag: tag.to_string(),
+            metadata: Map::new(),
+        }
+    }
+
+    /// Add a metadata field to the route. Values that fail to serialize are stored as `null`.
+    pub fn with_metadata(mut self, key: &str, value: impl Serialize) -> Self {
+        self.metadata.insert(
+            key.to_string(),
+            serde_json::to_value(value).unwrap_or_default(),
+        );
+        self
+    }
+}
+
+impl From<&str> for SemanticRoute {
+    fn from(tag: &str) -> Self {
+        Self::new(tag)
+    }
+}
+
+impl From<String> for SemanticRoute {
+    fn from(tag: String) -> Self {
+        Self {
+            tag,
+            metadata: Map::new(),
+        }
+    }
+}
+
+pub trait Router: VectorStoreIndex {
+    fn retrieve_route() -> impl std::future::Future<Output = Option<String>> + Send;
+}
+
+pub struct SemanticRouterBuilder<V> {
+    store: Option<V>,
+    threshold: Option<f64>,
+    route_thresholds: HashMap<String, f64>,
+    default_route: Option<String>,
+    /// Route definitions (route and example utterances) to insert into the store when building.
+    routes: Vec<(SemanticRoute, Vec<String>)>,
+    llm_fallback: Option<Arc<dyn RouteAgent>>,
+    route_descriptions: Vec<(String, String)>,
+    lexical: LexicalMatcher,
+    /// Regex patterns as `(tag, pattern)` pairs. These are compiled when building.
+    patterns: Vec<(String, String)>,
+    /// Rules as `(kind, tag, text)`, in the order they're checked. Patterns are compiled when building.
+    rules: Vec<(RuleKind, String, String)>,
+    metrics: Option<Arc<dyn RouterMetrics>>,
+    score_transform: ScoreTransform,
+    aggregation: Aggregation,
+    guardrails: HashMap<String, String>,
+    middleware: Middleware,
+    cache: Option<RouteCache>,
+    reranker: Option<Arc<dyn Reranker>>,
+}
+
+impl<V> Default for SemanticRouterBuilder<V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<V> SemanticRouterBuilder<V> {
+    pub fn new() -> Self {
+        Self {
+            store: None,
+            threshold: None,
+            route_thresholds: HashMap::new(),
+            default_route: None,
+            routes: Vec::new(),
+            llm_fallback: None,
+            route_descriptions: Vec::new(),
+            lexical: LexicalMatcher::new(DEFAULT_LEXICAL_WEIGHT),
+            patterns: Vec::new(),
+            rules: Vec::new(),
+            metrics: None,
+            score_transform: ScoreTransform::Identity,
+            aggregation: Aggregation::Max,
+            guardrails: HashMap::new(),
+            middleware: Middleware::default(),
+            cache: None,
+            reranker: None,
+        }
+    }
+
+    pub fn store(mut self, router: V) -> Self {
+        self.store = Some(router);
+
+        self
+    }
+
+    pub fn threshold(mut self, threshold: f64) -> Self {
+        self.threshold = Some(threshold);
+
+        self
+    }
+
+    /// Set the score threshold for a single route. Routes without their own threshold use the global threshold.
+    pub fn route_threshold(mut self, tag: &str, threshold: f64) -> Self {
+        self.route_thresholds.insert(tag.to_string(), threshold);
+
+        self
+    }
+
+    /// Route queries that don't score above the threshold for any route to the given tag, rather than returning no route.
+    pub fn default_route(mut self, tag: &str) -> Self {
+        self.default_route = Some(tag.to_string());
+
+        self
+    }
+
+    /// Classify queries that don't score above the threshold for any route with an LLM, before giving up.
+    /// The agent is prompted with the descriptions set with [`Self::route_description`] and should be small and fast, as it adds latency to low-confidence queries.
+    pub fn llm_fallback<A>(mut self, agent: A) -> Self
+    where
+        A: RouteAgent + 'static,
+    {
+        self.llm_fallback = Some(Arc::new(agent));
+
+        self
+    }
+
+    /// Describe a route for the LLM classifier and the reranker. Only routes with a description can be picked by the classifier.
+    pub fn route_description(mut self, tag: &str, description: &str) -> Self {
+        self.route_descriptions
+            .push((tag.to_string(), description.to_string()));
+
+        self
+    }
+
+    /// Match a route whenever the query contains a phrase (ignoring case), ie `"invoice #"`.
+    /// A matching phrase gives the route a score of 1, as if it matched semantically.
+    pub fn phrase(mut self, tag: &str, phrase: &str) -> Self {
+        self.lexical.add_phrase(tag, phrase);
+
+        self
+    }
+
+    /// Match a route whenever the query matches a regex pattern, giving it a score of 1 like [`Self::phrase`].
+    /// Invalid patterns cause [`Self::build`] to fail.
+    pub fn pattern(mut self, tag: &str, pattern: &str) -> Self {
+        self.patterns.push((tag.to_string(), pattern.to_string()));
+
+        self
+    }
+
+    /// Route queries that equal `text` (ignoring case and surrounding whitespace) straight to a route, without a vector query.
+    /// Useful for commands like "/help". Rules are checked in the order they're added, before anything else.
+    pub fn rule_exact(mut self, tag: &str, text: &str) -> Self {
+        self.rules
+            .push((RuleKind::Exact, tag.to_string(), text.to_string()));
+
+        self
+    }
+
+    /// Route queries that match a regex pattern straight to a route, without a vector query (ie order numbers).
+    /// Unlike [`Self::pattern`], a match routes the query immediately rather than raising the route's score.
+    /// Invalid patterns cause [`Self::build`] to fail.
+    pub fn rule_pattern(mut self, tag: &str, pattern: &str) -> Self {
+        self.rules
+            .push((RuleKind::Pattern, tag.to_string(), pattern.to_string()));
+
+        self
+    }
+
+    /// Add keywords to a route. The more (distinctive) keywords a query contains, the higher the route's lexical score,
+    /// which is blended with the semantic score according to [`Self::lexical_weight`].
+    pub fn keywords<I, S>(mut self, tag: &str, keywords: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        for keyword in keywords {
+            self.lexical.add_keyword(tag, keyword.as_ref());
+        }
+
+        self
+    }
+
+    /// How much of a route's combined score comes from keyword matching, between 0 and 1. Defaults to 0.3.
+    /// Lexical scores can only raise a route's score above its semantic score.
+    pub fn lexical_weight(mut self, weight: f64) -> Self {
+        self.lexical.set_weight(weight);
+
+        self
+    }
+
+    /// Set how raw store scores are converted into similarities before they're compared to thresholds.
+    /// Defaults to [`ScoreTransform::Identity`], which suits stores that return cosine similarity.
+    pub fn score_transform(mut self, transform: ScoreTransform) -> Self {
+        self.score_transform = transform;
+
+        self
+    }
+
+    /// Set how the scores of a route's utterances are combined into the route's score.
+    /// Defaults to [`Aggregation::Max`]. Use [`Aggregation::Mean`] so that routes with many example utterances aren't
+    /// outscored by a route with one highly specific utterance.
+    pub fn aggregation(mut self, aggregation: Aggregation) -> Self {
+        self.aggregation = aggregation;
+
+        self
+    }
+
+    /// Rescore the candidate routes of every query with a reranker (ie a cross-encoder) before thresholds are applied.
+    /// Thresholds are then compared to the reranker's scores. Rerankers score route descriptions, so describe every route with [`Self::route_description`].
+    pub fn reranker<R>(mut self, reranker: R) -> Self
+    where
+        R: Reranker + 'static,
+    {
+        self.reranker = Some(Arc::new(reranker));
+
+        self
+    }
+
+    /// Cache the candidate routes of recent queries, so that repeated queries skip the embedding call and vector store lookup.
+    pub fn cache(mut self, cache: RouteCache) -> Self {
+        self.cache = Some(cache);
+
+        self
+    }
+
+    /// Record routing decisions and response latencies in a metrics sink, ie an [`InMemoryMetrics`].
+    /// The sink is shared, so keep a clone of the `Arc` to read the metrics back.
+    pub fn metrics(mut self, metrics: Arc<dyn RouterMetrics>) -> Self {
+        self.metrics = Some(metrics);
+
+        self
+    }
+
+    /// Add a hook that rewrites queries before they're routed, ie to scrub PII. Agents receive the rewritten query.
+    /// Hooks run in the order they were added.
+    pub fn pre_route<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&str) -> String + Send + Sync + 'static,
+    {
+        self.middleware.add_pre(Arc::new(hook));
+
+        self
+    }
+
+    /// Add a hook that post-processes responses, ie to format them. Hooks run in the order they were added.
+    /// They aren't applied to streamed responses.
+    pub fn post_response<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&RouteContext, String) -> String + Send + Sync + 'static,
+    {
+        self.middleware.add_post(Arc::new(hook));
+
+        self
+    }
+
+    /// Mark a route as a guardrail, ie for jailbreak attempts or prohibited topics.
+    /// When a query scores above the guardrail's threshold, routing stops and the refusal is returned without invoking any agent,
+    /// even if another route scores higher. Guardrail utterances are defined like any other route, ie with [`Self::route`].
+    pub fn guardrail(mut self, tag: &str, refusal: &str) -> Self {
+        self.guardrails.insert(tag.to_string(), refusal.to_string());
+
+        self
+    }
+
+    /// Define a route by its example utterances. The utterances are embedded and inserted into the store by [`Self::build_with_routes`].
+    /// The route can be a tag, or a [`SemanticRoute`] with metadata.
+    pub fn route<R, I, S>(mut self, route: R, utterances: I) -> Self
+    where
+        R: Into<SemanticRoute>,
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.routes.push((
+            route.into(),
+            utterances.into_iter().map(Into::into).collect(),
+        ));
+
+        self
+    }
+
+    pub fn build(self) -> Result<SemanticRouter<V>, SemanticRouterError> {
+        let Some(store) = self.store else {
+            return Err(SemanticRouterError::StoreNotFound);
+        };
+
+        if !self.routes.is_empty() {
+            return Err(SemanticRouterError::RoutesNotInserted);
+        }
+
+        let mut lexical = self.lexical;
+        for (tag, pattern) in self.patterns {
+            lexical.add_pattern(&tag, regex::Regex::new(&pattern)?);
+        }
+        let rules = RouteRules::compile(self.rules)?;
+
+        let threshold = self.threshold.unwrap_or(0.8);
+        let descriptions = self.route_descriptions.iter().cloned().collect();
+
+        Ok(SemanticRouter {
+            store,
+            threshold,
+            route_thresholds: self.route_thresholds,
+            default_route: self.default_route,
+            llm_fallback: self
+                .llm_fallback
+                .map(|agent| LlmClassifier::new(agent, self.route_descriptions)),
+            lexical: (!lexical.is_empty()).then_some(lexical),
+            rules,
+            metrics: self.metrics,
+            score_transform: self.score_transform,
+            aggregation: self.aggregation,
+            guardrails: self.guardrails,
+            middleware: self.middleware,
+            cache: self.cache,
+            reranker: self.reranker,
+            descriptions,
+        })
+    }
+}
+
+impl<M> SemanticRouterBuilder<InMemoryRouteIndex<M>>
+where
+    M: EmbeddingModel,
+{
+    /// Use an [`InMemoryRouteIndex`] that embeds routes (and queries) with the given model, rather than a pre-built store.
+    /// Routes are then defined with [`Self::route`].
+    pub fn embedding_model(mut self, model: M) -> Self {
+        self.store = Some(InMemoryRouteIndex::new(model));
+
+        self
+    }
+}
+
+impl SemanticRouterBuilder<MultiRouteIndex> {
+    /// Add a vector store (ie for a route group or tenant) to a [`MultiRouteIndex`], creating the index if no store was set.
+    /// The stores are queried in parallel, and their results merged.
+    pub fn add_store<S>(mut self, name: &str, store: S) -> Self
+    where
+        S: VectorStoreIndex + 'static,
+    {
+        self.store = Some(self.store.take().unwrap_or_default().store(name, store));
+
+        self
+    }
+}
+
+impl<V> SemanticRouterBuilder<V>
+where
+    V: InsertRoutes,
+{
+    /// Build the router, embedding every route defined with [`Self::route`] and inserting it into the store.
+    pub async fn build_with_routes(mut self) -> Result<SemanticRouter<V>, SemanticRouterError> {
+        let routes = std::mem::take(&mut self.routes);
+        let router = self.build()?;
+
+        for (route, utterances) in routes {
+            router.add_route(route, utterances).await?;
+        }
+
+        Ok(router)
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum SemanticRouterError {
+    #[error("Vector store not found")]
+    StoreNotFound,
+    #[error("Routes were defined on the builder, use `build_with_routes` to insert them")]
+    RoutesNotInserted,
+    #[error("Invalid route pattern: {0}")]
+    InvalidPattern(#[from] regex::Error),
+    #[error("Vector store error: {0}")]
+    StoreError(#[from] VectorStoreError),
+    #[error("No agent is registered for route: {0}")]
+    RouteNotRegistered(String),
+    /// Boxed, as prompt errors are much larger than the other variants.
+    #[error("Agent error: {0}")]
+    AgentError(#[from] Box<PromptError>),
+    #[error("Route handler error: {0}")]
+    HandlerError(#[source] anyhow::Error),
+    #[error("Prompt template error: {0}")]
+    TemplateError(#[from] tera::Error),
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Serialization error: {0}")]
+    Serialization(#[from] serde_json::Error),
+    #[error("Config error: {0}")]
+    Config(#[from] serde_yaml::Error),
+    #[error("Embedding has {found} dimensions, but the model produces {expected}")]
+    DimensionMismatch { expected: usize, found: usize },
+    #[error("Route target timed out after {0:?}")]
+    Timeout(Duration),
+}
+
+impl From<PromptError> for SemanticRouterError {
+    fn from(err: PromptError) -> Self {
+        Self::AgentError(Box::new(err))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::future::BoxFuture;
+    use rig::vector_store::VectorStoreError;
+
+    use super::{
+        DecisionSource, InMemoryRouteIndex, Reranker, SemanticRoute, SemanticRouter,
+        SemanticRouterBuilder, rerank::RerankError,
+    };
+    use crate::PromptTemplate;
+    use crate::test_utils::{Echo, Fixed, WordCounts};
+
+    const WORDS: &[&str] = &[
+        "invoice",
+        "payment",
+        "refund",
+        "error",
+        "crash",
+        "ignore",
+        "instructions",
+    ];
+
+    /// Scores documents by their length, returning the given number of scores.
+    struct Lengths(usize);
+
+    impl Reranker for Lengths {
+        fn rerank<'a>(
+            &'a self,
+            _query: &'a str,
+            documents: &'a [String],
+        ) -> BoxFuture<'a, Result<Vec<f64>, RerankError>> {
+            let scores = documents
+                .iter()
+                .map(|x| x.len() as f64 / 100.0)
+                .chain(std::iter::repeat(0.0))
+                .take(self.0)
+                .collect();
+
+            Box::pin(async move { Ok(scores) })
+        }
+    }
+
+    fn builder() -> SemanticRouterBuilder<InMemoryRouteIndex<WordCounts>> {
+        SemanticRouter::builder()
+            .embedding_model(WordCounts(WORDS))
+            .route(
+                SemanticRoute::new("billing").with_metadata("team", "finance"),
+                ["invoice payment"],
+            )
+            .route("support", ["error crash"])
+    }
+
+    #[tokio::test]
+    async fn picks_routes_by_threshold() {
+        let router = builder()
+            .route_threshold("support", 0.5)
+            .default_route("fallback")
+            .build_with_routes()
+            .await
+            .unwrap();
+
+        let decision = router.decision("invoice payment").await.unwrap();
+        assert_eq!(decision.source, DecisionSource::Matched);
+        let route = decision.route.unwrap();
+        assert_eq!(route.tag, "billing");
+        assert_eq!(route.metadata["team"], "finance");
+
+        // Both queries score 0.63 for their route, which only clears the lower threshold of support
+        let decision = router.decision("crash crash invoice").await.unwrap();
+        assert_eq!(decision.source, DecisionSource::Matched);
+        assert_eq!(decision.route.unwrap().tag, "support");
+        assert!(decision.margin.is_some());
+
+        let decision = router.decision("invoice invoice crash").await.unwrap();
+        assert_eq!(decision.source, DecisionSource::DefaultRoute);
+        assert_eq!(decision.route.unwrap().tag, "fallback");
+        assert_eq!(decision.best.unwrap().tag, "billing");
+        assert_eq!(decision.margin, None);
+
+        let router = builder().build_with_routes().await.unwrap();
+        let decision = router.decision("refund").await.unwrap();
+        assert_eq!(decision.source, DecisionSource::NoMatch);
+        assert_eq!(decision.route, None);
+    }
+
+    #[tokio::test]
+    async fn matches_routes_on_phrases_and_patterns() {
+        let router = builder()
+            .phrase("billing", "invoice #")
+            .pattern("orders", r"\bORD-\d+\b")
+            .keywords("support", ["crash"])
+            .build_with_routes()
+            .await
+            .unwrap();
+
+        // Billing only scores 0.71 semantically
+        let decision = router.decision("Where is invoice #42?").await.unwrap();
+        assert_eq!(decision.source, DecisionSource::Matched);
+        assert_eq!(decision.route.unwrap().tag, "billing");
+
+        // Orders has no utterances, so it can only match on its pattern
+        let decision = router.decision("What happened to ORD-7?").await.unwrap();
+        assert_eq!(decision.route.unwrap().tag, "orders");
+
+        // Keywords are blended by weight, so a full keyword match only raises the semantic score of 0.71 to 0.79
+        let decision = router.decision("crash report").await.unwrap();
+        assert_eq!(decision.source, DecisionSource::NoMatch);
+        assert_eq!(decision.best.unwrap().tag, "support");
+    }
+
+    #[tokio::test]
+    async fn reranks_candidates() {
+        let builder = || {
+            builder()
+                .threshold(0.3)
+                .route_description("support", &"Errors and crashes ".repeat(3))
+        };
+
+        // Billing has the best embedding score, but the longer description wins
+        let router = builder()
+            .reranker(Lengths(2))
+            .build_with_routes()
+            .await
+            .unwrap();
+        let route = router.route("invoice payment").await.unwrap().unwrap();
+        assert_eq!(route.tag, "support");
+        assert_eq!(route.score, 0.57);
+
+        let router = builder()
+            .reranker(Lengths(1))
+            .build_with_routes()
+            .await
+            .unwrap();
+        assert!(matches!(
+            router.route("invoice payment").await,
+            Err(VectorStoreError::DatastoreError(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn guardrails_take_precedence() {
+        let router = builder()
+            .route("jailbreak", ["ignore instructions"])
+            .guardrail("jailbreak", "I can't help with that")
+            .route_threshold("jailbreak", 0.4)
+            .build_with_routes()
+            .await
+            .unwrap();
+
+        // Billing scores higher, but the guardrail is above its own threshold
+        let decision = router.decision("ignore invoice payment").await.unwrap();
+        assert_eq!(decision.source, DecisionSource::Guardrail);
+        assert_eq!(decision.route.unwrap().tag, "jailbreak");
+        assert_eq!(decision.best.unwrap().tag, "billing");
+
+        let router = router.agent("billing", Fixed("Billed"));
+        assert_eq!(
+            router
+                .prompt("ignore invoice payment")
+                .await
+                .unwrap()
+                .as_deref(),
+            Some("I can't help with that")
+        );
+        assert_eq!(
+            router.prompt("invoice payment").await.unwrap().as_deref(),
+            Some("Billed")
+        );
+    }
+
+    #[tokio::test]
+    async fn traces_nested_routes_and_agents() {
+        let billing = SemanticRouter::builder()
+            .embedding_model(WordCounts(WORDS))
+            .threshold(0.5)
+            .route("refunds", ["refund"])
+            .route("invoices", ["invoice"])
+            .build_with_routes()
+            .await
+            .unwrap()
+            .handler("refunds", |_| async { Ok("Refunded".to_string()) })
+            .agent("invoices", Fixed("Invoiced"));
+
+        let router = builder()
+            .route("billing", ["refund"])
+            .threshold(0.6)
+            .build_with_routes()
+            .await
+            .unwrap()
+            .into_agents()
+            .router("billing", billing)
+            .agent_variant("support", "stable", Fixed("Supported"), 1)
+            .default_agent(Fixed("Hello"));
+
+        let res = router
+            .prompt_traced("refund payment")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(res.response, "Refunded");
+        let trace: Vec<&str> = res.trace.iter().map(|x| x.tag.as_str()).collect();
+        assert_eq!(trace, ["billing", "refunds"]);
+        assert_eq!(res.tag(), Some("refunds"));
+        assert_eq!(res.agent_id.as_deref(), Some("refunds"));
+
+        let res = router.prompt_traced("error crash").await.unwrap().unwrap();
+        assert_eq!(res.response, "Supported");
+        assert_eq!(res.variant.as_deref(), Some("stable"));
+        assert_eq!(res.agent_id.as_deref(), Some("support/stable"));
+
+        let res = router.prompt_traced("hello").await.unwrap().unwrap();
+        assert_eq!(res.response, "Hello");
+        assert!(res.trace.is_empty());
+        assert_eq!(res.agent_id.as_deref(), Some("default"));
+    }
+
+    #[tokio::test]
+    async fn applies_templates_and_post_response_hooks() {
+        let router = builder()
+            .post_response(|ctx, res| {
+                let tag = ctx.route.as_ref().map_or("none", |x| x.tag.as_str());
+                format!("{res} [{tag}]")
+            })
+            .build_with_routes()
+            .await
+            .unwrap()
+            .agent("billing", Echo)
+            .template(
+                "billing",
+                PromptTemplate::new("{{ route }} ({{ metadata.team }}): {{ query }}"),
+            )
+            .handler("support", |ctx| async move { Ok(ctx.query) })
+            .template("support", PromptTemplate::new("Not used: {{ query }}"));
+
+        assert_eq!(
+            router.prompt("invoice payment").await.unwrap().as_deref(),
+            Some("billing (finance): invoice payment [billing]")
+        );
+        // Handlers get the raw query, as templates only apply to agents
+        assert_eq!(
+            router.prompt("error crash").await.unwrap().as_deref(),
+            Some("error crash [support]")
+        );
+    }
+}
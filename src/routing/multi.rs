@@ -0,0 +1,157 @@
+//! A route index spread over several vector stores.
+use std::sync::Arc;
+
+use futures::future::{BoxFuture, try_join_all};
+use rig::vector_store::{VectorStoreError, VectorStoreIndex};
+use serde::Deserialize;
+use serde_json::Value;
+
+/// Retrieved documents as `(score, id, document)` tuples.
+type JsonResults = Vec<(f64, String, Value)>;
+
+/// A dyn-compatible handle to a vector store, returning documents as JSON.
+trait RouteStore: Send + Sync {
+    fn top_n_json<'a>(
+        &'a self,
+        query: &'a str,
+        n: usize,
+    ) -> BoxFuture<'a, Result<JsonResults, VectorStoreError>>;
+
+    fn top_n_ids_dyn<'a>(
+        &'a self,
+        query: &'a str,
+        n: usize,
+    ) -> BoxFuture<'a, Result<Vec<(f64, String)>, VectorStoreError>>;
+}
+
+impl<V> RouteStore for V
+where
+    V: VectorStoreIndex,
+{
+    fn top_n_json<'a>(
+        &'a self,
+        query: &'a str,
+        n: usize,
+    ) -> BoxFuture<'a, Result<JsonResults, VectorStoreError>> {
+        Box::pin(self.top_n::<Value>(query, n))
+    }
+
+    fn top_n_ids_dyn<'a>(
+        &'a self,
+        query: &'a str,
+        n: usize,
+    ) -> BoxFuture<'a, Result<Vec<(f64, String)>, VectorStoreError>> {
+        Box::pin(self.top_n_ids(query, n))
+    }
+}
+
+/// A route index that aggregates several vector stores (ie one per route group or tenant), so that route utterances don't need to live in one index.
+///
+/// Every store is queried in parallel, and the results are merged by score. The stores should return comparable scores
+/// (ie all cosine similarity), as a single [`ScoreTransform`](super::ScoreTransform) is applied to the merged results.
+/// Result IDs are prefixed with the name of the store they came from, as `name:id`.
+#[derive(Clone, Default)]
+pub struct MultiRouteIndex {
+    stores: Vec<(String, Arc<dyn RouteStore>)>,
+}
+
+impl MultiRouteIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a store under a name, ie the route group or tenant it holds.
+    pub fn store<V>(mut self, name: &str, store: V) -> Self
+    where
+        V: VectorStoreIndex + 'static,
+    {
+        self.stores.push((name.to_string(), Arc::new(store)));
+        self
+    }
+
+    /// The names of the stores, in the order they were added.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.stores.iter().map(|(name, _)| name.as_str())
+    }
+}
+
+impl std::fmt::Debug for MultiRouteIndex {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MultiRouteIndex")
+            .field("stores", &self.names().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+/// Sort merged results by score (highest first) and keep the best `n`.
+fn merge<T>(mut results: Vec<(f64, String, T)>, n: usize) -> Vec<(f64, String, T)> {
+    results.sort_by(|a, b| b.0.total_cmp(&a.0));
+    results.truncate(n);
+    results
+}
+
+impl VectorStoreIndex for MultiRouteIndex {
+    async fn top_n<T: for<'a> Deserialize<'a> + Send>(
+        &self,
+        query: &str,
+        n: usize,
+    ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
+        let results = try_join_all(self.stores.iter().map(|(name, store)| async move {
+            let res = store.top_n_json(query, n).await?;
+
+            Ok::<_, VectorStoreError>(
+                res.into_iter()
+                    .map(|(score, id, doc)| (score, format!("{name}:{id}"), doc))
+                    .collect::<Vec<_>>(),
+            )
+        }))
+        .await?;
+
+        merge(results.into_iter().flatten().collect(), n)
+            .into_iter()
+            .map(|(score, id, doc)| Ok((score, id, serde_json::from_value(doc)?)))
+            .collect()
+    }
+
+    async fn top_n_ids(
+        &self,
+        query: &str,
+        n: usize,
+    ) -> Result<Vec<(f64, String)>, VectorStoreError> {
+        let results = try_join_all(self.stores.iter().map(|(name, store)| async move {
+            let res = store.top_n_ids_dyn(query, n).await?;
+
+            Ok::<_, VectorStoreError>(
+                res.into_iter()
+                    .map(|(score, id)| (score, format!("{name}:{id}"), ()))
+                    .collect::<Vec<_>>(),
+            )
+        }))
+        .await?;
+
+        Ok(merge(results.into_iter().flatten().collect(), n)
+            .into_iter()
+            .map(|(score, id, _)| (score, id))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::merge;
+
+    #[test]
+    fn merges_results_by_score() {
+        let merged = merge(
+            vec![
+                (0.5, "a:0".to_string(), ()),
+                (0.9, "b:3".to_string(), ()),
+                (0.7, "a:1".to_string(), ()),
+            ],
+            2,
+        );
+
+        let ids: Vec<&str> = merged.iter().map(|(_, id, _)| id.as_str()).collect();
+        assert_eq!(ids, ["b:3", "a:1"]);
+    }
+}
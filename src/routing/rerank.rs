@@ -0,0 +1,132 @@
+//! Re-ranking of candidate routes.
+//!
+//! Embedding similarity is a fast but coarse signal, which struggles to separate closely related routes ("refunds" vs "billing disputes").
+//! A [`Reranker`] (ie a cross-encoder, or a provider's rerank endpoint) scores the query against each candidate route directly,
+//! and its scores replace the retrieval scores before thresholds are applied.
+use futures::future::BoxFuture;
+
+/// An error from a reranker.
+pub type RerankError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Scores how relevant documents are to a query.
+///
+/// Implement this for a local cross-encoder (ie a BERT cross-encoder running on Candle) or a provider's rerank API.
+/// The documents are route descriptions (see [`SemanticRouterBuilder::route_description`](super::SemanticRouterBuilder::route_description)),
+/// or the route's tag if it has no description.
+pub trait Reranker: Send + Sync {
+    /// Score each document for the query. Scores should be between 0 and 1 (higher is more relevant), with one score per document, in order.
+    fn rerank<'a>(
+        &'a self,
+        query: &'a str,
+        documents: &'a [String],
+    ) -> BoxFuture<'a, Result<Vec<f64>, RerankError>>;
+}
+
+#[cfg(feature = "cohere")]
+pub use cohere::CohereReranker;
+
+#[cfg(feature = "cohere")]
+mod cohere {
+    use futures::future::BoxFuture;
+    use serde::{Deserialize, Serialize};
+
+    use super::{RerankError, Reranker};
+
+    const COHERE_API_BASE_URL: &str = "https://api.cohere.com/v2";
+
+    /// A reranker using Cohere's rerank API, ie with the `rerank-v3.5` model.
+    #[derive(Clone)]
+    pub struct CohereReranker {
+        base_url: String,
+        api_key: String,
+        model: String,
+        http_client: reqwest::Client,
+    }
+
+    impl std::fmt::Debug for CohereReranker {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.debug_struct("CohereReranker")
+                .field("base_url", &self.base_url)
+                .field("model", &self.model)
+                .field("api_key", b"<REDACTED>")
+                .finish()
+        }
+    }
+
+    #[derive(Serialize)]
+    struct RerankRequest<'a> {
+        model: &'a str,
+        query: &'a str,
+        documents: &'a [String],
+    }
+
+    #[derive(Deserialize)]
+    struct RerankResponse {
+        results: Vec<RerankResult>,
+    }
+
+    #[derive(Deserialize)]
+    struct RerankResult {
+        index: usize,
+        relevance_score: f64,
+    }
+
+    impl CohereReranker {
+        pub fn new(api_key: &str, model: &str) -> Self {
+            Self {
+                base_url: COHERE_API_BASE_URL.to_string(),
+                api_key: api_key.to_string(),
+                model: model.to_string(),
+                http_client: reqwest::Client::new(),
+            }
+        }
+
+        /// Create a reranker from the `COHERE_API_KEY` environment variable.
+        pub fn from_env(model: &str) -> Self {
+            let api_key = std::env::var("COHERE_API_KEY")
+                .expect("expected COHERE_API_KEY to exist as an environment variable");
+
+            Self::new(&api_key, model)
+        }
+
+        pub fn with_custom_client(mut self, client: reqwest::Client) -> Self {
+            self.http_client = client;
+            self
+        }
+    }
+
+    impl Reranker for CohereReranker {
+        fn rerank<'a>(
+            &'a self,
+            query: &'a str,
+            documents: &'a [String],
+        ) -> BoxFuture<'a, Result<Vec<f64>, RerankError>> {
+            Box::pin(async move {
+                let res: RerankResponse = self
+                    .http_client
+                    .post(format!("{}/rerank", self.base_url))
+                    .bearer_auth(&self.api_key)
+                    .json(&RerankRequest {
+                        model: &self.model,
+                        query,
+                        documents,
+                    })
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .json()
+                    .await?;
+
+                // Results are sorted by relevance, so put them back in document order
+                let mut scores = vec![0.0; documents.len()];
+                for result in res.results {
+                    if let Some(score) = scores.get_mut(result.index) {
+                        *score = result.relevance_score;
+                    }
+                }
+
+                Ok(scores)
+            })
+        }
+    }
+}
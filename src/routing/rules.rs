@@ -0,0 +1,91 @@
+//! Rules that route queries before any vector query is made.
+//!
+//! Commands ("/help") and identifiers (order numbers) are cheaper to route, and more reliably routed, by exact or regex matching
+//! than by embeddings. Rules are checked in the order they were added, and the first one that fires picks the route.
+use regex::Regex;
+
+/// How a rule is matched, before it's compiled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum RuleKind {
+    /// The (trimmed) query equals the text, ignoring case.
+    Exact,
+    /// The query matches a regex pattern.
+    Pattern,
+}
+
+#[derive(Debug, Clone)]
+enum Condition {
+    Exact(String),
+    Pattern(Regex),
+}
+
+#[derive(Debug, Clone)]
+struct Rule {
+    tag: String,
+    condition: Condition,
+}
+
+impl Rule {
+    fn matches(&self, query: &str) -> bool {
+        match &self.condition {
+            Condition::Exact(text) => query.trim().to_lowercase() == *text,
+            Condition::Pattern(regex) => regex.is_match(query),
+        }
+    }
+}
+
+/// Rules that route queries without a vector query, in the order they're checked.
+#[derive(Debug, Clone, Default)]
+pub(super) struct RouteRules {
+    rules: Vec<Rule>,
+}
+
+impl RouteRules {
+    /// Compile rules given as `(kind, tag, text)`. Fails on the first invalid regex pattern.
+    pub(super) fn compile(rules: Vec<(RuleKind, String, String)>) -> Result<Self, regex::Error> {
+        let rules = rules
+            .into_iter()
+            .map(|(kind, tag, text)| {
+                let condition = match kind {
+                    RuleKind::Exact => Condition::Exact(text.trim().to_lowercase()),
+                    RuleKind::Pattern => Condition::Pattern(Regex::new(&text)?),
+                };
+
+                Ok(Rule { tag, condition })
+            })
+            .collect::<Result<_, regex::Error>>()?;
+
+        Ok(Self { rules })
+    }
+
+    /// The tag of the first rule that fires for a query, if any.
+    pub(super) fn matches(&self, query: &str) -> Option<&str> {
+        self.rules
+            .iter()
+            .find(|x| x.matches(query))
+            .map(|x| x.tag.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RouteRules, RuleKind};
+
+    #[test]
+    fn first_matching_rule_wins() {
+        let rules = RouteRules::compile(vec![
+            (RuleKind::Exact, "help".into(), "/help".into()),
+            (RuleKind::Pattern, "orders".into(), r"\bORD-\d+\b".into()),
+            (RuleKind::Pattern, "catch_all".into(), r"ORD".into()),
+        ])
+        .unwrap();
+
+        assert_eq!(rules.matches("  /HELP "), Some("help"));
+        assert_eq!(rules.matches("/help me"), None);
+        assert_eq!(rules.matches("Where is ORD-42?"), Some("orders"));
+        assert_eq!(rules.matches("ORD pending"), Some("catch_all"));
+        assert_eq!(rules.matches("hello"), None);
+
+        assert!(RouteRules::compile(vec![(RuleKind::Pattern, "x".into(), "(".into())]).is_err());
+    }
+}
@@ -0,0 +1,144 @@
+//! Normalization of the scores returned by vector stores.
+//!
+//! Some vector stores return a cosine similarity between -1 and 1, others a distance where lower is closer, and embedding models
+//! differ in how spread out their similarities are. A [`ScoreTransform`] maps raw scores onto a similarity scale before they are
+//! compared to route thresholds, so that a threshold means the same thing regardless of the store.
+//!
+//! Routes usually have several example utterances. An [`Aggregation`] combines the scores of a route's utterances into the route's score.
+use serde::{Deserialize, Serialize};
+
+/// How raw vector store scores are converted into similarity scores.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ScoreTransform {
+    /// Use scores as-is. The store returns a similarity where higher is closer.
+    #[default]
+    Identity,
+    /// The store returns a distance (where lower is closer), which is converted to a similarity with `1 / (1 + distance)`.
+    DistanceToSimilarity,
+    /// Linearly rescale scores so that `low` maps to 0 and `high` maps to 1, clamping anything outside that range.
+    /// For distances, `low` is greater than `high`.
+    MinMax { low: f64, high: f64 },
+}
+
+impl ScoreTransform {
+    /// Convert a raw score into a similarity.
+    pub fn apply(&self, score: f64) -> f64 {
+        match self {
+            Self::Identity => score,
+            Self::DistanceToSimilarity => 1.0 / (1.0 + score.max(0.0)),
+            Self::MinMax { low, high } => {
+                if low == high {
+                    return if score == *high { 1.0 } else { 0.0 };
+                }
+
+                ((score - low) / (high - low)).clamp(0.0, 1.0)
+            }
+        }
+    }
+
+    /// Build a [`ScoreTransform::MinMax`] from a sample of raw scores, ie the scores of a set of typical queries.
+    /// Set `is_distance` if lower scores are closer. Returns [`ScoreTransform::Identity`] if there are no samples.
+    pub fn calibrate(samples: &[f64], is_distance: bool) -> Self {
+        let finite = samples.iter().copied().filter(|x| x.is_finite());
+        let Some((min, max)) = finite.fold(None, |acc: Option<(f64, f64)>, x| match acc {
+            Some((min, max)) => Some((min.min(x), max.max(x))),
+            None => Some((x, x)),
+        }) else {
+            return Self::Identity;
+        };
+
+        if is_distance {
+            Self::MinMax {
+                low: max,
+                high: min,
+            }
+        } else {
+            Self::MinMax {
+                low: min,
+                high: max,
+            }
+        }
+    }
+}
+
+/// How the (transformed) scores of a route's matched utterances are combined into the route's score.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Aggregation {
+    /// Use the best utterance score. Favours routes with one highly specific utterance.
+    #[default]
+    Max,
+    /// Average the best `top_n` utterance scores. Routes with fewer matched utterances average over the ones they have.
+    Mean { top_n: usize },
+    /// Add up the best `top_n` utterance scores. Rewards routes that many utterances match, but scores can exceed 1,
+    /// so thresholds should be set accordingly.
+    Sum { top_n: usize },
+}
+
+impl Aggregation {
+    /// How many utterances per route are used.
+    pub fn depth(&self) -> usize {
+        match self {
+            Self::Max => 1,
+            Self::Mean { top_n } | Self::Sum { top_n } => (*top_n).max(1),
+        }
+    }
+
+    /// Combine utterance scores into a single score. Returns 0 if there are no scores.
+    pub fn apply(&self, scores: &[f64]) -> f64 {
+        let mut scores = scores.to_vec();
+        scores.sort_by(|a, b| b.total_cmp(a));
+        scores.truncate(self.depth());
+
+        match self {
+            Self::Max => scores.first().copied().unwrap_or_default(),
+            Self::Mean { .. } if scores.is_empty() => 0.0,
+            Self::Mean { .. } => scores.iter().sum::<f64>() / scores.len() as f64,
+            Self::Sum { .. } => scores.iter().sum(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Aggregation, ScoreTransform};
+
+    #[test]
+    fn aggregates_scores() {
+        let scores = [0.6, 0.9, 0.8];
+
+        assert_eq!(Aggregation::Max.apply(&scores), 0.9);
+        assert!((Aggregation::Mean { top_n: 2 }.apply(&scores) - 0.85).abs() < 1e-9);
+        assert!((Aggregation::Mean { top_n: 5 }.apply(&scores) - 0.7666666666).abs() < 1e-9);
+        assert!((Aggregation::Sum { top_n: 2 }.apply(&scores) - 1.7).abs() < 1e-9);
+        assert_eq!(Aggregation::Mean { top_n: 3 }.apply(&[]), 0.0);
+    }
+
+    #[test]
+    fn transforms_scores() {
+        assert_eq!(ScoreTransform::Identity.apply(0.42), 0.42);
+        assert_eq!(ScoreTransform::DistanceToSimilarity.apply(0.0), 1.0);
+        assert_eq!(ScoreTransform::DistanceToSimilarity.apply(1.0), 0.5);
+
+        let similarity = ScoreTransform::calibrate(&[0.7, 0.9, 0.8], false);
+        assert_eq!(
+            similarity,
+            ScoreTransform::MinMax {
+                low: 0.7,
+                high: 0.9
+            }
+        );
+        assert!((similarity.apply(0.8) - 0.5).abs() < 1e-9);
+        assert_eq!(similarity.apply(0.95), 1.0);
+
+        let distance = ScoreTransform::calibrate(&[0.2, 1.2], true);
+        assert_eq!(distance.apply(0.2), 1.0);
+        assert_eq!(distance.apply(1.2), 0.0);
+
+        assert_eq!(
+            ScoreTransform::calibrate(&[], false),
+            ScoreTransform::Identity
+        );
+    }
+}
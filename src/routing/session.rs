@@ -0,0 +1,241 @@
+//! Conversation-aware routing.
+use std::time::Instant;
+
+use rig::{
+    message::Message,
+    vector_store::{VectorStoreError, VectorStoreIndex},
+};
+use tracing::Instrument;
+
+use super::{
+    RouteContext, RouteDecision, RouteMatch, RoutedResponse, SemanticRouterError,
+    SemanticRouterWithAgents, decision_span, metrics::DecisionSource,
+};
+
+/// How much higher another route has to score than the current route before a session switches to it.
+const DEFAULT_STICKINESS_MARGIN: f64 = 0.1;
+
+/// A conversation with a [`SemanticRouterWithAgents`].
+///
+/// The session keeps the chat history and passes it to the routed agent on every turn.
+/// Follow-ups like "what about tomorrow?" rarely match any route on their own, so the session sticks to the route of the previous turn
+/// unless the new query scores above the threshold for another route, and beats the current route's score by a margin.
+pub struct RouterSession<'a, V> {
+    router: &'a SemanticRouterWithAgents<V>,
+    history: Vec<Message>,
+    current_route: Option<String>,
+    margin: f64,
+}
+
+impl<'a, V> RouterSession<'a, V>
+where
+    V: VectorStoreIndex,
+{
+    pub(super) fn new(router: &'a SemanticRouterWithAgents<V>) -> Self {
+        Self {
+            router,
+            history: Vec::new(),
+            current_route: None,
+            margin: DEFAULT_STICKINESS_MARGIN,
+        }
+    }
+
+    /// Set how much higher another route has to score than the current route to switch to it. Defaults to 0.1.
+    pub fn with_margin(mut self, margin: f64) -> Self {
+        self.margin = margin;
+        self
+    }
+
+    /// The route of the last turn, if any.
+    pub fn current_route(&self) -> Option<&str> {
+        self.current_route.as_deref()
+    }
+
+    pub fn history(&self) -> &[Message] {
+        &self.history
+    }
+
+    /// Clear the chat history and the current route.
+    pub fn reset(&mut self) {
+        self.history.clear();
+        self.current_route = None;
+    }
+
+    /// Route a query (taking the current route into account), then chat with the routed agent.
+    /// Returns `Ok(None)` if no route matched and there is no default agent, in which case the history is left unchanged.
+    pub async fn chat(&mut self, query: &str) -> Result<Option<String>, SemanticRouterError> {
+        let query = &self.router.router.middleware.rewrite(query);
+        let route = self.next_route(query).await?;
+        let tag = route.as_ref().map(|x| x.tag.clone());
+
+        let Some(target) = self.router.target_for(tag.as_deref())? else {
+            return Ok(None);
+        };
+
+        let ctx = RouteContext {
+            query: query.to_string(),
+            route,
+            history: self.history.clone(),
+            turns: 0,
+            margin: None,
+        };
+        let Some(RoutedResponse { response, .. }) = self.router.respond_with(target, ctx).await?
+        else {
+            return Ok(None);
+        };
+
+        self.history.push(Message::user(query.as_str()));
+        self.history.push(Message::assistant(&response));
+        // Refused queries don't change the route of the conversation
+        if !tag
+            .as_deref()
+            .is_some_and(|x| self.router.router.is_guardrail(x))
+        {
+            self.current_route = tag;
+        }
+
+        Ok(Some(response))
+    }
+
+    /// Picks (and records) the route for the next turn, applying stickiness to the current route.
+    async fn next_route(&self, query: &str) -> Result<Option<RouteMatch>, SemanticRouterError> {
+        let router = &self.router.router;
+
+        let Some(current) = &self.current_route else {
+            return Ok(router.route_rewritten(query).await?);
+        };
+
+        let span = decision_span(query);
+        let start = Instant::now();
+
+        // Rules are explicit, so they override the current route
+        let decision = match router.rule_decision(query, start) {
+            Some(decision) => decision,
+            None => {
+                self.sticky_decision(current, query, start)
+                    .instrument(span.clone())
+                    .await?
+            }
+        };
+        router.record_decision(&span, &decision);
+
+        Ok(decision.route)
+    }
+
+    /// The decision for a query that no rule fires for, given the current route.
+    async fn sticky_decision(
+        &self,
+        current: &str,
+        query: &str,
+        start: Instant,
+    ) -> Result<RouteDecision, VectorStoreError> {
+        let router = &self.router.router;
+
+        let mut candidates = router.scored_routes(query).await?;
+        let best = candidates.first().cloned();
+
+        // Guardrails apply regardless of the current route
+        if let Some(blocked) = router.guardrail_match(&candidates) {
+            return Ok(RouteDecision {
+                route: Some(blocked),
+                best,
+                source: DecisionSource::Guardrail,
+                margin: None,
+                duration: start.elapsed(),
+            });
+        }
+        candidates.retain(|x| !router.is_guardrail(&x.tag));
+        let margin = match candidates.as_slice() {
+            [first, second, ..] => Some(first.score - second.score),
+            _ => None,
+        };
+        let current_match = match candidates.iter().position(|x| x.tag == current) {
+            Some(idx) => candidates.remove(idx),
+            None => RouteMatch::new(current, 0.0),
+        };
+        let current_score = current_match.score;
+
+        let switch_to = candidates.into_iter().next().filter(|best| {
+            best.score >= router.threshold(&best.tag) && best.score > current_score + self.margin
+        });
+
+        let (route, source, margin) = match switch_to {
+            Some(route) => {
+                tracing::info!(
+                    "Switching route from {current} to {} ({})",
+                    route.tag,
+                    route.score
+                );
+
+                (route, DecisionSource::Matched, margin)
+            }
+            None => (current_match, DecisionSource::Session, None),
+        };
+
+        Ok(RouteDecision {
+            route: Some(route),
+            best,
+            source,
+            margin,
+            duration: start.elapsed(),
+        })
+    }
+}
+
+impl<V> std::fmt::Debug for RouterSession<'_, V> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RouterSession")
+            .field("history", &self.history)
+            .field("current_route", &self.current_route)
+            .field("margin", &self.margin)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::routing::{InMemoryMetrics, SemanticRouter};
+    use crate::test_utils::{Fixed, WordCounts};
+
+    #[tokio::test]
+    async fn sticks_to_the_current_route() {
+        let metrics = Arc::new(InMemoryMetrics::new());
+        let router = SemanticRouter::builder()
+            .embedding_model(WordCounts(&[
+                "invoice", "payment", "error", "crash", "ignore",
+            ]))
+            .route("billing", ["invoice payment"])
+            .route("support", ["error crash"])
+            .route("jailbreak", ["ignore"])
+            .guardrail("jailbreak", "Refused")
+            .rule_exact("help", "/help")
+            .metrics(metrics.clone())
+            .build_with_routes()
+            .await
+            .unwrap()
+            .agent("billing", Fixed("Billing"))
+            .agent("support", Fixed("Support"))
+            .agent("help", Fixed("Help"));
+
+        let mut session = router.session().with_margin(0.5);
+        let mut chat = async |query| session.chat(query).await.unwrap().unwrap();
+
+        assert_eq!(chat("invoice payment").await, "Billing");
+        // Follow-ups don't match any route
+        assert_eq!(chat("and tomorrow?").await, "Billing");
+        // Support scores 0.82 and billing 0.41, which is within the margin
+        assert_eq!(chat("invoice error crash").await, "Billing");
+        assert_eq!(chat("error crash").await, "Support");
+        assert_eq!(chat("ignore that").await, "Refused");
+        assert_eq!(chat("/help").await, "Help");
+        assert_eq!(session.current_route(), Some("help"));
+        assert_eq!(session.history().len(), 12);
+
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.decisions, 6);
+        assert_eq!(snapshot.routes["billing"].hits, 3);
+        assert_eq!(snapshot.routes["jailbreak"].hits, 1);
+    }
+}
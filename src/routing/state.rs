@@ -0,0 +1,229 @@
+//! Saving and loading routers, so that route utterances don't have to be embedded again on every startup,
+//! and moving routers to a new embedding model.
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufReader, BufWriter, Write},
+    path::Path,
+};
+
+use rig::embeddings::EmbeddingModel;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    Aggregation, InMemoryRouteIndex, RouteUtterance, ScoreTransform, SemanticRouter,
+    SemanticRouterError, SemanticRouterWithAgents,
+};
+
+/// The serializable state of a [`SemanticRouter`]: its routes (with embeddings) and scoring settings.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct RouterState {
+    threshold: f64,
+    #[serde(default)]
+    route_thresholds: HashMap<String, f64>,
+    #[serde(default)]
+    default_route: Option<String>,
+    #[serde(default)]
+    score_transform: ScoreTransform,
+    #[serde(default)]
+    aggregation: Aggregation,
+    #[serde(default)]
+    guardrails: HashMap<String, String>,
+    /// Route descriptions, used by the reranker (and the LLM classifier, if one is set on a rebuilt router).
+    #[serde(default)]
+    descriptions: HashMap<String, String>,
+    utterances: Vec<RouteUtterance>,
+}
+
+impl<M> SemanticRouter<InMemoryRouteIndex<M>>
+where
+    M: EmbeddingModel,
+{
+    /// Save the routes (including their embeddings), thresholds, default route, score transform, aggregation, guardrails
+    /// and route descriptions to a JSON file.
+    ///
+    /// The LLM classifier, lexical patterns, rules, metrics sink, hooks, cache and reranker are not saved. To use them with saved routes,
+    /// load just the index with [`InMemoryRouteIndex::load`] and pass it to the builder.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), SemanticRouterError> {
+        let state = RouterState {
+            threshold: self.threshold,
+            route_thresholds: self.route_thresholds.clone(),
+            default_route: self.default_route.clone(),
+            score_transform: self.score_transform,
+            aggregation: self.aggregation,
+            guardrails: self.guardrails.clone(),
+            descriptions: self.descriptions.clone(),
+            utterances: self.store.utterances(),
+        };
+
+        let mut writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer(&mut writer, &state)?;
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    /// Load a router saved with [`Self::save`]. The model should be the one the routes were embedded with, as it's used to embed queries.
+    pub fn load(model: M, path: impl AsRef<Path>) -> Result<Self, SemanticRouterError> {
+        let state: RouterState = serde_json::from_reader(BufReader::new(File::open(path)?))?;
+
+        Ok(SemanticRouter {
+            store: InMemoryRouteIndex::from_utterances(model, state.utterances)?,
+            threshold: state.threshold,
+            route_thresholds: state.route_thresholds,
+            default_route: state.default_route,
+            llm_fallback: None,
+            lexical: None,
+            rules: Default::default(),
+            metrics: None,
+            score_transform: state.score_transform,
+            aggregation: state.aggregation,
+            guardrails: state.guardrails,
+            middleware: Default::default(),
+            cache: None,
+            reranker: None,
+            descriptions: state.descriptions,
+        })
+    }
+
+    /// Re-embed every route utterance with a different embedding model, keeping tags, metadata and all other settings.
+    ///
+    /// Scores from the new model may be spread differently, so thresholds (and the score transform, see [`Self::calibrate`])
+    /// may need adjusting. The route cache is cleared, as its candidates were scored with the old model.
+    pub async fn reembed<N>(
+        self,
+        model: N,
+    ) -> Result<SemanticRouter<InMemoryRouteIndex<N>>, SemanticRouterError>
+    where
+        N: EmbeddingModel,
+    {
+        let store = self.store.reembed(model).await?;
+        if let Some(cache) = &self.cache {
+            cache.clear();
+        }
+
+        Ok(SemanticRouter {
+            store,
+            threshold: self.threshold,
+            route_thresholds: self.route_thresholds,
+            default_route: self.default_route,
+            llm_fallback: self.llm_fallback,
+            lexical: self.lexical,
+            rules: self.rules,
+            metrics: self.metrics,
+            score_transform: self.score_transform,
+            aggregation: self.aggregation,
+            guardrails: self.guardrails,
+            middleware: self.middleware,
+            cache: self.cache,
+            reranker: self.reranker,
+            descriptions: self.descriptions,
+        })
+    }
+}
+
+impl<M> SemanticRouterWithAgents<InMemoryRouteIndex<M>>
+where
+    M: EmbeddingModel,
+{
+    /// Re-embed the routes of the router with a different embedding model. See [`SemanticRouter::reembed`].
+    pub async fn reembed<N>(
+        self,
+        model: N,
+    ) -> Result<SemanticRouterWithAgents<InMemoryRouteIndex<N>>, SemanticRouterError>
+    where
+        N: EmbeddingModel,
+    {
+        Ok(SemanticRouterWithAgents {
+            router: self.router.reembed(model).await?,
+            agents: self.agents,
+            default_agent: self.default_agent,
+            templates: self.templates,
+            failover: self.failover,
+            splitter: self.splitter,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::routing::{Aggregation, SemanticRoute, SemanticRouter};
+    use crate::test_utils::WordCounts;
+
+    const WORDS: &[&str] = &["invoice", "payment", "error", "crash"];
+
+    #[tokio::test]
+    async fn saves_and_loads_routers() {
+        let router = SemanticRouter::builder()
+            .embedding_model(WordCounts(WORDS))
+            .threshold(0.6)
+            .route_threshold("support", 0.9)
+            .default_route("support")
+            .aggregation(Aggregation::Sum { top_n: 2 })
+            .guardrail("refused", "No")
+            .route_description("billing", "Invoices and payments")
+            .route(
+                SemanticRoute::new("billing").with_metadata("team", "finance"),
+                ["invoice", "payment"],
+            )
+            .route("support", ["error crash"])
+            .build_with_routes()
+            .await
+            .unwrap();
+
+        let path = std::env::temp_dir().join(format!("router-{}.json", std::process::id()));
+        router.save(&path).unwrap();
+        let loaded = SemanticRouter::load(WordCounts(WORDS), &path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.threshold("billing"), 0.6);
+        assert_eq!(loaded.threshold("support"), 0.9);
+        assert_eq!(loaded.default_route(), Some("support"));
+        assert_eq!(loaded.aggregation(), Aggregation::Sum { top_n: 2 });
+        assert_eq!(loaded.refusal("refused"), Some("No"));
+        assert_eq!(loaded.descriptions, router.descriptions);
+        assert_eq!(
+            serde_json::to_value(loaded.store.utterances()).unwrap(),
+            serde_json::to_value(router.store.utterances()).unwrap()
+        );
+
+        let route = loaded.route("invoice").await.unwrap().unwrap();
+        assert_eq!(route.tag, "billing");
+        assert_eq!(route.metadata["team"], "finance");
+    }
+
+    #[tokio::test]
+    async fn reembeds_routes_with_another_model() {
+        let router = SemanticRouter::builder()
+            .embedding_model(WordCounts(WORDS))
+            .threshold(0.7)
+            .route(
+                SemanticRoute::new("billing").with_metadata("team", "finance"),
+                ["invoice payment"],
+            )
+            .route("support", ["error crash"])
+            .build_with_routes()
+            .await
+            .unwrap();
+        let before = router.store.utterances();
+
+        let upgraded = WordCounts(&["refund", "crash", "error", "payment", "invoice"]);
+        let router = router.reembed(upgraded).await.unwrap();
+        let after = router.store.utterances();
+
+        assert_eq!(after.len(), before.len());
+        for (before, after) in before.iter().zip(&after) {
+            assert_eq!(after.tag, before.tag);
+            assert_eq!(after.metadata, before.metadata);
+            assert_eq!(after.utterance, before.utterance);
+            assert_eq!(after.embedding.len(), 5);
+        }
+        assert_eq!(after[0].embedding, [0.0, 0.0, 0.0, 1.0, 1.0]);
+        assert_eq!(after[1].embedding, [0.0, 1.0, 1.0, 0.0, 0.0]);
+
+        assert_eq!(router.threshold("billing"), 0.7);
+        let route = router.route("payment invoice").await.unwrap().unwrap();
+        assert_eq!(route.tag, "billing");
+        assert_eq!(route.metadata["team"], "finance");
+    }
+}
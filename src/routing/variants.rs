@@ -0,0 +1,86 @@
+//! Weighted A/B routing between several agents registered on the same route.
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    sync::Arc,
+};
+
+use super::RouteAgent;
+
+/// One of several agents that serve a route, ie a stable and an experimental model.
+#[derive(Clone)]
+pub(super) struct AgentVariant {
+    pub(super) name: String,
+    /// The relative share of requests the variant serves.
+    pub(super) weight: u32,
+    pub(super) agent: Arc<dyn RouteAgent>,
+}
+
+/// Pick a variant for a request, in proportion to the variant weights.
+pub(super) fn pick(variants: &[AgentVariant]) -> Option<&AgentVariant> {
+    pick_with(variants, random())
+}
+
+/// Pick a variant with a random number. Variants with a weight of 0 are only picked if every variant has a weight of 0.
+fn pick_with(variants: &[AgentVariant], roll: u64) -> Option<&AgentVariant> {
+    let total: u64 = variants.iter().map(|x| u64::from(x.weight)).sum();
+    if total == 0 {
+        return variants.first();
+    }
+
+    let mut roll = roll % total;
+    variants.iter().find(|x| {
+        let weight = u64::from(x.weight);
+        if roll < weight {
+            return true;
+        }
+        roll -= weight;
+        false
+    })
+}
+
+/// A random number from the standard library's randomly seeded hasher, which is plenty for splitting traffic.
+fn random() -> u64 {
+    RandomState::new().build_hasher().finish()
+}
+
+impl std::fmt::Debug for AgentVariant {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AgentVariant")
+            .field("name", &self.name)
+            .field("weight", &self.weight)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::{AgentVariant, pick_with};
+    use crate::test_utils::Echo;
+
+    fn variant(name: &str, weight: u32) -> AgentVariant {
+        AgentVariant {
+            name: name.to_string(),
+            weight,
+            agent: Arc::new(Echo),
+        }
+    }
+
+    #[test]
+    fn picks_variants_by_weight() {
+        let variants = [variant("stable", 90), variant("experimental", 10)];
+        let picks: Vec<&str> = (0..100)
+            .filter_map(|roll| pick_with(&variants, roll))
+            .map(|x| x.name.as_str())
+            .collect();
+
+        assert_eq!(picks.iter().filter(|x| **x == "stable").count(), 90);
+        assert_eq!(picks.iter().filter(|x| **x == "experimental").count(), 10);
+
+        let disabled = [variant("a", 0), variant("b", 0)];
+        assert_eq!(pick_with(&disabled, 7).map(|x| x.name.as_str()), Some("a"));
+        assert!(pick_with(&[], 7).is_none());
+    }
+}
@@ -0,0 +1,154 @@
+//! Agents (and models) with canned responses, shared by the tests of several modules.
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+};
+
+use futures::future::BoxFuture;
+use rig::{
+    OneOrMany,
+    completion::{
+        CompletionError, CompletionModel, CompletionRequest, CompletionResponse, PromptError,
+    },
+    embeddings::{Embedding, EmbeddingError, EmbeddingModel},
+    message::{AssistantContent, Message},
+    streaming::StreamingCompletionResponse,
+};
+
+use crate::agents::DynAgent;
+
+/// Answers every prompt with a fixed response.
+pub(crate) struct Fixed(pub &'static str);
+
+impl DynAgent for Fixed {
+    fn dyn_prompt(
+        &self,
+        _query: String,
+        _turns: usize,
+    ) -> BoxFuture<'_, Result<String, PromptError>> {
+        Box::pin(async move { Ok(self.0.to_string()) })
+    }
+}
+
+/// Answers every prompt with the prompt itself.
+pub(crate) struct Echo;
+
+impl DynAgent for Echo {
+    fn dyn_prompt(
+        &self,
+        query: String,
+        _turns: usize,
+    ) -> BoxFuture<'_, Result<String, PromptError>> {
+        Box::pin(async move { Ok(query) })
+    }
+}
+
+/// Answers prompts with the given responses, in order.
+pub(crate) struct Scripted(pub Mutex<Vec<&'static str>>);
+
+impl DynAgent for Scripted {
+    fn dyn_prompt(
+        &self,
+        _query: String,
+        _turns: usize,
+    ) -> BoxFuture<'_, Result<String, PromptError>> {
+        let res = self.0.lock().unwrap().remove(0);
+
+        Box::pin(async move { Ok(res.to_string()) })
+    }
+}
+
+/// Embeds texts by counting how often each of a fixed set of words occurs in them, so that similarities are predictable.
+#[derive(Clone)]
+pub(crate) struct WordCounts(pub &'static [&'static str]);
+
+impl EmbeddingModel for WordCounts {
+    const MAX_DOCUMENTS: usize = 2;
+
+    fn ndims(&self) -> usize {
+        self.0.len()
+    }
+
+    fn embed_texts(
+        &self,
+        texts: impl IntoIterator<Item = String> + Send,
+    ) -> impl Future<Output = Result<Vec<Embedding>, EmbeddingError>> + Send {
+        let embeddings = texts
+            .into_iter()
+            .map(|document| {
+                let words: Vec<String> = document
+                    .split(|x: char| !x.is_alphanumeric())
+                    .map(str::to_lowercase)
+                    .collect();
+                let vec = self
+                    .0
+                    .iter()
+                    .map(|word| words.iter().filter(|x| x == word).count() as f64)
+                    .collect();
+
+                Embedding { document, vec }
+            })
+            .collect();
+
+        async move { Ok(embeddings) }
+    }
+}
+
+/// A completion model that answers each request with the next of the given responses. `Err` responses (and requests
+/// after the last response) fail with a provider error.
+#[derive(Clone, Default)]
+pub(crate) struct ScriptedModel {
+    responses: Arc<Mutex<VecDeque<Result<&'static str, &'static str>>>>,
+    /// The messages of each request, ending with its prompt.
+    requests: Arc<Mutex<Vec<Vec<Message>>>>,
+}
+
+impl ScriptedModel {
+    pub(crate) fn new(
+        responses: impl IntoIterator<Item = Result<&'static str, &'static str>>,
+    ) -> Self {
+        Self {
+            responses: Arc::new(Mutex::new(responses.into_iter().collect())),
+            requests: Arc::default(),
+        }
+    }
+
+    pub(crate) fn requests(&self) -> Vec<Vec<Message>> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+impl CompletionModel for ScriptedModel {
+    type Response = ();
+    type StreamingResponse = ();
+
+    async fn completion(
+        &self,
+        request: CompletionRequest,
+    ) -> Result<CompletionResponse<()>, CompletionError> {
+        self.requests
+            .lock()
+            .unwrap()
+            .push(request.chat_history.into_iter().collect());
+
+        match self.responses.lock().unwrap().pop_front() {
+            Some(Ok(response)) => Ok(CompletionResponse {
+                choice: OneOrMany::one(AssistantContent::text(response)),
+                raw_response: (),
+            }),
+            Some(Err(err)) => Err(CompletionError::ProviderError(err.to_string())),
+            None => Err(CompletionError::ProviderError(
+                "No responses left".to_string(),
+            )),
+        }
+    }
+
+    async fn stream(
+        &self,
+        _request: CompletionRequest,
+    ) -> Result<StreamingCompletionResponse<()>, CompletionError> {
+        Err(CompletionError::ProviderError(
+            "Streaming isn't scripted".to_string(),
+        ))
+    }
+}